@@ -0,0 +1,268 @@
+// Client side of the client/server split. Keys, the encrypted input and the encrypted digest are exchanged with the
+// server binary through files in a directory. This binary only needs the "client" feature, so it is built without any
+// of the gate evaluation code:
+//
+// cargo run --release --example client --no-default-features --features client -- keygen <dir>
+// cargo run --release --example client --no-default-features --features client -- encrypt <dir> <input> [--algorithm <name>]
+// cargo run --release --example client --no-default-features --features client -- encrypt <dir> <input> --server-padding
+// cargo run --release --example client --no-default-features --features client -- encrypt <dir> <input> --transcipher
+// cargo run --release --example client --no-default-features --features client -- encrypt <dir> <input> --copies <k>
+// cargo run --release --example client --no-default-features --features client -- send <dir> <input> <address>
+// cargo run --release --example client --features tls -- send <dir> <input> <address> --tls-ca <pem>
+// cargo run --release --example client --no-default-features --features client -- decrypt <dir> [--format <format> [--algorithm <name>]]
+// cargo run --release --example client --no-default-features --features client -- verify <dir> [--expected <digest>]
+// cargo run --release --example client -- verify <dir> --against <digest file>
+//
+// verify decrypts the match bit written by the server when it was given an expected digest. With --expected, it
+// decrypts the digest and compares it with the given (hex) digest instead. With --against (which needs the "server"
+// feature), the digest is compared homomorphically with another encrypted digest file and only the result is
+// decrypted. verify prints PASS or FAIL, and exits with status 1 on FAIL for scripted checks. With --server-padding,
+// only the message bits are encrypted and the server pads them, which reveals the message length. With --transcipher,
+// the message is encrypted with Trivium under a fresh random key and IV, and only the key is encrypted under TFHE, so
+// the upload is about the size of the message (see the trivium module). It also reveals the length. decrypt prints the
+// digest in hex by default, or as a hex multihash (--format multihash) or a CIDv1 of raw content (--format cid), which
+// name the algorithm: sha256 unless --algorithm gives another one with digests of the same length.
+// decrypt and verify --expected also read the compact digest written by server --compact. With --copies, the padded
+// input is encrypted k times (k odd) with fresh randomness, for a server started with --copies to vote on the digests.
+//
+// send encrypts the input block by block and sends each block to a server started with --listen <address> as soon as
+// it's encrypted, instead of writing the encrypted input to the directory. With --tls-ca, the connection uses TLS and
+// the server certificate must be signed by (or be) the given certificate, for the host name of the address.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::{env, process};
+use tfhe::boolean::prelude::*;
+use sha256_fhe::client::{bools_to_hex, decrypt_bools, encrypt_bools, encrypt_bools_many};
+use sha256_fhe::algorithm::Algorithm;
+use sha256_fhe::compact::CompactCiphertexts;
+use sha256_fhe::multihash::{cid_v1, multihash, multihash_code, CODEC_RAW};
+use sha256_fhe::padding::{input_bytes, message_bits};
+use sha256_fhe::parameters::ParameterSet;
+use sha256_fhe::serialization::{deserialize_from, serialize_blocks_into, serialize_into, PayloadKind};
+use sha256_fhe::trivium::TranscipheredInput;
+
+const PARAMETER_SET: ParameterSet = ParameterSet::Default;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+
+    match args.get(1).map(String::as_str) {
+        Some("keygen") if args.len() == 3 => keygen(Path::new(&args[2])),
+        Some("encrypt") if args.len() == 4 => encrypt(Path::new(&args[2]), &args[3], Algorithm::Sha256, 1),
+        Some("encrypt") if args.len() == 6 && args[4] == "--algorithm" => {
+            let algorithm = Algorithm::from_name(&args[5]).ok_or(format!("unknown algorithm {}", args[5]))?;
+            encrypt(Path::new(&args[2]), &args[3], algorithm, 1)
+        }
+        Some("encrypt") if args.len() == 6 && args[4] == "--copies" => {
+            let copies = args[5].parse().ok().filter(|count: &usize| count % 2 == 1).ok_or(format!("invalid copy count {}", args[5]))?;
+            encrypt(Path::new(&args[2]), &args[3], Algorithm::Sha256, copies)
+        }
+        Some("encrypt") if args.len() == 5 && args[4] == "--server-padding" => encrypt_message(Path::new(&args[2]), &args[3]),
+        Some("encrypt") if args.len() == 5 && args[4] == "--transcipher" => encrypt_transciphered(Path::new(&args[2]), &args[3]),
+        Some("send") if args.len() == 5 => send(Path::new(&args[2]), &args[3], &args[4], None),
+        #[cfg(feature = "tls")]
+        Some("send") if args.len() == 7 && args[5] == "--tls-ca" => send(Path::new(&args[2]), &args[3], &args[4], Some(Path::new(&args[6]))),
+        Some("decrypt") if args.len() == 3 => decrypt(Path::new(&args[2]), "hex", None),
+        Some("decrypt") if args.len() == 5 && args[3] == "--format" => decrypt(Path::new(&args[2]), &args[4], None),
+        Some("decrypt") if args.len() == 7 && args[3] == "--format" && args[5] == "--algorithm" => {
+            let algorithm = Algorithm::from_name(&args[6]).ok_or(format!("unknown algorithm {}", args[6]))?;
+            decrypt(Path::new(&args[2]), &args[4], Some(algorithm))
+        }
+        Some("verify") if args.len() == 3 => verify(Path::new(&args[2])),
+        Some("verify") if args.len() == 5 && args[3] == "--expected" => verify_expected(Path::new(&args[2]), &args[4]),
+        #[cfg(feature = "server")]
+        Some("verify") if args.len() == 5 && args[3] == "--against" => verify_against(Path::new(&args[2]), Path::new(&args[4])),
+        _ => {
+            eprintln!("usage: client keygen <dir> | client encrypt <dir> <input> [--algorithm <name> | --server-padding | --transcipher | --copies <k>] | client send <dir> <input> <address> [--tls-ca <pem>] | client decrypt <dir> [--format hex|multihash|cid [--algorithm <name>]] | client verify <dir> [--expected <digest> | --against <digest file>]");
+            process::exit(1);
+        }
+    }
+}
+
+// Generates the keys, keeping the client key and sending the (compressed) server key
+fn keygen(dir: &Path) -> Result<(), Box<dyn Error>> {
+    let (ck, sk) = PARAMETER_SET.gen_keys_compressed();
+
+    write(dir, PayloadKind::ClientKey, &ck)?;
+    write(dir, PayloadKind::CompressedServerKey, &sk)?;
+
+    println!("Keys written to {}", dir.display());
+    Ok(())
+}
+
+// Pads and encrypts the input (copies times, one copy after the other), which is interpreted as hex if it starts with "0x"
+fn encrypt(dir: &Path, input: &str, algorithm: Algorithm, copies: usize) -> Result<(), Box<dyn Error>> {
+    let ck: ClientKey = read(dir, PayloadKind::ClientKey)?;
+
+    let padded_input = algorithm.pad(&input_bytes(input));
+    let encrypted_input = encrypt_bools_many(&vec![padded_input.clone(); copies], &ck).concat();
+    write(dir, PayloadKind::EncryptedInput, &encrypted_input)?;
+
+    println!("Encrypted input ({} blocks) written to {}", padded_input.len() / algorithm.block_bits(), dir.display());
+    Ok(())
+}
+
+// Encrypts the unpadded message, leaving the padding to the server
+fn encrypt_message(dir: &Path, input: &str) -> Result<(), Box<dyn Error>> {
+    let ck: ClientKey = read(dir, PayloadKind::ClientKey)?;
+
+    let message = message_bits(&input_bytes(input));
+    let encrypted_message = encrypt_bools(&message, &ck);
+    write(dir, PayloadKind::EncryptedMessage, &encrypted_message)?;
+
+    println!("Encrypted message ({} bytes) written to {}", message.len() / 8, dir.display());
+    Ok(())
+}
+
+// Encrypts the unpadded message with Trivium and the Trivium key under TFHE, leaving the transciphering and the padding
+// to the server
+fn encrypt_transciphered(dir: &Path, input: &str) -> Result<(), Box<dyn Error>> {
+    let ck: ClientKey = read(dir, PayloadKind::ClientKey)?;
+
+    let message = input_bytes(input);
+    let input = TranscipheredInput::new(&message, &rand::random(), rand::random(), &ck);
+    write(dir, PayloadKind::TranscipheredInput, &input)?;
+
+    println!("Transciphered message ({} bytes) written to {}", message.len(), dir.display());
+    Ok(())
+}
+
+// Pads the input and streams it to the server, encrypting each block while the previous ones are on their way
+fn send(dir: &Path, input: &str, address: &str, tls_ca: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let ck: ClientKey = read(dir, PayloadKind::ClientKey)?;
+
+    let algorithm = Algorithm::Sha256;
+    let padded_input = algorithm.pad(&input_bytes(input));
+    let blocks = padded_input.chunks(algorithm.block_bits()).map(|block| encrypt_bools(block, &ck));
+    let connection = TcpStream::connect(address)?;
+
+    match tls_ca {
+        None => serialize_blocks_into(BufWriter::new(connection), blocks, PARAMETER_SET)?,
+        #[cfg(feature = "tls")]
+        Some(ca) => {
+            let config = sha256_fhe::transport::client_config(ca)?;
+            let host = address.rsplit_once(':').map_or(address, |(host, _)| host).to_string();
+            let tls = rustls::ClientConnection::new(config, host.try_into()?)?;
+            let mut connection = rustls::StreamOwned::new(tls, connection);
+
+            serialize_blocks_into(BufWriter::new(&mut connection), blocks, PARAMETER_SET)?;
+            connection.conn.send_close_notify();
+            connection.flush()?;
+
+            // Wait for the server to close the connection. Closing first with the session tickets it sent still unread
+            // would reset the connection, and the server could lose the last blocks
+            let _ = std::io::Read::read_to_end(&mut connection, &mut vec![]);
+        }
+        #[cfg(not(feature = "tls"))]
+        Some(_) => unreachable!("--tls-ca needs the tls feature"),
+    }
+
+    println!("Encrypted input ({} blocks) sent to {}", padded_input.len() / algorithm.block_bits(), address);
+    Ok(())
+}
+
+// The algorithm, which multihash and cid name, is the first one with digests of that length unless it's given
+fn decrypt(dir: &Path, format: &str, algorithm: Option<Algorithm>) -> Result<(), Box<dyn Error>> {
+    let ck: ClientKey = read(dir, PayloadKind::ClientKey)?;
+    let encrypted_output = read_digest(dir)?;
+
+    let output = decrypt_bools(&encrypted_output, &ck);
+    let algorithm = match algorithm {
+        Some(algorithm) if algorithm.digest_bits() != output.len() => {
+            return Err(format!("the digest has {} bits, not the {} of {}", output.len(), algorithm.digest_bits(), algorithm).into());
+        }
+        Some(algorithm) => algorithm,
+        None => Algorithm::ALL
+            .iter()
+            .copied()
+            .find(|algorithm| algorithm.digest_bits() == output.len())
+            .ok_or(format!("no algorithm has {}-bit digests", output.len()))?,
+    };
+    let digest: Vec<u8> = output.chunks(8).map(|byte| byte.iter().fold(0, |acc, bit| (acc << 1) | *bit as u8)).collect();
+
+    match format {
+        "hex" => println!("{}", bools_to_hex(output)),
+        "multihash" | "cid" if multihash_code(algorithm).is_none() => {
+            return Err(format!("{} has no multihash code", algorithm).into());
+        }
+        "multihash" => println!("{}", multihash(algorithm, &digest).unwrap().iter().map(|byte| format!("{:02x}", byte)).collect::<String>()),
+        "cid" => println!("{}", cid_v1(CODEC_RAW, algorithm, &digest).unwrap()),
+        _ => return Err(format!("unknown format {}, expected hex, multihash or cid", format).into()),
+    }
+    Ok(())
+}
+
+fn verify(dir: &Path) -> Result<(), Box<dyn Error>> {
+    let ck: ClientKey = read(dir, PayloadKind::ClientKey)?;
+    let encrypted_match: Ciphertext = read(dir, PayloadKind::EncryptedMatch)?;
+
+    report(ck.decrypt(&encrypted_match), "the digest computed by the server")
+}
+
+fn verify_expected(dir: &Path, expected: &str) -> Result<(), Box<dyn Error>> {
+    let ck: ClientKey = read(dir, PayloadKind::ClientKey)?;
+    let encrypted_output = read_digest(dir)?;
+
+    let output = bools_to_hex(decrypt_bools(&encrypted_output, &ck));
+    if !output.eq_ignore_ascii_case(expected) {
+        println!("expected {}", expected.to_ascii_lowercase());
+        println!("got      {}", output);
+    }
+    report(output.eq_ignore_ascii_case(expected), "the decrypted digest")
+}
+
+// Compares the two encrypted digests with the equality circuit, so only the comparison result is decrypted
+#[cfg(feature = "server")]
+fn verify_against(dir: &Path, other: &Path) -> Result<(), Box<dyn Error>> {
+    use sha256_fhe::boolean_ops::eq_bits;
+    use sha256_fhe::serialization::deserialize_compressed_server_key;
+
+    let ck: ClientKey = read(dir, PayloadKind::ClientKey)?;
+    let file = BufReader::new(File::open(dir.join(PayloadKind::CompressedServerKey.file_name()))?);
+    let sk = deserialize_compressed_server_key(file, PARAMETER_SET)?;
+
+    let encrypted_output: Vec<Ciphertext> = read(dir, PayloadKind::EncryptedDigest)?;
+    let other_output: Vec<Ciphertext> = deserialize_from(BufReader::new(File::open(other)?), PayloadKind::EncryptedDigest, PARAMETER_SET)?;
+    if encrypted_output.len() != other_output.len() {
+        return Err("the digests have different lengths".into());
+    }
+
+    let encrypted_match = eq_bits(&encrypted_output, &other_output, &sk);
+    report(ck.decrypt(&encrypted_match), &format!("the digest compared with {}", other.display()))
+}
+
+// Prints the verification result, exiting with status 1 on failure
+fn report(pass: bool, subject: &str) -> Result<(), Box<dyn Error>> {
+    if pass {
+        println!("PASS: {} matches", subject);
+        Ok(())
+    } else {
+        println!("FAIL: {} does not match", subject);
+        process::exit(1);
+    }
+}
+
+fn write<T: serde::Serialize>(dir: &Path, kind: PayloadKind, value: &T) -> Result<(), Box<dyn Error>> {
+    let mut file = BufWriter::new(File::create(dir.join(kind.file_name()))?);
+    serialize_into(&mut file, kind, value, PARAMETER_SET)?;
+    file.flush()?;
+    Ok(())
+}
+
+// Encrypted digest written by the server, which may be compacted (server --compact)
+fn read_digest(dir: &Path) -> Result<Vec<Ciphertext>, Box<dyn Error>> {
+    if dir.join(PayloadKind::CompactDigest.file_name()).exists() {
+        let compact: CompactCiphertexts = read(dir, PayloadKind::CompactDigest)?;
+        return Ok(compact.expand()?);
+    }
+    read(dir, PayloadKind::EncryptedDigest)
+}
+
+fn read<T: serde::de::DeserializeOwned>(dir: &Path, kind: PayloadKind) -> Result<T, Box<dyn Error>> {
+    let file = BufReader::new(File::open(dir.join(kind.file_name()))?);
+    Ok(deserialize_from(file, kind, PARAMETER_SET)?)
+}
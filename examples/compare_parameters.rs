@@ -0,0 +1,106 @@
+// Compares the supported parameter sets on this machine: the same input is hashed under each of them, and the key
+// generation and hash latency, the payload sizes and the failure probability are printed side by side:
+//
+// cargo run --release --example compare_parameters -- [<input>]
+//
+// The input is text, or hex if it starts with "0x" ("abc" by default). The failure probability of a hash is derived
+// from the published failure probability of one gate and the number of gates it bootstrapped.
+
+use std::error::Error;
+use std::time::Instant;
+use std::{env, process};
+use sha2::{Digest, Sha256};
+use tfhe::boolean::prelude::*;
+use sha256_fhe::boolean_ops::bootstrapped_gates;
+use sha256_fhe::client::{bools_to_hex, decrypt_bools, encrypt_bools};
+use sha256_fhe::compact::DEFAULT_COEFFICIENT_BITS;
+use sha256_fhe::padding::{input_bytes, pad_sha256_data};
+use sha256_fhe::parameters::ParameterSet;
+use sha256_fhe::serialization::{
+    compact_digest_size, encrypted_digest_size, encrypted_input_size, serialize_into, PayloadKind,
+};
+use sha256_fhe::sha256::sha256_fhe;
+
+struct Measurements {
+    keygen_seconds: f64,
+    server_key_bytes: usize,
+    input_bytes: u64,
+    digest_bytes: u64,
+    compact_digest_bytes: u64,
+    hash_seconds: f64,
+    gates: u64,
+    correct: bool,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    let message = match args.as_slice() {
+        [_] => b"abc".to_vec(),
+        [_, input] => input_bytes(input),
+        _ => {
+            eprintln!("usage: compare_parameters [<input>]");
+            process::exit(1);
+        }
+    };
+    let expected: String = Sha256::digest(&message).iter().map(|byte| format!("{:02x}", byte)).collect();
+
+    let mut results = vec![];
+    for &parameter_set in ParameterSet::ALL {
+        println!("Hashing {} bytes with the {} parameters", message.len(), parameter_set);
+        results.push((parameter_set, measure(parameter_set, &message, &expected)?));
+    }
+    println!();
+
+    let row = |name: &str, value: &dyn Fn(ParameterSet, &Measurements) -> String| {
+        print!("{:<28}", name);
+        for (parameter_set, measurements) in &results {
+            print!("{:>16}", value(*parameter_set, measurements));
+        }
+        println!();
+    };
+    row("", &|parameter_set, _| parameter_set.to_string());
+    row("key generation (s)", &|_, m| format!("{:.2}", m.keygen_seconds));
+    row("compressed server key (MB)", &|_, m| format!("{:.1}", m.server_key_bytes as f64 / 1e6));
+    row("encrypted input (KB)", &|_, m| format!("{:.0}", m.input_bytes as f64 / 1e3));
+    row("encrypted digest (KB)", &|_, m| format!("{:.0}", m.digest_bytes as f64 / 1e3));
+    row("compact digest (KB)", &|_, m| format!("{:.0}", m.compact_digest_bytes as f64 / 1e3));
+    row("hash (s)", &|_, m| format!("{:.2}", m.hash_seconds));
+    row("bootstrapped gates", &|_, m| m.gates.to_string());
+    row("time per gate (ms)", &|_, m| format!("{:.3}", m.hash_seconds * 1e3 / m.gates.max(1) as f64));
+    row("gate failure probability", &|parameter_set, _| format!("2^{:.0}", parameter_set.gate_failure_log2()));
+    row("hash failure probability", &|parameter_set, m| {
+        format!("2^{:.1}", parameter_set.gate_failure_log2() + (m.gates.max(1) as f64).log2())
+    });
+    row("digest correct", &|_, m| if m.correct { "yes" } else { "NO" }.to_string());
+
+    if results.iter().any(|(_, measurements)| !measurements.correct) {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+fn measure(parameter_set: ParameterSet, message: &[u8], expected: &str) -> Result<Measurements, Box<dyn Error>> {
+    let start = Instant::now();
+    let (ck, compressed) = parameter_set.gen_keys_compressed();
+    let sk = ServerKey::from(compressed.clone());
+    let keygen_seconds = start.elapsed().as_secs_f64();
+
+    let mut server_key = vec![];
+    serialize_into(&mut server_key, PayloadKind::CompressedServerKey, &compressed, parameter_set)?;
+
+    let encrypted_input = encrypt_bools(&pad_sha256_data(message), &ck);
+    let (start, gates) = (Instant::now(), bootstrapped_gates());
+    let encrypted_output = sha256_fhe(encrypted_input, &sk);
+    let (hash_seconds, gates) = (start.elapsed().as_secs_f64(), bootstrapped_gates() - gates);
+
+    Ok(Measurements {
+        keygen_seconds,
+        server_key_bytes: server_key.len(),
+        input_bytes: encrypted_input_size(message.len(), parameter_set),
+        digest_bytes: encrypted_digest_size(parameter_set),
+        compact_digest_bytes: compact_digest_size(parameter_set, DEFAULT_COEFFICIENT_BITS),
+        hash_seconds,
+        gates,
+        correct: bools_to_hex(decrypt_bools(&encrypted_output, &ck)) == expected,
+    })
+}
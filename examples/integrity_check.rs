@@ -0,0 +1,64 @@
+// Second half of the encrypted file-integrity example. Each file is encrypted and hashed again, and the new digest is
+// compared with the one stored by integrity_register without decrypting either of them. Only the encrypted equality
+// bit is decrypted, so the check reveals whether a file changed and nothing about its hash:
+//
+// cargo run --release --example integrity_check -- <store> <file>...
+//
+// Exits with status 1 if any file was tampered with.
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+use std::{env, process};
+use tfhe::boolean::prelude::*;
+use sha256_fhe::boolean_ops::eq_bits;
+use sha256_fhe::client::encrypt_bools;
+use sha256_fhe::padding::pad_sha256_data;
+use sha256_fhe::parameters::ParameterSet;
+use sha256_fhe::serialization::{deserialize_from, PayloadKind};
+use sha256_fhe::sha256::sha256_fhe;
+
+const PARAMETER_SET: ParameterSet = ParameterSet::Default;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        eprintln!("usage: integrity_check <store> <file>...");
+        process::exit(1);
+    }
+    let store = Path::new(&args[1]);
+
+    let ck: ClientKey = read(&store.join(PayloadKind::ClientKey.file_name()), PayloadKind::ClientKey)?;
+    let sk: ServerKey = read(&store.join(PayloadKind::ServerKey.file_name()), PayloadKind::ServerKey)?;
+
+    let mut tampered = false;
+    for path in &args[2..] {
+        let path = Path::new(path);
+        let name = path.file_name().ok_or_else(|| format!("{} is not a file", path.display()))?;
+        let stored_digest: Vec<Ciphertext> = read(
+            &store.join(format!("{}.digest.bin", name.to_string_lossy())),
+            PayloadKind::EncryptedDigest,
+        )?;
+
+        let data = fs::read(path)?;
+        let encrypted_digest = sha256_fhe(encrypt_bools(&pad_sha256_data(&data), &ck), &sk);
+
+        if ck.decrypt(&eq_bits(&encrypted_digest, &stored_digest, &sk)) {
+            println!("ok       {}", path.display());
+        } else {
+            println!("TAMPERED {}", path.display());
+            tampered = true;
+        }
+    }
+
+    if tampered {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+fn read<T: serde::de::DeserializeOwned>(path: &Path, kind: PayloadKind) -> Result<T, Box<dyn Error>> {
+    let file = BufReader::new(File::open(path)?);
+    Ok(deserialize_from(file, kind, PARAMETER_SET)?)
+}
@@ -0,0 +1,78 @@
+// First half of the encrypted file-integrity example. Each file is encrypted and hashed homomorphically, and the
+// encrypted digest is kept in the store directory, so the store never holds a plaintext hash that could leak the
+// contents of small or guessable files. The keys are generated on the first run and reused afterwards:
+//
+// cargo run --release --example integrity_register -- <store> <file>...
+//
+// The integrity_check example later re-hashes the files and compares the digests homomorphically.
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::{env, process};
+use tfhe::boolean::prelude::*;
+use sha256_fhe::client::encrypt_bools;
+use sha256_fhe::padding::pad_sha256_data;
+use sha256_fhe::parameters::ParameterSet;
+use sha256_fhe::serialization::{deserialize_from, serialize_into, PayloadKind};
+use sha256_fhe::sha256::sha256_fhe;
+
+const PARAMETER_SET: ParameterSet = ParameterSet::Default;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        eprintln!("usage: integrity_register <store> <file>...");
+        process::exit(1);
+    }
+    let store = Path::new(&args[1]);
+    fs::create_dir_all(store)?;
+
+    let (ck, sk) = load_or_generate_keys(store)?;
+
+    for path in &args[2..] {
+        let path = Path::new(path);
+        let data = fs::read(path)?;
+
+        let encrypted_input = encrypt_bools(&pad_sha256_data(&data), &ck);
+        let encrypted_digest = sha256_fhe(encrypted_input, &sk);
+
+        write(&digest_path(store, path)?, PayloadKind::EncryptedDigest, &encrypted_digest)?;
+        println!("registered {}", path.display());
+    }
+
+    Ok(())
+}
+
+fn load_or_generate_keys(store: &Path) -> Result<(ClientKey, ServerKey), Box<dyn Error>> {
+    let client_key_path = store.join(PayloadKind::ClientKey.file_name());
+    let server_key_path = store.join(PayloadKind::ServerKey.file_name());
+
+    if client_key_path.exists() {
+        return Ok((read(&client_key_path, PayloadKind::ClientKey)?, read(&server_key_path, PayloadKind::ServerKey)?));
+    }
+
+    let (ck, sk) = PARAMETER_SET.gen_keys();
+    write(&client_key_path, PayloadKind::ClientKey, &ck)?;
+    write(&server_key_path, PayloadKind::ServerKey, &sk)?;
+    Ok((ck, sk))
+}
+
+// The encrypted digest of a file is stored under its file name
+fn digest_path(store: &Path, file: &Path) -> Result<std::path::PathBuf, Box<dyn Error>> {
+    let name = file.file_name().ok_or_else(|| format!("{} is not a file", file.display()))?;
+    Ok(store.join(format!("{}.digest.bin", name.to_string_lossy())))
+}
+
+fn write<T: serde::Serialize>(path: &Path, kind: PayloadKind, value: &T) -> Result<(), Box<dyn Error>> {
+    let mut file = BufWriter::new(File::create(path)?);
+    serialize_into(&mut file, kind, value, PARAMETER_SET)?;
+    file.flush()?;
+    Ok(())
+}
+
+fn read<T: serde::de::DeserializeOwned>(path: &Path, kind: PayloadKind) -> Result<T, Box<dyn Error>> {
+    let file = BufReader::new(File::open(path)?);
+    Ok(deserialize_from(file, kind, PARAMETER_SET)?)
+}
@@ -0,0 +1,157 @@
+// Collision experiments on sha256 reduced to its first rounds. search looks for two messages whose reduced-round digests
+// collide (on their first --bits bits, or entirely by default), and check takes a pair found elsewhere, e.g. with a
+// differential attack. Either way, the pair is then confirmed on the clear backend, so by the gate circuit itself, and
+// with --fhe under real encryption too, decrypting only the encrypted comparison of the two digests:
+//
+// cargo run --release --example reduced_collisions -- search <rounds> [--bits <count>] [--fhe]
+// cargo run --release --example reduced_collisions -- check <rounds> <message> <message> [--bits <count>] [--fhe]
+//
+// Messages are text, or hex if they start with "0x". With up to 13 rounds, search builds a full collision from two
+// one-block messages that only differ in message word 13, which these rounds don't use. With more rounds, it runs a
+// birthday search on the plaintext reduced-round hash, which takes about 2^(bits/2) hashes and as many stored digests,
+// so it needs a --bits of at most 48
+
+use std::collections::HashMap;
+use std::time::Instant;
+use std::{env, process};
+use rand::Rng;
+use sha256_fhe::boolean_ops::eq_bits;
+use sha256_fhe::client::{bools_to_hex, decrypt_bools, encrypt_bools};
+use sha256_fhe::padding::{input_bytes, pad_sha256_data};
+use sha256_fhe::parameters::ParameterSet;
+use sha256_fhe::sha256::{sha256_clear_reduced, sha256_plain_reduced, sha256_reduced, ROUNDS};
+
+// Longest one-block message, and the largest truncation the birthday search accepts
+const BLOCK_MESSAGE_BYTES: usize = 55;
+const MAX_SEARCH_BITS: usize = 48;
+
+struct Args {
+    rounds: usize,
+    messages: Option<(Vec<u8>, Vec<u8>)>,
+    bits: usize,
+    fhe: bool,
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|| {
+        eprintln!("usage: reduced_collisions search <rounds> [--bits <count>] [--fhe] | reduced_collisions check <rounds> <message> <message> [--bits <count>] [--fhe]");
+        eprintln!("rounds is between 1 and {}, and bits between 1 and 256", ROUNDS);
+        process::exit(1);
+    });
+
+    let (first, second) = match args.messages {
+        Some(messages) => messages,
+        None => search(args.rounds, args.bits).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        }),
+    };
+
+    if !confirm(args.rounds, &first, &second, args.bits, args.fhe) {
+        process::exit(1);
+    }
+}
+
+fn parse_args() -> Option<Args> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let rounds = args.get(1)?.parse().ok().filter(|rounds| (1..=ROUNDS).contains(rounds))?;
+
+    let (messages, options) = match args[0].as_str() {
+        "search" => (None, &args[2..]),
+        "check" if args.len() >= 4 => (Some((input_bytes(&args[2]), input_bytes(&args[3]))), &args[4..]),
+        _ => return None,
+    };
+
+    let mut parsed = Args { rounds, messages, bits: 256, fhe: false };
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--bits" => parsed.bits = options.next()?.parse().ok().filter(|bits| (1..=256).contains(bits))?,
+            "--fhe" => parsed.fhe = true,
+            _ => return None,
+        }
+    }
+    Some(parsed)
+}
+
+// Finds two different messages whose digests collide on their first bits
+fn search(rounds: usize, bits: usize) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let mut rng = rand::thread_rng();
+
+    if rounds <= 13 {
+        let first: Vec<u8> = (0..BLOCK_MESSAGE_BYTES).map(|_| rng.gen()).collect();
+        let mut second = first.clone();
+        second[13 * 4] ^= 0x80;
+
+        println!("Rounds 0 to {} don't use message word 13: flipping its first bit", rounds - 1);
+        return Ok((first, second));
+    }
+
+    if bits > MAX_SEARCH_BITS {
+        return Err(format!("no generic collision search for {} rounds on {} bits: give --bits {} or less", rounds, bits, MAX_SEARCH_BITS));
+    }
+
+    println!("Birthday search on the first {} bits, expecting about 2^{} hashes", bits, bits / 2);
+    let start = Instant::now();
+    let mut seen: HashMap<Vec<bool>, [u8; 16]> = HashMap::new();
+
+    loop {
+        let message: [u8; 16] = rng.gen();
+        let mut digest = sha256_plain_reduced(&pad_sha256_data(&message), rounds);
+        digest.truncate(bits);
+
+        match seen.insert(digest, message) {
+            Some(previous) if previous != message => {
+                println!("Found after {} hashes in {:.1}s", seen.len() + 1, start.elapsed().as_secs_f64());
+                return Ok((previous.to_vec(), message.to_vec()));
+            }
+            _ => {}
+        }
+    }
+}
+
+// Hashes the pair on the clear backend, and under encryption with fhe, printing whether the digests collide on their
+// first bits
+fn confirm(rounds: usize, first: &[u8], second: &[u8], bits: usize, fhe: bool) -> bool {
+    println!("Message 1: 0x{}", hex(first));
+    println!("Message 2: 0x{}", hex(second));
+    if first == second {
+        println!("FAIL: the messages are the same");
+        return false;
+    }
+
+    let (ck, sk) = ParameterSet::Default.gen_keys();
+    let (first, second) = (pad_sha256_data(first), pad_sha256_data(second));
+
+    let (first_digest, second_digest) = rayon::join(
+        || sha256_clear_reduced(&first, rounds, &sk),
+        || sha256_clear_reduced(&second, rounds, &sk),
+    );
+    println!("Digest 1:  {}", bools_to_hex(first_digest.clone()));
+    println!("Digest 2:  {}", bools_to_hex(second_digest.clone()));
+    let mut pass = report("clear backend", first_digest[..bits] == second_digest[..bits], rounds, bits);
+
+    if fhe {
+        let start = Instant::now();
+        let (first_digest, second_digest) = rayon::join(
+            || sha256_reduced(encrypt_bools(&first, &ck), rounds, &sk),
+            || sha256_reduced(encrypt_bools(&second, &ck), rounds, &sk),
+        );
+        let encrypted_match = eq_bits(&first_digest[..bits], &second_digest[..bits], &sk);
+        println!("Hashed under FHE in {:.1}s", start.elapsed().as_secs_f64());
+
+        pass &= report("FHE", decrypt_bools(&[encrypted_match], &ck)[0], rounds, bits);
+    }
+
+    pass
+}
+
+fn report(backend: &str, collide: bool, rounds: usize, bits: usize) -> bool {
+    let status = if collide { "PASS" } else { "FAIL" };
+    println!("{}: {} digests with {} rounds {} on their first {} bits", status, backend, rounds, if collide { "collide" } else { "differ" }, bits);
+    collide
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
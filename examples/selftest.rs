@@ -0,0 +1,89 @@
+// Acceptance test for new machines and new tfhe versions. Hashes random inputs of random lengths and diffs the results
+// against the sha2 crate, always with the clear backend (trivial ciphertexts) and optionally with real encryption:
+//
+// cargo run --release --example selftest -- [--clear <count>] [--fhe <count>] [--cached-keys]
+//
+// With --cached-keys, the keys are read from the key cache (see the key_cache module) rather than generated, for
+// repeated runs on the same machine.
+
+use std::time::Instant;
+use std::{env, process};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use sha256_fhe::client::{bools_to_hex, decrypt_bools, encrypt_bools};
+use sha256_fhe::key_cache::KeyCache;
+use sha256_fhe::padding::pad_sha256_data;
+use sha256_fhe::parameters::ParameterSet;
+use sha256_fhe::sha256::{sha256_clear, sha256_fhe};
+
+fn main() {
+    let (clear_count, fhe_count, cached_keys) = parse_args().unwrap_or_else(|| {
+        eprintln!("usage: selftest [--clear <count>] [--fhe <count>] [--cached-keys]");
+        process::exit(1);
+    });
+
+    let (ck, sk) = if cached_keys {
+        let cache = KeyCache::from_env();
+        cache.keys(ParameterSet::Default).unwrap_or_else(|e| {
+            eprintln!("can't use the key cache {}: {}", cache.dir().display(), e);
+            process::exit(1);
+        })
+    } else {
+        ParameterSet::Default.gen_keys()
+    };
+    let mut rng = rand::thread_rng();
+    let mut failures = 0;
+
+    println!("Clear backend: {} inputs", clear_count);
+    for _ in 0..clear_count {
+        let message: Vec<u8> = (0..rng.gen_range(0..256)).map(|_| rng.gen()).collect();
+        let output = bools_to_hex(sha256_clear(&pad_sha256_data(&message), &sk));
+
+        failures += report(&message, &output, None);
+    }
+
+    println!("FHE: {} inputs", fhe_count);
+    for _ in 0..fhe_count {
+        let message: Vec<u8> = (0..rng.gen_range(0..128)).map(|_| rng.gen()).collect();
+
+        let start = Instant::now();
+        let encrypted_output = sha256_fhe(encrypt_bools(&pad_sha256_data(&message), &ck), &sk);
+        let elapsed = start.elapsed().as_secs_f64();
+        let output = bools_to_hex(decrypt_bools(&encrypted_output, &ck));
+
+        failures += report(&message, &output, Some(elapsed));
+    }
+
+    if failures > 0 {
+        println!("FAILED: {} of {} inputs hashed incorrectly", failures, clear_count + fhe_count);
+        process::exit(1);
+    }
+    println!("OK: all {} inputs match sha2", clear_count + fhe_count);
+}
+
+fn parse_args() -> Option<(usize, usize, bool)> {
+    let (mut clear_count, mut fhe_count, mut cached_keys) = (100, 0, false);
+
+    let mut args = env::args().skip(1);
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--clear" => clear_count = args.next()?.parse().ok()?,
+            "--fhe" => fhe_count = args.next()?.parse().ok()?,
+            "--cached-keys" => cached_keys = true,
+            _ => return None,
+        }
+    }
+    Some((clear_count, fhe_count, cached_keys))
+}
+
+// Prints one line per input and returns 1 on mismatch
+fn report(message: &[u8], output: &str, elapsed: Option<f64>) -> usize {
+    let expected: String = Sha256::digest(message).iter().map(|byte| format!("{:02x}", byte)).collect();
+    let status = if output == expected { "ok" } else { "MISMATCH" };
+
+    match elapsed {
+        Some(secs) => println!("  {:>3} bytes  {}  {:.1}s  {}", message.len(), output, secs, status),
+        None => println!("  {:>3} bytes  {}  {}", message.len(), output, status),
+    }
+    if output == expected { 0 } else { 1 }
+}
@@ -0,0 +1,657 @@
+// Server side of the client/server split. It reads the compressed server key and the encrypted input written by the
+// client binary, computes the hash and writes the encrypted digest back to the same directory. This binary only needs
+// the "server" feature, so it has no access to the client key helpers:
+//
+// cargo run --release --example server --no-default-features --features server -- <dir> [--algorithm <name>]
+//     [--expect <digest>] [--compact] [--server-padding | --transcipher | --copies <k>] [--listen <address>] [--progress <address>]
+//     [--max-message-len <bytes>] [--max-failure-log2 <log2>] [--netlist-cache <dir>] [--report <file>] [--threads <count>]
+//     [--config <file>]
+//
+// The algorithm defaults to sha256 and must match the one the client padded the input for. --copies, --progress,
+// --netlist-cache and the checkpoints are only available for sha256, and --listen isn't available for streebog.
+// With --expect, the digest is compared against the given (hex) digest and only the encrypted match bit is written back.
+// With --compact, the digest is written compacted (see the compact module), about 2.7 times smaller to download.
+// With --server-padding, the server reads the unpadded encrypted message and appends the padding itself.
+// With --transcipher, it reads a Trivium encrypted message with its encrypted Trivium key (`client encrypt
+// --transcipher`), decrypts it homomorphically into TFHE ciphertexts and pads it.
+// With --copies, it reads k independently encrypted copies of the padded input (`client encrypt --copies <k>`), hashes
+// each of them and writes the bitwise majority of the k digests, which is only wrong if most copies fail (see the
+// redundancy module). It costs k hashes, and isn't checkpointed.
+// With --listen, the encrypted input isn't read from the directory but received from the first client connecting to the
+// address (`client send`), and each block is compressed as soon as it arrives, while the next ones are still in transit.
+// With --progress, round-by-round progress is streamed as Server-Sent Events to the clients connecting to the address
+// (e.g. --progress 127.0.0.1:8080, then `curl -N http://127.0.0.1:8080` or an EventSource in a web UI). With the "tls"
+// feature, --tls-cert <pem> --tls-key <pem> serve the progress stream over HTTPS and receive the input over TLS instead.
+//
+// The computation uses one thread per core by default, or --threads threads. With --config, the thread count can also
+// be set in a runtime configuration file (`threads = <count>`, see the config module) that is re-read before every
+// block, so the server can be made to use fewer cores mid-computation on a shared machine by editing the file.
+//
+// With --report, a JSON report of the run (algorithm, parameters, blocks, threads, the duration, bootstrapped gate count,
+// resident and peak memory of each phase, and the resident memory after each block) is written to the given file once
+// the output is written. Memory is only reported on Linux.
+//
+// Before hashing, the server prints the number of gates the job bootstraps and the resulting probability of a wrong
+// output (see the failure module), which is also in the report. With --max-failure-log2, it refuses jobs whose failure
+// probability is above 2^<log2>. A job received with --listen is estimated from the largest input it can receive.
+//
+// Client-provided payloads are bounded before they are read: the server key by the size of a compressed key, and the
+// encrypted input by the size of the encrypted input for a message of --max-message-len bytes (64 KiB by default).
+//
+// The hash state is checkpointed to the directory after every block, so a server that is stopped (e.g. for an upgrade)
+// resumes from the last completed block when it is started again on the same input, losing at most one block of work.
+// An input received with --listen isn't stored, so it's not checkpointed.
+//
+// With --netlist-cache, the whole input is hashed at once by executing the optimized netlist of its block count (see the
+// netlist module), specialized to the padding when it's appended by the server. The netlist of each block count is
+// built once and cached in the given directory (see the netlist_cache module). It isn't checkpointed.
+//
+// With the "s3" feature, <dir> can also be the URL of a bucket of S3-compatible object storage, http://<host>[:<port>]/
+// <bucket>[/<prefix>], which then holds the payloads (under the prefix) and the checkpoint instead of a local directory.
+// The credentials are read from AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY and AWS_REGION (see the object_store module).
+
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::net::TcpListener;
+use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::channel;
+use std::{env, process, thread};
+use tfhe::boolean::prelude::*;
+use sha256_fhe::algorithm::Algorithm;
+use sha256_fhe::boolean_ops::{eq_bits, trivial_bytes};
+use sha256_fhe::compact::{CompactCiphertexts, DEFAULT_COEFFICIENT_BITS};
+use sha256_fhe::config::RuntimeConfig;
+use sha256_fhe::failure::FailureEstimate;
+use sha256_fhe::memory::memory_usage;
+use sha256_fhe::netlist_cache::{NetlistCache, NetlistConfig};
+#[cfg(feature = "s3")]
+use sha256_fhe::object_store::S3Store;
+use sha256_fhe::parameters::ParameterSet;
+use sha256_fhe::progress::{ProgressObserver, ProgressServer};
+use sha256_fhe::redundancy::sha256_fhe_redundant;
+use sha256_fhe::report::RunReport;
+use sha256_fhe::serialization::{
+    compressed_server_key_size_limit, deserialize_compressed_server_key, deserialize_from, deserialize_from_limited,
+    encrypted_block_size, encrypted_message_size, serialize_into, transciphered_input_size, PayloadKind, SerializationError,
+};
+use sha256_fhe::sha256::{RoundObserver, Sha256State};
+use sha256_fhe::sm3::Sm3State;
+use sha256_fhe::stream::hash_stream;
+use sha256_fhe::threads::ThreadLimit;
+use sha256_fhe::trivium::{keystream_gates, TranscipheredInput, KEY_BITS};
+
+const PARAMETER_SET: ParameterSet = ParameterSet::Default;
+
+const DEFAULT_MAX_MESSAGE_LEN: usize = 64 * 1024;
+
+// A client sending its input with --listen may pause for this long between two blocks (e.g. to encrypt the next one)
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(60);
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        usage();
+    }
+    let storage = Storage::open(&args[1])?;
+
+    let mut algorithm = Algorithm::Sha256;
+    let mut expected = None;
+    let mut compact = false;
+    let mut server_padding = false;
+    let mut transcipher = false;
+    let mut copies = 1;
+    let mut listen_address = None;
+    let mut progress_address = None;
+    let mut max_message_len = DEFAULT_MAX_MESSAGE_LEN;
+    let mut max_failure_log2 = None;
+    let mut report_path = None;
+    let mut threads = None;
+    let mut config_path = None;
+    let mut netlist_cache = None;
+    #[cfg(feature = "tls")]
+    let (mut tls_cert, mut tls_key) = (None, None);
+    let mut flags = args[2..].iter();
+    while let Some(flag) = flags.next() {
+        match (flag.as_str(), flags.clone().next()) {
+            ("--algorithm", Some(name)) => {
+                algorithm = Algorithm::from_name(name).ok_or(format!("unknown algorithm {}", name))?;
+                flags.next();
+            }
+            ("--expect", Some(digest)) => {
+                expected = Some(digest.clone());
+                flags.next();
+            }
+            ("--compact", _) => compact = true,
+            ("--server-padding", _) => server_padding = true,
+            ("--transcipher", _) => transcipher = true,
+            ("--copies", Some(count)) => {
+                copies = count.parse().ok().filter(|count| count % 2 == 1).ok_or(format!("invalid copy count {}", count))?;
+                flags.next();
+            }
+            ("--max-message-len", Some(len)) => {
+                max_message_len = len.parse().map_err(|_| format!("invalid message length {}", len))?;
+                flags.next();
+            }
+            ("--max-failure-log2", Some(log2)) => {
+                max_failure_log2 = Some(log2.parse::<f64>().map_err(|_| format!("invalid failure probability {}", log2))?);
+                flags.next();
+            }
+            ("--report", Some(path)) => {
+                report_path = Some(path.clone());
+                flags.next();
+            }
+            ("--threads", Some(count)) => {
+                threads = Some(count.parse().ok().filter(|count| *count > 0).ok_or(format!("invalid thread count {}", count))?);
+                flags.next();
+            }
+            ("--config", Some(path)) => {
+                config_path = Some(PathBuf::from(path));
+                flags.next();
+            }
+            ("--netlist-cache", Some(dir)) => {
+                netlist_cache = Some(NetlistCache::new(dir));
+                flags.next();
+            }
+            ("--listen", Some(address)) => {
+                listen_address = Some(address.clone());
+                flags.next();
+            }
+            ("--progress", Some(address)) => {
+                progress_address = Some(address.clone());
+                flags.next();
+            }
+            #[cfg(feature = "tls")]
+            ("--tls-cert", Some(path)) => {
+                tls_cert = Some(path.clone());
+                flags.next();
+            }
+            #[cfg(feature = "tls")]
+            ("--tls-key", Some(path)) => {
+                tls_key = Some(path.clone());
+                flags.next();
+            }
+            _ => usage(),
+        }
+    }
+    let expected = expected.map(|digest| parse_digest(&digest, algorithm)).transpose()?;
+    if listen_address.is_some() && (server_padding || transcipher || progress_address.is_some()) {
+        return Err("--listen can't be combined with --server-padding, --transcipher or --progress".into());
+    }
+    if compact && expected.is_some() {
+        return Err("--compact can't be combined with --expect, which only writes the match bit".into());
+    }
+    if server_padding && transcipher {
+        return Err("--server-padding and --transcipher can't be combined".into());
+    }
+    if copies > 1 && (server_padding || transcipher || listen_address.is_some() || progress_address.is_some()) {
+        return Err("--copies can't be combined with --server-padding, --transcipher, --listen or --progress".into());
+    }
+    if algorithm != Algorithm::Sha256 && (copies > 1 || progress_address.is_some()) {
+        return Err(format!("--copies and --progress are only available for sha256, not {}", algorithm).into());
+    }
+    if listen_address.is_some() && matches!(algorithm, Algorithm::Streebog256 | Algorithm::Streebog512) {
+        return Err("--listen isn't available for streebog, whose last block is only known once the input ends".into());
+    }
+    if netlist_cache.is_some() && (copies > 1 || listen_address.is_some() || progress_address.is_some()) {
+        return Err("--netlist-cache can't be combined with --copies, --listen or --progress".into());
+    }
+
+    #[cfg(feature = "tls")]
+    let tls_config = match (tls_cert, tls_key) {
+        (Some(cert), Some(key)) => Some(sha256_fhe::transport::server_config(Path::new(&cert), Path::new(&key))?),
+        (None, None) => None,
+        _ => return Err("--tls-cert and --tls-key must be given together".into()),
+    };
+
+    // The configuration file takes precedence, since it's the one that can be changed later
+    let config = config_path.as_deref().map(RuntimeConfig::load).transpose()?.unwrap_or_default();
+    let default_threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let threads = Threads {
+        limit: ThreadLimit::new(config.threads.or(threads).unwrap_or(default_threads))?,
+        config_path,
+    };
+
+    // The block count is only known once the input is read
+    let mut report = RunReport::new(algorithm, PARAMETER_SET, 0);
+    report.threads = threads.limit.threads();
+
+    let sk = report.phase("load_key", || -> Result<ServerKey, Box<dyn Error>> {
+        let key = storage.read(PayloadKind::CompressedServerKey, compressed_server_key_size_limit(PARAMETER_SET))?;
+        Ok(deserialize_compressed_server_key(key, PARAMETER_SET)?)
+    })?;
+
+    // Before the first gate is timed, so that the hash phase doesn't include the one-time costs of the threads
+    let calibration = report.phase("warm_up", || threads.limit.warm_up(PARAMETER_SET, &sk));
+    println!("Warmed up at {:.0} bootstrapped gates per second", calibration.gates_per_second());
+
+    if let Some(address) = listen_address {
+        let max_blocks = algorithm.padded_len(max_message_len) / algorithm.block_bits();
+        let estimate = estimate_job(algorithm, max_blocks, None, copies, expected.is_some());
+        check_estimate(estimate, max_failure_log2, &mut report)?;
+
+        let listener = TcpListener::bind(address.as_str())?;
+        println!("Waiting for the encrypted input on {}", listener.local_addr()?);
+        let (connection, peer) = listener.accept()?;
+        connection.set_read_timeout(Some(RECEIVE_TIMEOUT))?;
+        println!("Computing the {} hash of the blocks received from {}", algorithm, peer);
+
+        #[cfg(feature = "tls")]
+        let (encrypted_output, blocks) = report.phase("hash", || -> Result<_, Box<dyn Error>> {
+            match tls_config {
+                Some(config) => {
+                    let connection = rustls::StreamOwned::new(rustls::ServerConnection::new(config)?, connection);
+                    Ok(threads.limit.install(|| receive(algorithm, connection, max_blocks, &sk))?)
+                }
+                None => Ok(threads.limit.install(|| receive(algorithm, connection, max_blocks, &sk))?),
+            }
+        })?;
+        #[cfg(not(feature = "tls"))]
+        let (encrypted_output, blocks) =
+            report.phase("hash", || threads.limit.install(|| receive(algorithm, connection, max_blocks, &sk)))?;
+
+        report.blocks = blocks;
+        println!("Received and hashed {} blocks", blocks);
+        return finish(&storage, report, report_path, expected, compact, encrypted_output, &sk);
+    }
+
+    let (input_kind, max_input_size) = if server_padding {
+        (PayloadKind::EncryptedMessage, encrypted_message_size(max_message_len, PARAMETER_SET))
+    } else if transcipher {
+        (PayloadKind::TranscipheredInput, transciphered_input_size(max_message_len, PARAMETER_SET))
+    } else {
+        // One frame of the padded input bits of all the copies
+        let padded_input_size = encrypted_block_size(copies * algorithm.padded_len(max_message_len), PARAMETER_SET);
+        (PayloadKind::EncryptedInput, padded_input_size)
+    };
+
+    let mut transciphered = None;
+    let (input_crc, encrypted_input) = report.phase("load_input", || -> Result<_, Box<dyn Error>> {
+        let mut reader = CrcReader::new(storage.read(input_kind, max_input_size)?);
+
+        let encrypted_input = if server_padding {
+            // The message may have any length in bits
+            let encrypted_message: Vec<Ciphertext> =
+                deserialize_from_limited(&mut reader, PayloadKind::EncryptedMessage, PARAMETER_SET, max_input_size)?;
+            algorithm.pad_encrypted(encrypted_message, &sk)
+        } else if transcipher {
+            let input: TranscipheredInput =
+                deserialize_from_limited(&mut reader, PayloadKind::TranscipheredInput, PARAMETER_SET, max_input_size)?;
+            if input.key.len() != KEY_BITS {
+                return Err(format!("transciphered input has a {}-bit key instead of {}", input.key.len(), KEY_BITS).into());
+            }
+
+            // Transciphered in its own phase below
+            transciphered = Some(input);
+            vec![]
+        } else {
+            let encrypted_input: Vec<Ciphertext> =
+                deserialize_from_limited(&mut reader, PayloadKind::EncryptedInput, PARAMETER_SET, max_input_size)?;
+            if encrypted_input.is_empty() || !encrypted_input.len().is_multiple_of(copies * algorithm.block_bits()) {
+                let bits = encrypted_input.len();
+                return Err(format!("encrypted input has {} bits, not {} copies of a whole number of blocks", bits, copies).into());
+            }
+            encrypted_input
+        };
+
+        // Checksum of the input file, identifying the input that a checkpoint belongs to
+        Ok((reader.finish()?, encrypted_input))
+    })?;
+    let (blocks, transciphered_bits) = match &transciphered {
+        Some(input) => (algorithm.padded_len(input.ciphertext.len()) / algorithm.block_bits(), Some(input.ciphertext.len() * 8)),
+        None => (encrypted_input.len() / copies / algorithm.block_bits(), None),
+    };
+    let estimate = estimate_job(algorithm, blocks, transciphered_bits, copies, expected.is_some());
+    check_estimate(estimate, max_failure_log2, &mut report)?;
+
+    let encrypted_input = match transciphered {
+        Some(input) => report.phase("transcipher", || {
+            println!("Transciphering {} bytes", input.ciphertext.len());
+            algorithm.pad_encrypted(threads.limit.install(|| input.transcipher(&sk)), &sk)
+        }),
+        None => encrypted_input,
+    };
+
+    let total_blocks = encrypted_input.len() / copies / algorithm.block_bits();
+    report.blocks = total_blocks;
+    if copies > 1 {
+        println!("Computing the {} hash of {} copies of {} blocks", algorithm, copies, total_blocks);
+        let copies = encrypted_input.chunks(encrypted_input.len() / copies).map(<[Ciphertext]>::to_vec).collect();
+        let encrypted_output = report.phase("hash", || threads.limit.install(|| sha256_fhe_redundant(copies, &sk)));
+        return finish(&storage, report, report_path, expected, compact, encrypted_output, &sk);
+    }
+    println!("Computing the {} hash of {} blocks", algorithm, total_blocks);
+
+    if let Some(cache) = netlist_cache {
+        let netlist = report.phase("load_netlist", || cache.netlist(NetlistConfig::new(algorithm, total_blocks)))?;
+        println!("Executing a netlist of {} bootstrapped gates from {}", netlist.bootstrapped_gates(), cache.dir().display());
+        let encrypted_output =
+            report.phase("hash", || threads.limit.install(|| netlist.execute_specialized(&encrypted_input, &sk)));
+        return finish(&storage, report, report_path, expected, compact, encrypted_output, &sk);
+    }
+
+    // The events are broadcast from another thread, until the observer (and its sender) is dropped
+    let mut progress = None;
+    let mut broadcaster = None;
+    if let Some(address) = progress_address {
+        #[cfg(feature = "tls")]
+        let server = match tls_config {
+            Some(config) => ProgressServer::bind_tls(address.as_str(), config)?,
+            None => ProgressServer::bind(address.as_str())?,
+        };
+        #[cfg(not(feature = "tls"))]
+        let server = ProgressServer::bind(address.as_str())?;
+        println!("Streaming progress events on {}", server.local_addr());
+
+        let (sender, receiver) = channel();
+        progress = Some(ProgressObserver::new(sender, total_blocks));
+        broadcaster = Some(thread::spawn(move || server.broadcast(receiver)));
+    }
+
+    let mut block_resident_bytes = vec![];
+    let encrypted_output = report.phase("hash", || match algorithm {
+        Algorithm::Sha256 => {
+            let observer = progress.as_mut().map(|observer| observer as &mut (dyn RoundObserver + Send));
+            sha256_checkpointed(&storage, &encrypted_input, input_crc, observer, &mut block_resident_bytes, &threads, &sk)
+        }
+        Algorithm::Sm3 | Algorithm::Streebog256 | Algorithm::Streebog512 => {
+            Ok(threads.limit.install(|| algorithm.hash(encrypted_input, &sk)))
+        }
+    })?;
+    report.block_resident_bytes = block_resident_bytes;
+
+    drop(progress);
+    if let Some(broadcaster) = broadcaster {
+        broadcaster.join().expect("progress broadcaster panicked");
+    }
+
+    finish(&storage, report, report_path, expected, compact, encrypted_output, &sk)
+}
+
+// Writes the encrypted digest, or the encrypted match bit when a digest is expected, and then the run report
+fn finish(
+    storage: &Storage,
+    mut report: RunReport,
+    report_path: Option<String>,
+    expected: Option<Vec<u8>>,
+    compact: bool,
+    encrypted_output: Vec<Ciphertext>,
+    sk: &ServerKey,
+) -> Result<(), Box<dyn Error>> {
+    report.phase("write_output", || -> Result<(), Box<dyn Error>> {
+        if let Some(expected) = expected {
+            let encrypted_match = eq_bits(&encrypted_output, &trivial_bytes(&expected, sk), sk);
+            storage.write(PayloadKind::EncryptedMatch, &encrypted_match)?;
+            println!("Encrypted match bit written to {}", storage);
+        } else if compact {
+            let compact = CompactCiphertexts::compact(&encrypted_output, PARAMETER_SET, DEFAULT_COEFFICIENT_BITS);
+            storage.write(PayloadKind::CompactDigest, &compact)?;
+            // Removes the digest of a previous run written in the other form, which the client would read instead
+            storage.remove(PayloadKind::EncryptedDigest)?;
+            println!("Compact encrypted digest written to {}", storage);
+        } else {
+            storage.write(PayloadKind::EncryptedDigest, &encrypted_output)?;
+            storage.remove(PayloadKind::CompactDigest)?;
+            println!("Encrypted digest written to {}", storage);
+        }
+        Ok(())
+    })?;
+
+    if let Some(path) = report_path {
+        report.write(Path::new(&path))?;
+        println!("Run report written to {}", path);
+    }
+
+    Ok(())
+}
+
+// Estimate of a job hashing the given number of blocks, with what comes before and after the hash
+fn estimate_job(
+    algorithm: Algorithm,
+    blocks: usize,
+    transciphered_bits: Option<usize>,
+    copies: usize,
+    expect: bool,
+) -> FailureEstimate {
+    let mut estimate = FailureEstimate::hash(algorithm, blocks, PARAMETER_SET)
+        .redundant(copies, algorithm.digest_bits(), PARAMETER_SET);
+    if let Some(bits) = transciphered_bits {
+        estimate = FailureEstimate::new(keystream_gates(bits), PARAMETER_SET).then(estimate);
+    }
+    if expect {
+        // eq_bits takes an XNOR per bit and an AND tree
+        estimate = estimate.then(FailureEstimate::new(2 * algorithm.digest_bits() as u64 - 1, PARAMETER_SET));
+    }
+    estimate
+}
+
+// Prints the estimate and records it in the report, refusing the job if its failure probability is too high
+fn check_estimate(
+    estimate: FailureEstimate,
+    max_failure_log2: Option<f64>,
+    report: &mut RunReport,
+) -> Result<(), Box<dyn Error>> {
+    println!("Estimated {}", estimate);
+    report.failure = Some(estimate);
+
+    match max_failure_log2 {
+        Some(max) if estimate.exceeds(max) => {
+            Err(format!("the failure probability is above the allowed 2^{} (see --copies to lower it)", max).into())
+        }
+        _ => Ok(()),
+    }
+}
+
+struct Threads {
+    limit: ThreadLimit,
+    config_path: Option<PathBuf>,
+}
+
+impl Threads {
+    // Applies the thread count of the configuration file, if it changed. A configuration file that can't be read (e.g.
+    // while it's being edited) keeps the current count rather than stopping the computation
+    fn refresh(&self) {
+        let Some(path) = &self.config_path else { return };
+
+        match RuntimeConfig::load(path) {
+            Ok(RuntimeConfig { threads: Some(count) }) if count != self.limit.threads() => {
+                match self.limit.set_threads(count) {
+                    Ok(()) => println!("Using {} threads from now on", count),
+                    Err(e) => eprintln!("can't use {} threads: {}", count, e),
+                }
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("ignoring {}: {}", path.display(), e),
+        }
+    }
+}
+
+// Hashes the block stream sent by a client, compressing each block as soon as it arrives
+fn receive(
+    algorithm: Algorithm,
+    connection: impl Read + Send,
+    max_blocks: usize,
+    sk: &ServerKey,
+) -> Result<(Vec<Ciphertext>, usize), SerializationError> {
+    match algorithm {
+        Algorithm::Sha256 => hash_stream::<Sha256State, _>(connection, max_blocks, PARAMETER_SET, sk),
+        Algorithm::Sm3 => hash_stream::<Sm3State, _>(connection, max_blocks, PARAMETER_SET, sk),
+        Algorithm::Streebog256 | Algorithm::Streebog512 => unreachable!("--listen is refused for streebog"),
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Checkpoint {
+    input_crc: u32,
+    state: Sha256State,
+}
+
+// Compresses the blocks one by one, saving the state after each of them and resuming from a saved state that belongs
+// to the same input. The checkpoint is removed once the hash is complete. The resident memory after each block that is
+// compressed is appended to block_resident_bytes, where the platform reports it
+fn sha256_checkpointed(
+    storage: &Storage,
+    padded_input: &[Ciphertext],
+    input_crc: u32,
+    mut observer: Option<&mut (dyn RoundObserver + Send)>,
+    block_resident_bytes: &mut Vec<u64>,
+    threads: &Threads,
+    sk: &ServerKey,
+) -> Result<Vec<Ciphertext>, Box<dyn Error>> {
+    let mut state = match read_checkpoint(storage) {
+        Some(checkpoint) if checkpoint.input_crc == input_crc && checkpoint.state.length() <= padded_input.len() as u64 => {
+            println!("Resuming from block {}", checkpoint.state.length() / 512);
+            checkpoint.state
+        }
+        _ => Sha256State::new(sk),
+    };
+
+    let done = state.length() as usize;
+    for chunk in padded_input[done..].chunks(512) {
+        threads.refresh();
+        threads.limit.install(|| {
+            state.compress_observed(chunk, observer.as_mut().map(|observer| &mut **observer as &mut dyn RoundObserver), sk)
+        });
+        block_resident_bytes.extend(memory_usage().map(|memory| memory.resident_bytes));
+
+        // Writes are atomic, so stopping the server mid-write never leaves a corrupted checkpoint
+        storage.write(PayloadKind::Checkpoint, &Checkpoint { input_crc, state: state.clone() })?;
+    }
+
+    storage.remove(PayloadKind::Checkpoint)?;
+    Ok(state.into())
+}
+
+// A missing or unreadable checkpoint just means starting over
+fn read_checkpoint(storage: &Storage) -> Option<Checkpoint> {
+    let checkpoint = storage.read(PayloadKind::Checkpoint, u64::MAX).ok()?;
+    deserialize_from(checkpoint, PayloadKind::Checkpoint, PARAMETER_SET).ok()
+}
+
+fn usage() -> ! {
+    eprintln!("usage: server <dir | url> [--algorithm <name>] [--expect <digest>] [--compact] [--server-padding | --transcipher | --copies <k> | --listen <address>] [--progress <address>] [--tls-cert <pem> --tls-key <pem>] [--max-message-len <bytes>] [--max-failure-log2 <log2>] [--netlist-cache <dir>] [--report <file>] [--threads <count>] [--config <file>]");
+    process::exit(1);
+}
+
+fn parse_digest(hex: &str, algorithm: Algorithm) -> Result<Vec<u8>, Box<dyn Error>> {
+    let digest_len = algorithm.digest_bits() / 8;
+    if hex.len() != 2 * digest_len || !hex.is_ascii() {
+        return Err(format!("expected {} digest must be {} hex characters", algorithm, 2 * digest_len).into());
+    }
+
+    (0..digest_len).map(|i| Ok(u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)?)).collect()
+}
+
+// Where the payloads and the checkpoint are read and written: a local directory, or with the "s3" feature a bucket of
+// object storage
+enum Storage {
+    Dir(PathBuf),
+    #[cfg(feature = "s3")]
+    S3(S3Store),
+}
+
+impl Storage {
+    // A URL is never taken for a directory, including an https:// URL (which object storage doesn't support) and any URL
+    // without the "s3" feature
+    fn open(location: &str) -> Result<Storage, Box<dyn Error>> {
+        if location.starts_with("http://") || location.starts_with("https://") {
+            #[cfg(feature = "s3")]
+            return Ok(Storage::S3(S3Store::from_env(location)?));
+            #[cfg(not(feature = "s3"))]
+            return Err(format!("{} is an object storage URL, which needs the s3 feature", location).into());
+        }
+
+        Ok(Storage::Dir(PathBuf::from(location)))
+    }
+
+    // Reader of a payload that can't be larger than max_size bytes, which is checked before reading it. A local file is
+    // streamed, while an object is downloaded as a whole
+    fn read(&self, kind: PayloadKind, max_size: u64) -> Result<Box<dyn Read>, Box<dyn Error>> {
+        let too_large = || format!("{} is larger than the {} bytes allowed", kind.file_name(), max_size);
+
+        match self {
+            Storage::Dir(dir) => {
+                let path = dir.join(kind.file_name());
+                if fs::metadata(&path)?.len() > max_size {
+                    return Err(too_large().into());
+                }
+                Ok(Box::new(BufReader::new(File::open(path)?)))
+            }
+            #[cfg(feature = "s3")]
+            Storage::S3(store) => match store.get(kind.file_name(), max_size) {
+                Ok(Some(payload)) => Ok(Box::new(io::Cursor::new(payload))),
+                Ok(None) => Err(io::Error::new(io::ErrorKind::NotFound, format!("{} not found", kind.file_name())).into()),
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => Err(format!("{}: {}", too_large(), e).into()),
+                Err(e) => Err(e.into()),
+            },
+        }
+    }
+
+    // Writes are atomic: a local file is written to a temporary file first, and objects are replaced as a whole
+    fn write<T: serde::Serialize>(&self, kind: PayloadKind, value: &T) -> Result<(), Box<dyn Error>> {
+        let mut payload = vec![];
+        serialize_into(&mut payload, kind, value, PARAMETER_SET)?;
+
+        match self {
+            Storage::Dir(dir) => {
+                let path = dir.join(kind.file_name());
+                let tmp_path = path.with_extension("tmp");
+                fs::write(&tmp_path, payload)?;
+                fs::rename(&tmp_path, &path)?;
+            }
+            #[cfg(feature = "s3")]
+            Storage::S3(store) => store.put(kind.file_name(), &payload)?,
+        }
+        Ok(())
+    }
+
+    // Removes a payload, if there is one
+    fn remove(&self, kind: PayloadKind) -> Result<(), Box<dyn Error>> {
+        match self {
+            Storage::Dir(dir) => match fs::remove_file(dir.join(kind.file_name())) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            },
+            #[cfg(feature = "s3")]
+            Storage::S3(store) => Ok(store.delete(kind.file_name())?),
+        }
+    }
+}
+
+// Reader computing the CRC-32 of what it reads
+struct CrcReader<R> {
+    inner: R,
+    hasher: crc32fast::Hasher,
+}
+
+impl<R: Read> CrcReader<R> {
+    fn new(inner: R) -> Self {
+        CrcReader { inner, hasher: crc32fast::Hasher::new() }
+    }
+
+    // Checksum of the whole payload, including what is left after the part that was read
+    fn finish(mut self) -> io::Result<u32> {
+        io::copy(&mut self, &mut io::sink())?;
+        Ok(self.hasher.finalize())
+    }
+}
+
+impl<R: Read> Read for CrcReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+impl fmt::Display for Storage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Storage::Dir(dir) => write!(f, "{}", dir.display()),
+            #[cfg(feature = "s3")]
+            Storage::S3(store) => write!(f, "{}", store),
+        }
+    }
+}
@@ -0,0 +1,114 @@
+// Hashing service: runs the sha256 jobs submitted over HTTP under one server key (or under the key of each client), and
+// keeps their digests until the clients download them (see the service module for the endpoints). It only needs the "server" feature:
+//
+// cargo run --release --example service --no-default-features --features server -- <dir> <address>
+//     [--jobs <count>] [--threads-per-job <count>] [--max-blocks <count>] [--max-held-jobs <count>]
+//     [--max-jobs-per-client <count>] [--api-keys <file>] [--key-registry]
+//
+// The compressed server key is read from <dir>, as written by `client keygen`. With --key-registry, the service has no
+// key of its own, and <dir> holds the keys that its clients register instead (see the key_registry module): a client
+// uploads its compressed server key with `curl -T <dir>/compressed_server_key.bin http://<address>/keys/<client>`, and
+// submits jobs to /jobs?key=<client> (with API keys, its jobs always use its own key).
+//
+// Up to --jobs jobs (1 by default) run at a time, each on --threads-per-job threads (all the cores divided by the job
+// count by default). Unless the keys are registered by the clients, the job slots are warmed up under the service's
+// key before the first request, with a calibration batch that also measures their speed (see the warm_up module).
+//
+// Jobs are admitted within the ServiceLimits of the service module: inputs of up to --max-blocks blocks (1025 by
+// default, a 64 KiB message), at most --max-held-jobs jobs held until they're deleted (64) and --max-jobs-per-client
+// for each client IP address (4).
+//
+// With --api-keys, requests need one of the keys of the file (see the service module), e.g. with curl's
+// `-H "Authorization: Bearer <key>"`. The clients are then told apart by their keys, which can have their own job
+// limit.
+//
+// The inputs of `client encrypt` can be submitted and the digests downloaded with curl, for `client decrypt` to read:
+//
+// curl --data-binary @<dir>/input.bin http://<address>/jobs
+// curl http://<address>/jobs/<id>
+// curl -o <dir>/digest.bin http://<address>/jobs/<id>/digest
+
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::TcpListener;
+use std::path::Path;
+use std::{env, process, thread};
+use sha256_fhe::jobs::Scheduler;
+use sha256_fhe::key_registry::KeyRegistry;
+use sha256_fhe::parameters::ParameterSet;
+use sha256_fhe::serialization::{deserialize_compressed_server_key, PayloadKind};
+use sha256_fhe::service::{ApiKeys, JobService, ServiceLimits};
+
+const PARAMETER_SET: ParameterSet = ParameterSet::Default;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = parse_args().unwrap_or_else(|| {
+        eprintln!(
+            "usage: service <dir> <address> [--jobs <count>] [--threads-per-job <count>] [--max-blocks <count>] \
+            [--max-held-jobs <count>] [--max-jobs-per-client <count>] [--api-keys <file>] [--key-registry]"
+        );
+        process::exit(1);
+    });
+
+    let threads_per_job = match args.threads_per_job {
+        Some(threads) => threads,
+        None => (thread::available_parallelism()?.get() / args.jobs).max(1),
+    };
+    let scheduler = Scheduler::new(args.jobs, threads_per_job)?;
+
+    let mut service = if args.key_registry {
+        JobService::with_registry(KeyRegistry::open(&args.dir, PARAMETER_SET)?, PARAMETER_SET, args.limits)
+    } else {
+        let file = BufReader::new(File::open(Path::new(&args.dir).join(PayloadKind::CompressedServerKey.file_name()))?);
+        let sk = deserialize_compressed_server_key(file, PARAMETER_SET)?;
+        let calibration = &scheduler.warm_up(PARAMETER_SET, &sk)[0];
+        println!("Warmed up at {:.0} bootstrapped gates per second per job", calibration.gates_per_second());
+        JobService::new(sk, PARAMETER_SET, args.limits)
+    };
+
+    let listener = TcpListener::bind(&args.address)?;
+    println!("Serving {} jobs of {} threads at http://{}", args.jobs, threads_per_job, listener.local_addr()?);
+    if let Some(path) = args.api_keys {
+        service = service.with_api_keys(ApiKeys::load(Path::new(&path))?);
+    }
+    service.serve(listener, &scheduler);
+    Ok(())
+}
+
+struct Args {
+    dir: String,
+    address: String,
+    jobs: usize,
+    threads_per_job: Option<usize>,
+    limits: ServiceLimits,
+    api_keys: Option<String>,
+    key_registry: bool,
+}
+
+fn parse_args() -> Option<Args> {
+    let mut args = env::args().skip(1);
+    let mut parsed = Args {
+        dir: args.next()?,
+        address: args.next()?,
+        jobs: 1,
+        threads_per_job: None,
+        limits: ServiceLimits::default(),
+        api_keys: None,
+        key_registry: false,
+    };
+
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--jobs" => parsed.jobs = args.next()?.parse().ok().filter(|jobs| *jobs > 0)?,
+            "--threads-per-job" => parsed.threads_per_job = Some(args.next()?.parse().ok().filter(|threads| *threads > 0)?),
+            "--max-blocks" => parsed.limits.max_blocks = args.next()?.parse().ok()?,
+            "--max-held-jobs" => parsed.limits.max_jobs = args.next()?.parse().ok()?,
+            "--max-jobs-per-client" => parsed.limits.max_jobs_per_client = args.next()?.parse().ok()?,
+            "--api-keys" => parsed.api_keys = Some(args.next()?),
+            "--key-registry" => parsed.key_registry = true,
+            _ => return None,
+        }
+    }
+    Some(parsed)
+}
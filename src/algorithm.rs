@@ -0,0 +1,198 @@
+// This module is the registry of the hash algorithms supported by this crate, so that one deployed service (and the
+// example binaries) can select the algorithm at runtime by name. Padding is client side and always available, while
+// the homomorphic evaluation requires the "server" feature.
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "server")]
+use tfhe::boolean::prelude::*;
+use crate::padding::{pad_sha256_data, pad_streebog_data, padded_len, streebog_padded_len};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Algorithm {
+    Sha256,
+    Sm3,
+    Streebog256,
+    Streebog512,
+}
+
+impl Algorithm {
+    pub const ALL: &'static [Algorithm] = &[Algorithm::Sha256, Algorithm::Sm3, Algorithm::Streebog256, Algorithm::Streebog512];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Sm3 => "sm3",
+            Algorithm::Streebog256 => "streebog256",
+            Algorithm::Streebog512 => "streebog512",
+        }
+    }
+
+    // Case-insensitive lookup by name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|algorithm| algorithm.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn digest_bits(&self) -> usize {
+        match self {
+            Algorithm::Sha256 | Algorithm::Sm3 | Algorithm::Streebog256 => 256,
+            Algorithm::Streebog512 => 512,
+        }
+    }
+
+    pub fn block_bits(&self) -> usize {
+        match self {
+            Algorithm::Sha256 | Algorithm::Sm3 | Algorithm::Streebog256 | Algorithm::Streebog512 => 512,
+        }
+    }
+
+    // Padded input bits, ready to be encrypted
+    pub fn pad(&self, data: &[u8]) -> Vec<bool> {
+        match self {
+            Algorithm::Sha256 | Algorithm::Sm3 => pad_sha256_data(data),
+            Algorithm::Streebog256 | Algorithm::Streebog512 => pad_streebog_data(data),
+        }
+    }
+
+    // Length in bits of the padded input for a message of message_len bytes
+    pub fn padded_len(&self, message_len: usize) -> usize {
+        match self {
+            Algorithm::Sha256 | Algorithm::Sm3 => padded_len(message_len),
+            Algorithm::Streebog256 | Algorithm::Streebog512 => streebog_padded_len(message_len),
+        }
+    }
+
+    // Encrypted digest of an encrypted input padded with pad()
+    #[cfg(feature = "server")]
+    pub fn hash(&self, padded_input: Vec<Ciphertext>, sk: &ServerKey) -> Vec<Ciphertext> {
+        match self {
+            Algorithm::Sha256 => crate::sha256::sha256_fhe(padded_input, sk),
+            Algorithm::Sm3 => crate::sm3::sm3_fhe(padded_input, sk),
+            Algorithm::Streebog256 => crate::streebog::streebog_fhe(padded_input, 256, sk),
+            Algorithm::Streebog512 => crate::streebog::streebog_fhe(padded_input, 512, sk),
+        }
+    }
+
+    // Bootstrapped gates of the compression of an encrypted block into an encrypted state. The first block starts from
+    // the public initial state, where a few gates are free, so this is an upper bound for every block
+    #[cfg(feature = "server")]
+    pub fn bootstrapped_gates_per_block(&self) -> u64 {
+        match self {
+            Algorithm::Sha256 if cfg!(feature = "ladner_fischer") => 171_094,
+            Algorithm::Sha256 => 145_943,
+            Algorithm::Sm3 if cfg!(feature = "ladner_fischer") => 139_360,
+            Algorithm::Sm3 => 126_112,
+            Algorithm::Streebog256 | Algorithm::Streebog512 if cfg!(feature = "ladner_fischer") => 1_774_416,
+            Algorithm::Streebog256 | Algorithm::Streebog512 => 1_770_543,
+        }
+    }
+
+    // Bootstrapped gates of the hash of a padded input of the given number of blocks, an upper bound as above. Streebog
+    // compresses its checksum once more after the blocks
+    #[cfg(feature = "server")]
+    pub fn bootstrapped_gates(&self, blocks: usize) -> u64 {
+        let compressions = match self {
+            Algorithm::Sha256 | Algorithm::Sm3 => blocks,
+            Algorithm::Streebog256 | Algorithm::Streebog512 => blocks + 1,
+        };
+        self.bootstrapped_gates_per_block() * compressions as u64
+    }
+
+    // Netlist of the hash of a padded input of the given number of blocks (see the netlist module), or None if the
+    // algorithm has no netlist (only sha256 has one)
+    #[cfg(feature = "server")]
+    pub fn netlist(&self, blocks: usize, adder: crate::netlist::Adder) -> Option<crate::netlist::Netlist> {
+        match self {
+            Algorithm::Sha256 => Some(crate::netlist::sha256_netlist(blocks, adder)),
+            Algorithm::Sm3 | Algorithm::Streebog256 | Algorithm::Streebog512 => None,
+        }
+    }
+
+    // Server side padding of an encrypted message whose length is public
+    #[cfg(feature = "server")]
+    pub fn pad_encrypted(&self, message: Vec<Ciphertext>, sk: &ServerKey) -> Vec<Ciphertext> {
+        match self {
+            Algorithm::Sha256 => crate::sha256::pad_encrypted(message, sk),
+            Algorithm::Sm3 => crate::sm3::pad_encrypted(message, sk),
+            Algorithm::Streebog256 | Algorithm::Streebog512 => crate::streebog::pad_encrypted(message, sk),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        for algorithm in Algorithm::ALL {
+            assert_eq!(Algorithm::from_name(algorithm.name()), Some(*algorithm));
+        }
+        assert_eq!(Algorithm::from_name("SHA256"), Some(Algorithm::Sha256));
+        assert_eq!(Algorithm::from_name("SM3"), Some(Algorithm::Sm3));
+        assert_eq!(Algorithm::from_name("Streebog512"), Some(Algorithm::Streebog512));
+        assert_eq!(Algorithm::from_name("md5"), None);
+    }
+
+    #[test]
+    #[cfg(feature = "server")]
+    fn test_hash() {
+        use crate::boolean_ops::trivial_bytes;
+        use crate::client::bools_to_hex;
+        use crate::streebog::{streebog256, streebog512};
+        use crate::test_keys::test_keys;
+
+        let (ck, sk) = test_keys();
+        let decrypt = |bits: &[Ciphertext]| -> Vec<bool> { bits.iter().map(|bit| ck.decrypt(bit)).collect() };
+        // Streebog is checked against the padding of its module, whose examples of RFC 6986 are tested there
+        let message = trivial_bytes(b"abc", &sk);
+
+        for algorithm in Algorithm::ALL {
+            let input = algorithm.pad(b"abc").iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+            let digest = decrypt(&algorithm.hash(input, &sk));
+
+            assert_eq!(digest.len(), algorithm.digest_bits());
+            assert_eq!(algorithm.pad(b"abc").len(), algorithm.padded_len(3));
+            assert_eq!(algorithm.padded_len(3) % algorithm.block_bits(), 0);
+            let expected = match algorithm {
+                Algorithm::Sha256 => "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+                Algorithm::Sm3 => "66c7f0f462eeedd9d1f2d46bdc10e4e24167c4875cf2f7a2297da02b8f4ba8e0",
+                Algorithm::Streebog256 => &bools_to_hex(decrypt(&streebog256(&message, &sk))),
+                Algorithm::Streebog512 => &bools_to_hex(decrypt(&streebog512(&message, &sk))),
+            };
+            assert_eq!(bools_to_hex(digest), expected, "{}", algorithm);
+        }
+    }
+
+    // Gates are only bootstrapped on encrypted inputs, so this takes minutes. The count is process-wide, so it's only
+    // exact when no other test bootstraps gates concurrently, as with `cargo test -- --ignored`. A third block of input
+    // is compressed into an encrypted state, so it adds the gates of a block
+    #[test]
+    #[ignore]
+    #[cfg(feature = "server")]
+    fn test_bootstrapped_gates_per_block() {
+        use crate::boolean_ops::bootstrapped_gates;
+        use crate::test_keys::test_keys;
+
+        let (ck, sk) = test_keys();
+
+        for algorithm in Algorithm::ALL {
+            let input = |blocks| (0..blocks * algorithm.block_bits()).map(|i| ck.encrypt(i % 3 == 0)).collect();
+            let (two_blocks, three_blocks) = (input(2), input(3));
+
+            let gates = bootstrapped_gates();
+            algorithm.hash(two_blocks, &sk);
+            let two_blocks_gates = bootstrapped_gates() - gates;
+            algorithm.hash(three_blocks, &sk);
+            let three_blocks_gates = bootstrapped_gates() - gates - two_blocks_gates;
+
+            assert_eq!(three_blocks_gates - two_blocks_gates, algorithm.bootstrapped_gates_per_block(), "{}", algorithm);
+        }
+    }
+}
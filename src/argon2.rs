@@ -0,0 +1,415 @@
+// This module is an EXPERIMENTAL implementation of Argon2id (RFC 9106, version 0x13) over an encrypted password, for
+// research into encrypted password hardening. It is built on the BLAKE2b engine, and only supports a single lane with
+// small memory and time parameters: every 1 KiB block of memory is 8192 ciphertexts, so even the minimum of 8 blocks
+// takes hundreds of megabytes with real encryption.
+//
+// The first half of the first pass uses data-independent addressing, whose reference blocks only depend on public
+// values and are computed in the clear. Afterwards, the reference block depends on the (encrypted) previous block, so
+// its index is computed homomorphically and the block is selected obliviously with a tree of MUXes over all the
+// candidates.
+//
+// Computing a block takes long, so the computation is exposed as an Argon2State that fills one block per step and can
+// be serialized between steps, to checkpoint as often as every block.
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tfhe::boolean::prelude::*;
+use crate::blake2b::{blake2b, Word};
+use crate::boolean_ops::{
+    accumulate, and_gate, from_le_bytes, rotate_right, select_index, sub, swap_bytes, trivial_bytes, trivial_word, xor, xor_gate,
+};
+
+const VERSION: u32 = 0x13;
+// Argon2 type code of Argon2id
+const TYPE_ID: u32 = 2;
+const BLOCK_WORDS: usize = 128;
+const BLOCK_BITS: usize = 64 * BLOCK_WORDS;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Argon2Params {
+    // Memory size m in 1 KiB blocks (at least 8)
+    pub memory_blocks: u32,
+    // Number of passes t over the memory
+    pub iterations: u32,
+    // Tag length in bytes (at least 4)
+    pub tag_len: u32,
+}
+
+impl Argon2Params {
+    pub fn new(memory_blocks: u32, iterations: u32, tag_len: u32) -> Self {
+        assert!(memory_blocks >= 8, "memory must be at least 8 blocks");
+        assert!(iterations >= 1, "at least one pass is needed");
+        assert!(tag_len >= 4, "tag must be at least 4 bytes long");
+
+        Argon2Params { memory_blocks, iterations, tag_len }
+    }
+
+    // The memory is a whole number of blocks per segment, with 4 segments per lane
+    fn lane_length(&self) -> usize {
+        self.memory_blocks as usize / 4 * 4
+    }
+}
+
+// Encrypted Argon2id tag of the password, with a public salt (of at least 8 bytes)
+pub fn argon2id(password: &[Ciphertext], salt: &[u8], params: Argon2Params, sk: &ServerKey) -> Vec<Ciphertext> {
+    let mut state = Argon2State::new(password, salt, params, sk);
+    while !state.is_done() {
+        state.step(sk);
+    }
+    state.finalize(sk)
+}
+
+// Memory and position of an Argon2id computation
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Argon2State {
+    params: Argon2Params,
+    // Blocks computed so far, as 128 words of 64 bits
+    memory: Vec<Vec<Ciphertext>>,
+    pass: usize,
+    // Next block to compute in the current pass
+    position: usize,
+}
+
+impl Argon2State {
+    // Hashes the inputs and fills the first two blocks
+    pub fn new(password: &[Ciphertext], salt: &[u8], params: Argon2Params, sk: &ServerKey) -> Self {
+        assert_eq!(password.len() % 8, 0, "password is not a whole number of bytes");
+        assert!(salt.len() >= 8, "salt must be at least 8 bytes long");
+
+        // One lane, and no secret nor associated data
+        let le32 = |value: u32| trivial_bytes(&value.to_le_bytes(), sk);
+        let mut input = vec![];
+        for value in [1, params.tag_len, params.memory_blocks, params.iterations, VERSION, TYPE_ID, password.len() as u32 / 8] {
+            input.extend(le32(value));
+        }
+        input.extend_from_slice(password);
+        input.extend(le32(salt.len() as u32));
+        input.extend(trivial_bytes(salt, sk));
+        input.extend(le32(0));
+        input.extend(le32(0));
+        let h0 = blake2b(&input, 64, sk);
+
+        let memory = (0..2u32)
+            .map(|i| {
+                let mut seed = h0.clone();
+                seed.extend(le32(i));
+                seed.extend(le32(0));
+                let bytes = h_prime(&seed, 1024, sk);
+                bytes.chunks(64).flat_map(from_le_bytes::<64>).collect()
+            })
+            .collect();
+
+        Argon2State { params, memory, pass: 0, position: 2 }
+    }
+
+    pub fn params(&self) -> Argon2Params {
+        self.params
+    }
+
+    // Pass and position of the next block, for progress reports
+    pub fn progress(&self) -> (usize, usize) {
+        (self.pass, self.position)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pass == self.params.iterations as usize
+    }
+
+    // Computes the next block from the previous one and a reference block
+    pub fn step(&mut self, sk: &ServerKey) {
+        assert!(!self.is_done(), "every pass is already done");
+
+        let lane_length = self.params.lane_length();
+        let previous = if self.position == 0 { lane_length - 1 } else { self.position - 1 };
+        let reference = self.reference_block(sk);
+
+        let mut block = compress(&self.memory[previous], &reference, sk);
+        if self.pass > 0 {
+            // Passes after the first XOR the new block into the old one
+            block = xor_blocks(&block, &self.memory[self.position], sk);
+        }
+
+        if self.pass == 0 {
+            self.memory.push(block);
+        } else {
+            self.memory[self.position] = block;
+        }
+
+        self.position += 1;
+        if self.position == lane_length {
+            self.position = 0;
+            self.pass += 1;
+        }
+    }
+
+    // Tag of the requested length, from the last block of the lane
+    pub fn finalize(self, sk: &ServerKey) -> Vec<Ciphertext> {
+        assert!(self.is_done(), "the passes over the memory are not done");
+
+        let last = &self.memory[self.params.lane_length() - 1];
+        let bytes: Vec<Ciphertext> = last.chunks(64).flat_map(|word| swap_bytes::<64>(&word.to_vec().try_into().unwrap())).collect();
+        h_prime(&bytes, self.params.tag_len as usize, sk)
+    }
+
+    fn reference_block(&self, sk: &ServerKey) -> Vec<Ciphertext> {
+        let lane_length = self.params.lane_length();
+        let segment_length = lane_length / 4;
+        let (slice, index) = (self.position / segment_length, self.position % segment_length);
+
+        // Blocks that can be referenced, counted from start in memory order
+        let (area_size, start) = if self.pass == 0 {
+            (slice * segment_length + index - 1, 0)
+        } else {
+            (lane_length - segment_length + index - 1, (slice + 1) % 4 * segment_length)
+        };
+
+        if self.pass == 0 && slice < 2 {
+            let j1 = self.address(slice, index) & 0xffffffff;
+            let relative = area_size as u64 - 1 - ((area_size as u64 * ((j1 * j1) >> 32)) >> 32);
+            return self.memory[(start + relative as usize) % lane_length].clone();
+        }
+
+        // J1 is the low half of the first word of the previous block
+        let previous = if self.position == 0 { lane_length - 1 } else { self.position - 1 };
+        let j1: [Ciphertext; 32] = self.memory[previous][32..64].to_vec().try_into().unwrap();
+        let relative = encrypted_relative_position(&j1, area_size as u64, sk);
+
+        let candidates = (0..area_size).map(|i| self.memory[(start + i) % lane_length].clone()).collect();
+        select_index(&relative, candidates, sk)
+    }
+
+    // Pseudo-random value of the data-independent addressing, computed in the clear
+    fn address(&self, slice: usize, index: usize) -> u64 {
+        let mut input = [0u64; BLOCK_WORDS];
+        input[..7].copy_from_slice(&[
+            self.pass as u64,
+            0,
+            slice as u64,
+            self.params.lane_length() as u64,
+            self.params.iterations as u64,
+            TYPE_ID as u64,
+            (index / BLOCK_WORDS + 1) as u64,
+        ]);
+
+        let zero = [0u64; BLOCK_WORDS];
+        let addresses = compress_plain(&zero, &compress_plain(&zero, &input));
+        addresses[index % BLOCK_WORDS]
+    }
+}
+
+// area_size - 1 - ((area_size * (j1^2 >> 32)) >> 32), the position of the reference block within the reference area
+fn encrypted_relative_position(j1: &[Ciphertext; 32], area_size: u64, sk: &ServerKey) -> [Ciphertext; 32] {
+    let square: [Ciphertext; 64] = accumulate::<64, 64>(&shifted_products(j1, j1, 0, sk), sk);
+    let x: [Ciphertext; 32] = square[..32].to_vec().try_into().unwrap();
+
+    // area_size is public, so the product is a sum of shifted copies of x
+    let mut shifted = vec![];
+    for k in (0..32).filter(|k| (area_size >> k) & 1 == 1) {
+        let mut word = trivial_word::<64>(0, sk);
+        word[32 - k..64 - k].clone_from_slice(&x);
+        shifted.push(word);
+    }
+    let product: [Ciphertext; 64] = accumulate::<64, 64>(&shifted, sk);
+    let y: [Ciphertext; 32] = product[..32].to_vec().try_into().unwrap();
+
+    sub(&trivial_word(area_size - 1, sk), &y, sk)
+}
+
+// The partial products of a * b, each one shifted left by shift more bits, as 64-bit words (bits past 64 are dropped)
+fn shifted_products(a: &[Ciphertext; 32], b: &[Ciphertext; 32], shift: usize, sk: &ServerKey) -> Vec<[Ciphertext; 64]> {
+    (0..32)
+        .into_par_iter()
+        .map(|j| {
+            let mut word = trivial_word::<64>(0, sk);
+            for k in (0..32).filter(|k| k + j + shift < 64) {
+                word[63 - (k + j + shift)] = and_gate(sk, &a[31 - k], &b[31 - j]);
+            }
+            word
+        })
+        .collect()
+}
+
+// Variable-length hash H' built on BLAKE2b
+fn h_prime(input: &[Ciphertext], output_len: usize, sk: &ServerKey) -> Vec<Ciphertext> {
+    let mut data = trivial_bytes(&(output_len as u32).to_le_bytes(), sk);
+    data.extend_from_slice(input);
+
+    if output_len <= 64 {
+        return blake2b(&data, output_len, sk);
+    }
+
+    // Each intermediate hash contributes its first 32 bytes, and the last one covers the rest
+    let r = output_len.div_ceil(32) - 2;
+    let mut v = blake2b(&data, 64, sk);
+    let mut output = v[..256].to_vec();
+    for _ in 1..r {
+        v = blake2b(&v, 64, sk);
+        output.extend_from_slice(&v[..256]);
+    }
+    output.extend(blake2b(&v, output_len - 32 * r, sk));
+
+    output
+}
+
+// Compression function G of two blocks: the permutation P is applied to the rows and then to the columns of X xor Y,
+// and the result is XORed with X xor Y again
+fn compress(x: &[Ciphertext], y: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    let r = xor_blocks(x, y, sk);
+    let mut q: Vec<Word> = r.chunks(64).map(|word| word.to_vec().try_into().unwrap()).collect();
+
+    // Rows are 16 consecutive words, columns are pairs of words taken from each row
+    let rows: Vec<[usize; 16]> = (0..8).map(|i| std::array::from_fn(|k| 16 * i + k)).collect();
+    let columns: Vec<[usize; 16]> = (0..8).map(|i| std::array::from_fn(|k| 16 * (k / 2) + 2 * i + k % 2)).collect();
+
+    for groups in [rows, columns] {
+        let permuted: Vec<[Word; 16]> = groups
+            .par_iter()
+            .map(|group| permute(std::array::from_fn(|k| q[group[k]].clone()), sk))
+            .collect();
+
+        for (words, group) in permuted.into_iter().zip(groups) {
+            for (word, index) in words.into_iter().zip(group) {
+                q[index] = word;
+            }
+        }
+    }
+
+    let z: Vec<Ciphertext> = q.into_iter().flatten().collect();
+    xor_blocks(&z, &r, sk)
+}
+
+// Permutation P, the BLAKE2b round with the multiplication-hardened BlaMka mixing function
+fn permute(mut v: [Word; 16], sk: &ServerKey) -> [Word; 16] {
+    const STEPS: [[[usize; 4]; 4]; 2] = [
+        [[0, 4, 8, 12], [1, 5, 9, 13], [2, 6, 10, 14], [3, 7, 11, 15]],
+        [[0, 5, 10, 15], [1, 6, 11, 12], [2, 7, 8, 13], [3, 4, 9, 14]],
+    ];
+
+    for step in STEPS {
+        let mixed: Vec<[Word; 4]> = step.par_iter().map(|[a, b, c, d]| mix([&v[*a], &v[*b], &v[*c], &v[*d]], sk)).collect();
+
+        for (words, indices) in mixed.into_iter().zip(step) {
+            for (word, index) in words.into_iter().zip(indices) {
+                v[index] = word;
+            }
+        }
+    }
+
+    v
+}
+
+fn mix([a, b, c, d]: [&Word; 4], sk: &ServerKey) -> [Word; 4] {
+    let a = blamka(a, b, sk);
+    let d = rotate_right(&xor(d, &a, sk), 32, sk);
+    let c = blamka(c, &d, sk);
+    let b = rotate_right(&xor(b, &c, sk), 24, sk);
+
+    let a = blamka(&a, &b, sk);
+    let d = rotate_right(&xor(&d, &a, sk), 16, sk);
+    let c = blamka(&c, &d, sk);
+    let b = rotate_right(&xor(&b, &c, sk), 63, sk);
+
+    [a, b, c, d]
+}
+
+// x + y + 2 * lo(x) * lo(y), with the partial products of the multiplication summed in the same CSA tree as x and y
+fn blamka(x: &Word, y: &Word, sk: &ServerKey) -> Word {
+    let lo = |word: &Word| -> [Ciphertext; 32] { word[32..].to_vec().try_into().unwrap() };
+
+    let mut operands = shifted_products(&lo(x), &lo(y), 1, sk);
+    operands.push(x.clone());
+    operands.push(y.clone());
+
+    accumulate::<64, 64>(&operands, sk)
+}
+
+fn xor_blocks(a: &[Ciphertext], b: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    assert!(a.len() == BLOCK_BITS && b.len() == BLOCK_BITS, "blocks are 8192 bits long");
+    a.par_iter().zip(b.par_iter()).map(|(a, b)| xor_gate(sk, a, b)).collect()
+}
+
+// Compression function G in the clear, for the data-independent addresses
+fn compress_plain(x: &[u64; BLOCK_WORDS], y: &[u64; BLOCK_WORDS]) -> [u64; BLOCK_WORDS] {
+    let r: [u64; BLOCK_WORDS] = std::array::from_fn(|i| x[i] ^ y[i]);
+    let mut q = r;
+
+    for i in 0..8 {
+        let row: [usize; 16] = std::array::from_fn(|k| 16 * i + k);
+        permute_plain(&mut q, &row);
+    }
+    for i in 0..8 {
+        let column: [usize; 16] = std::array::from_fn(|k| 16 * (k / 2) + 2 * i + k % 2);
+        permute_plain(&mut q, &column);
+    }
+
+    std::array::from_fn(|i| q[i] ^ r[i])
+}
+
+fn permute_plain(q: &mut [u64; BLOCK_WORDS], indices: &[usize; 16]) {
+    let blamka = |x: u64, y: u64| x.wrapping_add(y).wrapping_add(((x & 0xffffffff) * (y & 0xffffffff)).wrapping_mul(2));
+    let mut mix = |a: usize, b: usize, c: usize, d: usize| {
+        let (a, b, c, d) = (indices[a], indices[b], indices[c], indices[d]);
+        q[a] = blamka(q[a], q[b]);
+        q[d] = (q[d] ^ q[a]).rotate_right(32);
+        q[c] = blamka(q[c], q[d]);
+        q[b] = (q[b] ^ q[c]).rotate_right(24);
+        q[a] = blamka(q[a], q[b]);
+        q[d] = (q[d] ^ q[a]).rotate_right(16);
+        q[c] = blamka(q[c], q[d]);
+        q[b] = (q[b] ^ q[c]).rotate_right(63);
+    };
+
+    mix(0, 4, 8, 12);
+    mix(1, 5, 9, 13);
+    mix(2, 6, 10, 14);
+    mix(3, 7, 11, 15);
+    mix(0, 5, 10, 15);
+    mix(1, 6, 11, 12);
+    mix(2, 7, 8, 13);
+    mix(3, 4, 9, 14);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{decrypt_bytes, from_hex};
+    use crate::test_keys::test_keys;
+
+    #[test]
+    fn test_blamka() {
+        let (ck, sk) = test_keys();
+
+        let (x, y) = (0xfedcba9876543210u64, 0x0123456789abcdefu64);
+        let expected = x.wrapping_add(y).wrapping_add(((x & 0xffffffff) * (y & 0xffffffff)).wrapping_mul(2));
+        let result = blamka(&trivial_word(x, &sk), &trivial_word(y, &sk), &sk);
+        assert_eq!(result.iter().fold(0u64, |acc, bit| (acc << 1) | ck.decrypt(bit) as u64), expected);
+    }
+
+    // Tags checked against the RustCrypto argon2 crate (password "password", salt "somesalt", one lane), computed with
+    // the clear backend. The first one only takes one pass, while the second one also covers the later passes
+    #[test]
+    fn test_argon2id() {
+        let (ck, sk) = test_keys();
+        let password = trivial_bytes(b"password", &sk);
+
+        let tag = argon2id(&password, b"somesalt", Argon2Params::new(8, 1, 32), &sk);
+        assert_eq!(decrypt_bytes(&tag, &ck), from_hex("f137f8e186a403a679ccd0606e5ab5dcdafe43c1640855ac8c6e33e9bd63eeb3"));
+    }
+
+    #[test]
+    fn test_argon2id_checkpoint() {
+        let (ck, sk) = test_keys();
+        let password = trivial_bytes(b"password", &sk);
+
+        // Resuming from a serialized state after every block gives the same tag
+        let mut state = Argon2State::new(&password, b"somesalt", Argon2Params::new(8, 2, 32), &sk);
+        while !state.is_done() {
+            state.step(&sk);
+            state = bincode::deserialize(&bincode::serialize(&state).unwrap()).unwrap();
+        }
+        assert_eq!(
+            decrypt_bytes(&state.finalize(&sk), &ck),
+            from_hex("fdb4ddb6d5887131b66f0b2a3740c077dd05b755845861f6b5a1dde8b1071646"),
+        );
+    }
+}
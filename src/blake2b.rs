@@ -0,0 +1,164 @@
+// This module implements BLAKE2b (RFC 7693) with the generic 64-bit word operations, plain or keyed, with any output
+// length from 1 to 64 bytes. The message and the key are encrypted while their lengths are public, so the block
+// counters and the final block flag are public and only need trivial ciphertexts.
+//
+// BLAKE2b reads the message as little-endian 64-bit words and outputs the little-endian encoding of its state, with
+// every byte stored most significant bit first, like everywhere else in the crate.
+
+use rayon::prelude::*;
+use tfhe::boolean::prelude::*;
+use crate::boolean_ops::{accumulate, add, from_le_bytes, rotate_right, swap_bytes, trivial_word, xor, xor_constant};
+
+pub type Word = [Ciphertext; 64];
+
+pub const BLOCK_BITS: usize = 1024;
+
+const IV: [u64; 8] = [
+    0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+    0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
+];
+
+const SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+// The G function is applied to the columns of the 4x4 state and then to its diagonals
+const COLUMNS: [[usize; 4]; 4] = [[0, 4, 8, 12], [1, 5, 9, 13], [2, 6, 10, 14], [3, 7, 11, 15]];
+const DIAGONALS: [[usize; 4]; 4] = [[0, 5, 10, 15], [1, 6, 11, 12], [2, 7, 8, 13], [3, 4, 9, 14]];
+
+// Unkeyed hash of output_len bytes
+pub fn blake2b(message: &[Ciphertext], output_len: usize, sk: &ServerKey) -> Vec<Ciphertext> {
+    blake2b_keyed(&[], message, output_len, sk)
+}
+
+// Keyed hash (a MAC) of output_len bytes, with a key of up to 64 bytes
+pub fn blake2b_keyed(key: &[Ciphertext], message: &[Ciphertext], output_len: usize, sk: &ServerKey) -> Vec<Ciphertext> {
+    assert!((1..=64).contains(&output_len), "output length must be between 1 and 64 bytes");
+    assert!(key.len().is_multiple_of(8) && key.len() <= 512, "key must be a whole number of bytes, at most 64");
+    assert_eq!(message.len() % 8, 0, "message is not a whole number of bytes");
+
+    let parameter_block = 0x01010000 ^ ((key.len() as u64 / 8) << 8) ^ output_len as u64;
+    let mut h: [Word; 8] = std::array::from_fn(|i| trivial_word(IV[i] ^ if i == 0 { parameter_block } else { 0 }, sk));
+
+    // A key is padded to a whole block that comes before the message
+    let mut data = key.to_vec();
+    if !key.is_empty() {
+        data.resize(BLOCK_BITS, sk.trivial_encrypt(false));
+    }
+    data.extend_from_slice(message);
+
+    // The last block is zero padded (an empty input is a single block of zeros), and the counter only covers the bytes
+    // actually hashed
+    let total_bytes = data.len() as u128 / 8;
+    let blocks = data.len().div_ceil(BLOCK_BITS).max(1);
+    data.resize(blocks * BLOCK_BITS, sk.trivial_encrypt(false));
+
+    for (i, block) in data.chunks(BLOCK_BITS).enumerate() {
+        let last = i == blocks - 1;
+        let counter = if last { total_bytes } else { (i as u128 + 1) * 128 };
+        compress(&mut h, block, counter, last, sk);
+    }
+
+    h.iter().flat_map(swap_bytes).take(8 * output_len).collect()
+}
+
+// Compression function F, mixing a 1024-bit block into the state. The counter is the number of bytes hashed so far
+pub fn compress(h: &mut [Word; 8], block: &[Ciphertext], counter: u128, last: bool, sk: &ServerKey) {
+    assert_eq!(block.len(), BLOCK_BITS, "block is not 1024 bits long");
+
+    let m: Vec<Word> = block.chunks(64).map(from_le_bytes).collect();
+
+    let mut v: [Word; 16] = std::array::from_fn(|i| if i < 8 { h[i].clone() } else { trivial_word(IV[i - 8], sk) });
+    v[12] = xor_constant(&v[12], counter as u64, sk);
+    v[13] = xor_constant(&v[13], (counter >> 64) as u64, sk);
+    if last {
+        v[14] = xor_constant(&v[14], u64::MAX, sk);
+    }
+
+    for round in 0..12 {
+        let s = &SIGMA[round % 10];
+
+        // The four G functions of each step touch different words, so they run in parallel
+        for (step, indices) in [COLUMNS, DIAGONALS].iter().enumerate() {
+            let mixed: Vec<[Word; 4]> = indices
+                .par_iter()
+                .enumerate()
+                .map(|(i, [a, b, c, d])| {
+                    let x = &m[s[8 * step + 2 * i]];
+                    let y = &m[s[8 * step + 2 * i + 1]];
+                    g([&v[*a], &v[*b], &v[*c], &v[*d]], x, y, sk)
+                })
+                .collect();
+
+            for (words, indices) in mixed.into_iter().zip(indices) {
+                for (word, index) in words.into_iter().zip(indices) {
+                    v[*index] = word;
+                }
+            }
+        }
+    }
+
+    for i in 0..8 {
+        h[i] = xor(&h[i], &xor(&v[i], &v[i + 8], sk), sk);
+    }
+}
+
+// Mixing function G, where a + b + x is a single CSA followed by one addition
+fn g([a, b, c, d]: [&Word; 4], x: &Word, y: &Word, sk: &ServerKey) -> [Word; 4] {
+    let a = accumulate::<64, 64>(&[a.clone(), b.clone(), x.clone()], sk);
+    let d = rotate_right(&xor(d, &a, sk), 32, sk);
+    let c = add(c, &d, sk);
+    let b = rotate_right(&xor(b, &c, sk), 24, sk);
+
+    let a = accumulate::<64, 64>(&[a, b.clone(), y.clone()], sk);
+    let d = rotate_right(&xor(&d, &a, sk), 16, sk);
+    let c = add(&c, &d, sk);
+    let b = rotate_right(&xor(&b, &c, sk), 63, sk);
+
+    [a, b, c, d]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boolean_ops::trivial_bytes;
+    use crate::test_helpers::{decrypt_bytes, from_hex};
+    use crate::test_keys::test_keys;
+
+    // RFC 7693 appendix A, plus an empty, a multi-block and a keyed input (checked against Python's hashlib), computed
+    // with the clear backend
+    #[test]
+    fn test_blake2b() {
+        let (ck, sk) = test_keys();
+
+        assert_eq!(
+            decrypt_bytes(&blake2b(&trivial_bytes(b"abc", &sk), 64, &sk), &ck),
+            from_hex("ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d17d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923"),
+        );
+        assert_eq!(
+            decrypt_bytes(&blake2b(&[], 64, &sk), &ck),
+            from_hex("786a02f742015903c6c6fd852552d272912f4740e15847618a86e217f71f5419d25e1031afee585313896444934eb04b903a685b1448b755d56f701afe9be2ce"),
+        );
+
+        let message: Vec<u8> = (0..200).collect();
+        assert_eq!(
+            decrypt_bytes(&blake2b(&trivial_bytes(&message, &sk), 32, &sk), &ck),
+            from_hex("63c3d97a9f8894d5e043a707b0fee7f7ec4c049a23bbf1079df20b4165f9e22d"),
+        );
+
+        let key: Vec<u8> = (0..64).collect();
+        assert_eq!(
+            decrypt_bytes(&blake2b_keyed(&trivial_bytes(&key, &sk), &trivial_bytes(&[0, 1, 2], &sk), 16, &sk), &ck),
+            from_hex("ccf63a654ecd4b2e373ef98b7e8776f0"),
+        );
+    }
+}
@@ -1,25 +1,39 @@
 // This module contains all the operations and functions used in the sha256 function, implemented with homomorphic boolean
 // operations. Both the bitwise operations, which serve as the building blocks for other functions, and the adders employ
 // parallel processing techniques.
+//
+// Words are arrays of N encrypted bits, most significant bit first. Except for the sha256 sigma functions, every
+// operation is generic over the word width so that 32-bit and 64-bit algorithms share the same implementation (the
+// adders require N to be a power of two).
 
+use std::sync::atomic::{AtomicU64, Ordering};
 use rayon::prelude::*;
 use tfhe::boolean::prelude::{BinaryBooleanGates, Ciphertext, ServerKey};
 
+// Gates evaluated by these operations that needed a bootstrap, over the whole process. Gates with a trivial operand are
+// evaluated without one and aren't counted
+static BOOTSTRAPPED_GATES: AtomicU64 = AtomicU64::new(0);
+
 // Implementation of a Carry Save Adder, which computes sum and carry sequences very efficiently. We then add the final
 // sum and carry values to obtain the result. CSAs are useful to speed up sequential additions
-pub fn csa(a: &[Ciphertext; 32], b: &[Ciphertext; 32], c: &[Ciphertext; 32], sk: &ServerKey) -> ([Ciphertext; 32], [Ciphertext; 32]) {
+pub fn csa<const N: usize>(
+    a: &[Ciphertext; N],
+    b: &[Ciphertext; N],
+    c: &[Ciphertext; N],
+    sk: &ServerKey,
+) -> ([Ciphertext; N], [Ciphertext; N]) {
 
     let (carry, sum) = rayon::join(
         || {
-            maj(&a, &b, &c, sk)
+            maj(a, b, c, sk)
         },
         || {
-            xor(&a, &xor(&b, &c, sk), sk)
+            xor(a, &xor(b, c, sk), sk)
         },
     );
 
     // perform a left shift by one to discard the carry-out and set the carry-in to 0
-    let mut shifted_carry = trivial_bools(&[false; 32], sk);
+    let mut shifted_carry = trivial_bools(&[false; N], sk);
     for (i, elem) in carry.into_iter().enumerate() {
         if i == 0 {
             continue;
@@ -31,34 +45,123 @@ pub fn csa(a: &[Ciphertext; 32], b: &[Ciphertext; 32], c: &[Ciphertext; 32], sk:
     (sum, shifted_carry)
 }
 
-pub fn add(a: &[Ciphertext; 32], b: &[Ciphertext; 32], sk: &ServerKey) -> [Ciphertext; 32] {
+pub fn add<const N: usize>(a: &[Ciphertext; N], b: &[Ciphertext; N], sk: &ServerKey) -> [Ciphertext; N] {
+    add_with_carry(a, b, sk).0
+}
+
+// Returns the sum modulo 2^N along with the carry-out bit, for additions that must not silently wrap
+pub fn add_with_carry<const N: usize>(a: &[Ciphertext; N], b: &[Ciphertext; N], sk: &ServerKey) -> ([Ciphertext; N], Ciphertext) {
     let (propagate, generate) = rayon::join(
         || xor(a, b, sk),
         || and(a, b, sk)
     );
 
+    // The carries can be computed with a | b in place of a ^ b. When an operand is public (a constant, or a trivial
+    // initial value) a | b is free, and its known ones and the known zeros of the generate bits make most gates of the
+    // prefix network have a trivial operand, so they degenerate to copies instead of bootstrapping
+    let transmit = if is_trivial(a) || is_trivial(b) { or(a, b, sk) } else { propagate.clone() };
+
     #[cfg(feature = "ladner_fischer")]
-    let carry = ladner_fischer(&propagate, &generate, sk);
+    let (carry, carry_out) = ladner_fischer(&transmit, &generate, sk);
 
     #[cfg(not(feature = "ladner_fischer"))]
-    let carry = brent_kung(&propagate, &generate, sk);
+    let (carry, carry_out) = brent_kung(&transmit, &generate, sk);
 
     let sum = xor(&propagate, &carry, sk);
 
-    sum
+    (sum, carry_out)
+}
+
+// Adds a public N-bit constant (N <= 64), such as a round constant or a length field. It's the regular adder, which
+// takes the cheaper carry computation for public operands
+pub fn add_constant<const N: usize>(x: &[Ciphertext; N], constant: u64, sk: &ServerKey) -> [Ciphertext; N] {
+    add(x, &trivial_word(constant, sk), sk)
+}
+
+fn is_trivial(bits: &[Ciphertext]) -> bool {
+    bits.iter().all(|bit| matches!(bit, Ciphertext::Trivial(_)))
+}
+
+// Subtraction modulo 2^N, using the identity a - b = !(!a + b) so that only the existing adder and NOT gates (which
+// don't need bootstrapping) are required
+pub fn sub<const N: usize>(a: &[Ciphertext; N], b: &[Ciphertext; N], sk: &ServerKey) -> [Ciphertext; N] {
+    not(&add(&not(a, sk), b, sk), sk)
+}
+
+// Unsigned a < b, which is the carry-out of !a + b = (2^N - 1 - a) + b
+pub fn lt<const N: usize>(a: &[Ciphertext; N], b: &[Ciphertext; N], sk: &ServerKey) -> Ciphertext {
+    add_with_carry(&not(a, sk), b, sk).1
+}
+
+// Unsigned a <= b
+pub fn le<const N: usize>(a: &[Ciphertext; N], b: &[Ciphertext; N], sk: &ServerKey) -> Ciphertext {
+    sk.not(&lt(b, a, sk))
+}
+
+// Adds many N-bit words into an M-bit sum (M >= N), so that the total doesn't wrap modulo 2^N. The words are zero
+// extended (with trivial bits, which are free to operate on) and reduced with a tree of CSAs before the final addition
+pub fn accumulate<const N: usize, const M: usize>(words: &[[Ciphertext; N]], sk: &ServerKey) -> [Ciphertext; M] {
+    assert!(M >= N, "accumulator is narrower than the words");
+
+    let mut operands: Vec<[Ciphertext; M]> = words.iter().map(|word| {
+        let mut wide = trivial_bools(&[false; M], sk);
+        wide[M - N..].clone_from_slice(word);
+        wide
+    }).collect();
+
+    // Each level turns every three operands into two, in parallel
+    while operands.len() > 2 {
+        let leftover = operands.len() % 3;
+        let rest = operands.split_off(operands.len() - leftover);
+
+        let mut reduced: Vec<[Ciphertext; M]> = operands
+            .par_chunks(3)
+            .flat_map(|chunk| {
+                let (sum, carry) = csa(&chunk[0], &chunk[1], &chunk[2], sk);
+                vec![sum, carry]
+            })
+            .collect();
+
+        reduced.extend(rest);
+        operands = reduced;
+    }
+
+    match operands.len() {
+        0 => trivial_bools(&[false; M], sk),
+        1 => operands.pop().unwrap(),
+        _ => add(&operands[0], &operands[1], sk),
+    }
+}
+
+// Population count (Hamming weight) of any number of bits, such as a word or a digest, as an M-bit number. Each bit is a
+// 1-bit word for accumulate, so the tree of CSAs only bootstraps where the partial counts are, and the zero extension
+// is free. M must be a power of two (for the final adder) wide enough for the count, e.g. 16 for a 256-bit digest
+pub fn popcount<const M: usize>(bits: &[Ciphertext], sk: &ServerKey) -> [Ciphertext; M] {
+    assert!(M >= 64 || bits.len() < 1 << M, "accumulator is too narrow for the count");
+
+    let words: Vec<[Ciphertext; 1]> = bits.iter().map(|bit| [bit.clone()]).collect();
+    accumulate::<1, M>(&words, sk)
 }
 
 // Implementation of the Brent Kung parallel prefix algorithm
-// This function computes the carry signals in parallel while minimizing the number of homomorphic operations
+// This function computes the carry signals in parallel while minimizing the number of homomorphic operations. It also
+// returns the carry-out, which is the group generate of the whole word
 #[cfg(not(feature = "ladner_fischer"))]
-fn brent_kung(propagate: &[Ciphertext; 32], generate: &[Ciphertext; 32], sk: &ServerKey) -> [Ciphertext; 32] {
+fn brent_kung<const N: usize>(
+    propagate: &[Ciphertext; N],
+    generate: &[Ciphertext; N],
+    sk: &ServerKey,
+) -> ([Ciphertext; N], Ciphertext) {
+    assert!(N.is_power_of_two(), "word width is not a power of two");
+    let stages = N.trailing_zeros() as usize;
+
     let mut propagate = propagate.clone();
     let mut generate = generate.clone();
 
-    for d in 0..5 { // first 5 stages
+    for d in 0..stages { // first log2(N) stages
         let stride = 1 << d;
 
-        let indices: Vec<(usize, usize)> = (0..32 - stride)
+        let indices: Vec<(usize, usize)> = (0..N - stride)
             .rev()
             .step_by(2 * stride)
             .map(|i| i + 1 - stride)
@@ -72,11 +175,11 @@ fn brent_kung(propagate: &[Ciphertext; 32], generate: &[Ciphertext; 32], sk: &Se
 
             if n == 0 { // grey cell
                 new_p = propagate[index].clone();
-                new_g = sk.or(&generate[index], &sk.and(&generate[index + stride], &propagate[index]));
+                new_g = or_gate(sk, &generate[index], &and_gate(sk, &generate[index + stride], &propagate[index]));
 
             } else { // black cell
-                new_p = sk.and(&propagate[index], &propagate[index + stride]);
-                new_g = sk.or(&generate[index], &sk.and(&generate[index + stride], &propagate[index]));
+                new_p = and_gate(sk, &propagate[index], &propagate[index + stride]);
+                new_g = or_gate(sk, &generate[index], &and_gate(sk, &generate[index + stride], &propagate[index]));
             }
 
             (index, new_p, new_g)
@@ -87,10 +190,10 @@ fn brent_kung(propagate: &[Ciphertext; 32], generate: &[Ciphertext; 32], sk: &Se
             generate[index] = new_g;
         }
 
-        if d == 4 {
+        if d == stages - 1 {
             let mut cells = 0;
-            for d_2 in 0..4 { // last 4 stages
-                let stride = 1 << (4 - d_2 - 1);
+            for d_2 in 0..stages - 1 { // last log2(N) - 1 stages
+                let stride = 1 << (stages - d_2 - 2);
                 cells += 1 << d_2;
 
                 let indices: Vec<(usize, usize)> = (0..cells).map(|cell| {
@@ -98,7 +201,7 @@ fn brent_kung(propagate: &[Ciphertext; 32], generate: &[Ciphertext; 32], sk: &Se
                 }).collect();
 
                 let updates: Vec<(usize, Ciphertext)> = indices.into_par_iter().map(|(_, index)| {
-                    let new_g = sk.or(&generate[index], &sk.and(&generate[index+stride], &propagate[index]));
+                    let new_g = or_gate(sk, &generate[index], &and_gate(sk, &generate[index+stride], &propagate[index]));
 
                     (index, new_g)
                 }).collect();
@@ -110,25 +213,30 @@ fn brent_kung(propagate: &[Ciphertext; 32], generate: &[Ciphertext; 32], sk: &Se
         }
     }
 
-    let mut carry = trivial_bools(&[false; 32], sk);
-    for bit in 0..31 {
-        carry[bit] = generate[bit + 1].clone();
-    }
+    let mut carry = trivial_bools(&[false; N], sk);
+    carry[..N - 1].clone_from_slice(&generate[1..]);
 
-    carry
+    (carry, generate[0].clone())
 }
 
 // Implementation of the Ladner Fischer parallel prefix algorithm
 // This function may perform better than the previous one when many threads are available as it has less stages
 #[cfg(feature = "ladner_fischer")]
-fn ladner_fischer(propagate: &[Ciphertext; 32], generate: &[Ciphertext; 32], sk: &ServerKey) -> [Ciphertext; 32] {
+fn ladner_fischer<const N: usize>(
+    propagate: &[Ciphertext; N],
+    generate: &[Ciphertext; N],
+    sk: &ServerKey,
+) -> ([Ciphertext; N], Ciphertext) {
+    assert!(N.is_power_of_two(), "word width is not a power of two");
+    let stages = N.trailing_zeros() as usize;
+
     let mut propagate = propagate.clone();
     let mut generate = generate.clone();
 
-    for d in 0..5 {
+    for d in 0..stages {
         let stride = 1 << d;
 
-        let indices: Vec<(usize, usize)> = (0..32 - stride)
+        let indices: Vec<(usize, usize)> = (0..N - stride)
             .rev()
             .step_by(2 * stride)
             .flat_map(|i| (0..stride).map(move |count| (i, count)))
@@ -144,13 +252,13 @@ fn ladner_fischer(propagate: &[Ciphertext; 32], generate: &[Ciphertext; 32], sk:
                 let new_p;
                 let new_g;
 
-                if index < 32 - (2 * stride) { // black cell
-                    new_p = sk.and(&propagate[index], &p);
-                    new_g = sk.or(&generate[index], &sk.and(&g, &propagate[index]));
+                if index < N - (2 * stride) { // black cell
+                    new_p = and_gate(sk, &propagate[index], &p);
+                    new_g = or_gate(sk, &generate[index], &and_gate(sk, &g, &propagate[index]));
 
                 } else { // grey cell
                     new_p = propagate[index].clone();
-                    new_g = sk.or(&generate[index], &sk.and(&g, &propagate[index]));
+                    new_g = or_gate(sk, &generate[index], &and_gate(sk, &g, &propagate[index]));
                 }
                 (index, new_p, new_g)
             })
@@ -162,12 +270,10 @@ fn ladner_fischer(propagate: &[Ciphertext; 32], generate: &[Ciphertext; 32], sk:
         }
     }
 
-    let mut carry = trivial_bools(&[false; 32], sk);
-    for bit in 0..31 {
-        carry[bit] = generate[bit + 1].clone();
-    }
+    let mut carry = trivial_bools(&[false; N], sk);
+    carry[..N - 1].clone_from_slice(&generate[1..]);
 
-    carry
+    (carry, generate[0].clone())
 }
 
 // 2 (homomorphic) bitwise ops
@@ -200,29 +306,89 @@ pub fn sigma_upper_case_1(x: &[Ciphertext; 32], sk: &ServerKey) -> [Ciphertext;
 }
 
 // 0 bitwise ops
-fn rotate_right(x: &[Ciphertext; 32], n: usize, sk: &ServerKey) -> [Ciphertext; 32] {
-    let mut result = trivial_bools(&[false; 32], sk);
-    for i in 0..32 {
-        result[(i + n) % 32] = x[i].clone();
+pub fn rotate_right<const N: usize>(x: &[Ciphertext; N], n: usize, sk: &ServerKey) -> [Ciphertext; N] {
+    let mut result = trivial_bools(&[false; N], sk);
+    for (i, bit) in x.iter().enumerate() {
+        result[(i + n) % N] = bit.clone();
     }
     result
 }
 
-fn shift_right(x: &[Ciphertext; 32], n: usize, sk: &ServerKey) -> [Ciphertext; 32] {
-    let mut result = trivial_bools(&[false; 32], sk);
-    for i in 0..(32 - n) {
-        result[i + n] = x[i].clone();
+pub fn rotate_left<const N: usize>(x: &[Ciphertext; N], n: usize, sk: &ServerKey) -> [Ciphertext; N] {
+    rotate_right(x, N - n % N, sk)
+}
+
+pub fn shift_right<const N: usize>(x: &[Ciphertext; N], n: usize, sk: &ServerKey) -> [Ciphertext; N] {
+    let n = n.min(N);
+    let mut result = trivial_bools(&[false; N], sk);
+    result[n..].clone_from_slice(&x[..N - n]);
+    result
+}
+
+pub fn shift_left<const N: usize>(x: &[Ciphertext; N], n: usize, sk: &ServerKey) -> [Ciphertext; N] {
+    let n = n.min(N);
+    let mut result = trivial_bools(&[false; N], sk);
+    result[..N - n].clone_from_slice(&x[n..]);
+    result
+}
+
+// Byte and word order permutations (e.g. for Bitcoin-style displayed hashes or little-endian protocols)
+pub fn swap_bytes<const N: usize>(x: &[Ciphertext; N]) -> [Ciphertext; N] {
+    assert_eq!(N % 8, 0, "word width is not a whole number of bytes");
+    std::array::from_fn(|i| x[(N / 8 - 1 - i / 8) * 8 + i % 8].clone())
+}
+
+// Reverses the byte order of a value of any length, such as a digest
+pub fn reverse_bytes(bits: &[Ciphertext]) -> Vec<Ciphertext> {
+    assert_eq!(bits.len() % 8, 0, "value is not a whole number of bytes");
+    bits.chunks(8).rev().flatten().cloned().collect()
+}
+
+// Reverses the order of the word_size-bit words of a value, keeping the bit order within each word
+pub fn reverse_words(bits: &[Ciphertext], word_size: usize) -> Vec<Ciphertext> {
+    assert_eq!(bits.len() % word_size, 0, "value is not a whole number of words");
+    bits.chunks(word_size).rev().flatten().cloned().collect()
+}
+
+// Barrel shifters, where the amount is itself encrypted (most significant bit first). There is one layer of N MUXes per
+// amount bit, each one applying the rotation or shift by that bit's weight when the bit is set
+pub fn rotate_right_by<const N: usize>(x: &[Ciphertext; N], amount: &[Ciphertext], sk: &ServerKey) -> [Ciphertext; N] {
+    barrel_shift(x, amount, sk, |x, n| rotate_right(x, n % N, sk))
+}
+
+pub fn rotate_left_by<const N: usize>(x: &[Ciphertext; N], amount: &[Ciphertext], sk: &ServerKey) -> [Ciphertext; N] {
+    barrel_shift(x, amount, sk, |x, n| rotate_left(x, n % N, sk))
+}
+
+pub fn shift_right_by<const N: usize>(x: &[Ciphertext; N], amount: &[Ciphertext], sk: &ServerKey) -> [Ciphertext; N] {
+    barrel_shift(x, amount, sk, |x, n| shift_right(x, n, sk))
+}
+
+pub fn shift_left_by<const N: usize>(x: &[Ciphertext; N], amount: &[Ciphertext], sk: &ServerKey) -> [Ciphertext; N] {
+    barrel_shift(x, amount, sk, |x, n| shift_left(x, n, sk))
+}
+
+fn barrel_shift<const N: usize, F>(x: &[Ciphertext; N], amount: &[Ciphertext], sk: &ServerKey, op: F) -> [Ciphertext; N]
+where
+    F: Fn(&[Ciphertext; N], usize) -> [Ciphertext; N],
+{
+    assert!(amount.len() <= 32, "shift amount is wider than 32 bits");
+
+    let mut result = x.clone();
+    for (j, bit) in amount.iter().rev().enumerate() {
+        let shifted = op(&result, 1 << j);
+        result = select(bit, &shifted, &result, sk);
     }
     result
 }
 
 // 1 bitwise op
-pub fn ch(x: &[Ciphertext; 32], y: &[Ciphertext; 32], z: &[Ciphertext; 32], sk: &ServerKey) -> [Ciphertext; 32] {
+pub fn ch<const N: usize>(x: &[Ciphertext; N], y: &[Ciphertext; N], z: &[Ciphertext; N], sk: &ServerKey) -> [Ciphertext; N] {
     mux(x, y, z, sk)
 }
 
 // 4 bitwise ops
-pub fn maj(x: &[Ciphertext; 32], y: &[Ciphertext; 32], z: &[Ciphertext; 32], sk: &ServerKey) -> [Ciphertext; 32] {
+pub fn maj<const N: usize>(x: &[Ciphertext; N], y: &[Ciphertext; N], z: &[Ciphertext; N], sk: &ServerKey) -> [Ciphertext; N] {
 
     let (lhs, rhs) = rayon::join(
         || and(x, &xor(y, z, sk), sk),
@@ -233,13 +399,49 @@ pub fn maj(x: &[Ciphertext; 32], y: &[Ciphertext; 32], z: &[Ciphertext; 32], sk:
 
 // Parallelized homomorphic bitwise ops
 // Building block for most of the previous functions
-fn xor(a: &[Ciphertext; 32], b: &[Ciphertext; 32], sk: &ServerKey) -> [Ciphertext; 32] {
-    let result: Vec<Ciphertext> = (0..32)
+pub fn xor<const N: usize>(a: &[Ciphertext; N], b: &[Ciphertext; N], sk: &ServerKey) -> [Ciphertext; N] {
+    bitwise(a, b, xor_gate, sk)
+}
+
+// NOT doesn't need bootstrapping, so it isn't parallelized
+pub fn not<const N: usize>(a: &[Ciphertext; N], sk: &ServerKey) -> [Ciphertext; N] {
+    std::array::from_fn(|i| sk.not(&a[i]))
+}
+
+pub fn and<const N: usize>(a: &[Ciphertext; N], b: &[Ciphertext; N], sk: &ServerKey) -> [Ciphertext; N] {
+    bitwise(a, b, and_gate, sk)
+}
+
+pub fn or<const N: usize>(a: &[Ciphertext; N], b: &[Ciphertext; N], sk: &ServerKey) -> [Ciphertext; N] {
+    bitwise(a, b, or_gate, sk)
+}
+
+// NAND, NOR and XNOR are native tfhe gates, with a single bootstrap per bit like AND and OR
+pub fn nand<const N: usize>(a: &[Ciphertext; N], b: &[Ciphertext; N], sk: &ServerKey) -> [Ciphertext; N] {
+    bitwise(a, b, nand_gate, sk)
+}
+
+pub fn nor<const N: usize>(a: &[Ciphertext; N], b: &[Ciphertext; N], sk: &ServerKey) -> [Ciphertext; N] {
+    bitwise(a, b, nor_gate, sk)
+}
+
+pub fn xnor<const N: usize>(a: &[Ciphertext; N], b: &[Ciphertext; N], sk: &ServerKey) -> [Ciphertext; N] {
+    bitwise(a, b, xnor_gate, sk)
+}
+
+// Applies a two-input gate to every pair of bits in parallel
+fn bitwise<const N: usize>(
+    a: &[Ciphertext; N],
+    b: &[Ciphertext; N],
+    gate: fn(&ServerKey, &Ciphertext, &Ciphertext) -> Ciphertext,
+    sk: &ServerKey,
+) -> [Ciphertext; N] {
+    let result: Vec<Ciphertext> = (0..N)
         .into_par_iter()
-        .map(|i| sk.xor(&a[i], &b[i]))
+        .map(|i| gate(sk, &a[i], &b[i]))
         .collect();
 
-    let mut array = trivial_bools(&[false; 32], sk);
+    let mut array = trivial_bools(&[false; N], sk);
     for (i, elem) in result.into_iter().enumerate() {
         array[i] = elem;
     }
@@ -247,14 +449,81 @@ fn xor(a: &[Ciphertext; 32], b: &[Ciphertext; 32], sk: &ServerKey) -> [Ciphertex
     array
 }
 
-fn and(a: &[Ciphertext; 32], b: &[Ciphertext; 32], sk: &ServerKey) -> [Ciphertext; 32] {
+// Independent bitwise operations on N-bit words, queued and then evaluated together in a single parallel pass over all
+// of their bits. Separate calls each wait for their own 32 gates, nested in joins, while a batch of the same depth of
+// several functions (e.g. the first XOR of the four sigmas of a round) has one synchronization point, and enough ready
+// gates to keep every core busy
+pub struct GateBatch<'a, const N: usize> {
+    operations: Vec<BatchOperation<'a, N>>,
+}
+
+enum BatchOperation<'a, const N: usize> {
+    Gate(fn(&ServerKey, &Ciphertext, &Ciphertext) -> Ciphertext, &'a [Ciphertext; N], &'a [Ciphertext; N]),
+    Mux(&'a [Ciphertext; N], &'a [Ciphertext; N], &'a [Ciphertext; N]),
+}
+
+impl<'a, const N: usize> GateBatch<'a, N> {
+    pub fn new() -> Self {
+        GateBatch { operations: vec![] }
+    }
+
+    // Each operation returns the index of its result in the output of run
+    pub fn xor(&mut self, a: &'a [Ciphertext; N], b: &'a [Ciphertext; N]) -> usize {
+        self.push(BatchOperation::Gate(xor_gate, a, b))
+    }
+
+    pub fn and(&mut self, a: &'a [Ciphertext; N], b: &'a [Ciphertext; N]) -> usize {
+        self.push(BatchOperation::Gate(and_gate, a, b))
+    }
+
+    pub fn or(&mut self, a: &'a [Ciphertext; N], b: &'a [Ciphertext; N]) -> usize {
+        self.push(BatchOperation::Gate(or_gate, a, b))
+    }
+
+    pub fn mux(&mut self, condition: &'a [Ciphertext; N], then: &'a [Ciphertext; N], otherwise: &'a [Ciphertext; N]) -> usize {
+        self.push(BatchOperation::Mux(condition, then, otherwise))
+    }
+
+    fn push(&mut self, operation: BatchOperation<'a, N>) -> usize {
+        self.operations.push(operation);
+        self.operations.len() - 1
+    }
 
-    let result: Vec<Ciphertext> = (0..32)
+    // Results of the operations, in the order they were queued
+    pub fn run(self, sk: &ServerKey) -> Vec<[Ciphertext; N]> {
+        let bits: Vec<Ciphertext> = (0..self.operations.len() * N)
+            .into_par_iter()
+            .map(|i| match &self.operations[i / N] {
+                BatchOperation::Gate(gate, a, b) => gate(sk, &a[i % N], &b[i % N]),
+                BatchOperation::Mux(condition, then, otherwise) => {
+                    mux_gate(sk, &condition[i % N], &then[i % N], &otherwise[i % N])
+                }
+            })
+            .collect();
+
+        let mut bits = bits.into_iter();
+        self.operations.iter().map(|_| std::array::from_fn(|_| bits.next().unwrap())).collect()
+    }
+}
+
+impl<const N: usize> Default for GateBatch<'_, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn mux<const N: usize>(
+    condition: &[Ciphertext; N],
+    then: &[Ciphertext; N],
+    otherwise: &[Ciphertext; N],
+    sk: &ServerKey,
+) -> [Ciphertext; N] {
+    let result: Vec<Ciphertext> = (0..N)
         .into_par_iter()
-        .map(|i| sk.and(&a[i], &b[i]))
+        .map(|i| mux_gate(sk, &condition[i], &then[i], &otherwise[i]))
         .collect();
 
-    let mut array = trivial_bools(&[false; 32], sk);
+    let mut array = trivial_bools(&[false; N], sk);
     for (i, elem) in result.into_iter().enumerate() {
         array[i] = elem;
     }
@@ -262,13 +531,11 @@ fn and(a: &[Ciphertext; 32], b: &[Ciphertext; 32], sk: &ServerKey) -> [Ciphertex
     array
 }
 
-fn mux(condition: &[Ciphertext; 32], then: &[Ciphertext; 32], otherwise: &[Ciphertext; 32], sk: &ServerKey) -> [Ciphertext; 32] {
-    let result: Vec<Ciphertext> = (0..32)
-        .into_par_iter()
-        .map(|i| sk.mux(&condition[i], &then[i], &otherwise[i]))
-        .collect();
+// Word-level select: returns a if condition is true, b otherwise, with the same condition bit driving every MUX
+pub fn select<const N: usize>(condition: &Ciphertext, a: &[Ciphertext; N], b: &[Ciphertext; N], sk: &ServerKey) -> [Ciphertext; N] {
+    let result = select_bits(condition, a, b, sk);
 
-    let mut array = trivial_bools(&[false; 32], sk);
+    let mut array = trivial_bools(&[false; N], sk);
     for (i, elem) in result.into_iter().enumerate() {
         array[i] = elem;
     }
@@ -276,25 +543,158 @@ fn mux(condition: &[Ciphertext; 32], then: &[Ciphertext; 32], otherwise: &[Ciphe
     array
 }
 
-// Trivial encryption of 32 bools
-pub fn trivial_bools(bools: &[bool; 32], sk: &ServerKey) -> [Ciphertext; 32] {
+// Same as select, for values of any length such as the 256-bit digest returned by sha256_fhe
+pub fn select_bits(condition: &Ciphertext, a: &[Ciphertext], b: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    assert_eq!(a.len(), b.len(), "selected values have different lengths");
+
+    a.par_iter()
+        .zip(b.par_iter())
+        .map(|(a, b)| mux_gate(sk, condition, a, b))
+        .collect()
+}
+
+// Oblivious table lookup: returns candidates[index], with the encrypted index most significant bit first. It's a tree
+// of MUXes, one level per index bit, so every candidate is touched. The index must be below the number of candidates
+pub fn select_index(index: &[Ciphertext], mut candidates: Vec<Vec<Ciphertext>>, sk: &ServerKey) -> Vec<Ciphertext> {
+    assert!(!candidates.is_empty(), "nothing to select from");
+
+    // A candidate without a sibling at some level could only be skipped by an index past the last candidate
+    for bit in index.iter().rev() {
+        if candidates.len() == 1 {
+            break;
+        }
+        candidates = candidates
+            .par_chunks(2)
+            .map(|pair| if pair.len() == 2 { select_bits(bit, &pair[1], &pair[0], sk) } else { pair[0].clone() })
+            .collect();
+    }
+
+    assert_eq!(candidates.len(), 1, "index is too short for the number of candidates");
+    candidates.pop().unwrap()
+}
+
+// Encrypted equality of two bit strings: XNOR each pair of bits and then AND all the results together
+pub fn eq_bits(a: &[Ciphertext], b: &[Ciphertext], sk: &ServerKey) -> Ciphertext {
+    assert_eq!(a.len(), b.len(), "compared values have different lengths");
+
+    let matches: Vec<Ciphertext> = a.par_iter()
+        .zip(b.par_iter())
+        .map(|(a, b)| xnor_gate(sk, a, b))
+        .collect();
+
+    all(&matches, sk)
+}
+
+// Encrypted AND of all the bits (true if there are none). The reduction is a balanced tree, so the depth is logarithmic
+pub fn all(bits: &[Ciphertext], sk: &ServerKey) -> Ciphertext {
+    bits.par_iter()
+        .cloned()
+        .reduce_with(|a, b| and_gate(sk, &a, &b))
+        .unwrap_or_else(|| sk.trivial_encrypt(true))
+}
+
+// Encrypted OR of all the bits (false if there are none), with the same balanced tree as all
+pub fn any(bits: &[Ciphertext], sk: &ServerKey) -> Ciphertext {
+    bits.par_iter()
+        .cloned()
+        .reduce_with(|a, b| or_gate(sk, &a, &b))
+        .unwrap_or_else(|| sk.trivial_encrypt(false))
+}
+
+// Encrypted XOR of all the bits (false if there are none), with the same balanced tree as all
+pub fn parity(bits: &[Ciphertext], sk: &ServerKey) -> Ciphertext {
+    bits.par_iter()
+        .cloned()
+        .reduce_with(|a, b| xor_gate(sk, &a, &b))
+        .unwrap_or_else(|| sk.trivial_encrypt(false))
+}
+
+// Trivial encryption of N bools
+pub fn trivial_bools<const N: usize>(bools: &[bool; N], sk: &ServerKey) -> [Ciphertext; N] {
+    std::array::from_fn(|i| sk.trivial_encrypt(bools[i]))
+}
+
+// Trivial encryption of the bits of some public bytes, most significant bit of each byte first
+pub fn trivial_bytes(bytes: &[u8], sk: &ServerKey) -> Vec<Ciphertext> {
+    bytes.iter()
+        .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1))
+        .map(|bit| sk.trivial_encrypt(bit))
+        .collect()
+}
+
+// Trivial encryption of a public N-bit word (N <= 64)
+pub fn trivial_word<const N: usize>(value: u64, sk: &ServerKey) -> [Ciphertext; N] {
+    std::array::from_fn(|i| sk.trivial_encrypt((value >> (N - 1 - i)) & 1 == 1))
+}
+
+// XOR with a public N-bit constant (N <= 64), which only takes NOT gates
+pub fn xor_constant<const N: usize>(x: &[Ciphertext; N], constant: u64, sk: &ServerKey) -> [Ciphertext; N] {
+    std::array::from_fn(|i| if (constant >> (N - 1 - i)) & 1 == 1 { sk.not(&x[i]) } else { x[i].clone() })
+}
+
+// Word from N / 8 little-endian bytes, as read by little-endian algorithms such as SipHash or BLAKE2
+pub fn from_le_bytes<const N: usize>(bytes: &[Ciphertext]) -> [Ciphertext; N] {
+    let word: [Ciphertext; N] = bytes.to_vec().try_into().expect("word has the wrong number of bytes");
+    swap_bytes(&word)
+}
+
+// Number of bootstrapped gates evaluated so far by this process, for reports and benchmarks. Concurrent computations
+// are counted together, so the difference between two readings only belongs to one computation if it ran alone
+pub fn bootstrapped_gates() -> u64 {
+    BOOTSTRAPPED_GATES.load(Ordering::Relaxed)
+}
+
+fn count_gate(bootstrapped: bool) {
+    if bootstrapped {
+        BOOTSTRAPPED_GATES.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn encrypted(bit: &Ciphertext) -> bool {
+    matches!(bit, Ciphertext::Encrypted(_))
+}
+
+// The gates below are the only ones evaluated by this module (and by the netlist executor), so that they are counted
+pub(crate) fn and_gate(sk: &ServerKey, a: &Ciphertext, b: &Ciphertext) -> Ciphertext {
+    count_gate(encrypted(a) && encrypted(b));
+    sk.and(a, b)
+}
+
+pub(crate) fn or_gate(sk: &ServerKey, a: &Ciphertext, b: &Ciphertext) -> Ciphertext {
+    count_gate(encrypted(a) && encrypted(b));
+    sk.or(a, b)
+}
+
+pub(crate) fn xor_gate(sk: &ServerKey, a: &Ciphertext, b: &Ciphertext) -> Ciphertext {
+    count_gate(encrypted(a) && encrypted(b));
+    sk.xor(a, b)
+}
+
+fn nand_gate(sk: &ServerKey, a: &Ciphertext, b: &Ciphertext) -> Ciphertext {
+    count_gate(encrypted(a) && encrypted(b));
+    sk.nand(a, b)
+}
+
+fn nor_gate(sk: &ServerKey, a: &Ciphertext, b: &Ciphertext) -> Ciphertext {
+    count_gate(encrypted(a) && encrypted(b));
+    sk.nor(a, b)
+}
 
-    [
-        sk.trivial_encrypt(bools[0]), sk.trivial_encrypt(bools[1]), sk.trivial_encrypt(bools[2]), sk.trivial_encrypt(bools[3]),
-        sk.trivial_encrypt(bools[4]), sk.trivial_encrypt(bools[5]), sk.trivial_encrypt(bools[6]), sk.trivial_encrypt(bools[7]),
-        sk.trivial_encrypt(bools[8]), sk.trivial_encrypt(bools[9]), sk.trivial_encrypt(bools[10]), sk.trivial_encrypt(bools[11]),
-        sk.trivial_encrypt(bools[12]), sk.trivial_encrypt(bools[13]), sk.trivial_encrypt(bools[14]), sk.trivial_encrypt(bools[15]),
-        sk.trivial_encrypt(bools[16]), sk.trivial_encrypt(bools[17]), sk.trivial_encrypt(bools[18]), sk.trivial_encrypt(bools[19]),
-        sk.trivial_encrypt(bools[20]), sk.trivial_encrypt(bools[21]), sk.trivial_encrypt(bools[22]), sk.trivial_encrypt(bools[23]),
-        sk.trivial_encrypt(bools[24]), sk.trivial_encrypt(bools[25]), sk.trivial_encrypt(bools[26]), sk.trivial_encrypt(bools[27]),
-        sk.trivial_encrypt(bools[28]), sk.trivial_encrypt(bools[29]), sk.trivial_encrypt(bools[30]), sk.trivial_encrypt(bools[31]),
-    ]
+fn xnor_gate(sk: &ServerKey, a: &Ciphertext, b: &Ciphertext) -> Ciphertext {
+    count_gate(encrypted(a) && encrypted(b));
+    sk.xnor(a, b)
+}
+
+pub(crate) fn mux_gate(sk: &ServerKey, condition: &Ciphertext, then: &Ciphertext, otherwise: &Ciphertext) -> Ciphertext {
+    count_gate(encrypted(condition) && (encrypted(then) || encrypted(otherwise)));
+    sk.mux(condition, then, otherwise)
 }
 
 #[cfg(test)]
 mod tests {
     use tfhe::boolean::prelude::*;
     use super::*;
+    use crate::test_keys::test_keys;
 
     fn to_bool_array(arr: [i32; 32]) -> [bool; 32] {
         let mut bool_arr = [false; 32];
@@ -305,35 +705,20 @@ mod tests {
         }
         bool_arr
     }
-    fn encrypt(bools: &[bool; 32], ck: &ClientKey) -> [Ciphertext; 32] {
-        [
-            ck.encrypt(bools[0]), ck.encrypt(bools[1]), ck.encrypt(bools[2]), ck.encrypt(bools[3]),
-            ck.encrypt(bools[4]), ck.encrypt(bools[5]), ck.encrypt(bools[6]), ck.encrypt(bools[7]),
-            ck.encrypt(bools[8]), ck.encrypt(bools[9]), ck.encrypt(bools[10]), ck.encrypt(bools[11]),
-            ck.encrypt(bools[12]), ck.encrypt(bools[13]), ck.encrypt(bools[14]), ck.encrypt(bools[15]),
-            ck.encrypt(bools[16]), ck.encrypt(bools[17]), ck.encrypt(bools[18]), ck.encrypt(bools[19]),
-            ck.encrypt(bools[20]), ck.encrypt(bools[21]), ck.encrypt(bools[22]), ck.encrypt(bools[23]),
-            ck.encrypt(bools[24]), ck.encrypt(bools[25]), ck.encrypt(bools[26]), ck.encrypt(bools[27]),
-            ck.encrypt(bools[28]), ck.encrypt(bools[29]), ck.encrypt(bools[30]), ck.encrypt(bools[31]),
-        ]
-    }
-    fn decrypt(bools: &[Ciphertext; 32], ck: &ClientKey) -> [bool; 32] {
-        [
-            ck.decrypt(&bools[0]), ck.decrypt(&bools[1]), ck.decrypt(&bools[2]), ck.decrypt(&bools[3]),
-            ck.decrypt(&bools[4]), ck.decrypt(&bools[5]), ck.decrypt(&bools[6]), ck.decrypt(&bools[7]),
-            ck.decrypt(&bools[8]), ck.decrypt(&bools[9]), ck.decrypt(&bools[10]), ck.decrypt(&bools[11]),
-            ck.decrypt(&bools[12]), ck.decrypt(&bools[13]), ck.decrypt(&bools[14]), ck.decrypt(&bools[15]),
-            ck.decrypt(&bools[16]), ck.decrypt(&bools[17]), ck.decrypt(&bools[18]), ck.decrypt(&bools[19]),
-            ck.decrypt(&bools[20]), ck.decrypt(&bools[21]), ck.decrypt(&bools[22]), ck.decrypt(&bools[23]),
-            ck.decrypt(&bools[24]), ck.decrypt(&bools[25]), ck.decrypt(&bools[26]), ck.decrypt(&bools[27]),
-            ck.decrypt(&bools[28]), ck.decrypt(&bools[29]), ck.decrypt(&bools[30]), ck.decrypt(&bools[31]),
-        ]
+    fn encrypt<const N: usize>(bools: &[bool; N], ck: &ClientKey) -> [Ciphertext; N] {
+        std::array::from_fn(|i| ck.encrypt(bools[i]))
+    }
+    fn decrypt<const N: usize>(bools: &[Ciphertext; N], ck: &ClientKey) -> [bool; N] {
+        std::array::from_fn(|i| ck.decrypt(&bools[i]))
+    }
+    fn u64_to_bools<const N: usize>(value: u64) -> [bool; N] {
+        std::array::from_fn(|i| (value >> (N - 1 - i)) & 1 == 1)
     }
 
 
     #[test]
     fn test_add_modulo_2_32() {
-        let (ck, sk) = gen_keys();
+        let (ck, sk) = test_keys();
 
         let a = encrypt(&to_bool_array([0,1,0,1,1,0,1,1,1,1,1,0,0,0,0,0,1,1,0,0,1,1,0,1,0,0,0,1,1,0,0,1,]), &ck);
         let b = encrypt(&to_bool_array([0,0,1,1,0,1,0,1,1,0,0,0,0,1,1,1,0,0,1,0,0,1,1,1,0,0,1,0,1,0,1,1,]), &ck);
@@ -354,7 +739,7 @@ mod tests {
 
     #[test]
     fn test_sigma0() {
-        let (ck, sk) = gen_keys();
+        let (ck, sk) = test_keys();
 
         let input = encrypt(&to_bool_array([0,1,1,0,1,1,1,1,0,0,1,0,0,0,0,0,0,1,1,1,0,1,1,1,0,1,1,0,1,1,1,1,]), &ck);
         let output = sigma0(&input, &sk);
@@ -366,7 +751,7 @@ mod tests {
 
     #[test]
     fn test_ch() {
-    let (ck, sk) = gen_keys();
+    let (ck, sk) = test_keys();
 
     let e = encrypt(&to_bool_array([0,1,0,1,0,0,0,1,0,0,0,0,1,1,1,0,0,1,0,1,0,0,1,0,0,1,1,1,1,1,1,1,]), &ck);
     let f = encrypt(&to_bool_array([1,0,0,1,1,0,1,1,0,0,0,0,0,1,0,1,0,1,1,0,1,0,0,0,1,0,0,0,1,1,0,0,]), &ck);
@@ -381,7 +766,7 @@ mod tests {
 
     #[test]
     fn test_maj() {
-        let (ck, sk) = gen_keys();
+        let (ck, sk) = test_keys();
 
         let a = encrypt(&to_bool_array([0,1,1,0,1,0,1,0,0,0,0,0,1,0,0,1,1,1,1,0,0,1,1,0,0,1,1,0,0,1,1,1,]), &ck);
         let b = encrypt(&to_bool_array([1,0,1,1,1,0,1,1,0,1,1,0,0,1,1,1,1,0,1,0,1,1,1,0,1,0,0,0,0,1,0,1,]), &ck);
@@ -393,4 +778,217 @@ mod tests {
 
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn test_add_other_widths() {
+        let (ck, sk) = test_keys();
+
+        let a: u64 = 0xdeadbeef_cafebabe;
+        let b: u64 = 0x31415926_53589793;
+        let output = add(&encrypt(&u64_to_bools::<64>(a), &ck), &encrypt(&u64_to_bools(b), &ck), &sk);
+        assert_eq!(decrypt(&output, &ck), u64_to_bools::<64>(a.wrapping_add(b)));
+
+        let output = add(&encrypt(&u64_to_bools::<8>(0xb7), &ck), &encrypt(&u64_to_bools(0x6c), &ck), &sk);
+        assert_eq!(decrypt(&output, &ck), u64_to_bools::<8>(0x23));
+    }
+
+    #[test]
+    fn test_rotations_and_shifts() {
+        let (ck, sk) = test_keys();
+
+        let x: u64 = 0x0123456789abcdef;
+        let input = encrypt(&u64_to_bools::<64>(x), &ck);
+
+        assert_eq!(decrypt(&rotate_right(&input, 13, &sk), &ck), u64_to_bools(x.rotate_right(13)));
+        assert_eq!(decrypt(&rotate_left(&input, 13, &sk), &ck), u64_to_bools(x.rotate_left(13)));
+        assert_eq!(decrypt(&shift_right(&input, 13, &sk), &ck), u64_to_bools(x >> 13));
+        assert_eq!(decrypt(&shift_left(&input, 13, &sk), &ck), u64_to_bools(x << 13));
+    }
+
+    #[test]
+    fn test_add_with_carry() {
+        let (ck, sk) = test_keys();
+
+        let a: u64 = 0xfedcba98;
+        let b: u64 = 0x87654321;
+        let (sum, carry_out) = add_with_carry(&encrypt(&u64_to_bools::<32>(a), &ck), &encrypt(&u64_to_bools(b), &ck), &sk);
+        assert_eq!(decrypt(&sum, &ck), u64_to_bools::<32>(a + b));
+        assert!(ck.decrypt(&carry_out));
+
+        let (_, carry_out) = add_with_carry(&encrypt(&u64_to_bools::<32>(a), &ck), &encrypt(&u64_to_bools(1), &ck), &sk);
+        assert!(!ck.decrypt(&carry_out));
+    }
+
+    #[test]
+    fn test_add_constant() {
+        let (ck, sk) = test_keys();
+
+        let values: [u64; 4] = [0, 0x12345678, 0xffffffff, 0x80000001];
+        let constants: [u64; 5] = [0, 1, 0x428a2f98, 0xffffffff, 0x80000000];
+        for value in values {
+            let x = encrypt(&u64_to_bools::<32>(value), &ck);
+            for constant in constants {
+                let expected = u64_to_bools::<32>((value as u32).wrapping_add(constant as u32) as u64);
+                assert_eq!(decrypt(&add_constant(&x, constant, &sk), &ck), expected);
+                assert_eq!(decrypt(&add(&trivial_word(constant, &sk), &x, &sk), &ck), expected);
+            }
+
+            let (_, carry_out) = add_with_carry(&x, &trivial_word(0xffffffff, &sk), &sk);
+            assert_eq!(ck.decrypt(&carry_out), value != 0);
+        }
+    }
+
+    #[test]
+    fn test_accumulate() {
+        let (ck, sk) = test_keys();
+
+        let values: [u64; 7] = [0xffffffff, 0xdeadbeef, 0x80000000, 0x12345678, 0xcafebabe, 0x00000001, 0x9abcdef0];
+        let words: Vec<[Ciphertext; 32]> = values.iter().map(|v| encrypt(&u64_to_bools(*v), &ck)).collect();
+
+        let output: [Ciphertext; 64] = accumulate(&words, &sk);
+        assert_eq!(decrypt(&output, &ck), u64_to_bools::<64>(values.iter().sum()));
+    }
+
+    #[test]
+    fn test_bitwise() {
+        let (ck, sk) = test_keys();
+
+        let (x, y) = (0xdeadbeefu64, 0x0f0f3c3cu64);
+        let a = encrypt(&u64_to_bools::<32>(x), &ck);
+        let b = encrypt(&u64_to_bools::<32>(y), &ck);
+        let word = |value: u64| u64_to_bools::<32>(value & 0xffffffff);
+
+        assert_eq!(decrypt(&and(&a, &b, &sk), &ck), word(x & y));
+        assert_eq!(decrypt(&or(&a, &b, &sk), &ck), word(x | y));
+        assert_eq!(decrypt(&xor(&a, &b, &sk), &ck), word(x ^ y));
+        assert_eq!(decrypt(&nand(&a, &b, &sk), &ck), word(!(x & y)));
+        assert_eq!(decrypt(&nor(&a, &b, &sk), &ck), word(!(x | y)));
+        assert_eq!(decrypt(&xnor(&a, &b, &sk), &ck), word(!(x ^ y)));
+        assert_eq!(decrypt(&not(&a, &sk), &ck), word(!x));
+    }
+
+    #[test]
+    fn test_gate_batch() {
+        let (ck, sk) = test_keys();
+
+        let (x, y, z) = (0xdeadbeefu64, 0x0f0f3c3cu64, 0x12345678u64);
+        let a = encrypt(&u64_to_bools::<32>(x), &ck);
+        let b = encrypt(&u64_to_bools::<32>(y), &ck);
+        let c = encrypt(&u64_to_bools::<32>(z), &ck);
+
+        let mut batch = GateBatch::new();
+        let indices = [batch.xor(&a, &b), batch.and(&b, &c), batch.or(&a, &c), batch.mux(&a, &b, &c)];
+        let output = batch.run(&sk);
+
+        assert_eq!(indices, [0, 1, 2, 3]);
+        assert_eq!(decrypt(&output[0], &ck), u64_to_bools(x ^ y));
+        assert_eq!(decrypt(&output[1], &ck), u64_to_bools(y & z));
+        assert_eq!(decrypt(&output[2], &ck), u64_to_bools(x | z));
+        assert_eq!(decrypt(&output[3], &ck), u64_to_bools((x & y) | (!x & z)));
+        assert!(GateBatch::<32>::new().run(&sk).is_empty());
+    }
+
+    #[test]
+    fn test_popcount() {
+        let (ck, sk) = test_keys();
+
+        for value in [0u64, 1, 0xdeadbeef, 0xffffffff, 0x80000001] {
+            let word = encrypt(&u64_to_bools::<32>(value), &ck);
+            assert_eq!(decrypt(&popcount::<8>(&word, &sk), &ck), u64_to_bools::<8>(value.count_ones() as u64));
+        }
+
+        // A 256-bit digest with every bit set needs 9 bits
+        let digest = encrypt(&[true; 256], &ck);
+        assert_eq!(decrypt(&popcount::<16>(&digest, &sk), &ck), u64_to_bools::<16>(256));
+        assert_eq!(decrypt(&popcount::<4>(&[], &sk), &ck), [false; 4]);
+    }
+
+    #[test]
+    fn test_sub_and_comparisons() {
+        let (ck, sk) = test_keys();
+
+        let pairs: [(u64, u64); 4] = [(0x12345678, 0x12345677), (5, 0xfffffff0), (0xabcdef01, 0xabcdef01), (0, 1)];
+        for (a, b) in pairs {
+            let x = encrypt(&u64_to_bools::<32>(a), &ck);
+            let y = encrypt(&u64_to_bools::<32>(b), &ck);
+
+            assert_eq!(decrypt(&sub(&x, &y, &sk), &ck), u64_to_bools::<32>((a as u32).wrapping_sub(b as u32) as u64));
+            assert_eq!(ck.decrypt(&lt(&x, &y, &sk)), a < b);
+            assert_eq!(ck.decrypt(&le(&x, &y, &sk)), a <= b);
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        let (ck, sk) = test_keys();
+
+        let a = encrypt(&u64_to_bools::<32>(0xdeadbeef), &ck);
+        let b = encrypt(&u64_to_bools::<32>(0x01234567), &ck);
+
+        assert_eq!(decrypt(&select(&ck.encrypt(true), &a, &b, &sk), &ck), u64_to_bools::<32>(0xdeadbeef));
+        assert_eq!(decrypt(&select(&ck.encrypt(false), &a, &b, &sk), &ck), u64_to_bools::<32>(0x01234567));
+
+        // Five candidates, so the last one has no sibling at the first level
+        let candidates: Vec<Vec<Ciphertext>> = (0..5u64).map(|i| encrypt(&u64_to_bools::<8>(i * 17), &ck).to_vec()).collect();
+        for i in 0..5u64 {
+            let index = encrypt(&u64_to_bools::<3>(i), &ck);
+            let selected: [Ciphertext; 8] = select_index(&index, candidates.clone(), &sk).try_into().unwrap();
+            assert_eq!(decrypt(&selected, &ck), u64_to_bools::<8>(i * 17));
+        }
+    }
+
+    #[test]
+    fn test_eq_bits() {
+        let (ck, sk) = test_keys();
+
+        let a = encrypt(&u64_to_bools::<32>(0xdeadbeef), &ck);
+        let b = encrypt(&u64_to_bools::<32>(0xdeadbeee), &ck);
+
+        assert!(ck.decrypt(&eq_bits(&a, &a, &sk)));
+        assert!(!ck.decrypt(&eq_bits(&a, &b, &sk)));
+        assert!(ck.decrypt(&all(&[], &sk)));
+        assert!(ck.decrypt(&any(&[eq_bits(&a, &b, &sk), eq_bits(&b, &b, &sk)], &sk)));
+        assert!(!ck.decrypt(&any(&[], &sk)));
+        // 0xdeadbeef has 24 bits set, 0xdeadbeee 23
+        assert!(!ck.decrypt(&parity(&a, &sk)) && ck.decrypt(&parity(&b, &sk)));
+        assert!(!ck.decrypt(&parity(&[], &sk)));
+    }
+
+    #[test]
+    fn test_barrel_shifts() {
+        let (ck, sk) = test_keys();
+
+        let x: u64 = 0x0123456789abcdef;
+        let input = encrypt(&u64_to_bools::<64>(x), &ck);
+
+        for n in [0, 1, 21, 63] {
+            let amount = encrypt(&u64_to_bools::<6>(n), &ck);
+
+            assert_eq!(decrypt(&rotate_right_by(&input, &amount, &sk), &ck), u64_to_bools(x.rotate_right(n as u32)));
+            assert_eq!(decrypt(&rotate_left_by(&input, &amount, &sk), &ck), u64_to_bools(x.rotate_left(n as u32)));
+            assert_eq!(decrypt(&shift_right_by(&input, &amount, &sk), &ck), u64_to_bools(x >> n));
+            assert_eq!(decrypt(&shift_left_by(&input, &amount, &sk), &ck), u64_to_bools(x << n));
+        }
+
+        // Amounts wider than log2(N) bits shift everything out
+        let amount = encrypt(&u64_to_bools::<7>(64), &ck);
+        assert_eq!(decrypt(&shift_right_by(&input, &amount, &sk), &ck), [false; 64]);
+        assert_eq!(decrypt(&rotate_right_by(&input, &amount, &sk), &ck), u64_to_bools(x));
+    }
+
+    #[test]
+    fn test_byte_and_word_order() {
+        let (ck, _) = test_keys();
+
+        let x: u64 = 0x0123456789abcdef;
+        let input = encrypt(&u64_to_bools::<64>(x), &ck);
+
+        assert_eq!(decrypt(&swap_bytes(&input), &ck), u64_to_bools(x.swap_bytes()));
+
+        let reversed: Vec<bool> = reverse_bytes(&input).iter().map(|c| ck.decrypt(c)).collect();
+        assert_eq!(reversed, u64_to_bools::<64>(x.swap_bytes()));
+
+        let reversed: Vec<bool> = reverse_words(&input, 32).iter().map(|c| ck.decrypt(c)).collect();
+        assert_eq!(reversed, u64_to_bools::<64>(x.rotate_left(32)));
+    }
 }
@@ -0,0 +1,128 @@
+// This module checks whether an encrypted input hashes to any digest of a public list, such as a list of breached
+// password hashes, without the server learning the input or which digest matched: the result is one encrypted bit.
+//
+// The input is hashed once for the whole list, and the comparisons share work too. The digest is split into chunks of
+// w bits, and the equality of each chunk with each value that it takes in the list is computed once, in w - 1 ANDs (the
+// digest bits are compared with constants, which only takes NOT gates). A listed digest then takes 256 / w - 1 ANDs to
+// combine its chunk equalities instead of 255, and w is chosen to minimize the total for the given list.
+
+use std::collections::{HashMap, HashSet};
+use rayon::prelude::*;
+use tfhe::boolean::prelude::*;
+use crate::boolean_ops::{all, any};
+use crate::sha256::sha256_fhe;
+
+const DIGEST_BITS: usize = 256;
+const CHUNK_WIDTHS: [usize; 5] = [1, 2, 4, 8, 16];
+
+// Encrypted bit telling whether the encrypted digest is in the list
+pub fn digest_in_list(digest: &[Ciphertext], list: &[[u8; 32]], sk: &ServerKey) -> Ciphertext {
+    assert_eq!(digest.len(), DIGEST_BITS, "the digest must have 256 bits");
+
+    let mut list = list.to_vec();
+    list.sort_unstable();
+    list.dedup();
+
+    let width = chunk_width(&list);
+    let values: HashSet<(usize, u32)> = list.iter()
+        .flat_map(|listed| (0..DIGEST_BITS / width).map(move |index| (index, chunk(listed, index, width))))
+        .collect();
+
+    let equalities: HashMap<(usize, u32), Ciphertext> = values.into_par_iter()
+        .map(|(index, value)| {
+            let bits: Vec<Ciphertext> = (0..width)
+                .map(|i| {
+                    let bit = &digest[index * width + i];
+                    if (value >> (width - 1 - i)) & 1 == 1 { bit.clone() } else { sk.not(bit) }
+                })
+                .collect();
+            ((index, value), all(&bits, sk))
+        })
+        .collect();
+
+    let matches: Vec<Ciphertext> = list.par_iter()
+        .map(|listed| {
+            let chunks: Vec<Ciphertext> = (0..DIGEST_BITS / width)
+                .map(|index| equalities[&(index, chunk(listed, index, width))].clone())
+                .collect();
+            all(&chunks, sk)
+        })
+        .collect();
+
+    any(&matches, sk)
+}
+
+// Encrypted bit telling whether the sha256 of the padded input is in the list
+pub fn sha256_in_list(padded_input: Vec<Ciphertext>, list: &[[u8; 32]], sk: &ServerKey) -> Ciphertext {
+    digest_in_list(&sha256_fhe(padded_input, sk), list, sk)
+}
+
+// Chunk width that takes the fewest ANDs to compare a digest with the (deduplicated) list
+fn chunk_width(list: &[[u8; 32]]) -> usize {
+    CHUNK_WIDTHS.into_iter().min_by_key(|&width| comparison_gates(list, width)).unwrap()
+}
+
+fn comparison_gates(list: &[[u8; 32]], width: usize) -> usize {
+    let chunks = DIGEST_BITS / width;
+    let values: usize = (0..chunks)
+        .map(|index| list.iter().map(|listed| chunk(listed, index, width)).collect::<HashSet<_>>().len())
+        .sum();
+
+    values * (width - 1) + list.len() * (chunks - 1)
+}
+
+// Value of the chunk of a digest at the given index, most significant bit first
+fn chunk(digest: &[u8; 32], index: usize, width: usize) -> u32 {
+    (index * width..(index + 1) * width).fold(0, |value, bit| (value << 1) | ((digest[bit / 8] >> (7 - bit % 8)) & 1) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+    use crate::boolean_ops::trivial_bytes;
+    use crate::padding::pad_sha256_data;
+    use crate::test_keys::test_keys;
+
+    fn sha256(data: &[u8]) -> [u8; 32] {
+        Sha256::digest(data).into()
+    }
+
+    #[test]
+    fn test_sha256_in_list() {
+        let (ck, sk) = test_keys();
+        let padded_input: Vec<Ciphertext> = pad_sha256_data(b"hunter2").iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+        let list = [sha256(b"password"), sha256(b"hunter2"), sha256(b"123456")];
+
+        assert!(ck.decrypt(&sha256_in_list(padded_input, &list, &sk)));
+    }
+
+    #[test]
+    fn test_digest_in_list() {
+        let (ck, sk) = test_keys();
+        let digest = sha256(b"hunter2");
+        let encrypted = trivial_bytes(&digest, &sk);
+
+        // A digest one bit away from the listed ones doesn't match
+        let mut near = digest;
+        near[31] ^= 1;
+        let others: Vec<[u8; 32]> = (0..100u32).map(|i| sha256(&i.to_le_bytes())).collect();
+
+        assert!(ck.decrypt(&digest_in_list(&encrypted, &[others.clone(), vec![digest, digest]].concat(), &sk)));
+        assert!(!ck.decrypt(&digest_in_list(&encrypted, &[others, vec![near]].concat(), &sk)));
+        assert!(!ck.decrypt(&digest_in_list(&encrypted, &[], &sk)));
+    }
+
+    #[test]
+    fn test_chunk_width() {
+        let few = [sha256(b"a")];
+        let many: Vec<[u8; 32]> = (0..1000u32).map(|i| sha256(&i.to_le_bytes())).collect();
+
+        assert_eq!(chunk(&few[0], 0, 8), few[0][0] as u32);
+        assert_eq!(chunk(&few[0], 15, 16), u16::from_be_bytes([few[0][30], few[0][31]]) as u32);
+        assert_eq!(comparison_gates(&few, chunk_width(&few)), 255);
+        // Comparing with a thousand digests takes about a quarter of the ANDs of separate comparisons
+        assert_eq!(chunk_width(&many), 4);
+        assert!(comparison_gates(&many, 4) < many.len() * 255 / 3);
+    }
+}
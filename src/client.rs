@@ -0,0 +1,232 @@
+// This module contains the client side helpers: encrypting the padded input bits and decrypting the digest. It doesn't
+// depend on the server side computation, so client applications can build the crate without the "server" feature.
+// The helpers that need the ClientKey require the "client" feature, while bools_to_hex is always available.
+
+#[cfg(feature = "client")]
+use std::{error, fmt};
+#[cfg(feature = "client")]
+use sha2::{Digest, Sha256};
+#[cfg(feature = "client")]
+use tfhe::boolean::prelude::*;
+#[cfg(feature = "client")]
+use crate::padding::pad_sha256_data;
+
+#[cfg(feature = "client")]
+pub fn encrypt_bools(bools: &[bool], ck: &ClientKey) -> Vec<Ciphertext> {
+    let mut ciphertext = vec![];
+
+    for bool in bools {
+        ciphertext.push(ck.encrypt(*bool));
+    }
+    ciphertext
+}
+
+// Encrypts the padded inputs of a batch (e.g. from padding::pad_sha256_many), one ciphertext vector per input
+#[cfg(feature = "client")]
+pub fn encrypt_bools_many(batch: &[Vec<bool>], ck: &ClientKey) -> Vec<Vec<Ciphertext>> {
+    batch.iter().map(|bools| encrypt_bools(bools, ck)).collect()
+}
+
+// Test helper: replaces this thread's tfhe engine with one seeded deterministically, so that the keys and ciphertexts
+// generated afterwards on this thread are bit-reproducible across runs (e.g. when debugging a nondeterministic failure)
+#[cfg(all(test, feature = "client"))]
+pub(crate) fn seed_thread_rng(seed: u128) {
+    use tfhe::boolean::engine::BooleanEngine;
+    use tfhe::core_crypto::commons::generators::DeterministicSeeder;
+    use tfhe::core_crypto::commons::math::random::{ActivatedRandomGenerator, Seed};
+
+    let mut seeder = DeterministicSeeder::<ActivatedRandomGenerator>::new(Seed(seed));
+    BooleanEngine::replace_thread_local(BooleanEngine::new_from_seeder(&mut seeder));
+}
+
+#[cfg(feature = "client")]
+pub fn decrypt_bools(ciphertext: &[Ciphertext], ck: &ClientKey) -> Vec<bool> {
+    let mut bools = vec![];
+
+    for cipher in ciphertext {
+        bools.push(ck.decrypt(cipher));
+    }
+    bools
+}
+
+// Error of hash_verified
+#[cfg(feature = "client")]
+#[derive(Debug)]
+pub enum VerifiedHashError<E> {
+    // The job couldn't be submitted, or its result couldn't be received
+    Submit(E),
+    // Every attempt returned a wrong digest, the last one being digest
+    Mismatch { attempts: usize, digest: Vec<u8> },
+}
+
+#[cfg(feature = "client")]
+impl<E: fmt::Display> fmt::Display for VerifiedHashError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VerifiedHashError::Submit(e) => write!(f, "job failed: {}", e),
+            VerifiedHashError::Mismatch { attempts, .. } => write!(f, "wrong digest in all {} attempts", attempts),
+        }
+    }
+}
+
+#[cfg(feature = "client")]
+impl<E: fmt::Debug + fmt::Display> error::Error for VerifiedHashError<E> {}
+
+// Hashes the message on the server, and checks the decrypted digest against the sha256 of the message computed locally.
+// A wrong digest (e.g. after a bootstrapping failure) is discarded and the job resubmitted, up to max_attempts times.
+// submit sends the encrypted padded input to the server and returns the encrypted digest. The input is encrypted again
+// for every attempt: resubmitting the same ciphertexts would fail the same way, since bootstrapping is deterministic.
+// Returns the digest and the number of attempts it took. The server learns that an attempt failed when it's resubmitted
+#[cfg(feature = "client")]
+pub fn hash_verified<E>(
+    message: &[u8],
+    max_attempts: usize,
+    ck: &ClientKey,
+    mut submit: impl FnMut(Vec<Ciphertext>) -> Result<Vec<Ciphertext>, E>,
+) -> Result<([u8; 32], usize), VerifiedHashError<E>> {
+    assert!(max_attempts > 0, "at least one attempt is needed");
+    let expected: [u8; 32] = Sha256::digest(message).into();
+    let padded_input = pad_sha256_data(message);
+
+    let mut digest = vec![];
+    for attempt in 1..=max_attempts {
+        let encrypted_digest = submit(encrypt_bools(&padded_input, ck)).map_err(VerifiedHashError::Submit)?;
+        digest = decrypt_bools(&encrypted_digest, ck)
+            .chunks(8)
+            .map(|bits| bits.iter().fold(0, |acc, bit| (acc << 1) | *bit as u8))
+            .collect();
+
+        if digest == expected {
+            return Ok((expected, attempt));
+        }
+    }
+
+    Err(VerifiedHashError::Mismatch { attempts: max_attempts, digest })
+}
+
+// To represent decrypted digest bools as hexadecimal String
+pub fn bools_to_hex(bools: Vec<bool>) -> String {
+    let mut hex_string = String::new();
+    let mut byte = 0u8;
+    let mut counter = 0;
+
+    for bit in bools {
+        byte <<= 1;
+        if bit {
+            byte |= 1;
+        }
+
+        counter += 1;
+
+        if counter == 8 {
+            hex_string.push_str(&format!("{:02x}", byte));
+            byte = 0;
+            counter = 0;
+        }
+    }
+
+    // Handle any remaining bits in case the bools vector length is not a multiple of 8
+    if counter > 0 {
+        byte <<= 8 - counter;
+        hex_string.push_str(&format!("{:02x}", byte));
+    }
+
+    hex_string
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_bool_array(arr: [i32; 32]) -> [bool; 32] {
+        let mut bool_arr = [false; 32];
+        for i in 0..32 {
+            if arr[i] == 1 {
+                bool_arr[i] = true;
+            }
+        }
+        bool_arr
+    }
+
+    #[test]
+    fn test_bools_to_hex() {
+        let bools = to_bool_array([1,0,0,1,0,0,0,0,1,0,1,1,1,1,1,0,1,1,1,1,1,1,1,1,1,1,1,1,1,0,1,0,]);
+        let hex_bools = bools_to_hex(bools.to_vec());
+
+        assert_eq!(hex_bools, "90befffa");
+    }
+
+    #[test]
+    #[cfg(feature = "client")]
+    fn test_encrypt_decrypt_bools() {
+        use crate::test_keys::test_keys;
+
+        let (ck, _) = test_keys();
+        let bools = vec![true, false, true, true, false];
+
+        assert_eq!(decrypt_bools(&encrypt_bools(&bools, &ck), &ck), bools);
+    }
+
+    #[test]
+    #[cfg(feature = "client")]
+    fn test_encrypt_bools_many() {
+        use crate::test_keys::test_keys;
+
+        let (ck, _) = test_keys();
+        let batch = vec![vec![true, false], vec![], vec![false, false, true]];
+
+        let encrypted = encrypt_bools_many(&batch, &ck);
+        let decrypted: Vec<Vec<bool>> = encrypted.iter().map(|ciphertext| decrypt_bools(ciphertext, &ck)).collect();
+        assert_eq!(decrypted, batch);
+    }
+
+    #[test]
+    #[cfg(feature = "client")]
+    fn test_seeded_encryption_is_reproducible() {
+        let bools = vec![true, false, true, true, false];
+        let run = || {
+            seed_thread_rng(42);
+            let (ck, _) = gen_keys();
+            bincode::serialize(&encrypt_bools(&bools, &ck)).unwrap()
+        };
+
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    #[cfg(all(feature = "client", feature = "server"))]
+    fn test_hash_verified() {
+        use crate::sha256::sha256_fhe;
+        use crate::test_keys::test_keys;
+
+        let (ck, sk) = test_keys();
+        let expected: [u8; 32] = Sha256::digest(b"abc").into();
+
+        // The first attempt fails, the second one succeeds
+        let mut jobs = 0;
+        let result = hash_verified(b"abc", 3, &ck, |input| -> Result<_, String> {
+            jobs += 1;
+            let mut digest = sha256_fhe(input, &sk);
+            if jobs == 1 {
+                digest[100] = sk.not(&digest[100]);
+            }
+            Ok(digest)
+        });
+        assert_eq!(result.unwrap(), (expected, 2));
+
+        // Every attempt fails
+        let result = hash_verified(b"abc", 3, &ck, |input| -> Result<_, String> {
+            Ok(sha256_fhe(input, &sk)[..128].to_vec())
+        });
+        match result {
+            Err(VerifiedHashError::Mismatch { attempts, digest }) => {
+                assert_eq!((attempts, digest), (3, expected[..16].to_vec()))
+            }
+            _ => panic!("expected a mismatch"),
+        }
+
+        // Submission errors are returned right away
+        let result = hash_verified(b"abc", 3, &ck, |_| Err("server unavailable"));
+        assert_eq!(result.unwrap_err().to_string(), "job failed: server unavailable");
+    }
+}
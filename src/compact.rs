@@ -0,0 +1,195 @@
+// This module shrinks encrypted outputs (such as the 256-bit digest) before they are sent back to the client. Each
+// ciphertext is an LWE ciphertext of lwe_dimension + 1 coefficients of 32 bits, but decryption only needs the top bits
+// of each coefficient: the client keeps the sign of the phase, whose noise is well below the 1/8 of the torus that
+// separates true from false. Rounding every coefficient to its top coefficient_bits bits (modulus switching) adds a
+// rounding error to the phase, and keeps the rest of the ciphertext as it is.
+//
+// With 12 bits, the rounding error of the default parameters has a standard deviation of about 2^22.5 (out of 2^32),
+// against about 2^26 for the noise the ciphertexts already have, so it doesn't change the decryption failure
+// probability noticeably, and the digest is 2.7 times smaller. Every bit less doubles the rounding error, and 10 bits
+// is the safe minimum: its error (2^24.5) only adds 6% to the deviation of the noise, where 9 bits already make the
+// failure probability four orders of magnitude larger, and 5 bits (2^29.5) go past the 1/8 margin itself.
+//
+// Compacting needs no key and can't reveal anything, since it's a public function of the ciphertexts. The client
+// expands the compact form back into regular ciphertexts and decrypts them as usual.
+
+use serde::{Deserialize, Serialize};
+use tfhe::boolean::prelude::*;
+use tfhe::core_crypto::entities::LweCiphertext;
+use crate::parameters::ParameterSet;
+
+pub const DEFAULT_COEFFICIENT_BITS: u32 = 12;
+
+// Phase of a trivial (noiseless) ciphertext of true, 1/8 of the torus, and of false, -1/8
+const PLAINTEXT_TRUE: u32 = 1 << 29;
+const PLAINTEXT_FALSE: u32 = 7 << 29;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CompactCiphertexts {
+    coefficient_bits: u32,
+    lwe_size: u32,
+    count: u32,
+    // Coefficients of every ciphertext, coefficient_bits each, packed most significant bit first
+    data: Vec<u8>,
+}
+
+impl CompactCiphertexts {
+    // Rounds the coefficients of the ciphertexts (under the keys of the parameter set) to their top coefficient_bits
+    // bits. Trivial ciphertexts become noiseless LWE ciphertexts of the same value
+    pub fn compact(ciphertexts: &[Ciphertext], parameter_set: ParameterSet, coefficient_bits: u32) -> Self {
+        assert!((1..=32).contains(&coefficient_bits), "coefficient bits must be between 1 and 32");
+        let lwe_size = parameter_set.parameters().lwe_dimension.0 + 1;
+
+        let mut writer = BitWriter::default();
+        for ciphertext in ciphertexts {
+            match ciphertext {
+                Ciphertext::Encrypted(lwe) => {
+                    assert_eq!(lwe.as_ref().len(), lwe_size, "ciphertext is not under the keys of the parameter set");
+                    for coefficient in lwe.as_ref() {
+                        writer.write(round(*coefficient, coefficient_bits), coefficient_bits);
+                    }
+                }
+                Ciphertext::Trivial(value) => {
+                    for _ in 1..lwe_size {
+                        writer.write(0, coefficient_bits);
+                    }
+                    let body = if *value { PLAINTEXT_TRUE } else { PLAINTEXT_FALSE };
+                    writer.write(round(body, coefficient_bits), coefficient_bits);
+                }
+            }
+        }
+
+        CompactCiphertexts {
+            coefficient_bits,
+            lwe_size: lwe_size as u32,
+            count: ciphertexts.len() as u32,
+            data: writer.finish(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    // Regular ciphertexts, with the rounded coefficients. Fails if the compact form is inconsistent, e.g. corrupted
+    pub fn expand(&self) -> Result<Vec<Ciphertext>, String> {
+        let bits = self.coefficient_bits;
+        if !(1..=32).contains(&bits) || self.lwe_size == 0 {
+            return Err(format!("invalid compact ciphertexts: {}-bit coefficients, LWE size {}", bits, self.lwe_size));
+        }
+        let total_bits = self.count as u64 * self.lwe_size as u64 * bits as u64;
+        if self.data.len() as u64 != total_bits.div_ceil(8) {
+            return Err(format!("compact ciphertexts have {} bytes instead of {}", self.data.len(), total_bits.div_ceil(8)));
+        }
+
+        let mut reader = BitReader { data: &self.data, position: 0 };
+        Ok((0..self.count)
+            .map(|_| {
+                let coefficients: Vec<u32> = (0..self.lwe_size)
+                    .map(|_| {
+                        let value = reader.read(bits);
+                        if bits == 32 { value as u32 } else { (value as u32) << (32 - bits) }
+                    })
+                    .collect();
+                Ciphertext::Encrypted(LweCiphertext::from_container(coefficients))
+            })
+            .collect())
+    }
+}
+
+// Top bits of a coefficient, rounded to the nearest (modulo 2^bits)
+fn round(coefficient: u32, bits: u32) -> u64 {
+    if bits == 32 {
+        return coefficient as u64;
+    }
+    ((coefficient as u64 + (1 << (31 - bits))) >> (32 - bits)) & ((1 << bits) - 1)
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    buffered: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u64, bits: u32) {
+        self.buffer = (self.buffer << bits) | value;
+        self.buffered += bits;
+        while self.buffered >= 8 {
+            self.buffered -= 8;
+            self.bytes.push((self.buffer >> self.buffered) as u8);
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.buffered > 0 {
+            self.bytes.push((self.buffer << (8 - self.buffered)) as u8);
+        }
+        self.bytes
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, bits: u32) -> u64 {
+        let mut value = 0;
+        for _ in 0..bits {
+            let bit = (self.data[self.position / 8] >> (7 - self.position % 8)) & 1;
+            value = (value << 1) | bit as u64;
+            self.position += 1;
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round() {
+        assert_eq!(round(0x1234_5678, 12), 0x123);
+        assert_eq!(round(0x1238_0000, 12), 0x124);
+        assert_eq!(round(0xfff8_0000, 12), 0);
+        assert_eq!(round(0x1234_5678, 32), 0x1234_5678);
+    }
+
+    #[test]
+    #[cfg(feature = "client")]
+    fn test_compact() {
+        let bits = [true, false, false, true, true, false, true, true, false];
+
+        for parameter_set in [ParameterSet::Default, ParameterSet::TfheLib] {
+            let (ck, sk) = parameter_set.gen_keys();
+            let mut ciphertexts: Vec<Ciphertext> = bits.iter().map(|bit| ck.encrypt(*bit)).collect();
+            ciphertexts.push(sk.trivial_encrypt(true));
+
+            for coefficient_bits in [DEFAULT_COEFFICIENT_BITS, 10, 32] {
+                let compact = CompactCiphertexts::compact(&ciphertexts, parameter_set, coefficient_bits);
+                assert_eq!(compact.len(), 10);
+
+                let expanded = compact.expand().unwrap();
+                let decrypted: Vec<bool> = expanded.iter().map(|ciphertext| ck.decrypt(ciphertext)).collect();
+                assert_eq!(decrypted[..9], bits);
+                assert!(decrypted[9]);
+            }
+
+            let lwe_size = parameter_set.parameters().lwe_dimension.0 + 1;
+            let compact = CompactCiphertexts::compact(&ciphertexts, parameter_set, DEFAULT_COEFFICIENT_BITS);
+            assert_eq!(compact.data.len(), (10 * lwe_size * 12).div_ceil(8));
+
+            let mut truncated = compact;
+            truncated.data.pop();
+            assert!(truncated.expand().is_err());
+        }
+    }
+}
@@ -0,0 +1,62 @@
+// This module reads the runtime configuration of a server: settings that an operator can change while the server is
+// running, without restarting it and losing the computation in progress. The file is made of `key = value` lines, with
+// blank lines and `#` comments ignored:
+//
+// # Leave some cores to the other tenants of this machine
+// threads = 8
+//
+// A server re-reads the file between two units of work (e.g. blocks) and applies the settings that are present.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RuntimeConfig {
+    // Threads of the homomorphic evaluation (see threads::ThreadLimit)
+    pub threads: Option<usize>,
+}
+
+impl RuntimeConfig {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut config = RuntimeConfig::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, value) = line.split_once('=').ok_or(format!("line {}: expected `key = value`", number + 1))?;
+            match key.trim() {
+                "threads" => match value.trim().parse() {
+                    Ok(threads) if threads > 0 => config.threads = Some(threads),
+                    _ => return Err(format!("line {}: threads must be a positive integer", number + 1)),
+                },
+                key => return Err(format!("line {}: unknown setting {}", number + 1, key)),
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = RuntimeConfig::parse("# co-tenanted machine\n\nthreads = 8 # half of the cores\n").unwrap();
+        assert_eq!(config.threads, Some(8));
+        assert_eq!(RuntimeConfig::parse("").unwrap(), RuntimeConfig::default());
+
+        assert_eq!(RuntimeConfig::parse("threads = 0").unwrap_err(), "line 1: threads must be a positive integer");
+        assert_eq!(RuntimeConfig::parse("\nthreads 8").unwrap_err(), "line 2: expected `key = value`");
+        assert_eq!(RuntimeConfig::parse("cores = 8").unwrap_err(), "line 1: unknown setting cores");
+    }
+}
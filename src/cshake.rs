@@ -0,0 +1,129 @@
+// This module implements cSHAKE128 and cSHAKE256 (NIST SP 800-185), the customizable variants of SHAKE, and the
+// encodings that SP 800-185 uses to frame their inputs. cSHAKE absorbs a first block that encodes a function name
+// (reserved for the functions NIST defines on it, such as KMAC) and a customization string, which separate the domains
+// of different uses of the same message, before the message itself. Both strings are public and only trivially
+// encrypted, while the message is encrypted and its length is public. With both strings empty, cSHAKE is SHAKE.
+
+use tfhe::boolean::prelude::*;
+use crate::keccak::{keccak_sponge, KeccakF1600, CSHAKE_SUFFIX, SHAKE_SUFFIX};
+use crate::sponge::Sponge;
+
+// cSHAKE128 and cSHAKE256 output of any length, in bytes
+pub fn cshake128(
+    message: &[Ciphertext],
+    output_len: usize,
+    function_name: &[u8],
+    customization: &[u8],
+    sk: &ServerKey,
+) -> Vec<Ciphertext> {
+    cshake(message, 256, output_len, function_name, customization, sk)
+}
+
+pub fn cshake256(
+    message: &[Ciphertext],
+    output_len: usize,
+    function_name: &[u8],
+    customization: &[u8],
+    sk: &ServerKey,
+) -> Vec<Ciphertext> {
+    cshake(message, 512, output_len, function_name, customization, sk)
+}
+
+fn cshake(
+    message: &[Ciphertext],
+    capacity: usize,
+    output_len: usize,
+    function_name: &[u8],
+    customization: &[u8],
+    sk: &ServerKey,
+) -> Vec<Ciphertext> {
+    let mut sponge = cshake_sponge(capacity, function_name, customization, sk);
+    sponge.absorb(message, sk);
+    sponge.squeeze(output_len, sk)
+}
+
+// Keccak sponge of cSHAKE with the given capacity, which has absorbed the block encoding the function name and the
+// customization string, for the functions that frame their own input after it
+pub fn cshake_sponge(capacity: usize, function_name: &[u8], customization: &[u8], sk: &ServerKey) -> Sponge<KeccakF1600> {
+    if function_name.is_empty() && customization.is_empty() {
+        return keccak_sponge(capacity, SHAKE_SUFFIX, sk);
+    }
+
+    let mut sponge = keccak_sponge(capacity, CSHAKE_SUFFIX, sk);
+    let rate = sponge.rate() / 8;
+
+    let strings = [encode_string(function_name), encode_string(customization)].concat();
+    sponge.absorb_plain(&bytepad(&strings, rate), sk);
+    sponge
+}
+
+// The integer encodings of SP 800-185: the big-endian bytes of x (at least one), preceded or followed by their count
+pub fn left_encode(x: u64) -> Vec<u8> {
+    let bytes = x.to_be_bytes();
+    let skip = (x.leading_zeros() as usize / 8).min(7);
+    [&[8 - skip as u8], &bytes[skip..]].concat()
+}
+
+pub fn right_encode(x: u64) -> Vec<u8> {
+    let mut encoded = left_encode(x);
+    encoded.rotate_left(1);
+    encoded
+}
+
+// The length in bits of the string, followed by the string
+pub fn encode_string(string: &[u8]) -> Vec<u8> {
+    [left_encode(8 * string.len() as u64), string.to_vec()].concat()
+}
+
+// The width, followed by the input and zeros up to a multiple of the width
+pub fn bytepad(input: &[u8], width: usize) -> Vec<u8> {
+    let mut padded = [left_encode(width as u64), input.to_vec()].concat();
+    padded.resize(padded.len().next_multiple_of(width), 0);
+    padded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boolean_ops::trivial_bytes;
+    use crate::keccak::shake128;
+    use crate::test_helpers::{decrypt_bytes, from_hex};
+    use crate::test_keys::test_keys;
+
+    #[test]
+    fn test_encodings() {
+        assert_eq!(left_encode(0), [1, 0]);
+        assert_eq!(left_encode(168), [1, 168]);
+        assert_eq!(left_encode(256), [2, 1, 0]);
+        assert_eq!(left_encode(u64::MAX), [8, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(right_encode(256), [1, 0, 2]);
+        assert_eq!(encode_string(b"KMAC"), [1, 32, b'K', b'M', b'A', b'C']);
+        assert_eq!(bytepad(&[7; 3], 8), [1, 8, 7, 7, 7, 0, 0, 0]);
+        assert_eq!(bytepad(&[7; 6], 8), [1, 8, 7, 7, 7, 7, 7, 7]);
+    }
+
+    // NIST cSHAKE samples #1 and #4, a function name with a customization string spanning two blocks (checked against
+    // a Python model), and the empty strings that make it SHAKE, computed with the clear backend
+    #[test]
+    fn test_cshake() {
+        let (ck, sk) = test_keys();
+        let data = trivial_bytes(&[0, 1, 2, 3], &sk);
+
+        assert_eq!(
+            decrypt_bytes(&cshake128(&data, 32, b"", b"Email Signature", &sk), &ck),
+            from_hex("c1c36925b6409a04f1b504fcbca9d82b4017277cb5ed2b2065fc1d3814d5aaf5"),
+        );
+        assert_eq!(
+            decrypt_bytes(&cshake256(&data, 64, b"", b"Email Signature", &sk), &ck),
+            from_hex("d008828e2b80ac9d2218ffee1d070c48b8e4c87bff32c9699d5b6896eee0edd164020e2be0560858d9c00c037e34a96937c561a74c412bb4c746469527281c8c"),
+        );
+
+        let customization = [b'x'; 150];
+        assert_eq!(
+            decrypt_bytes(&cshake128(&data, 16, b"Example", &customization, &sk), &ck),
+            from_hex("28a38632bd2e1fed3669fcacd1b9fc21"),
+        );
+
+        assert_eq!(decrypt_bytes(&cshake128(&data, 32, b"", b"", &sk), &ck), decrypt_bytes(&shake128(&data, 32, &sk), &ck));
+    }
+}
@@ -0,0 +1,296 @@
+// This module contains debugging helpers for tests, available with the "debug-decrypt" feature. They hold the ClientKey
+// next to the server side computation, so they must never be used on a real server: their only purpose is to localize
+// which round diverges when a bug (or a noise failure) corrupts a hash, and to inject simulated noise failures to check
+// that they are detected.
+
+use std::fmt;
+use tfhe::boolean::prelude::*;
+use crate::sha256::{compute_w_plain, sha256_fhe_hooked, sha256_fhe_observed, RoundHook, RoundObserver, H0, K};
+
+// Decrypted working variables a..h after a round
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RoundState {
+    pub block: usize,
+    pub round: usize,
+    pub words: [u32; 8],
+}
+
+// Round observer that decrypts the intermediate states, printing and recording them
+pub struct DecryptingObserver<'a> {
+    ck: &'a ClientKey,
+    pub states: Vec<RoundState>,
+}
+
+impl<'a> DecryptingObserver<'a> {
+    pub fn new(ck: &'a ClientKey) -> Self {
+        DecryptingObserver { ck, states: vec![] }
+    }
+}
+
+impl RoundObserver for DecryptingObserver<'_> {
+    fn after_round(&mut self, block: usize, round: usize, state: [&[Ciphertext; 32]; 8]) {
+        let words = state.map(|word| word.iter().fold(0u32, |acc, bit| (acc << 1) | self.ck.decrypt(bit) as u32));
+
+        println!(
+            "block {} round {:2}: {}",
+            block,
+            round,
+            words.iter().map(|word| format!("{:08x}", word)).collect::<Vec<_>>().join(" "),
+        );
+        self.states.push(RoundState { block, round, words });
+    }
+}
+
+// Plaintext reference sha256 of a padded input, returning the working variables after every round of every block
+pub fn reference_trace(padded_input: &[bool]) -> Vec<RoundState> {
+    assert_eq!(padded_input.len() % 512, 0, "padded input length is not a multiple of 512");
+
+    let mut hash = H0;
+    let mut states = Vec::with_capacity(padded_input.len() / 512 * 64);
+
+    for (block, chunk) in padded_input.chunks(512).enumerate() {
+        let words: [u32; 16] = std::array::from_fn(|i| chunk[32 * i..32 * (i + 1)].iter().fold(0, |acc, bit| (acc << 1) | *bit as u32));
+        let w = compute_w_plain(&words);
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = hash;
+
+        for round in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let temp1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[round]).wrapping_add(w[round]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+
+            states.push(RoundState { block, round, words: [a, b, c, d, e, f, g, h] });
+        }
+
+        for (word, value) in hash.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+
+    states
+}
+
+// First working variable where the FHE computation differs from the plaintext reference
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub block: usize,
+    pub round: usize,
+    // Index of the working variable, 0 for a to 7 for h
+    pub word: usize,
+    pub expected: u32,
+    pub found: u32,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "block {} round {}: {} is {:08x}, expected {:08x}",
+            self.block, self.round, (b'a' + self.word as u8) as char, self.found, self.expected,
+        )
+    }
+}
+
+// Decrypts every round of the FHE computation and compares it with the plaintext reference in lockstep, stopping the
+// comparison at the first divergence
+struct LockstepObserver<'a> {
+    ck: &'a ClientKey,
+    reference: std::vec::IntoIter<RoundState>,
+    divergence: Option<Divergence>,
+}
+
+impl RoundObserver for LockstepObserver<'_> {
+    fn after_round(&mut self, block: usize, round: usize, state: [&[Ciphertext; 32]; 8]) {
+        let expected = self.reference.next().expect("reference trace is shorter than the computation");
+        if self.divergence.is_some() {
+            return;
+        }
+
+        let found = state.map(|word| word.iter().fold(0u32, |acc, bit| (acc << 1) | self.ck.decrypt(bit) as u32));
+        if let Some(word) = (0..8).find(|i| found[*i] != expected.words[*i]) {
+            self.divergence = Some(Divergence { block, round, word, expected: expected.words[word], found: found[word] });
+        }
+    }
+}
+
+// Hashes the encrypted input (which should encrypt padded_input) while checking every round against the plaintext
+// reference. Returns the encrypted digest along with the first divergence, if any
+pub fn differential_trace(
+    padded_input: &[bool],
+    encrypted_input: Vec<Ciphertext>,
+    ck: &ClientKey,
+    sk: &ServerKey,
+) -> (Vec<Ciphertext>, Option<Divergence>) {
+    assert_eq!(padded_input.len(), encrypted_input.len(), "encrypted input has a different length");
+
+    let mut observer = LockstepObserver {
+        ck,
+        reference: reference_trace(padded_input).into_iter(),
+        divergence: None,
+    };
+    let digest = sha256_fhe_observed(encrypted_input, &mut observer, sk);
+
+    (digest, observer.divergence)
+}
+
+// Simulated bootstrapping failure: a bit of a working variable (0 for a to 7 for h) is flipped at the end of a round.
+// Bits are numbered from the most significant one, like the ciphertexts of a word
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fault {
+    pub block: usize,
+    pub round: usize,
+    pub word: usize,
+    pub bit: usize,
+}
+
+// Round hook that corrupts the working variables with the given faults, recording the ones it injected
+pub struct FaultInjector<'a> {
+    faults: Vec<Fault>,
+    pub injected: Vec<Fault>,
+    sk: &'a ServerKey,
+}
+
+impl<'a> FaultInjector<'a> {
+    pub fn new(faults: &[Fault], sk: &'a ServerKey) -> Self {
+        FaultInjector { faults: faults.to_vec(), injected: vec![], sk }
+    }
+}
+
+impl RoundHook for FaultInjector<'_> {
+    fn after_round(&mut self, block: usize, round: usize, state: &mut [[Ciphertext; 32]; 8]) {
+        for fault in self.faults.iter().filter(|fault| fault.block == block && fault.round == round) {
+            state[fault.word][fault.bit] = self.sk.not(&state[fault.word][fault.bit]);
+            self.injected.push(*fault);
+        }
+    }
+}
+
+// Injects the faults of each round, then checks the corrupted state against the plaintext reference
+struct FaultyLockstep<'a> {
+    injector: FaultInjector<'a>,
+    lockstep: LockstepObserver<'a>,
+}
+
+impl RoundHook for FaultyLockstep<'_> {
+    fn after_round(&mut self, block: usize, round: usize, state: &mut [[Ciphertext; 32]; 8]) {
+        self.injector.after_round(block, round, state);
+        self.lockstep.after_round(block, round, state.each_ref());
+    }
+}
+
+// Same as differential_trace, with faults injected into the computation. Returns the encrypted (corrupted) digest, the
+// faults that were injected and the first divergence, which should be the earliest fault
+pub fn differential_trace_faulty(
+    padded_input: &[bool],
+    encrypted_input: Vec<Ciphertext>,
+    faults: &[Fault],
+    ck: &ClientKey,
+    sk: &ServerKey,
+) -> (Vec<Ciphertext>, Vec<Fault>, Option<Divergence>) {
+    assert_eq!(padded_input.len(), encrypted_input.len(), "encrypted input has a different length");
+
+    let mut hook = FaultyLockstep {
+        injector: FaultInjector::new(faults, sk),
+        lockstep: LockstepObserver { ck, reference: reference_trace(padded_input).into_iter(), divergence: None },
+    };
+    let digest = sha256_fhe_hooked(encrypted_input, &mut hook, sk);
+
+    (digest, hook.injector.injected, hook.lockstep.divergence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boolean_ops::{eq_bits, trivial_bytes};
+    use crate::client::bools_to_hex;
+    use crate::padding::pad_sha256_input;
+    use crate::test_keys::test_keys;
+
+    #[test]
+    fn test_decrypting_observer() {
+        let (ck, sk) = test_keys();
+
+        let input = pad_sha256_input("abc").iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+        let mut observer = DecryptingObserver::new(&ck);
+        sha256_fhe_observed(input, &mut observer, &sk);
+
+        // First and last rounds of the FIPS 180-2 sha256("abc") example
+        assert_eq!(observer.states.len(), 64);
+        assert_eq!(
+            observer.states[0].words,
+            [0x5d6aebcd, 0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xfa2a4622, 0x510e527f, 0x9b05688c, 0x1f83d9ab],
+        );
+        assert_eq!(
+            observer.states[63].words,
+            [0x506e3058, 0xd39a2165, 0x04d24d6c, 0xb85e2ce9, 0x5ef50f24, 0xfb121210, 0x948d25b6, 0x961f4894],
+        );
+    }
+
+    #[test]
+    fn test_differential_trace() {
+        let (ck, sk) = test_keys();
+
+        let padded_input = pad_sha256_input("abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq");
+        let encrypted_input = padded_input.iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+
+        let (_, divergence) = differential_trace(&padded_input, encrypted_input, &ck, &sk);
+        assert_eq!(divergence, None);
+
+        // Flipping a bit of the second block makes the first round of that block diverge
+        let mut corrupted: Vec<Ciphertext> = padded_input.iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+        corrupted[512 + 31] = sk.not(&corrupted[512 + 31]);
+
+        let (_, divergence) = differential_trace(&padded_input, corrupted, &ck, &sk);
+        let divergence = divergence.unwrap();
+        assert_eq!((divergence.block, divergence.round, divergence.word), (1, 0, 0));
+        assert_eq!(divergence.found ^ divergence.expected, 1);
+    }
+
+    #[test]
+    fn test_fault_injection() {
+        let (ck, sk) = test_keys();
+        let decrypt = |bits: &[Ciphertext]| bools_to_hex(bits.iter().map(|bit| ck.decrypt(bit)).collect());
+
+        let padded_input = pad_sha256_input("abc");
+        let encrypted_input: Vec<Ciphertext> = padded_input.iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+        let expected = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let expected_bytes: Vec<u8> = (0..32).map(|i| u8::from_str_radix(&expected[2 * i..2 * i + 2], 16).unwrap()).collect();
+
+        // Without faults, the injector leaves the computation as it is
+        let mut injector = FaultInjector::new(&[], &sk);
+        assert_eq!(decrypt(&sha256_fhe_hooked(encrypted_input.clone(), &mut injector, &sk)), expected);
+
+        // The lockstep comparison reports the earliest of two faults, at the corrupted bit
+        let faults = [
+            Fault { block: 0, round: 40, word: 0, bit: 0 },
+            Fault { block: 0, round: 10, word: 4, bit: 3 },
+        ];
+        let (digest, injected, divergence) = differential_trace_faulty(&padded_input, encrypted_input.clone(), &faults, &ck, &sk);
+        assert_eq!(injected, [faults[1], faults[0]]);
+        let divergence = divergence.unwrap();
+        assert_eq!((divergence.block, divergence.round, divergence.word), (0, 10, 4));
+        assert_eq!(divergence.found ^ divergence.expected, 1 << 28);
+        assert!(divergence.to_string().starts_with("block 0 round 10: e is "));
+
+        // The corrupted digest fails the comparison with the expected digest, like the server's --expect match bit
+        assert_ne!(decrypt(&digest), expected);
+        assert!(!ck.decrypt(&eq_bits(&digest, &trivial_bytes(&expected_bytes, &sk), &sk)));
+
+        // Even a fault in the last round, whose bit only reaches the digest through the feed-forward, is detected
+        let fault = Fault { block: 0, round: 63, word: 7, bit: 31 };
+        let (digest, _, divergence) = differential_trace_faulty(&padded_input, encrypted_input, &[fault], &ck, &sk);
+        assert_eq!(divergence.map(|divergence| (divergence.round, divergence.word)), Some((63, 7)));
+        assert!(!ck.decrypt(&eq_bits(&digest, &trivial_bytes(&expected_bytes, &sk), &sk)));
+    }
+}
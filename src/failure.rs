@@ -0,0 +1,101 @@
+// This module estimates the probability that a computation returns a wrong result before running it, from the number of
+// gates it bootstraps and the failure probability of one gate for the parameter set. A wrong result needs at least one
+// gate to fail, so by the union bound its probability is at most the gate count times the failure probability of a gate.
+// Probabilities are kept as their log2, since the interesting ones are far below what an f64 near 1 can tell apart.
+
+use std::fmt;
+use crate::algorithm::Algorithm;
+use crate::parameters::ParameterSet;
+use crate::redundancy::{majority_gates, redundant_failure_log2};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FailureEstimate {
+    pub bootstrapped_gates: u64,
+    // Upper bound on the log2 of the failure probability, -inf for a computation without bootstrapped gates
+    pub failure_log2: f64,
+}
+
+impl FailureEstimate {
+    pub fn new(bootstrapped_gates: u64, parameter_set: ParameterSet) -> Self {
+        let failure_log2 = match bootstrapped_gates {
+            0 => f64::NEG_INFINITY,
+            gates => (parameter_set.gate_failure_log2() + (gates as f64).log2()).min(0.0),
+        };
+
+        FailureEstimate { bootstrapped_gates, failure_log2 }
+    }
+
+    // Hash of a padded input of the given number of blocks
+    pub fn hash(algorithm: Algorithm, blocks: usize, parameter_set: ParameterSet) -> Self {
+        FailureEstimate::new(algorithm.bootstrapped_gates(blocks), parameter_set)
+    }
+
+    // Both computations, which fail if either of them does
+    pub fn then(self, other: FailureEstimate) -> Self {
+        let (high, low) = if self.failure_log2 >= other.failure_log2 {
+            (self.failure_log2, other.failure_log2)
+        } else {
+            (other.failure_log2, self.failure_log2)
+        };
+        let failure_log2 = if low == f64::NEG_INFINITY { high } else { (high + (1.0 + (low - high).exp2()).log2()).min(0.0) };
+
+        FailureEstimate { bootstrapped_gates: self.bootstrapped_gates + other.bootstrapped_gates, failure_log2 }
+    }
+
+    // This computation on an odd number of copies, followed by the bitwise majority of their outputs of the given size
+    // (see the redundancy module)
+    pub fn redundant(self, copies: usize, output_bits: usize, parameter_set: ParameterSet) -> Self {
+        let computation = FailureEstimate {
+            bootstrapped_gates: copies as u64 * self.bootstrapped_gates,
+            failure_log2: redundant_failure_log2(self.failure_log2, copies),
+        };
+
+        computation.then(FailureEstimate::new(majority_gates(copies, output_bits), parameter_set))
+    }
+
+    // Whether the failure probability is above 2^max_failure_log2
+    pub fn exceeds(&self, max_failure_log2: f64) -> bool {
+        self.failure_log2 > max_failure_log2
+    }
+}
+
+impl fmt::Display for FailureEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} bootstrapped gates, failure probability ", self.bootstrapped_gates)?;
+        match self.failure_log2 {
+            f64::NEG_INFINITY => write!(f, "0"),
+            failure_log2 => write!(f, "2^{:.1}", failure_log2),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_estimate() {
+        let gate = FailureEstimate::new(1, ParameterSet::Default);
+        assert_eq!(gate.failure_log2, ParameterSet::Default.gate_failure_log2());
+        assert_eq!(FailureEstimate::new(1 << 20, ParameterSet::Default).failure_log2, -20.0);
+        assert_eq!(FailureEstimate::new(u64::MAX, ParameterSet::Default).failure_log2, 0.0);
+
+        let none = FailureEstimate::new(0, ParameterSet::Default);
+        assert_eq!(none.to_string(), "0 bootstrapped gates, failure probability 0");
+        assert_eq!(gate.then(none), gate);
+        assert_eq!(gate.then(gate), FailureEstimate::new(2, ParameterSet::Default));
+
+        let hash = FailureEstimate::hash(Algorithm::Sha256, 2, ParameterSet::Default);
+        assert_eq!(hash.bootstrapped_gates, 2 * Algorithm::Sha256.bootstrapped_gates_per_block());
+        assert!(hash.exceeds(-30.0) && !hash.exceeds(-20.0));
+        let streebog = FailureEstimate::hash(Algorithm::Streebog256, 2, ParameterSet::Default);
+        assert_eq!(streebog.bootstrapped_gates, 3 * Algorithm::Streebog256.bootstrapped_gates_per_block());
+
+        // Voting over 3 copies takes 3 hashes and 4 gates per digest bit. Two copies failing is then so unlikely that the
+        // 1024 gates of the vote itself make most of the failure probability
+        let voted = hash.redundant(3, 256, ParameterSet::Default);
+        assert_eq!(voted.bootstrapped_gates, 3 * hash.bootstrapped_gates + 1024);
+        assert!((voted.failure_log2 + 30.0).abs() < 0.01);
+        assert_eq!(hash.redundant(1, 256, ParameterSet::Default), hash);
+    }
+}
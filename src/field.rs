@@ -0,0 +1,221 @@
+// This module implements arithmetic modulo a public prime p on encrypted field elements, for the hashes designed for
+// proof systems (Poseidon and MiMC), whose rounds are additions, multiplications and powers in a prime field rather
+// than bitwise operations. An element is an N-bit word holding its representative below p, most significant bit first,
+// with p < 2^N and N a power of two (for the adders).
+//
+// Everything is built from the generic word adders. An addition is an adder, a comparison with p (the carry-out of
+// adding 2^N - p) and a select; a doubling is the same without the first adder. A multiplication is N steps of
+// double-and-add over the bits of one operand, so its depth is linear in N and it costs about 2N additions: a product
+// of 64-bit elements bootstraps about 66k gates, and a product with a public constant about 40k. These hashes are cheap
+// in a proof system, not under FHE, where sha256 compresses a block with about as many gates as two products.
+
+use tfhe::boolean::prelude::*;
+use crate::boolean_ops::{add_with_carry, and, or_gate, select, sub, trivial_word};
+
+// The Goldilocks prime 2^64 - 2^32 + 1 of Plonky2 and other STARK systems
+pub const GOLDILOCKS: u64 = 0xffff_ffff_0000_0001;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrimeField<const N: usize> {
+    modulus: u64,
+}
+
+impl<const N: usize> PrimeField<N> {
+    // The modulus must be a prime that fits in N bits, which isn't checked beyond being odd
+    pub fn new(modulus: u64) -> Self {
+        assert!(N.is_power_of_two() && N <= 64, "elements must be a power of two of at most 64 bits");
+        assert!(modulus > 2 && !modulus.is_multiple_of(2), "modulus must be an odd prime");
+        assert!(N == 64 || modulus >> N == 0, "modulus doesn't fit in N bits");
+
+        PrimeField { modulus }
+    }
+
+    pub fn modulus(&self) -> u64 {
+        self.modulus
+    }
+
+    // Trivial encryption of a public element, reduced modulo p
+    pub fn constant(&self, value: u64, sk: &ServerKey) -> [Ciphertext; N] {
+        trivial_word(value % self.modulus, sk)
+    }
+
+    pub fn add(&self, a: &[Ciphertext; N], b: &[Ciphertext; N], sk: &ServerKey) -> [Ciphertext; N] {
+        let (sum, carry) = add_with_carry(a, b, sk);
+        self.reduce(&sum, &carry, sk)
+    }
+
+    // a - b is a + (p - b), where p - b is between 1 and p
+    pub fn sub(&self, a: &[Ciphertext; N], b: &[Ciphertext; N], sk: &ServerKey) -> [Ciphertext; N] {
+        self.add(a, &sub(&trivial_word(self.modulus, sk), b, sk), sk)
+    }
+
+    pub fn neg(&self, a: &[Ciphertext; N], sk: &ServerKey) -> [Ciphertext; N] {
+        self.sub(&self.constant(0, sk), a, sk)
+    }
+
+    // Double-and-add over the bits of b, most significant first, adding a wherever b has a one
+    pub fn mul(&self, a: &[Ciphertext; N], b: &[Ciphertext; N], sk: &ServerKey) -> [Ciphertext; N] {
+        b.iter().fold(self.constant(0, sk), |product, bit| {
+            let multiple = and(a, &std::array::from_fn(|_| bit.clone()), sk);
+            self.add(&self.double(&product, sk), &multiple, sk)
+        })
+    }
+
+    // Same as mul, where the ones of the public constant are the only additions
+    pub fn mul_constant(&self, a: &[Ciphertext; N], constant: u64, sk: &ServerKey) -> [Ciphertext; N] {
+        let constant = constant % self.modulus;
+        (0..N).rev().fold(self.constant(0, sk), |product, i| {
+            let doubled = self.double(&product, sk);
+            if (constant >> i) & 1 == 1 { self.add(&doubled, a, sk) } else { doubled }
+        })
+    }
+
+    // Power to a public exponent (at least 1) by left-to-right square-and-multiply
+    pub fn pow(&self, a: &[Ciphertext; N], exponent: u64, sk: &ServerKey) -> [Ciphertext; N] {
+        assert!(exponent > 0, "exponent must be positive");
+
+        (0..exponent.ilog2()).rev().fold(a.clone(), |power, i| {
+            let squared = self.mul(&power, &power, sk);
+            if (exponent >> i) & 1 == 1 { self.mul(&squared, a, sk) } else { squared }
+        })
+    }
+
+    // The same operations on public elements, for constants and reference computations
+    pub fn add_plain(&self, a: u64, b: u64) -> u64 {
+        ((a as u128 + b as u128) % self.modulus as u128) as u64
+    }
+
+    pub fn mul_plain(&self, a: u64, b: u64) -> u64 {
+        ((a as u128 * b as u128) % self.modulus as u128) as u64
+    }
+
+    pub fn pow_plain(&self, a: u64, exponent: u64) -> u64 {
+        (0..64).rev().fold(1, |power, i| {
+            let squared = self.mul_plain(power, power);
+            if (exponent >> i) & 1 == 1 { self.mul_plain(squared, a) } else { squared }
+        })
+    }
+
+    // Inverse of a nonzero element, as a^(p - 2)
+    pub fn inverse_plain(&self, a: u64) -> u64 {
+        assert!(!a.is_multiple_of(self.modulus), "zero has no inverse");
+        self.pow_plain(a, self.modulus - 2)
+    }
+
+    // Whether x^exponent is a permutation of the field, i.e. the exponent is coprime with p - 1
+    pub fn power_is_permutation(&self, exponent: u64) -> bool {
+        let (mut a, mut b) = (exponent, self.modulus - 1);
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a == 1
+    }
+
+    fn double(&self, a: &[Ciphertext; N], sk: &ServerKey) -> [Ciphertext; N] {
+        let shifted = std::array::from_fn(|i| if i + 1 < N { a[i + 1].clone() } else { sk.trivial_encrypt(false) });
+        self.reduce(&shifted, &a[0], sk)
+    }
+
+    // Reduces a value below 2p, given as its low N bits and its carry, by subtracting p if it's at least p: the carry
+    // of adding 2^N - p to the low bits is set when they are at least p
+    fn reduce(&self, low: &[Ciphertext; N], carry: &Ciphertext, sk: &ServerKey) -> [Ciphertext; N] {
+        let (reduced, at_least_p) = add_with_carry(low, &trivial_word(self.modulus.wrapping_neg(), sk), sk);
+        select(&or_gate(sk, carry, &at_least_p), &reduced, low, sk)
+    }
+}
+
+// The Grain LFSR that generates the round constants of the Poseidon reference implementation, with bit i of the 80-bit
+// state the i-th oldest one
+pub(crate) struct Grain(u128);
+
+impl Grain {
+    // Seeded with the parameters of a prime field instance with an x^alpha S-box, followed by 30 ones, and run 160
+    // times before its output is used
+    pub(crate) fn new(field_bits: u64, width: usize, full_rounds: usize, partial_rounds: usize) -> Self {
+        let fields = [
+            (1, 2),
+            (0, 4),
+            (field_bits, 12),
+            (width as u64, 12),
+            (full_rounds as u64, 10),
+            (partial_rounds as u64, 10),
+            ((1 << 30) - 1, 30),
+        ];
+        let seed = fields
+            .into_iter()
+            .flat_map(|(value, len)| (0..len).rev().map(move |k| (value >> k) & 1))
+            .enumerate()
+            .fold(0u128, |seed, (i, bit)| seed | (bit as u128) << i);
+
+        let mut grain = Grain(seed);
+        for _ in 0..160 {
+            grain.step();
+        }
+        grain
+    }
+
+    fn step(&mut self) -> bool {
+        let bit = [62, 51, 38, 23, 13, 0].iter().fold(0, |bit, i| bit ^ (self.0 >> i) & 1);
+        self.0 = (self.0 >> 1) | bit << 79;
+        bit == 1
+    }
+
+    // The bits are filtered in pairs: the second bit is output when the first one is set, and dropped otherwise
+    fn next_bit(&mut self) -> bool {
+        loop {
+            if self.step() {
+                return self.step();
+            }
+            self.step();
+        }
+    }
+
+    // Element made of as many bits as the modulus, most significant first, sampling again while it isn't below it
+    pub(crate) fn element(&mut self, modulus: u64) -> u64 {
+        let bits = 64 - modulus.leading_zeros();
+        loop {
+            let element = (0..bits).fold(0, |element, _| element << 1 | self.next_bit() as u64);
+            if element < modulus {
+                return element;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{decrypt_uint, encrypt_uint};
+    use crate::test_keys::test_keys;
+
+    #[test]
+    fn test_prime_field() {
+        let (ck, sk) = test_keys();
+
+        // A 16-bit prime close to 2^16, so that sums and doublings overflow the word
+        let field = PrimeField::<16>::new(65519);
+        let values = [0, 1, 2, 3, 4000, 32760, 65517, 65518];
+        let encrypted: Vec<[Ciphertext; 16]> = values.iter().map(|value| encrypt_uint(*value, &ck)).collect();
+
+        for (a, x) in values.iter().zip(&encrypted) {
+            for (b, y) in values.iter().zip(&encrypted) {
+                assert_eq!(decrypt_uint(&field.add(x, y, &sk), &ck), field.add_plain(*a, *b), "{} + {}", a, b);
+                assert_eq!(decrypt_uint(&field.sub(x, y, &sk), &ck), field.add_plain(*a, 65519 - b), "{} - {}", a, b);
+                assert_eq!(decrypt_uint(&field.mul(x, y, &sk), &ck), field.mul_plain(*a, *b), "{} * {}", a, b);
+            }
+            assert_eq!(decrypt_uint(&field.neg(x, &sk), &ck), (65519 - a) % 65519);
+            assert_eq!(decrypt_uint(&field.mul_constant(x, 65517, &sk), &ck), field.mul_plain(*a, 65517));
+            assert_eq!(decrypt_uint(&field.pow(x, 7, &sk), &ck), field.pow_plain(*a, 7));
+        }
+
+        assert_eq!(field.mul_plain(field.inverse_plain(4000), 4000), 1);
+        assert_eq!(field.pow_plain(3, 65518), 1);
+        assert!(field.power_is_permutation(3) && !field.power_is_permutation(2));
+
+        let field = PrimeField::<64>::new(GOLDILOCKS);
+        let (a, b) = (GOLDILOCKS - 1, 0x1234_5678_9abc_def0);
+        let (x, y) = (encrypt_uint(a, &ck), encrypt_uint(b, &ck));
+        assert_eq!(decrypt_uint(&field.mul(&x, &y, &sk), &ck), field.mul_plain(a, b));
+        assert_eq!(decrypt_uint(&field.add(&x, &x, &sk), &ck), GOLDILOCKS - 2);
+    }
+}
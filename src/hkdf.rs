@@ -0,0 +1,58 @@
+// This module implements HKDF-SHA256 (RFC 5869) on top of the homomorphic HMAC. The keying material is encrypted, while
+// the lengths of every input and of the output are public.
+
+use tfhe::boolean::prelude::*;
+use crate::boolean_ops::trivial_bytes;
+use crate::hmac::hmac_sha256;
+
+// Extracts a 256-bit pseudorandom key from the input keying material. An empty salt is equivalent to 32 zero bytes,
+// since HMAC zero pads its key anyway
+pub fn hkdf_extract(salt: &[Ciphertext], ikm: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    hmac_sha256(salt, ikm, sk)
+}
+
+// Expands the pseudorandom key into `length` bytes of output keying material, bound to the info string
+pub fn hkdf_expand(prk: &[Ciphertext], info: &[Ciphertext], length: usize, sk: &ServerKey) -> Vec<Ciphertext> {
+    assert!(length <= 255 * 32, "HKDF-SHA256 output is limited to 255 blocks");
+
+    let mut okm = Vec::with_capacity(length * 8);
+    let mut block = Vec::new();
+
+    for counter in 1..=length.div_ceil(32) as u8 {
+        let mut message = block;
+        message.extend_from_slice(info);
+        message.extend(trivial_bytes(&[counter], sk));
+
+        block = hmac_sha256(prk, &message, sk);
+        okm.extend_from_slice(&block);
+    }
+
+    okm.truncate(length * 8);
+    okm
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{decrypt_bytes, from_hex};
+    use crate::test_keys::test_keys;
+
+    // RFC 5869 test case 1, computed with the clear backend
+    #[test]
+    fn test_hkdf() {
+        let (ck, sk) = test_keys();
+
+        let ikm = trivial_bytes(&[0x0b; 22], &sk);
+        let salt = trivial_bytes(&from_hex("000102030405060708090a0b0c"), &sk);
+        let info = trivial_bytes(&from_hex("f0f1f2f3f4f5f6f7f8f9"), &sk);
+
+        let prk = hkdf_extract(&salt, &ikm, &sk);
+        assert_eq!(decrypt_bytes(&prk, &ck), from_hex("077709362c2e32df0ddc3f0dc47bba6390b6c73bb50f9c3122ec844ad7c2b3e5"));
+
+        let okm = hkdf_expand(&prk, &info, 42, &sk);
+        assert_eq!(
+            decrypt_bytes(&okm, &ck),
+            from_hex("3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865"),
+        );
+    }
+}
@@ -0,0 +1,148 @@
+// This module implements HMAC-SHA256 (RFC 2104) on top of the homomorphic sha256 function. The key and the message are
+// encrypted (public parts can be trivially encrypted with trivial_bytes), while their lengths are public.
+//
+// It also has the keyed prefix MAC sha256(key || message), for protocols that use this simpler construction, along with
+// the length extension attack that makes it unsafe as a general MAC and motivates HMAC.
+
+use tfhe::boolean::prelude::*;
+use crate::boolean_ops::eq_bits;
+use crate::padding::MdPadding;
+use crate::sha256::{pad_encrypted, sha256_fhe, Sha256Hasher, Sha256State};
+
+const BLOCK_BITS: usize = 512;
+const IPAD: u8 = 0x36;
+const OPAD: u8 = 0x5c;
+
+pub fn hmac_sha256(key: &[Ciphertext], message: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    assert_eq!(key.len() % 8, 0, "key is not a whole number of bytes");
+    assert_eq!(message.len() % 8, 0, "message is not a whole number of bytes");
+
+    // Keys longer than a block are hashed first, and then every key is zero padded to a full block
+    let mut block_key = if key.len() > BLOCK_BITS {
+        sha256_fhe(pad_encrypted(key.to_vec(), sk), sk)
+    } else {
+        key.to_vec()
+    };
+    block_key.resize(BLOCK_BITS, sk.trivial_encrypt(false));
+
+    let mut inner_input = xor_pad(&block_key, IPAD, sk);
+    inner_input.extend_from_slice(message);
+    let inner = sha256_fhe(pad_encrypted(inner_input, sk), sk);
+
+    let mut outer_input = xor_pad(&block_key, OPAD, sk);
+    outer_input.extend(inner);
+    sha256_fhe(pad_encrypted(outer_input, sk), sk)
+}
+
+// Computes the HMAC of the message and compares it to the tag, which may be truncated to its first bytes (as in RFC
+// 4868). Only the encrypted acceptance bit is returned, so the server doesn't learn whether the tag was valid
+pub fn hmac_verify(key: &[Ciphertext], message: &[Ciphertext], tag: &[Ciphertext], sk: &ServerKey) -> Ciphertext {
+    assert_eq!(tag.len() % 8, 0, "tag is not a whole number of bytes");
+    assert!(!tag.is_empty() && tag.len() <= 256, "tag must be between 1 and 32 bytes long");
+
+    let mac = hmac_sha256(key, message, sk);
+    eq_bits(&mac[..tag.len()], tag, sk)
+}
+
+// Prefix MAC: the sha256 digest of the key followed by the message, padded as a whole
+pub fn prefix_mac(key: &[Ciphertext], message: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    assert_eq!(key.len() % 8, 0, "key is not a whole number of bytes");
+    assert_eq!(message.len() % 8, 0, "message is not a whole number of bytes");
+
+    let mut input = key.to_vec();
+    input.extend_from_slice(message);
+    sha256_fhe(pad_encrypted(input, sk), sk)
+}
+
+// Length extension of a prefix MAC: given the tag of some key || message of keyed_len bits, computes the valid tag of
+// key || message || glue || suffix without knowing the key, where glue is extension_glue(keyed_len). Only the length
+// of the key is needed, which an attacker can guess
+pub fn extend_prefix_mac(tag: &[Ciphertext], keyed_len: usize, suffix: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    assert_eq!(tag.len(), 256, "tag is not a full sha256 digest");
+
+    // The tag is the chaining value after the padded key || message, so hashing can resume from it
+    let words = std::array::from_fn(|i| tag[32 * i..32 * (i + 1)].to_vec().try_into().unwrap());
+    let compressed = MdPadding::SHA256.padded_len(keyed_len) as u64;
+
+    let mut hasher = Sha256Hasher::from_state(Sha256State::from_words(words, compressed));
+    hasher.update(suffix, sk);
+    hasher.finalize(sk)
+}
+
+// Bits between the original message and the suffix in the message authenticated by extend_prefix_mac: the padding of
+// key || message, which is public
+pub fn extension_glue(keyed_len: usize) -> Vec<bool> {
+    MdPadding::SHA256.padding(keyed_len as u64)
+}
+
+// XORs every byte of the key block with a public pad byte, which only takes NOT gates
+fn xor_pad(block_key: &[Ciphertext], pad: u8, sk: &ServerKey) -> Vec<Ciphertext> {
+    block_key.iter()
+        .enumerate()
+        .map(|(i, bit)| if (pad >> (7 - i % 8)) & 1 == 1 { sk.not(bit) } else { bit.clone() })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+    use crate::boolean_ops::trivial_bytes;
+    use crate::test_helpers::{decrypt_bytes, from_hex};
+    use crate::test_keys::test_keys;
+
+    // RFC 4231 test cases 2 and 6 (a key longer than the block), computed with the clear backend
+    #[test]
+    fn test_hmac_sha256() {
+        let (ck, sk) = test_keys();
+
+        let key = trivial_bytes(b"Jefe", &sk);
+        let message = trivial_bytes(b"what do ya want for nothing?", &sk);
+        assert_eq!(
+            decrypt_bytes(&hmac_sha256(&key, &message, &sk), &ck),
+            from_hex("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"),
+        );
+
+        let key = trivial_bytes(&[0xaa; 131], &sk);
+        let message = trivial_bytes(b"Test Using Larger Than Block-Size Key - Hash Key First", &sk);
+        assert_eq!(
+            decrypt_bytes(&hmac_sha256(&key, &message, &sk), &ck),
+            from_hex("60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"),
+        );
+    }
+
+    #[test]
+    fn test_hmac_verify() {
+        let (ck, sk) = test_keys();
+
+        let key = trivial_bytes(b"Jefe", &sk);
+        let message = trivial_bytes(b"what do ya want for nothing?", &sk);
+        let tag = from_hex("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+
+        assert!(ck.decrypt(&hmac_verify(&key, &message, &trivial_bytes(&tag, &sk), &sk)));
+        assert!(ck.decrypt(&hmac_verify(&key, &message, &trivial_bytes(&tag[..16], &sk), &sk)));
+
+        let mut bad_tag = tag.clone();
+        bad_tag[31] ^= 1;
+        assert!(!ck.decrypt(&hmac_verify(&key, &message, &trivial_bytes(&bad_tag, &sk), &sk)));
+    }
+
+    #[test]
+    fn test_prefix_mac() {
+        let (ck, sk) = test_keys();
+
+        let key = trivial_bytes(b"secret key", &sk);
+        let message = trivial_bytes(b"user=alice", &sk);
+        let tag = prefix_mac(&key, &message, &sk);
+        assert_eq!(decrypt_bytes(&tag, &ck), Sha256::digest(b"secret keyuser=alice").to_vec());
+
+        // The forged tag is valid for the extended message, although it was computed without the key
+        let suffix = trivial_bytes(b"&admin=true", &sk);
+        let forged = extend_prefix_mac(&tag, key.len() + message.len(), &suffix, &sk);
+
+        let mut extended = message;
+        extended.extend(extension_glue(key.len() + extended.len()).iter().map(|bit| sk.trivial_encrypt(*bit)));
+        extended.extend(suffix);
+        assert_eq!(decrypt_bytes(&forged, &ck), decrypt_bytes(&prefix_mac(&key, &extended, &sk), &ck));
+    }
+}
@@ -0,0 +1,493 @@
+// This module runs several hashing jobs concurrently, each one with its own thread budget. Every job slot owns a rayon
+// pool of threads_per_job threads, and the gate-level parallelism of a job stays within its slot's pool, so e.g. 3 jobs
+// of 16 threads share a 48-core machine fairly instead of the jobs running one after the other on the global pool.
+//
+// Partitioning leaves cores idle during the serial sections of a job (e.g. between the dependent additions of a round),
+// where its pool has fewer ready gates than threads. An interleaved scheduler instead runs all its jobs on one shared
+// pool, where the threads that run out of gates of their own job steal the ready gates of the others, which gives a
+// higher aggregate throughput when jobs run for long enough to overlap, at the cost of per-job latency guarantees.
+//
+// Jobs wait in a priority queue, so small interactive requests aren't stuck behind a big batch job submitted earlier.
+// With preemption, a job goes back to the queue after each block (its Sha256State is kept), and a higher priority job
+// submitted in the meantime takes its slot.
+//
+// The queue keeps track of where every job is, down to the round of the block being compressed, so a service can
+// report the progress of a job to a client polling it (see the service module).
+//
+// A job can carry its own server key (submit_with_key), so that the jobs of several clients, each with their own keys,
+// share the queue and the job slots. The other jobs are computed under the key given to Scheduler::serve.
+//
+// Waiting for a job fails with a JobError rather than blocking forever when the queue doesn't have it (an id that was
+// never submitted, or whose digest was already taken), or when it isn't done within the timeout of wait_timeout.
+//
+// A job whose computation panics (e.g. a server key that doesn't fit its ciphertexts) is failed with the panic message
+// as its reason, rather than taking down its job slot and the scheduler with it, so the other jobs go on.
+
+use std::any::Any;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+use tfhe::boolean::prelude::*;
+use crate::parameters::ParameterSet;
+use crate::sha256::Sha256State;
+use crate::warm_up::{calibrate, Calibration};
+pub use crate::protocol::{JobId, JobStatus};
+
+pub struct Scheduler {
+    pools: Vec<ThreadPool>,
+    // Jobs running at a time, spread over the pools
+    slots: usize,
+}
+
+impl Scheduler {
+    // Scheduler running up to concurrent_jobs jobs at a time, each one with threads_per_job threads
+    pub fn new(concurrent_jobs: usize, threads_per_job: usize) -> Result<Self, ThreadPoolBuildError> {
+        assert!(concurrent_jobs > 0 && threads_per_job > 0, "the scheduler needs at least one job slot and thread");
+
+        let pools = (0..concurrent_jobs)
+            .map(|_| ThreadPoolBuilder::new().num_threads(threads_per_job).build())
+            .collect::<Result<_, _>>()?;
+
+        Ok(Scheduler { pools, slots: concurrent_jobs })
+    }
+
+    // Scheduler running up to concurrent_jobs jobs at a time, interleaving their gates on a single pool of threads
+    pub fn interleaved(concurrent_jobs: usize, threads: usize) -> Result<Self, ThreadPoolBuildError> {
+        assert!(concurrent_jobs > 0 && threads > 0, "the scheduler needs at least one job slot and thread");
+
+        let pool = ThreadPoolBuilder::new().num_threads(threads).build()?;
+        Ok(Scheduler { pools: vec![pool], slots: concurrent_jobs })
+    }
+
+    // Runs a calibration batch on each pool, so that their threads are ready for the first job (see the warm_up module),
+    // returning the calibration of each pool
+    pub fn warm_up(&self, parameter_set: ParameterSet, sk: &ServerKey) -> Vec<Calibration> {
+        self.pools.iter().map(|pool| pool.install(|| calibrate(parameter_set, sk))).collect()
+    }
+
+    // Hashes every padded input, returning the digests in the same order. Each slot takes the next pending job as soon
+    // as it finishes the previous one
+    pub fn run(&self, padded_inputs: Vec<Vec<Ciphertext>>, sk: &ServerKey) -> Vec<Vec<Ciphertext>> {
+        let queue = JobQueue::new(false);
+        let ids: Vec<JobId> = padded_inputs.into_iter().map(|padded_input| queue.submit(0, padded_input, sk)).collect();
+        queue.close();
+
+        self.serve(&queue, sk);
+        ids.into_iter().map(|id| queue.wait(id).unwrap_or_else(|e| panic!("job {} of the batch: {}", id, e))).collect()
+    }
+
+    // Runs the jobs of the queue on the job slots, returning once the queue is closed and every job is done. Jobs can
+    // be submitted from other threads in the meantime. Jobs without a key of their own are computed under sk
+    pub fn serve(&self, queue: &JobQueue, sk: &ServerKey) {
+        self.serve_with(queue, Some(sk));
+    }
+
+    // Same as serve, for a queue whose jobs were all submitted with their own key
+    pub fn serve_keyed(&self, queue: &JobQueue) {
+        self.serve_with(queue, None);
+    }
+
+    fn serve_with(&self, queue: &JobQueue, default_key: Option<&ServerKey>) {
+        std::thread::scope(|scope| {
+            for slot in 0..self.slots {
+                let pool = &self.pools[slot % self.pools.len()];
+                scope.spawn(move || {
+                    while let Some(mut job) = queue.next() {
+                        let key = job.key.clone();
+                        let sk = key.as_deref().or(default_key).expect("job submitted without a server key");
+                        let result = panic::catch_unwind(AssertUnwindSafe(|| pool.install(|| queue.work_on(&mut job, sk))));
+                        match result {
+                            Ok(()) => queue.finish_or_requeue(job),
+                            Err(payload) => queue.fail(job.id, panic_message(payload.as_ref())),
+                        }
+                    }
+                });
+            }
+        });
+    }
+}
+
+// Job waiting in the queue, with its progress so far
+struct PendingJob {
+    id: JobId,
+    priority: u32,
+    padded_input: Vec<Ciphertext>,
+    state: Sha256State,
+    // Key of the job, if it isn't the one of the scheduler
+    key: Option<Arc<ServerKey>>,
+}
+
+// Higher priorities first, then the oldest job (the lowest id) first
+impl Ord for PendingJob {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority.cmp(&other.priority).then(other.id.cmp(&self.id))
+    }
+}
+
+impl PartialOrd for PendingJob {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for PendingJob {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for PendingJob {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JobError {
+    // The queue has no such job, or its digest was taken
+    NotFound,
+    // The job wasn't done within the timeout
+    Timeout,
+    Failed { reason: String },
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JobError::NotFound => write!(f, "no such job"),
+            JobError::Timeout => write!(f, "the job wasn't done in time"),
+            JobError::Failed { reason } => write!(f, "the job failed: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for JobError {}
+
+struct QueueState {
+    pending: BinaryHeap<PendingJob>,
+    // Status of the jobs being worked on
+    running: HashMap<JobId, JobStatus>,
+    digests: HashMap<JobId, Vec<Ciphertext>>,
+    // Reason of the jobs that failed, kept like the digests until they're taken
+    failures: HashMap<JobId, String>,
+    next_id: JobId,
+    closed: bool,
+}
+
+pub struct JobQueue {
+    preemptive: bool,
+    state: Mutex<QueueState>,
+    changed: Condvar,
+}
+
+impl JobQueue {
+    // With preemption, jobs are rescheduled after every block
+    pub fn new(preemptive: bool) -> Self {
+        JobQueue {
+            preemptive,
+            state: Mutex::new(QueueState {
+                pending: BinaryHeap::new(),
+                running: HashMap::new(),
+                digests: HashMap::new(),
+                failures: HashMap::new(),
+                next_id: 0,
+                closed: false,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    pub fn submit(&self, priority: u32, padded_input: Vec<Ciphertext>, sk: &ServerKey) -> JobId {
+        self.push(priority, padded_input, Sha256State::new(sk), None)
+    }
+
+    // Same as submit, for a job computed under its own key rather than the one of the scheduler
+    pub fn submit_with_key(&self, priority: u32, padded_input: Vec<Ciphertext>, key: Arc<ServerKey>) -> JobId {
+        self.push(priority, padded_input, Sha256State::new(&key), Some(key))
+    }
+
+    fn push(&self, priority: u32, padded_input: Vec<Ciphertext>, initial: Sha256State, key: Option<Arc<ServerKey>>) -> JobId {
+        assert!(!padded_input.is_empty() && padded_input.len().is_multiple_of(512),"padded input length is not a multiple of 512");
+
+        let mut state = self.state.lock().unwrap();
+        assert!(!state.closed, "job submitted to a closed queue");
+
+        let id = state.next_id;
+        state.next_id += 1;
+        state.pending.push(PendingJob { id, priority, padded_input, state: initial, key });
+
+        self.changed.notify_all();
+        id
+    }
+
+    // No more jobs will be submitted, so the scheduler returns once the pending ones are done
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.changed.notify_all();
+    }
+
+    // Blocks until the job is done and takes its digest, or its failure
+    pub fn wait(&self, id: JobId) -> Result<Vec<Ciphertext>, JobError> {
+        self.wait_until(id, None)
+    }
+
+    // Same as wait, giving up once the timeout has passed
+    pub fn wait_timeout(&self, id: JobId, timeout: Duration) -> Result<Vec<Ciphertext>, JobError> {
+        self.wait_until(id, Some(Instant::now() + timeout))
+    }
+
+    fn wait_until(&self, id: JobId, deadline: Option<Instant>) -> Result<Vec<Ciphertext>, JobError> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(digest) = state.digests.remove(&id) {
+                return Ok(digest);
+            }
+            if let Some(reason) = state.failures.remove(&id) {
+                return Err(JobError::Failed { reason });
+            }
+            // A job is always pending, running, done or failed until its digest or failure is taken
+            if !state.running.contains_key(&id) && !state.pending.iter().any(|job| job.id == id) {
+                return Err(JobError::NotFound);
+            }
+
+            state = match deadline {
+                None => self.changed.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(JobError::Timeout);
+                    }
+                    self.changed.wait_timeout(state, deadline - now).unwrap().0
+                }
+            };
+        }
+    }
+
+    // Where the job is, or None if there's no such job or its digest (or failure) was taken
+    pub fn status(&self, id: JobId) -> Option<JobStatus> {
+        let state = self.state.lock().unwrap();
+        if state.digests.contains_key(&id) {
+            return Some(JobStatus::Done);
+        }
+        if let Some(reason) = state.failures.get(&id) {
+            return Some(JobStatus::Failed { reason: reason.clone() });
+        }
+        if let Some(status) = state.running.get(&id) {
+            return Some(status.clone());
+        }
+        state.pending.iter().find(|job| job.id == id).map(|job| JobStatus::Queued {
+            block: job.state.length() as usize / 512,
+            total_blocks: job.padded_input.len() / 512,
+        })
+    }
+
+    // Digest of a job that is done, which stays in the queue until it's taken
+    pub fn digest(&self, id: JobId) -> Option<Vec<Ciphertext>> {
+        self.state.lock().unwrap().digests.get(&id).cloned()
+    }
+
+    // Removes the digest of a job that is done from the queue, returning it
+    pub fn take(&self, id: JobId) -> Option<Vec<Ciphertext>> {
+        self.state.lock().unwrap().digests.remove(&id)
+    }
+
+    // Removes a job that is done or failed from the queue, returning whether there was one
+    pub fn forget(&self, id: JobId) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.digests.remove(&id).is_some() || state.failures.remove(&id).is_some()
+    }
+
+    // Highest priority pending job, or None once the queue is closed and empty
+    fn next(&self) -> Option<PendingJob> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(job) = state.pending.pop() {
+                let status = JobStatus::Running {
+                    block: job.state.length() as usize / 512,
+                    round: 0,
+                    total_blocks: job.padded_input.len() / 512,
+                };
+                state.running.insert(job.id, status);
+                return Some(job);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    // Compresses the next block of the job, or all of them without preemption
+    fn work_on(&self, job: &mut PendingJob, sk: &ServerKey) {
+        let (id, total_blocks) = (job.id, job.padded_input.len() / 512);
+        let mut observer = |block, round: usize, _: [&[Ciphertext; 32]; 8]| {
+            let status = JobStatus::Running { block, round: round + 1, total_blocks };
+            self.state.lock().unwrap().running.insert(id, status);
+        };
+
+        loop {
+            let done = job.state.length() as usize;
+            job.state.compress_observed(&job.padded_input[done..done + 512], Some(&mut observer), sk);
+
+            if self.preemptive || job.state.length() as usize == job.padded_input.len() {
+                break;
+            }
+        }
+    }
+
+    fn finish_or_requeue(&self, job: PendingJob) {
+        let mut state = self.state.lock().unwrap();
+        state.running.remove(&job.id);
+
+        if job.state.length() as usize == job.padded_input.len() {
+            state.digests.insert(job.id, job.state.into());
+        } else {
+            state.pending.push(job);
+        }
+
+        self.changed.notify_all();
+    }
+
+    fn fail(&self, id: JobId, reason: String) {
+        let mut state = self.state.lock().unwrap();
+        state.running.remove(&id);
+        state.failures.insert(id, reason);
+
+        self.changed.notify_all();
+    }
+}
+
+// Message of a panic, which is a &str or a String unless it was raised with another payload
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => payload.downcast_ref::<String>().cloned().unwrap_or_else(|| "the computation panicked".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+    use crate::padding::pad_sha256_data;
+    use crate::test_helpers::decrypt_bytes;
+    use crate::test_keys::test_keys;
+
+    // Trivially encrypted inputs, so the jobs run with the clear backend
+    fn trivial_input(message: &[u8], sk: &ServerKey) -> Vec<Ciphertext> {
+        pad_sha256_data(message).iter().map(|bit| sk.trivial_encrypt(*bit)).collect()
+    }
+
+    #[test]
+    fn test_scheduler() {
+        let (ck, sk) = test_keys();
+
+        let messages: Vec<Vec<u8>> = (0..5u8).map(|i| vec![i; 30 * i as usize]).collect();
+        let inputs: Vec<Vec<Ciphertext>> = messages.iter().map(|message| trivial_input(message, &sk)).collect();
+
+        for scheduler in [Scheduler::new(2, 2).unwrap(), Scheduler::interleaved(3, 2).unwrap()] {
+            assert_eq!(scheduler.warm_up(ParameterSet::Default, &sk).len(), scheduler.pools.len());
+            let digests = scheduler.run(inputs.clone(), &sk);
+
+            for (digest, message) in digests.iter().zip(&messages) {
+                assert_eq!(decrypt_bytes(digest, &ck), Sha256::digest(message).to_vec());
+            }
+        }
+    }
+
+    #[test]
+    fn test_priorities() {
+        let (ck, sk) = test_keys();
+
+        for preemptive in [false, true] {
+            let queue = JobQueue::new(preemptive);
+            let batch = queue.submit(0, trivial_input(&[0xab; 300], &sk), &sk);
+            let interactive = queue.submit(5, trivial_input(b"interactive", &sk), &sk);
+            let background = queue.submit(0, trivial_input(b"background", &sk), &sk);
+            queue.close();
+
+            // Taken one at a time, as by a single slot, jobs are done highest priority first, then in submission order
+            let mut completed = vec![];
+            while let Some(mut job) = queue.next() {
+                let id = job.id;
+                queue.work_on(&mut job, &sk);
+                queue.finish_or_requeue(job);
+                if queue.status(id) == Some(JobStatus::Done) {
+                    completed.push(id);
+                }
+            }
+            assert_eq!(completed, vec![interactive, batch, background]);
+
+            assert_eq!(decrypt_bytes(&queue.wait(batch).unwrap(), &ck), Sha256::digest([0xab; 300]).to_vec());
+            assert_eq!(decrypt_bytes(&queue.wait(interactive).unwrap(), &ck), Sha256::digest(b"interactive").to_vec());
+            assert_eq!(decrypt_bytes(&queue.wait(background).unwrap(), &ck), Sha256::digest(b"background").to_vec());
+
+            // The digests were taken
+            assert!(matches!(queue.wait(batch), Err(JobError::NotFound)));
+        }
+    }
+
+    #[test]
+    fn test_status() {
+        let (_, sk) = test_keys();
+
+        let queue = JobQueue::new(true);
+        let id = queue.submit(0, trivial_input(&[0; 100], &sk), &sk);
+        assert_eq!(queue.status(id), Some(JobStatus::Queued { block: 0, total_blocks: 2 }));
+        assert_eq!(queue.status(id + 1), None);
+        assert!(matches!(queue.wait(id + 1), Err(JobError::NotFound)));
+        assert!(matches!(queue.wait_timeout(id, Duration::from_millis(10)), Err(JobError::Timeout)));
+
+        let mut job = queue.next().unwrap();
+        assert_eq!(queue.status(id), Some(JobStatus::Running { block: 0, round: 0, total_blocks: 2 }));
+        queue.work_on(&mut job, &sk);
+        assert_eq!(queue.status(id), Some(JobStatus::Running { block: 0, round: 64, total_blocks: 2 }));
+
+        // Preempted after its first block
+        queue.finish_or_requeue(job);
+        assert_eq!(queue.status(id), Some(JobStatus::Queued { block: 1, total_blocks: 2 }));
+
+        queue.close();
+        Scheduler::new(1, 1).unwrap().serve(&queue, &sk);
+        assert_eq!(queue.status(id), Some(JobStatus::Done));
+        assert!(queue.digest(id).is_some());
+        assert!(queue.take(id).is_some());
+        assert_eq!(queue.status(id), None);
+    }
+
+    #[test]
+    fn test_failed_job() {
+        let (ck, sk) = test_keys();
+
+        let queue = JobQueue::new(false);
+        let failing = queue.submit(1, trivial_input(b"failing", &sk), &sk);
+        // Its input is lost, so the computation panics on its first block
+        queue.state.lock().unwrap().pending.peek_mut().unwrap().padded_input.clear();
+        let other = queue.submit(0, trivial_input(b"other", &sk), &sk);
+        queue.close();
+
+        // The slot goes on with the next job
+        Scheduler::new(1, 1).unwrap().serve(&queue, &sk);
+        assert!(matches!(queue.status(failing), Some(JobStatus::Failed { reason }) if reason.contains("out of range")));
+        assert!(matches!(queue.wait(failing), Err(JobError::Failed { .. })));
+        assert_eq!(queue.status(failing), None);
+        assert_eq!(decrypt_bytes(&queue.wait(other).unwrap(), &ck), Sha256::digest(b"other").to_vec());
+    }
+
+    #[test]
+    fn test_job_keys() {
+        let (ck, sk) = test_keys();
+        let other = gen_keys();
+
+        // Jobs of two clients with different keys, in the same queue
+        let queue = JobQueue::new(true);
+        let input = |message: &[u8], ck: &ClientKey| pad_sha256_data(message).iter().map(|bit| ck.encrypt(*bit)).collect();
+        let first = queue.submit_with_key(0, input(b"first", &ck), Arc::new(sk));
+        let second = queue.submit_with_key(0, input(b"second", &other.0), Arc::new(other.1));
+        queue.close();
+
+        Scheduler::new(2, 1).unwrap().serve_keyed(&queue);
+        assert_eq!(decrypt_bytes(&queue.wait(first).unwrap(), &ck), Sha256::digest(b"first").to_vec());
+        assert_eq!(decrypt_bytes(&queue.wait(second).unwrap(), &other.0), Sha256::digest(b"second").to_vec());
+    }
+}
@@ -0,0 +1,92 @@
+// This module signs JSON Web Tokens with HS256 (RFC 7515) under an encrypted secret, so a token-signing service never
+// has access to the signing key. The header and payload are public, so their base64url encoding is done in the clear,
+// and only the HMAC is computed homomorphically. The client then decrypts the signature and assembles the token.
+
+#[cfg(feature = "server")]
+use tfhe::boolean::prelude::*;
+#[cfg(feature = "server")]
+use crate::boolean_ops::trivial_bytes;
+#[cfg(feature = "server")]
+use crate::hmac::hmac_sha256;
+
+const BASE64URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+// Unpadded base64url encoding, as used by every part of a JWT
+pub fn base64url_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() * 4).div_ceil(3));
+
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |acc, (i, byte)| acc | (*byte as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            encoded.push(BASE64URL[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+
+    encoded
+}
+
+// The part of the token covered by the signature: base64url(header) "." base64url(payload)
+pub fn signing_input(header: &str, payload: &str) -> String {
+    format!("{}.{}", base64url_encode(header.as_bytes()), base64url_encode(payload.as_bytes()))
+}
+
+// Encrypted HS256 signature of the token, under an encrypted secret
+#[cfg(feature = "server")]
+pub fn hs256_sign(header: &str, payload: &str, secret: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    let message = trivial_bytes(signing_input(header, payload).as_bytes(), sk);
+    hmac_sha256(secret, &message, sk)
+}
+
+// Assembles the compact token once the client has decrypted the signature
+pub fn encode_token(header: &str, payload: &str, signature: &[u8]) -> String {
+    format!("{}.{}", signing_input(header, payload), base64url_encode(signature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 7515 appendix A.1
+    const HEADER: &str = "{\"typ\":\"JWT\",\r\n \"alg\":\"HS256\"}";
+    const PAYLOAD: &str = "{\"iss\":\"joe\",\r\n \"exp\":1300819380,\r\n \"http://example.com/is_root\":true}";
+    const TOKEN: &str = "eyJ0eXAiOiJKV1QiLA0KICJhbGciOiJIUzI1NiJ9.\
+        eyJpc3MiOiJqb2UiLA0KICJleHAiOjEzMDA4MTkzODAsDQogImh0dHA6Ly9leGFtcGxlLmNvbS9pc19yb290Ijp0cnVlfQ.\
+        dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+
+    #[test]
+    fn test_base64url_encode() {
+        assert_eq!(base64url_encode(b""), "");
+        assert_eq!(base64url_encode(b"f"), "Zg");
+        assert_eq!(base64url_encode(b"fo"), "Zm8");
+        assert_eq!(base64url_encode(b"foo"), "Zm9v");
+        assert_eq!(base64url_encode(&[0xfb, 0xff]), "-_8");
+    }
+
+    #[test]
+    fn test_signing_input() {
+        let token = TOKEN.replace(' ', "");
+        assert_eq!(signing_input(HEADER, PAYLOAD), token[..token.rfind('.').unwrap()]);
+    }
+
+    #[test]
+    #[cfg(feature = "server")]
+    fn test_hs256_sign() {
+        use crate::test_keys::test_keys;
+
+        let (ck, sk) = test_keys();
+
+        let secret = [
+            3, 35, 53, 75, 43, 15, 165, 188, 131, 126, 6, 101, 119, 123, 166, 143, 90, 179, 40, 230, 240, 84, 201, 40,
+            169, 15, 132, 178, 210, 80, 46, 191, 211, 251, 90, 146, 210, 6, 71, 239, 150, 138, 180, 195, 119, 98, 61,
+            34, 61, 46, 33, 114, 5, 46, 79, 8, 192, 205, 154, 245, 103, 208, 128, 163,
+        ];
+
+        // The secret is trivially encrypted, so the signature is computed with the clear backend
+        let signature: Vec<u8> = hs256_sign(HEADER, PAYLOAD, &trivial_bytes(&secret, &sk), &sk)
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0u8, |acc, bit| (acc << 1) | ck.decrypt(bit) as u8))
+            .collect();
+
+        assert_eq!(encode_token(HEADER, PAYLOAD, &signature), TOKEN.replace(' ', ""));
+    }
+}
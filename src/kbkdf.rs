@@ -0,0 +1,75 @@
+// This module implements the KDF of NIST SP 800-108 in counter mode on top of the homomorphic HMAC. The key-derivation
+// key is encrypted, while the counter, the label, the context and the output length are public (trivial bits). Block i
+// of the output is HMAC(key, [i] || fixed input), with the counter before the fixed input data.
+
+use tfhe::boolean::prelude::*;
+use crate::boolean_ops::trivial_bytes;
+use crate::hmac::hmac_sha256;
+
+// Derives length_bits bits from the key, with a counter_bits-bit counter (8, 16, 24 or 32) before the fixed input
+pub fn kbkdf_counter_fixed(
+    key: &[Ciphertext],
+    fixed_input: &[u8],
+    length_bits: usize,
+    counter_bits: usize,
+    sk: &ServerKey,
+) -> Vec<Ciphertext> {
+    assert!([8, 16, 24, 32].contains(&counter_bits), "the counter must have 8, 16, 24 or 32 bits");
+    let blocks = length_bits.div_ceil(256);
+    assert!((blocks as u64) < 1 << counter_bits, "{} blocks don't fit in a {}-bit counter", blocks, counter_bits);
+
+    let mut output = Vec::with_capacity(blocks * 256);
+    for i in 1..=blocks as u32 {
+        let mut message = i.to_be_bytes()[4 - counter_bits / 8..].to_vec();
+        message.extend_from_slice(fixed_input);
+
+        output.extend(hmac_sha256(key, &trivial_bytes(&message, sk), sk));
+    }
+
+    output.truncate(length_bits);
+    output
+}
+
+// Derives length_bits bits from the key, with a 32-bit counter and the fixed input label || 0x00 || context || [L],
+// where [L] is the output length in bits as a 32-bit big-endian integer
+pub fn kbkdf_counter(
+    key: &[Ciphertext],
+    label: &[u8],
+    context: &[u8],
+    length_bits: usize,
+    sk: &ServerKey,
+) -> Vec<Ciphertext> {
+    let mut fixed_input = label.to_vec();
+    fixed_input.push(0);
+    fixed_input.extend_from_slice(context);
+    fixed_input.extend_from_slice(&(length_bits as u32).to_be_bytes());
+
+    kbkdf_counter_fixed(key, &fixed_input, length_bits, 32, sk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::padding::message_bits;
+    use crate::test_helpers::{decrypt_bits, hmac_sha256_plain};
+    use crate::test_keys::test_keys;
+
+    #[test]
+    fn test_kbkdf_counter() {
+        let (ck, sk) = test_keys();
+        let key = b"key-derivation key";
+
+        // Two blocks, the second one truncated
+        let fixed_input = b"label\x00context\x00\x00\x01\x80";
+        let mut expected = hmac_sha256_plain(key, &[&[0, 0, 0, 1], &fixed_input[..]].concat());
+        expected.extend(hmac_sha256_plain(key, &[&[0, 0, 0, 2], &fixed_input[..]].concat()));
+
+        let output = kbkdf_counter(&trivial_bytes(key, &sk), b"label", b"context", 384, &sk);
+        assert_eq!(decrypt_bits(&output, &ck), message_bits(&expected[..48]));
+
+        // An 8-bit counter, and an output that isn't a whole number of bytes
+        let expected = hmac_sha256_plain(key, &[&[1], &b"fixed"[..]].concat());
+        let output = kbkdf_counter_fixed(&trivial_bytes(key, &sk), b"fixed", 100, 8, &sk);
+        assert_eq!(decrypt_bits(&output, &ck), message_bits(&expected)[..100]);
+    }
+}
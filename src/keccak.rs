@@ -0,0 +1,159 @@
+// This module implements the Keccak-f[1600] permutation and the FIPS 202 hashes built on it with the generic sponge:
+// SHA3-224/256/384/512 and the SHAKE128/256 extendable-output functions. Keccak suits FHE well, since its only non-linear
+// step (chi) is one AND per bit and round, while the rotations and the lane permutation are free.
+//
+// The state is 25 little-endian 64-bit lanes, lane x + 5y at bytes 8(x + 5y)..8(x + 5y + 8) of the sponge state.
+
+use rayon::prelude::*;
+use tfhe::boolean::prelude::*;
+use crate::boolean_ops::{and, from_le_bytes, not, rotate_left, swap_bytes, xor, xor_constant};
+use crate::sponge::{Permutation, Sponge};
+
+type Lane = [Ciphertext; 64];
+
+// Delimited domain suffixes of FIPS 202, and of cSHAKE (SP 800-185)
+pub const SHA3_SUFFIX: u8 = 0x06;
+pub const SHAKE_SUFFIX: u8 = 0x1f;
+pub const CSHAKE_SUFFIX: u8 = 0x04;
+
+const ROUNDS: usize = 24;
+
+// Rotation offsets of rho, for lane x + 5y
+const RHO: [usize; 25] = [0, 1, 62, 28, 27, 36, 44, 6, 55, 20, 3, 10, 43, 25, 39, 41, 45, 15, 21, 8, 18, 2, 61, 56, 14];
+
+const RC: [u64; ROUNDS] = [
+    0x0000000000000001, 0x0000000000008082, 0x800000000000808a, 0x8000000080008000,
+    0x000000000000808b, 0x0000000080000001, 0x8000000080008081, 0x8000000000008009,
+    0x000000000000008a, 0x0000000000000088, 0x0000000080008009, 0x000000008000000a,
+    0x000000008000808b, 0x800000000000008b, 0x8000000000008089, 0x8000000000008003,
+    0x8000000000008002, 0x8000000000000080, 0x000000000000800a, 0x800000008000000a,
+    0x8000000080008081, 0x8000000000008080, 0x0000000080000001, 0x8000000080008008,
+];
+
+pub struct KeccakF1600;
+
+impl Permutation for KeccakF1600 {
+    const WIDTH: usize = 1600;
+
+    fn permute(state: &mut [Ciphertext], sk: &ServerKey) {
+        let mut lanes: Vec<Lane> = state.chunks(64).map(from_le_bytes).collect();
+
+        for rc in RC {
+            lanes = round(&lanes, rc, sk);
+        }
+
+        let bytes: Vec<Ciphertext> = lanes.iter().flat_map(swap_bytes).collect();
+        state.clone_from_slice(&bytes);
+    }
+}
+
+fn round(a: &[Lane], rc: u64, sk: &ServerKey) -> Vec<Lane> {
+    // theta: XOR every lane with the parities of two neighbouring columns
+    let c: Vec<Lane> = (0..5)
+        .into_par_iter()
+        .map(|x| (1..5).fold(a[x].clone(), |parity, y| xor(&parity, &a[x + 5 * y], sk)))
+        .collect();
+    let d: Vec<Lane> = (0..5)
+        .into_par_iter()
+        .map(|x| xor(&c[(x + 4) % 5], &rotate_left(&c[(x + 1) % 5], 1, sk), sk))
+        .collect();
+
+    // rho and pi only move bits around: lane (x, y) goes to (y, 2x + 3y)
+    let mut moved: Vec<(usize, Lane)> = (0..25)
+        .into_par_iter()
+        .map(|i| {
+            let (x, y) = (i % 5, i / 5);
+            (y + 5 * ((2 * x + 3 * y) % 5), rotate_left(&xor(&a[i], &d[x], sk), RHO[i], sk))
+        })
+        .collect();
+    moved.sort_by_key(|(i, _)| *i);
+    let b: Vec<Lane> = moved.into_iter().map(|(_, lane)| lane).collect();
+
+    // chi, the only non-linear step, and iota on the first lane
+    (0..25)
+        .into_par_iter()
+        .map(|i| {
+            let (x, y) = (i % 5, i / 5);
+            let lane = xor(&b[i], &and(&not(&b[(x + 1) % 5 + 5 * y], sk), &b[(x + 2) % 5 + 5 * y], sk), sk);
+            if i == 0 { xor_constant(&lane, rc, sk) } else { lane }
+        })
+        .collect()
+}
+
+// Keccak sponge with the given capacity and domain suffix
+pub fn keccak_sponge(capacity: usize, suffix: u8, sk: &ServerKey) -> Sponge<KeccakF1600> {
+    Sponge::new(KeccakF1600::WIDTH - capacity, suffix, sk)
+}
+
+// SHA3 with a digest of 224, 256, 384 or 512 bits, whose capacity is twice the digest length
+pub fn sha3(message: &[Ciphertext], digest_bits: usize, sk: &ServerKey) -> Vec<Ciphertext> {
+    assert!([224, 256, 384, 512].contains(&digest_bits), "unsupported SHA3 digest length");
+
+    let mut sponge = keccak_sponge(2 * digest_bits, SHA3_SUFFIX, sk);
+    sponge.absorb(message, sk);
+    sponge.squeeze(digest_bits / 8, sk)
+}
+
+pub fn sha3_256(message: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    sha3(message, 256, sk)
+}
+
+// SHAKE128 and SHAKE256 output of any length, in bytes
+pub fn shake128(message: &[Ciphertext], output_len: usize, sk: &ServerKey) -> Vec<Ciphertext> {
+    shake(message, 256, output_len, sk)
+}
+
+pub fn shake256(message: &[Ciphertext], output_len: usize, sk: &ServerKey) -> Vec<Ciphertext> {
+    shake(message, 512, output_len, sk)
+}
+
+fn shake(message: &[Ciphertext], capacity: usize, output_len: usize, sk: &ServerKey) -> Vec<Ciphertext> {
+    let mut sponge = keccak_sponge(capacity, SHAKE_SUFFIX, sk);
+    sponge.absorb(message, sk);
+    sponge.squeeze(output_len, sk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boolean_ops::trivial_bytes;
+    use crate::test_helpers::{decrypt_bytes, from_hex};
+    use crate::test_keys::test_keys;
+
+    // FIPS 202 examples, plus a multi-block message and an output longer than the rate (checked against Python's
+    // hashlib), computed with the clear backend
+    #[test]
+    fn test_sha3() {
+        let (ck, sk) = test_keys();
+
+        assert_eq!(
+            decrypt_bytes(&sha3_256(&trivial_bytes(b"abc", &sk), &sk), &ck),
+            from_hex("3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532"),
+        );
+        assert_eq!(
+            decrypt_bytes(&sha3(&[], 224, &sk), &ck),
+            from_hex("6b4e03423667dbb73b6e15454f0eb1abd4597f9a1b078e3f5b5a6bc7"),
+        );
+
+        let message: Vec<u8> = (0..200).collect();
+        assert_eq!(
+            decrypt_bytes(&sha3(&trivial_bytes(&message, &sk), 512, &sk), &ck),
+            from_hex("ea5d05f19348dd589793354793a15f37a73b4c0bb4e750b9a00757dfce2f8b65a64191bb9b137de00feef6474cfd47abf7880efbc51614a5715df12cfe0caee3"),
+        );
+    }
+
+    #[test]
+    fn test_shake() {
+        let (ck, sk) = test_keys();
+
+        assert_eq!(
+            decrypt_bytes(&shake128(&[], 32, &sk), &ck),
+            from_hex("7f9c2ba4e88f827d616045507605853ed73b8093f6efbc88eb1a6eacfa66ef26"),
+        );
+
+        // The rate of SHAKE256 is 136 bytes, so the output spans two blocks
+        let output = decrypt_bytes(&shake256(&trivial_bytes(b"abc", &sk), 140, &sk), &ck);
+        assert_eq!(output[..32], from_hex("483366601360a8771c6863080cc4114d8db44530f8f1e1ee4f94ea37e78b5739"));
+        assert_eq!(output[128..], from_hex("e8a2d7ec71a7cc29cf0ea610"));
+    }
+}
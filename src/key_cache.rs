@@ -0,0 +1,163 @@
+// This module caches generated keys on disk, one key pair per parameter set, so that repeated local runs (tests,
+// benchmarks, examples) only pay for key generation once. Like the test keys, cached keys are for local use only:
+// anyone who can read the cache directory can decrypt everything encrypted under them.
+//
+// Both keys are stored in a single payload, written to a temporary file and renamed, so that concurrent processes never
+// read a partial pair or a client key with the server key of another pair. A cached pair that can't be read (e.g.
+// written by another version of the crate or tfhe) is generated again and replaces it.
+//
+// On Unix, the cache directory is created readable by its owner only (0700) and the keys are written 0600. A directory
+// that belongs to another user, or that others can access, is refused before anything is read from it or written to
+// it: another local user could read the client key, or plant a key pair of their own.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Write};
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::{env, process};
+use tfhe::boolean::prelude::*;
+use crate::parameters::ParameterSet;
+use crate::serialization::{deserialize_from, serialize_into, PayloadKind, SerializationError};
+
+// Environment variable overriding the default cache directory
+pub const KEY_CACHE_DIR_VAR: &str = "SHA256_FHE_KEY_CACHE";
+
+pub struct KeyCache {
+    dir: PathBuf,
+}
+
+impl KeyCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        KeyCache { dir: dir.into() }
+    }
+
+    // Cache in the directory named by SHA256_FHE_KEY_CACHE, or in the user's cache directory ($XDG_CACHE_HOME or
+    // ~/.cache), or else in a directory of the user under the temporary directory
+    pub fn from_env() -> Self {
+        let user_cache = || {
+            let cache = env::var_os("XDG_CACHE_HOME").map(PathBuf::from);
+            cache.or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        };
+        let dir = env::var_os(KEY_CACHE_DIR_VAR).map(PathBuf::from).or_else(|| {
+            user_cache().filter(|dir| dir.is_absolute()).map(|dir| dir.join("sha256-fhe").join("keys"))
+        });
+
+        #[cfg(unix)]
+        let temporary = format!("sha256-fhe-keys-{}", current_uid());
+        #[cfg(not(unix))]
+        let temporary = "sha256-fhe-keys";
+        KeyCache::new(dir.unwrap_or_else(|| env::temp_dir().join(temporary)))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // Keys of the parameter set, read from the cache, or generated and cached if they aren't there yet
+    pub fn keys(&self, parameter_set: ParameterSet) -> Result<(ClientKey, ServerKey), SerializationError> {
+        self.open_dir()?;
+        let path = self.path(parameter_set);
+        if let Ok(keys) = File::open(&path).map_err(SerializationError::from).and_then(|file| {
+            deserialize_from(BufReader::new(file), PayloadKind::KeyPair, parameter_set)
+        }) {
+            return Ok(keys);
+        }
+
+        let keys = parameter_set.gen_keys();
+        let temporary = path.with_extension(format!("{}.tmp", process::id()));
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = BufWriter::new(options.open(&temporary)?);
+        serialize_into(&mut file, PayloadKind::KeyPair, &keys, parameter_set)?;
+        file.flush()?;
+        drop(file);
+        fs::rename(&temporary, &path)?;
+
+        Ok(keys)
+    }
+
+    // Removes the cached keys of the parameter set, if any
+    pub fn clear(&self, parameter_set: ParameterSet) -> Result<(), SerializationError> {
+        match fs::remove_file(self.path(parameter_set)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    // Creates the directory if needed, private to the current user, and checks that an existing one still is
+    fn open_dir(&self) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            fs::DirBuilder::new().recursive(true).mode(0o700).create(&self.dir)?;
+            let metadata = fs::metadata(&self.dir)?;
+            if metadata.uid() != current_uid() || metadata.mode() & 0o077 != 0 {
+                let message = format!("key cache {} isn't private to the current user", self.dir.display());
+                return Err(io::Error::new(io::ErrorKind::PermissionDenied, message));
+            }
+            Ok(())
+        }
+        #[cfg(not(unix))]
+        fs::create_dir_all(&self.dir)
+    }
+
+    fn path(&self, parameter_set: ParameterSet) -> PathBuf {
+        self.dir.join(format!("{}_{}", parameter_set, PayloadKind::KeyPair.file_name()))
+    }
+}
+
+#[cfg(unix)]
+fn current_uid() -> u32 {
+    // geteuid has no preconditions and can't fail
+    unsafe { libc::geteuid() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_cache() {
+        let dir = env::temp_dir().join(format!("sha256-fhe-key-cache-test-{}", process::id()));
+        let cache = KeyCache::new(&dir);
+
+        for parameter_set in ParameterSet::ALL.iter().copied() {
+            let (ck, _) = cache.keys(parameter_set).unwrap();
+            let encrypted = [ck.encrypt(true), ck.encrypt(false)];
+
+            // The cached pair is the one generated first, and its keys belong together
+            let (ck, sk) = cache.keys(parameter_set).unwrap();
+            assert!(ck.decrypt(&encrypted[0]) && !ck.decrypt(&encrypted[1]));
+            assert!(ck.decrypt(&sk.or(&encrypted[0], &encrypted[1])));
+        }
+        assert!(dir.join("default_key_pair.bin").exists() && dir.join("tfhe-lib_key_pair.bin").exists());
+
+        // A corrupted pair is replaced
+        fs::write(dir.join("default_key_pair.bin"), b"not keys").unwrap();
+        let (ck, sk) = cache.keys(ParameterSet::Default).unwrap();
+        assert!(!ck.decrypt(&sk.and(&ck.encrypt(true), &ck.encrypt(false))));
+        let file = BufReader::new(File::open(dir.join("default_key_pair.bin")).unwrap());
+        let keys: Result<(ClientKey, ServerKey), _> = deserialize_from(file, PayloadKind::KeyPair, ParameterSet::Default);
+        assert!(keys.is_ok());
+
+        cache.clear(ParameterSet::Default).unwrap();
+        cache.clear(ParameterSet::Default).unwrap();
+        assert!(!dir.join("default_key_pair.bin").exists());
+
+        // The keys are private, and a directory that others can access is refused
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+            assert_eq!((mode(&dir), mode(&dir.join("tfhe-lib_key_pair.bin"))), (0o700, 0o600));
+
+            fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+            let error = cache.keys(ParameterSet::TfheLib).err().unwrap();
+            assert!(error.to_string().contains("isn't private to the current user"));
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,150 @@
+// This module stores the server keys of the clients of a multi-tenant service, keyed by client id, so that every job is
+// computed under the key of the client that submitted it instead of a single key loaded at startup. Each key is kept on
+// disk as the compressed key the client uploaded (a CompressedServerKey payload, about 16 times smaller than the
+// decompressed key), in <dir>/<client>.bin, and survives restarts of the service.
+//
+// A key is decompressed the first time a job needs it, and then stays in memory (a decompressed key takes tens of MB)
+// until it's replaced or removed. Jobs hold the key they were submitted with, so replacing a key doesn't affect them.
+//
+// Keys are written to a temporary file and renamed, so a key being replaced is never read half-written. Client ids are
+// used as file names, so they are restricted to ASCII letters, digits, '-' and '_'.
+
+use std::collections::HashMap;
+use std::fs::{self, DirBuilder, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+#[cfg(unix)]
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex};
+use tfhe::boolean::prelude::*;
+use crate::parameters::ParameterSet;
+use crate::serialization::{
+    compressed_server_key_size_limit, deserialize_compressed_server_key, deserialize_from_limited, serialize_into,
+    PayloadKind, SerializationError,
+};
+
+const MAX_CLIENT_ID_LEN: usize = 64;
+
+pub struct KeyRegistry {
+    dir: PathBuf,
+    parameter_set: ParameterSet,
+    // Keys decompressed so far
+    loaded: Mutex<HashMap<String, Arc<ServerKey>>>,
+}
+
+impl KeyRegistry {
+    // Registry of the keys in the directory, which is created if needed
+    pub fn open(dir: impl Into<PathBuf>, parameter_set: ParameterSet) -> io::Result<Self> {
+        let dir = dir.into();
+        let mut builder = DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        builder.mode(0o700);
+        builder.create(&dir)?;
+
+        Ok(KeyRegistry { dir, parameter_set, loaded: Mutex::new(HashMap::new()) })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // Whether the id can name a client: 1 to 64 ASCII letters, digits, '-' or '_'
+    pub fn is_valid_id(client: &str) -> bool {
+        (1..=MAX_CLIENT_ID_LEN).contains(&client.len())
+            && client.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+    }
+
+    // Stores the key of the client, read from a CompressedServerKey payload (bounded by the size of a compressed key),
+    // replacing the previous one
+    pub fn register(&self, client: &str, reader: impl Read) -> Result<(), SerializationError> {
+        let path = self.path(client)?;
+        let limit = compressed_server_key_size_limit(self.parameter_set);
+        let key: CompressedServerKey =
+            deserialize_from_limited(reader, PayloadKind::CompressedServerKey, self.parameter_set, limit)?;
+
+        let temporary = path.with_extension(format!("{}.tmp", process::id()));
+        let mut file = BufWriter::new(File::create(&temporary)?);
+        serialize_into(&mut file, PayloadKind::CompressedServerKey, &key, self.parameter_set)?;
+        file.flush()?;
+        drop(file);
+        fs::rename(&temporary, &path)?;
+
+        self.loaded.lock().unwrap().remove(client);
+        Ok(())
+    }
+
+    // Key of the client, or None if it has no key
+    pub fn get(&self, client: &str) -> Result<Option<Arc<ServerKey>>, SerializationError> {
+        let path = self.path(client)?;
+        if let Some(key) = self.loaded.lock().unwrap().get(client) {
+            return Ok(Some(Arc::clone(key)));
+        }
+
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        // Decompressing takes a while, so another request may load the same key in the meantime, which is harmless
+        let key = Arc::new(deserialize_compressed_server_key(BufReader::new(file), self.parameter_set)?);
+        self.loaded.lock().unwrap().insert(client.to_string(), Arc::clone(&key));
+
+        Ok(Some(key))
+    }
+
+    // Removes the key of the client, returning whether it had one
+    pub fn remove(&self, client: &str) -> io::Result<bool> {
+        let path = self.path(client)?;
+        self.loaded.lock().unwrap().remove(client);
+
+        match fs::remove_file(path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn path(&self, client: &str) -> io::Result<PathBuf> {
+        if !Self::is_valid_id(client) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid client id {:?}", client)));
+        }
+        Ok(self.dir.join(client).with_extension("bin"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_keys::test_keys;
+
+    #[test]
+    fn test_key_registry() {
+        let dir = std::env::temp_dir().join(format!("sha256-fhe-key-registry-{}", process::id()));
+        let registry = KeyRegistry::open(&dir, ParameterSet::Default).unwrap();
+        let (ck, _) = test_keys();
+
+        let mut payload = vec![];
+        serialize_into(&mut payload, PayloadKind::CompressedServerKey, &CompressedServerKey::new(&ck), ParameterSet::Default)
+            .unwrap();
+        registry.register("alice", payload.as_slice()).unwrap();
+        assert!(registry.get("bob").unwrap().is_none());
+
+        // The key is found again after a restart, and computes under the client key
+        let registry = KeyRegistry::open(&dir, ParameterSet::Default).unwrap();
+        let sk = registry.get("alice").unwrap().unwrap();
+        assert!(Arc::ptr_eq(&sk, &registry.get("alice").unwrap().unwrap()));
+        assert!(ck.decrypt(&sk.and(&ck.encrypt(true), &ck.encrypt(true))));
+
+        assert!(registry.register("bob", &payload[..payload.len() - 1]).is_err());
+        assert!(registry.get("bob").unwrap().is_none());
+        assert!(registry.register("../alice", payload.as_slice()).is_err());
+        assert!(!KeyRegistry::is_valid_id("") && !KeyRegistry::is_valid_id("a.b") && KeyRegistry::is_valid_id("a-b_9"));
+
+        assert!(registry.remove("alice").unwrap());
+        assert!(!registry.remove("alice").unwrap());
+        assert!(registry.get("alice").unwrap().is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
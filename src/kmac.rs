@@ -0,0 +1,93 @@
+// This module implements KMAC128 and KMAC256 (NIST SP 800-185), the keyed hash built on cSHAKE, as a standard
+// alternative to HMAC with an encrypted key. It needs a single Keccak sponge instead of two nested hashes: the key is
+// framed and padded to a whole block in front of the message, and the output length is right-encoded after it, so tags
+// of different lengths are unrelated. The key and the message are encrypted while their lengths, the output length and
+// the customization string are public, so all the framing is made of trivial ciphertexts.
+//
+// KMAC is cSHAKE with the function name "KMAC" and the customization string given by the caller.
+
+use tfhe::boolean::prelude::*;
+use crate::cshake::{cshake_sponge, left_encode, right_encode};
+
+// KMAC128 tag of the given length in bytes, under an encrypted key and a public customization string (often empty)
+pub fn kmac128(
+    key: &[Ciphertext],
+    message: &[Ciphertext],
+    output_len: usize,
+    customization: &[u8],
+    sk: &ServerKey,
+) -> Vec<Ciphertext> {
+    kmac(key, message, output_len, customization, 256, sk)
+}
+
+pub fn kmac256(
+    key: &[Ciphertext],
+    message: &[Ciphertext],
+    output_len: usize,
+    customization: &[u8],
+    sk: &ServerKey,
+) -> Vec<Ciphertext> {
+    kmac(key, message, output_len, customization, 512, sk)
+}
+
+fn kmac(
+    key: &[Ciphertext],
+    message: &[Ciphertext],
+    output_len: usize,
+    customization: &[u8],
+    capacity: usize,
+    sk: &ServerKey,
+) -> Vec<Ciphertext> {
+    assert_eq!(key.len() % 8, 0, "key is not a whole number of bytes");
+    assert_eq!(message.len() % 8, 0, "message is not a whole number of bytes");
+
+    let mut sponge = cshake_sponge(capacity, b"KMAC", customization, sk);
+
+    // bytepad(encode_string(key), rate), absorbed in pieces so that only the key itself is encrypted
+    let rate = sponge.rate() / 8;
+    let framing = [left_encode(rate as u64), left_encode(key.len() as u64)].concat();
+    let padding = (rate - (framing.len() + key.len() / 8) % rate) % rate;
+    sponge.absorb_plain(&framing, sk);
+    sponge.absorb(key, sk);
+    sponge.absorb_plain(&vec![0; padding], sk);
+
+    sponge.absorb(message, sk);
+    sponge.absorb_plain(&right_encode(8 * output_len as u64), sk);
+    sponge.squeeze(output_len, sk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boolean_ops::trivial_bytes;
+    use crate::test_helpers::{decrypt_bytes, from_hex};
+    use crate::test_keys::test_keys;
+
+    // NIST KMAC samples #1, #2 and #4, and a key longer than the rate (checked against a Python model), computed with
+    // the clear backend
+    #[test]
+    fn test_kmac() {
+        let (ck, sk) = test_keys();
+        let key: Vec<u8> = (0x40..0x60).collect();
+        let (key, data) = (trivial_bytes(&key, &sk), trivial_bytes(&[0, 1, 2, 3], &sk));
+
+        assert_eq!(
+            decrypt_bytes(&kmac128(&key, &data, 32, b"", &sk), &ck),
+            from_hex("e5780b0d3ea6f7d3a429c5706aa43a00fadbd7d49628839e3187243f456ee14e"),
+        );
+        assert_eq!(
+            decrypt_bytes(&kmac128(&key, &data, 32, b"My Tagged Application", &sk), &ck),
+            from_hex("3b1fba963cd8b0b59e8c1a6d71888b7143651af8ba0a7070c0979e2811324aa5"),
+        );
+        assert_eq!(
+            decrypt_bytes(&kmac256(&key, &data, 64, b"My Tagged Application", &sk), &ck),
+            from_hex("20c570c31346f703c9ac36c61c03cb64c3970d0cfc787e9b79599d273a68d2f7f69d4cc3de9d104a351689f27cf6f5951f0103f33f4f24871024d9c27773a8dd"),
+        );
+
+        let long_key: Vec<u8> = (0..200).collect();
+        assert_eq!(
+            decrypt_bytes(&kmac128(&trivial_bytes(&long_key, &sk), &trivial_bytes(b"abc", &sk), 20, b"", &sk), &ck),
+            from_hex("09abf560f187db76e01f7884b38a54bea5bd0eb3"),
+        );
+    }
+}
@@ -0,0 +1,115 @@
+pub mod algorithm;
+pub mod padding;
+pub mod parameters;
+pub mod serialization;
+pub mod client;
+pub mod jwt;
+pub mod multihash;
+pub mod trivium;
+pub mod compact;
+pub mod memory;
+// Shared by the service and its client
+#[cfg(any(feature = "client", feature = "server"))]
+pub mod protocol;
+#[cfg(feature = "client")]
+pub mod key_cache;
+#[cfg(feature = "client")]
+pub mod service_client;
+
+// Server side computation, not needed by client applications that only pad, encrypt and decrypt. The server side
+// never needs the ClientKey, which is only available with the "client" feature
+#[cfg(feature = "server")]
+pub mod boolean_ops;
+#[cfg(feature = "server")]
+pub mod merkle_damgard;
+#[cfg(feature = "server")]
+pub mod sha256;
+#[cfg(feature = "server")]
+pub mod sm3;
+#[cfg(feature = "server")]
+pub mod redundancy;
+#[cfg(feature = "server")]
+pub mod breach;
+#[cfg(feature = "server")]
+pub mod netlist;
+#[cfg(feature = "server")]
+pub mod netlist_cache;
+#[cfg(feature = "server")]
+pub mod failure;
+#[cfg(feature = "server")]
+pub mod sponge;
+#[cfg(feature = "server")]
+pub mod keccak;
+#[cfg(feature = "server")]
+pub mod cshake;
+#[cfg(feature = "server")]
+pub mod kmac;
+#[cfg(feature = "server")]
+pub mod tuplehash;
+#[cfg(feature = "server")]
+pub mod parallelhash;
+#[cfg(feature = "server")]
+pub mod merkle;
+#[cfg(feature = "server")]
+pub mod pow;
+#[cfg(feature = "server")]
+pub mod hmac;
+#[cfg(feature = "server")]
+pub mod hkdf;
+#[cfg(feature = "server")]
+pub mod kbkdf;
+#[cfg(feature = "server")]
+pub mod sigv4;
+#[cfg(feature = "server")]
+pub mod pbkdf2;
+#[cfg(feature = "server")]
+pub mod siphash;
+#[cfg(feature = "server")]
+pub mod blake2b;
+#[cfg(feature = "server")]
+pub mod streebog;
+#[cfg(feature = "server")]
+pub mod field;
+#[cfg(feature = "server")]
+pub mod poseidon;
+#[cfg(feature = "server")]
+pub mod mimc;
+#[cfg(feature = "server")]
+pub mod argon2;
+#[cfg(feature = "server")]
+pub mod scrypt;
+#[cfg(feature = "server")]
+pub mod tls13;
+#[cfg(feature = "server")]
+pub mod jobs;
+#[cfg(feature = "server")]
+pub mod key_registry;
+#[cfg(feature = "server")]
+pub mod progress;
+#[cfg(feature = "server")]
+pub mod service;
+#[cfg(feature = "server")]
+pub mod report;
+#[cfg(feature = "server")]
+pub mod stream;
+#[cfg(feature = "server")]
+pub mod threads;
+#[cfg(feature = "server")]
+pub mod warm_up;
+#[cfg(feature = "server")]
+pub mod config;
+#[cfg(feature = "tls")]
+pub mod transport;
+#[cfg(feature = "s3")]
+pub mod object_store;
+
+// Key pair shared by tests, for this crate's tests and for dependents with the "test-keys" feature
+#[cfg(any(test, feature = "test-keys"))]
+pub mod test_keys;
+// Helpers shared by the tests of the gate evaluation modules
+#[cfg(all(test, feature = "server"))]
+mod test_helpers;
+
+// Test-only helpers that need both the ClientKey and the server side computation
+#[cfg(feature = "debug-decrypt")]
+pub mod debug;
@@ -1,13 +1,49 @@
-mod padding;
-mod boolean_ops;
-mod sha256;
-
-use std::io;
+use std::time::{Duration, Instant};
+use std::sync::mpsc::channel;
+use std::{env, hint, io, process, thread};
 use tfhe::boolean::prelude::*;
-use padding::pad_sha256_input;
-use sha256::{sha256_fhe, bools_to_hex};
+use sha256_fhe::boolean_ops::add;
+use sha256_fhe::client::{bools_to_hex, decrypt_bools, encrypt_bools};
+use sha256_fhe::padding::pad_sha256_input;
+use sha256_fhe::parameters::ParameterSet;
+use sha256_fhe::sha256::{sha256_fhe, sha256_fhe_observed};
+use sha256_fhe::threads::ThreadLimit;
+
+// Each micro-benchmark is repeated for at least this long, and at least MIN_ITERATIONS times
+const MIN_BENCH_TIME: Duration = Duration::from_secs(1);
+const MIN_ITERATIONS: usize = 3;
 
 fn main() {
+    // The hash uses one thread per core, unless --threads <count> is given. `bench` benchmarks the machine instead
+    let args: Vec<String> = env::args().collect();
+    let (bench, flags) = match args.get(1).map(String::as_str) {
+        Some("bench") => (true, &args[2..]),
+        _ => (false, &args[1..]),
+    };
+    let threads = match flags {
+        [] => None,
+        [flag, count] if flag == "--threads" => Some(count.parse().ok().filter(|count| *count > 0).unwrap_or_else(|| usage())),
+        _ => usage(),
+    };
+
+    if bench {
+        match threads {
+            Some(threads) => ThreadLimit::new(threads).expect("failed to start the threads").install(run_bench),
+            None => run_bench(),
+        }
+        return;
+    }
+
+    // The keys are generated while the input is typed, and the input is padded and encrypted with the client key while
+    // the server key is still being generated
+    let (input_sender, input_receiver) = channel();
+    let keygen = thread::spawn(move || {
+        ParameterSet::Default.gen_keys_with(|ck| {
+            let input: String = input_receiver.recv().expect("input was never read");
+            encrypt_bools(&pad_sha256_input(&input), ck)
+        })
+    });
+
     // INTRODUCE INPUT FROM STDIN
 
     let mut input = String::new();
@@ -23,15 +59,19 @@ fn main() {
 
     // CLIENT PADS DATA AND ENCRYPTS IT
 
-    let (ck, sk) = gen_keys();
-
-    let padded_input = pad_sha256_input(&input);
-    let encrypted_input = encrypt_bools(&padded_input, &ck);
+    input_sender.send(input).expect("key generation stopped");
+    let (ck, sk, encrypted_input) = keygen.join().expect("key generation panicked");
 
     // SERVER COMPUTES OVER THE ENCRYPTED PADDED DATA
 
     println!("Computing the hash");
-    let encrypted_output = sha256_fhe(encrypted_input, &sk);
+    let encrypted_output = match threads {
+        Some(threads) => {
+            let limit = ThreadLimit::new(threads).expect("failed to start the threads");
+            limit.install(|| sha256_fhe(encrypted_input, &sk))
+        }
+        None => sha256_fhe(encrypted_input, &sk),
+    };
 
     // CLIENT DECRYPTS THE OUTPUT
 
@@ -41,20 +81,64 @@ fn main() {
     println!("{}", outhex);
 }
 
-fn encrypt_bools(bools: &Vec<bool>, ck: &ClientKey) -> Vec<Ciphertext> {
-    let mut ciphertext = vec![];
-
-    for bool in bools {
-        ciphertext.push(ck.encrypt(*bool));
+// Benchmarks the building blocks of the hash, from a single gate to a whole hash, and prints a summary table
+fn run_bench() {
+    println!("Benchmarking with {} threads", rayon::current_num_threads());
+    let mut results = vec![];
+
+    let start = Instant::now();
+    let (ck, sk) = ParameterSet::Default.gen_keys();
+    results.push(("key generation", 1, start.elapsed()));
+
+    let (a, b) = (ck.encrypt(true), ck.encrypt(false));
+    results.push(calibrate("gate (and)", || sk.and(&a, &b)));
+
+    let x: [Ciphertext; 32] = std::array::from_fn(|i| ck.encrypt(i % 3 == 0));
+    let y: [Ciphertext; 32] = std::array::from_fn(|i| ck.encrypt(i % 5 == 0));
+    results.push(calibrate("32-bit adder", || add(&x, &y, &sk)));
+
+    // The rounds are timed within a one-block hash, from the end of each round to the end of the next
+    let input = encrypt_bools(&pad_sha256_input("abc"), &ck);
+    let mut round_ends = vec![];
+    let start = Instant::now();
+    let output = sha256_fhe_observed(input, &mut |_, _, _: [&[Ciphertext; 32]; 8]| round_ends.push(Instant::now()), &sk);
+    let hash = start.elapsed();
+
+    let mut rounds: Vec<Duration> = round_ends.windows(2).map(|ends| ends[1] - ends[0]).collect();
+    rounds.sort();
+    results.push(("round (median)", rounds.len(), rounds[rounds.len() / 2]));
+    results.push(("hash (1 block)", 1, hash));
+
+    let digest = bools_to_hex(decrypt_bools(&output, &ck));
+    assert_eq!(digest, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad", "wrong digest");
+
+    println!();
+    println!("{:<20}{:>12}{:>16}", "benchmark", "iterations", "time");
+    for (name, iterations, time) in results {
+        println!("{:<20}{:>12}{:>16}", name, iterations, format_duration(time));
     }
-    ciphertext
 }
 
-fn decrypt_bools(ciphertext: &Vec<Ciphertext>, ck: &ClientKey) -> Vec<bool> {
-    let mut bools = vec![];
+// Runs the operation repeatedly and returns the average time of an iteration
+fn calibrate<T>(name: &'static str, mut op: impl FnMut() -> T) -> (&'static str, usize, Duration) {
+    let start = Instant::now();
+    let mut iterations = 0;
+    while iterations < MIN_ITERATIONS || start.elapsed() < MIN_BENCH_TIME {
+        hint::black_box(op());
+        iterations += 1;
+    }
+    (name, iterations, start.elapsed() / iterations as u32)
+}
 
-    for cipher in ciphertext {
-        bools.push(ck.decrypt(&cipher));
+fn format_duration(duration: Duration) -> String {
+    match duration.as_secs_f64() {
+        secs if secs >= 1.0 => format!("{:.2} s", secs),
+        secs if secs >= 1e-3 => format!("{:.2} ms", secs * 1e3),
+        secs => format!("{:.2} us", secs * 1e6),
     }
-    bools
+}
+
+fn usage() -> ! {
+    eprintln!("usage: sha256-fhe [bench] [--threads <count>]");
+    process::exit(1);
 }
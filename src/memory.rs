@@ -0,0 +1,56 @@
+// This module reads the memory usage of the process, so that run reports can show how much memory each phase needs and
+// machines can be sized for multi-block or batch workloads. It reads /proc, so the figures are only available on Linux:
+// elsewhere memory_usage returns None.
+
+use std::fs;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryUsage {
+    // Resident set size, in bytes
+    pub resident_bytes: u64,
+    // Highest resident set size since the process started, or since the peak was last reset
+    pub peak_resident_bytes: u64,
+}
+
+// Current memory usage of the process
+pub fn memory_usage() -> Option<MemoryUsage> {
+    parse_status(&fs::read_to_string("/proc/self/status").ok()?)
+}
+
+// Resets the peak resident set size to the current one, so that the next peak is the one of the work that follows.
+// Returns false if the kernel doesn't support it, in which case the peak stays the one of the whole process
+pub fn reset_peak() -> bool {
+    fs::write("/proc/self/clear_refs", "5").is_ok()
+}
+
+// Reads the VmRSS and VmHWM lines of /proc/<pid>/status, given in kB
+fn parse_status(status: &str) -> Option<MemoryUsage> {
+    let field = |name: &str| -> Option<u64> {
+        let line = status.lines().find(|line| line.starts_with(name))?;
+        let kilobytes = line[name.len()..].trim().strip_suffix("kB")?.trim().parse::<u64>().ok()?;
+        Some(kilobytes * 1024)
+    };
+
+    Some(MemoryUsage { resident_bytes: field("VmRSS:")?, peak_resident_bytes: field("VmHWM:")? })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_status() {
+        let status = "Name:\tserver\nVmPeak:\t 2345678 kB\nVmHWM:\t  812340 kB\nVmRSS:\t  700000 kB\nThreads:\t8\n";
+        assert_eq!(
+            parse_status(status),
+            Some(MemoryUsage { resident_bytes: 700000 * 1024, peak_resident_bytes: 812340 * 1024 }),
+        );
+        assert_eq!(parse_status("Name:\tkernel thread\n"), None);
+
+        #[cfg(target_os = "linux")]
+        {
+            let usage = memory_usage().unwrap();
+            assert!(usage.resident_bytes > 0 && usage.peak_resident_bytes >= usage.resident_bytes);
+        }
+    }
+}
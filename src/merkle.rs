@@ -0,0 +1,195 @@
+// This module builds sha256 Merkle trees over encrypted leaves on the server side. Each leaf is an encrypted 256-bit
+// digest and each inner node is the sha256 hash of its two children concatenated. Levels with an odd number of nodes
+// pair the last node with itself, as in Bitcoin.
+//
+// The leaf index of a proof is public, so only the hashes stay hidden from the server: the client decrypts the sibling
+// path and the root and checks the inclusion of its leaf on its own.
+//
+// The module also provides a non-standard tree hashing mode for large inputs, whose chunks are hashed in parallel. Its
+// odd nodes are promoted to the next level unchanged as in RFC 6962, rather than paired with themselves: with the
+// Bitcoin rule, a message whose last chunk is repeated would have the same digest (CVE-2012-2459).
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tfhe::boolean::prelude::*;
+use crate::boolean_ops::trivial_bytes;
+use crate::sha256::{pad_encrypted, sha256_fhe};
+
+// Domain separation prefixes of the tree hashing mode (as in RFC 6962), so that a leaf can't be confused with a node
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+// Encrypted inclusion proof of a leaf: the sibling of each node on the path from the leaf up to the root (bottom to
+// top), and the root itself
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub siblings: Vec<Vec<Ciphertext>>,
+    pub root: Vec<Ciphertext>,
+}
+
+// Hash of two encrypted 256-bit children. The second block of the input only holds the padding of a 64-byte message,
+// so it is trivially encrypted and takes the constant schedule path of sha256_fhe
+pub fn merkle_node(left: &[Ciphertext], right: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    assert!(left.len() == 256 && right.len() == 256, "merkle nodes are 256-bit digests");
+
+    sha256_fhe(pad_encrypted([left, right].concat(), sk), sk)
+}
+
+type NodeFn = fn(&[Ciphertext], &[Ciphertext], &ServerKey) -> Vec<Ciphertext>;
+
+// Computes the next level of the tree, hashing the pairs of nodes in parallel. The last node of an odd level is paired
+// with itself, or promoted unchanged if `promote_odd` is set
+fn next_level(level: &[Vec<Ciphertext>], node: NodeFn, promote_odd: bool, sk: &ServerKey) -> Vec<Vec<Ciphertext>> {
+    level.par_chunks(2)
+        .map(|pair| match pair {
+            [left, right] => node(left, right, sk),
+            [last] if promote_odd => last.clone(),
+            [last] => node(last, last, sk),
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+// Encrypted root of the tree with the given leaves
+pub fn merkle_root(leaves: &[Vec<Ciphertext>], sk: &ServerKey) -> Vec<Ciphertext> {
+    merkle_proof(leaves, 0, sk).root
+}
+
+// Builds the whole tree and returns the sibling path of the leaf at `index` along with the root
+pub fn merkle_proof(leaves: &[Vec<Ciphertext>], index: usize, sk: &ServerKey) -> MerkleProof {
+    assert!(index < leaves.len(), "leaf index {} out of range for {} leaves", index, leaves.len());
+
+    let mut level = leaves.to_vec();
+    let mut index = index;
+    let mut siblings = Vec::new();
+
+    while level.len() > 1 {
+        // The last node of an odd level is its own sibling
+        let sibling = (index ^ 1).min(level.len() - 1);
+        siblings.push(level[sibling].clone());
+
+        level = next_level(&level, merkle_node, false, sk);
+        index /= 2;
+    }
+
+    MerkleProof {
+        siblings,
+        root: level.pop().unwrap(),
+    }
+}
+
+// Opt-in tree hashing mode: the message (a whole number of bytes) is split into chunks of chunk_bytes bytes, which are
+// hashed in parallel and then combined Merkle-style. Unlike the Merkle–Damgård chaining of sha256, the wall-clock time
+// scales with the number of cores. This is NOT sha256: the digest differs from the sha256 of the message, and it also
+// depends on the chunk size
+pub fn tree_hash(message: &[Ciphertext], chunk_bytes: usize, sk: &ServerKey) -> Vec<Ciphertext> {
+    assert_eq!(message.len() % 8, 0, "message is not a whole number of bytes");
+    assert!(chunk_bytes > 0, "chunk size must be positive");
+
+    let chunks: Vec<&[Ciphertext]> = if message.is_empty() {
+        vec![message]
+    } else {
+        message.chunks(chunk_bytes * 8).collect()
+    };
+
+    let mut level: Vec<Vec<Ciphertext>> = chunks.into_par_iter()
+        .map(|chunk| prefixed_hash(LEAF_PREFIX, &[chunk], sk))
+        .collect();
+
+    while level.len() > 1 {
+        level = next_level(&level, tree_node, true, sk);
+    }
+
+    level.pop().unwrap()
+}
+
+fn tree_node(left: &[Ciphertext], right: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    prefixed_hash(NODE_PREFIX, &[left, right], sk)
+}
+
+// sha256(prefix || parts...)
+fn prefixed_hash(prefix: u8, parts: &[&[Ciphertext]], sk: &ServerKey) -> Vec<Ciphertext> {
+    let mut input = trivial_bytes(&[prefix], sk);
+    for part in parts {
+        input.extend_from_slice(part);
+    }
+
+    sha256_fhe(pad_encrypted(input, sk), sk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+    use crate::test_keys::test_keys;
+
+    fn to_bools(bytes: &[u8]) -> Vec<bool> {
+        bytes.iter().flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1)).collect()
+    }
+
+    fn from_trivial(bits: &[Ciphertext]) -> Vec<u8> {
+        bits.chunks(8)
+            .map(|byte| byte.iter().fold(0u8, |acc, bit| match bit {
+                Ciphertext::Trivial(value) => (acc << 1) | *value as u8,
+                Ciphertext::Encrypted(_) => unreachable!(),
+            }))
+            .collect()
+    }
+
+    fn node(left: &[u8], right: &[u8]) -> Vec<u8> {
+        Sha256::digest([left, right].concat()).to_vec()
+    }
+
+    #[test]
+    fn test_merkle_proof() {
+        let (_, sk) = test_keys();
+
+        // Leaves are trivially encrypted, so the tree is computed with the clear backend
+        let leaves: Vec<Vec<u8>> = (0u8..3).map(|i| Sha256::digest([i]).to_vec()).collect();
+        let encrypted_leaves: Vec<Vec<Ciphertext>> = leaves.iter()
+            .map(|leaf| to_bools(leaf).iter().map(|bit| sk.trivial_encrypt(*bit)).collect())
+            .collect();
+
+        let left = node(&leaves[0], &leaves[1]);
+        let right = node(&leaves[2], &leaves[2]);
+        let root = node(&left, &right);
+
+        let proof = merkle_proof(&encrypted_leaves, 2, &sk);
+        let siblings: Vec<Vec<u8>> = proof.siblings.iter().map(|sibling| from_trivial(sibling)).collect();
+
+        assert_eq!(siblings, vec![leaves[2].clone(), left]);
+        assert_eq!(from_trivial(&proof.root), root);
+
+        let proof = merkle_proof(&encrypted_leaves, 0, &sk);
+        let siblings: Vec<Vec<u8>> = proof.siblings.iter().map(|sibling| from_trivial(sibling)).collect();
+
+        assert_eq!(siblings, vec![leaves[1].clone(), right]);
+        assert_eq!(from_trivial(&merkle_root(&encrypted_leaves, &sk)), root);
+    }
+
+    #[test]
+    fn test_tree_hash() {
+        let (_, sk) = test_keys();
+
+        let message: Vec<u8> = (0..200u8).collect();
+        let encrypted_message: Vec<Ciphertext> = to_bools(&message).iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+
+        let leaf = |chunk: &[u8]| Sha256::digest([&[0x00], chunk].concat()).to_vec();
+        let tree_node = |left: &[u8], right: &[u8]| Sha256::digest([&[0x01], left, right].concat()).to_vec();
+
+        // Chunks of 64 bytes give 4 leaves, the last one being 8 bytes long
+        let leaves: Vec<Vec<u8>> = message.chunks(64).map(leaf).collect();
+        let expected = tree_node(&tree_node(&leaves[0], &leaves[1]), &tree_node(&leaves[2], &leaves[3]));
+        assert_eq!(from_trivial(&tree_hash(&encrypted_message, 64, &sk)), expected);
+
+        // Three chunks: the third leaf is promoted, so repeating the last chunk changes the digest
+        let three = tree_node(&tree_node(&leaves[0], &leaves[1]), &leaves[2]);
+        let repeated = [&encrypted_message[..1536], &encrypted_message[1024..1536]].concat();
+        assert_eq!(from_trivial(&tree_hash(&encrypted_message[..1536], 64, &sk)), three);
+        assert_ne!(from_trivial(&tree_hash(&repeated, 64, &sk)), three);
+
+        // A single chunk is just the leaf hash
+        assert_eq!(from_trivial(&tree_hash(&encrypted_message, 200, &sk)), leaf(&message));
+        assert_eq!(from_trivial(&tree_hash(&[], 64, &sk)), leaf(&[]));
+    }
+}
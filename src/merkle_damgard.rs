@@ -0,0 +1,206 @@
+// This module is the generic Merkle–Damgård engine: block iteration, MD-strengthening padding and chaining, shared by
+// every hash built on a compression function (sha256 here, and any SHA-1/SHA-2/MD5/RIPEMD variant later on). A hash
+// only implements CompressionFn on its chaining value, and gets one-shot hashing of padded inputs, server side padding
+// and the incremental MerkleDamgard hasher from this module.
+//
+// The padding is the client side MdPadding with the parameters of the compression function. Its content only depends
+// on the message length, which is public whenever the server pads, so it is trivially encrypted.
+
+use serde::{Deserialize, Serialize};
+use tfhe::boolean::prelude::*;
+use crate::boolean_ops::trivial_bytes;
+use crate::padding::MdPadding;
+
+pub use crate::padding::Endianness;
+
+// Chaining value of a Merkle–Damgård hash, along with its compression function
+pub trait CompressionFn: Clone {
+    const BLOCK_BITS: usize;
+    // Width of the length field of the padding (at most 128)
+    const LENGTH_BITS: usize;
+    const LENGTH_ENDIANNESS: Endianness;
+
+    // Chaining value with the initial hash values
+    fn initial(sk: &ServerKey) -> Self;
+
+    // Compresses one block of BLOCK_BITS bits into the chaining value
+    fn compress(&mut self, block: &[Ciphertext], sk: &ServerKey);
+
+    // Digest once every block of the padded input has been compressed
+    fn digest(self) -> Vec<Ciphertext>;
+
+    // Number of input bits compressed into the chaining value (a whole number of blocks)
+    fn length(&self) -> u64;
+
+    // Padding parameters, shared with the client side padding
+    fn md_padding() -> MdPadding {
+        MdPadding { block_bits: Self::BLOCK_BITS, length_bits: Self::LENGTH_BITS, length_endianness: Self::LENGTH_ENDIANNESS }
+    }
+}
+
+// Compresses whole blocks into the chaining value, in order
+pub fn compress_blocks<C: CompressionFn>(state: &mut C, blocks: &[Ciphertext], sk: &ServerKey) {
+    assert_eq!(blocks.len() % C::BLOCK_BITS, 0, "input length is not a multiple of the block size");
+
+    for block in blocks.chunks(C::BLOCK_BITS) {
+        state.compress(block, sk);
+    }
+}
+
+// Digest of the padded input (or the padded remainder of a message, from a midstate)
+pub fn hash_padded<C: CompressionFn>(mut state: C, padded_input: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    compress_blocks(&mut state, padded_input, sk);
+    state.digest()
+}
+
+// Padding bits that follow a message of `length` bits
+pub fn padding<C: CompressionFn>(length: u64, sk: &ServerKey) -> Vec<Ciphertext> {
+    C::md_padding().padding(length).iter().map(|bit| sk.trivial_encrypt(*bit)).collect()
+}
+
+// Appends the padding to an encrypted message whose length is public
+pub fn pad_encrypted<C: CompressionFn>(mut message: Vec<Ciphertext>, sk: &ServerKey) -> Vec<Ciphertext> {
+    let padding = padding::<C>(message.len() as u64, sk);
+    message.extend(padding);
+    message
+}
+
+// Incremental hasher for inputs that arrive in pieces, such as an encrypted prefix followed later by a plaintext suffix.
+// Full blocks are compressed as soon as they are available, and the padding is appended on finalization since the
+// total length is public. It can be serialized to resume the computation later
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MerkleDamgard<C> {
+    state: C,
+    buffer: Vec<Ciphertext>,
+}
+
+impl<C: CompressionFn> MerkleDamgard<C> {
+    pub fn new(sk: &ServerKey) -> Self {
+        Self::from_state(C::initial(sk))
+    }
+
+    // Resumes hashing from a chaining value, such as a midstate shared by several messages, after the message bits that
+    // were compressed into it
+    pub fn from_state(state: C) -> Self {
+        MerkleDamgard { state, buffer: Vec::with_capacity(C::BLOCK_BITS) }
+    }
+
+    // Number of message bits absorbed so far
+    pub fn length(&self) -> u64 {
+        self.state.length() + self.buffer.len() as u64
+    }
+
+    // Absorbs encrypted message bits
+    pub fn update(&mut self, bits: &[Ciphertext], sk: &ServerKey) {
+        self.buffer.extend_from_slice(bits);
+
+        let full = self.buffer.len() - self.buffer.len() % C::BLOCK_BITS;
+        if full > 0 {
+            compress_blocks(&mut self.state, &self.buffer[..full], sk);
+            self.buffer.drain(..full);
+        }
+    }
+
+    // Absorbs plaintext message bytes as trivial ciphertexts
+    pub fn update_plain(&mut self, bytes: &[u8], sk: &ServerKey) {
+        self.update(&trivial_bytes(bytes, sk), sk);
+    }
+
+    // Absorbs plaintext message bits, for messages that aren't a whole number of bytes
+    pub fn update_plain_bits(&mut self, bits: &[bool], sk: &ServerKey) {
+        let bits: Vec<Ciphertext> = bits.iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+        self.update(&bits, sk);
+    }
+
+    // Appends the padding and returns the encrypted digest
+    pub fn finalize(mut self, sk: &ServerKey) -> Vec<Ciphertext> {
+        let length = self.length();
+        let mut tail = std::mem::take(&mut self.buffer);
+        tail.extend(padding::<C>(length, sk));
+
+        hash_padded(self.state, &tail, sk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::padding::{message_bits, pad_sha256_data};
+    use crate::test_helpers::decrypt_bits;
+    use crate::test_keys::test_keys;
+
+    // Compression function that records the blocks it's given, so its digest is the padded input
+    #[derive(Clone)]
+    struct Recorder<const BLOCK_BITS: usize, const LENGTH_BITS: usize, const LITTLE: bool>(Vec<Ciphertext>);
+
+    impl<const BLOCK_BITS: usize, const LENGTH_BITS: usize, const LITTLE: bool> CompressionFn
+        for Recorder<BLOCK_BITS, LENGTH_BITS, LITTLE>
+    {
+        const BLOCK_BITS: usize = BLOCK_BITS;
+        const LENGTH_BITS: usize = LENGTH_BITS;
+        const LENGTH_ENDIANNESS: Endianness = if LITTLE { Endianness::Little } else { Endianness::Big };
+
+        fn initial(_: &ServerKey) -> Self {
+            Recorder(vec![])
+        }
+
+        fn compress(&mut self, block: &[Ciphertext], _: &ServerKey) {
+            assert_eq!(block.len(), BLOCK_BITS);
+            self.0.extend_from_slice(block);
+        }
+
+        fn digest(self) -> Vec<Ciphertext> {
+            self.0
+        }
+
+        fn length(&self) -> u64 {
+            self.0.len() as u64
+        }
+    }
+
+    #[test]
+    fn test_padding() {
+        let (ck, sk) = test_keys();
+
+        // Every length around the one and two block boundaries matches the client side sha256 padding
+        for len in 0..=130 {
+            let message: Vec<u8> = (0..len).map(|i| (i * 31 + 7) as u8).collect();
+            let padded = pad_encrypted::<Recorder<512, 64, false>>(trivial_bytes(&message, &sk), &sk);
+            assert_eq!(decrypt_bits(&padded, &ck), pad_sha256_data(&message), "message length {}", len);
+        }
+
+        // 1024-bit blocks with a 128-bit length field (SHA-512), and a little-endian 64-bit field (MD5)
+        let padded = padding::<Recorder<1024, 128, false>>(24, &sk);
+        assert_eq!(padded.len(), 1000);
+        assert_eq!(decrypt_bits(&padded[1000 - 128..], &ck), message_bits(&24u128.to_be_bytes()));
+
+        let padded = padding::<Recorder<512, 64, true>>(8 * 300, &sk);
+        assert_eq!(padded.len(), 160);
+        assert_eq!(decrypt_bits(&padded[padded.len() - 64..], &ck), message_bits(&2400u64.to_le_bytes()));
+    }
+
+    #[test]
+    fn test_merkle_damgard() {
+        let (ck, sk) = test_keys();
+
+        let message: Vec<u8> = (0..150).collect();
+        let expected = pad_sha256_data(&message);
+
+        // Pieces of every size, straddling block boundaries
+        for piece in [1, 7, 64, 100] {
+            let mut hasher = MerkleDamgard::<Recorder<512, 64, false>>::new(&sk);
+            for chunk in message.chunks(piece) {
+                hasher.update_plain(chunk, &sk);
+            }
+            assert_eq!(hasher.length(), 150 * 8);
+            assert_eq!(decrypt_bits(&hasher.finalize(&sk), &ck), expected);
+        }
+
+        // Resuming from the chaining value after the first block
+        let mut state = Recorder::<512, 64, false>::initial(&sk);
+        compress_blocks(&mut state, &trivial_bytes(&message[..64], &sk), &sk);
+        let mut hasher = MerkleDamgard::from_state(state);
+        hasher.update_plain(&message[64..], &sk);
+        assert_eq!(decrypt_bits(&hasher.finalize(&sk), &ck), expected);
+    }
+}
@@ -0,0 +1,131 @@
+// This module is an EXPERIMENTAL implementation of MiMC (Albrecht et al., ASIACRYPT 2016) over encrypted elements of a
+// prime field, the other arithmetization-oriented design next to Poseidon, on the same field arithmetic. MiMC-p/p is a
+// block cipher whose rounds are x -> (x + k + c_i)^alpha, with enough rounds (log_alpha p) for the degree to reach the
+// size of the field, followed by a final key addition. The exponent is 3 where x^3 is a permutation of the field, and
+// e.g. 7 over the Goldilocks field, which takes fewer rounds of one more multiplication each.
+//
+// MiMC has no standard way to generate its round constants, so they come from the Grain LFSR of the Poseidon reference
+// seeded as for a width-1 instance with that many full rounds, with the first one zero as in the paper; constants of
+// other implementations can be given instead. The hash is the cipher in Miyaguchi-Preneel mode over the inputs.
+//
+// Every round is a dependent power, so the depth is the number of multiplications: 41 rounds of x^3 over a 64-bit field
+// are 82 products in a row, against 24 sequential rounds of one AND per bit for Keccak.
+
+use rayon::prelude::*;
+use tfhe::boolean::prelude::*;
+use crate::field::{Grain, PrimeField};
+
+#[derive(Clone, Debug)]
+pub struct Mimc<const N: usize> {
+    field: PrimeField<N>,
+    alpha: u64,
+    round_constants: Vec<u64>,
+}
+
+impl<const N: usize> Mimc<N> {
+    // MiMC with the exponent alpha (x^alpha must be a permutation of the field) and generated round constants
+    pub fn new(field: PrimeField<N>, alpha: u64) -> Self {
+        assert!(alpha > 1, "exponent must be at least 2");
+
+        // The smallest number of rounds whose degree alpha^rounds reaches p
+        let modulus = field.modulus();
+        let rounds = (1..)
+            .find(|rounds| (alpha as u128).checked_pow(*rounds).is_none_or(|degree| degree >= modulus as u128))
+            .unwrap();
+
+        let mut grain = Grain::new(64 - modulus.leading_zeros() as u64, 1, rounds as usize, 0);
+        let mut round_constants: Vec<u64> = (0..rounds).map(|_| grain.element(modulus)).collect();
+        round_constants[0] = 0;
+
+        Mimc::with_constants(field, alpha, round_constants)
+    }
+
+    // MiMC with the given round constants, one per round
+    pub fn with_constants(field: PrimeField<N>, alpha: u64, round_constants: Vec<u64>) -> Self {
+        assert!(field.power_is_permutation(alpha), "x^alpha isn't a permutation of the field");
+        assert!(!round_constants.is_empty(), "MiMC needs at least one round");
+
+        let round_constants = round_constants.iter().map(|constant| constant % field.modulus()).collect();
+        Mimc { field, alpha, round_constants }
+    }
+
+    pub fn rounds(&self) -> usize {
+        self.round_constants.len()
+    }
+
+    // MiMC-p/p encryption of x under the key, both encrypted
+    pub fn cipher(&self, x: &[Ciphertext; N], key: &[Ciphertext; N], sk: &ServerKey) -> [Ciphertext; N] {
+        let field = &self.field;
+
+        // The round keys don't depend on x, so they are all added up front in parallel
+        let round_keys: Vec<[Ciphertext; N]> = self.round_constants
+            .par_iter()
+            .map(|constant| field.add(key, &field.constant(*constant, sk), sk))
+            .collect();
+
+        let x = round_keys
+            .iter()
+            .fold(x.clone(), |x, round_key| field.pow(&field.add(&x, round_key, sk), self.alpha, sk));
+        field.add(&x, key, sk)
+    }
+
+    pub fn cipher_plain(&self, x: u64, key: u64) -> u64 {
+        let field = &self.field;
+        let x = self.round_constants.iter().fold(x, |x, constant| {
+            field.pow_plain(field.add_plain(field.add_plain(x, key), *constant), self.alpha)
+        });
+        field.add_plain(x, key)
+    }
+
+    // Permutation of the field, the cipher under the zero key
+    pub fn permute(&self, x: &[Ciphertext; N], sk: &ServerKey) -> [Ciphertext; N] {
+        self.cipher(x, &self.field.constant(0, sk), sk)
+    }
+
+    // Miyaguchi-Preneel hash of the inputs to one element: every input is encrypted under the running value as the key,
+    // which is then the sum of the three. It starts at the number of inputs, which is public
+    pub fn hash(&self, inputs: &[[Ciphertext; N]], sk: &ServerKey) -> [Ciphertext; N] {
+        let field = &self.field;
+        inputs.iter().fold(field.constant(inputs.len() as u64, sk), |h, input| {
+            let encrypted = self.cipher(input, &h, sk);
+            field.add(&field.add(&encrypted, &h, sk), input, sk)
+        })
+    }
+
+    pub fn hash_plain(&self, inputs: &[u64]) -> u64 {
+        let field = &self.field;
+        inputs.iter().fold(inputs.len() as u64 % field.modulus(), |h, input| {
+            field.add_plain(field.add_plain(self.cipher_plain(*input, h), h), *input)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::GOLDILOCKS;
+    use crate::test_helpers::{decrypt_uint, encrypt_uint};
+    use crate::test_keys::test_keys;
+
+    // Constants and outputs checked against a Python model, on a 16-bit field where x^3 is a permutation
+    #[test]
+    fn test_mimc() {
+        let (ck, sk) = test_keys();
+        let mimc = Mimc::new(PrimeField::<16>::new(65519), 3);
+        assert_eq!(mimc.rounds(), 11);
+        assert_eq!(mimc.round_constants[..3], [0, 25658, 46224]);
+
+        assert_eq!(mimc.cipher_plain(12345, 6789), 46882);
+        assert_eq!(decrypt_uint(&mimc.cipher(&encrypt_uint(12345, &ck), &encrypt_uint(6789, &ck), &sk), &ck), 46882);
+        assert_eq!(decrypt_uint(&mimc.permute(&encrypt_uint(0, &ck), &sk), &ck), 20807);
+
+        let inputs: Vec<[Ciphertext; 16]> = [1, 2, 3].into_iter().map(|value| encrypt_uint(value, &ck)).collect();
+        assert_eq!(mimc.hash_plain(&[1, 2, 3]), 12354);
+        assert_eq!(decrypt_uint(&mimc.hash(&inputs, &sk), &ck), 12354);
+
+        // Over the Goldilocks field, where 3 divides p - 1, in the clear
+        let mimc = Mimc::new(PrimeField::<64>::new(GOLDILOCKS), 7);
+        assert_eq!((mimc.rounds(), mimc.round_constants[1]), (23, 16244928298220367890));
+        assert_eq!(mimc.cipher_plain(1, 2), 734765764195419339);
+    }
+}
@@ -0,0 +1,107 @@
+// This module wraps a decrypted digest in the self-describing formats used by IPFS-style content addressing: multihash
+// (hash function code, digest length, digest) and CIDv1 (version, content codec, multihash) in base32 multibase. It only
+// works on the decrypted digest, so it is client side and always available.
+
+use crate::algorithm::Algorithm;
+
+// Multicodec codes of the content types commonly found in a CID
+pub const CODEC_RAW: u64 = 0x55;
+pub const CODEC_DAG_PB: u64 = 0x70;
+
+const BASE32: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+// Multicodec code of the hash function, or None if the multicodec table has none (Streebog)
+pub fn multihash_code(algorithm: Algorithm) -> Option<u64> {
+    match algorithm {
+        Algorithm::Sha256 => Some(0x12),
+        Algorithm::Sm3 => Some(0x534d),
+        Algorithm::Streebog256 | Algorithm::Streebog512 => None,
+    }
+}
+
+// Unsigned LEB128, the varint encoding of every multiformats prefix
+fn push_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+// Multihash of the digest, or None if the algorithm has no multicodec code
+pub fn multihash(algorithm: Algorithm, digest: &[u8]) -> Option<Vec<u8>> {
+    assert_eq!(digest.len() * 8, algorithm.digest_bits(), "digest length doesn't match the algorithm");
+
+    let mut bytes = vec![];
+    push_varint(&mut bytes, multihash_code(algorithm)?);
+    push_varint(&mut bytes, digest.len() as u64);
+    bytes.extend_from_slice(digest);
+    Some(bytes)
+}
+
+// CIDv1 of content with the given codec, in the default base32 multibase ("b" prefix). Use CODEC_RAW when the digest
+// is the hash of the raw bytes. None if the algorithm has no multicodec code
+pub fn cid_v1(codec: u64, algorithm: Algorithm, digest: &[u8]) -> Option<String> {
+    let mut bytes = vec![];
+    push_varint(&mut bytes, 1);
+    push_varint(&mut bytes, codec);
+    bytes.extend(multihash(algorithm, digest)?);
+
+    Some(format!("b{}", base32_encode(&bytes)))
+}
+
+// Lowercase unpadded RFC 4648 base32
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let (mut buffer, mut bits) = (0u16, 0);
+
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u16;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32[(buffer >> bits & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(BASE32[(buffer << (5 - bits) & 0x1f) as usize] as char);
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // sha256 of the empty string
+    const EMPTY_DIGEST: [u8; 32] = [
+        0xe3, 0xb0, 0xc4, 0x42, 0x98, 0xfc, 0x1c, 0x14, 0x9a, 0xfb, 0xf4, 0xc8, 0x99, 0x6f, 0xb9, 0x24,
+        0x27, 0xae, 0x41, 0xe4, 0x64, 0x9b, 0x93, 0x4c, 0xa4, 0x95, 0x99, 0x1b, 0x78, 0x52, 0xb8, 0x55,
+    ];
+
+    #[test]
+    fn test_multihash() {
+        let multihash = multihash(Algorithm::Sha256, &EMPTY_DIGEST).unwrap();
+        assert_eq!(multihash[..2], [0x12, 0x20]);
+        assert_eq!(multihash[2..], EMPTY_DIGEST);
+
+        // Codes from 128 up take several bytes
+        assert_eq!(super::multihash(Algorithm::Sm3, &EMPTY_DIGEST).unwrap()[..4], [0xcd, 0xa6, 0x01, 0x20]);
+        assert_eq!(super::multihash(Algorithm::Streebog256, &EMPTY_DIGEST), None);
+
+        let mut varint = vec![];
+        push_varint(&mut varint, 300);
+        assert_eq!(varint, [0xac, 0x02]);
+    }
+
+    #[test]
+    fn test_cid_v1() {
+        // The well-known CID of an empty raw block
+        assert_eq!(
+            cid_v1(CODEC_RAW, Algorithm::Sha256, &EMPTY_DIGEST).unwrap(),
+            "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku",
+        );
+        assert_eq!(base32_encode(b"foobar"), "mzxw6ytboi");
+    }
+}
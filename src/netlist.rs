@@ -0,0 +1,811 @@
+// This module builds the sha256 circuit of a given number of blocks once, as an explicit netlist of gates, and executes
+// it over inputs. The functions of the sha256 module re-derive the structure of the circuit through nested calls on
+// every hash, while a netlist is built once per message length class and can then be executed, inspected, transformed
+// or shipped elsewhere as plain data.
+//
+// A netlist is a list of gates in topological order, where every gate produces one wire: wire i is the output of gate
+// i. The executor splits the gates into levels, the bootstrapped gates of a level depending only on earlier levels, and
+// evaluates each level with one flat parallel iterator. NOT gates take no bootstrapping and run right after the level of
+// their operand. The value of a wire is dropped after the last level that uses it, so the memory held is about the
+// width of the circuit rather than its size.
+//
+// Netlist::optimize folds constants, merges common subexpressions and removes dead gates, which finds savings that are
+// awkward to express in the hand-written circuit: the IV of the first block and the round constants are folded into
+// the gates that use them, and the carry-save adders share the XOR of two operands between the sum and the carry.
+
+use std::collections::HashMap;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tfhe::boolean::prelude::*;
+use crate::boolean_ops::{and_gate, mux_gate, or_gate, xor_gate};
+use crate::sha256::{H0, K, ROUNDS};
+
+pub type Wire = u32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Gate {
+    // Bit of the input at the given index
+    Input(u32),
+    Constant(bool),
+    Not(Wire),
+    And(Wire, Wire),
+    Or(Wire, Wire),
+    Xor(Wire, Wire),
+    // Condition, then, otherwise
+    Mux(Wire, Wire, Wire),
+}
+
+impl Gate {
+    // Whether the gate is bootstrapped when its operands are encrypted
+    pub fn is_bootstrapped(&self) -> bool {
+        matches!(self, Gate::And(..) | Gate::Or(..) | Gate::Xor(..) | Gate::Mux(..))
+    }
+
+    pub fn operands(&self) -> impl Iterator<Item = Wire> {
+        let operands = match *self {
+            Gate::Input(_) | Gate::Constant(_) => [None, None, None],
+            Gate::Not(a) => [Some(a), None, None],
+            Gate::And(a, b) | Gate::Or(a, b) | Gate::Xor(a, b) => [Some(a), Some(b), None],
+            Gate::Mux(c, t, e) => [Some(c), Some(t), Some(e)],
+        };
+        operands.into_iter().flatten()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Netlist {
+    pub inputs: usize,
+    pub gates: Vec<Gate>,
+    pub outputs: Vec<Wire>,
+}
+
+impl Netlist {
+    // Number of gates that are bootstrapped when the inputs are encrypted
+    pub fn bootstrapped_gates(&self) -> usize {
+        self.gates.iter().filter(|gate| gate.is_bootstrapped()).count()
+    }
+
+    // Number of levels of bootstrapped gates, i.e. the length of the critical path in bootstraps
+    pub fn depth(&self) -> usize {
+        self.levels().into_iter().max().unwrap_or(0)
+    }
+
+    // Level of every gate: one more than its deepest operand for bootstrapped gates, the level of its deepest operand
+    // otherwise
+    fn levels(&self) -> Vec<usize> {
+        let mut levels: Vec<usize> = Vec::with_capacity(self.gates.len());
+        for gate in &self.gates {
+            let operands = gate.operands().map(|wire| levels[wire as usize]).max().unwrap_or(0);
+            levels.push(operands + gate.is_bootstrapped() as usize);
+        }
+
+        levels
+    }
+
+    // Checks that the netlist is well formed, e.g. after reading it from an untrusted file: every gate only uses earlier
+    // wires and inputs that exist, and every output is a wire. The other methods assume it and may panic otherwise
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, gate) in self.gates.iter().enumerate() {
+            if let Some(wire) = gate.operands().find(|&wire| wire as usize >= i) {
+                return Err(format!("gate {} uses wire {}, which isn't computed before it", i, wire));
+            }
+            if let Gate::Input(input) = *gate {
+                if input as usize >= self.inputs {
+                    return Err(format!("gate {} reads input {} of {}", i, input, self.inputs));
+                }
+            }
+        }
+        match self.outputs.iter().find(|&&wire| wire as usize >= self.gates.len()) {
+            Some(wire) => Err(format!("output wire {} of {} gates", wire, self.gates.len())),
+            None => Ok(()),
+        }
+    }
+
+    // Evaluates the netlist in the clear
+    pub fn evaluate(&self, inputs: &[bool]) -> Vec<bool> {
+        assert_eq!(inputs.len(), self.inputs, "wrong number of inputs");
+
+        let mut values: Vec<bool> = Vec::with_capacity(self.gates.len());
+        for gate in &self.gates {
+            let value = match *gate {
+                Gate::Input(i) => inputs[i as usize],
+                Gate::Constant(value) => value,
+                Gate::Not(a) => !values[a as usize],
+                Gate::And(a, b) => values[a as usize] & values[b as usize],
+                Gate::Or(a, b) => values[a as usize] | values[b as usize],
+                Gate::Xor(a, b) => values[a as usize] ^ values[b as usize],
+                Gate::Mux(c, t, e) => if values[c as usize] { values[t as usize] } else { values[e as usize] },
+            };
+            values.push(value);
+        }
+
+        self.outputs.iter().map(|&wire| values[wire as usize]).collect()
+    }
+
+    // Evaluates the netlist homomorphically, level by level
+    pub fn execute(&self, inputs: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+        assert_eq!(inputs.len(), self.inputs, "wrong number of inputs");
+
+        let levels = self.levels();
+        let depth = levels.iter().copied().max().unwrap_or(0);
+
+        let mut bootstrapped = vec![Vec::new(); depth + 1];
+        let mut free = vec![Vec::new(); depth + 1];
+        // Level after which each wire is no longer needed. Outputs are kept until the end
+        let mut last_use = levels.clone();
+        for (i, gate) in self.gates.iter().enumerate() {
+            let pass = if gate.is_bootstrapped() { &mut bootstrapped } else { &mut free };
+            pass[levels[i]].push(i);
+            for wire in gate.operands() {
+                last_use[wire as usize] = last_use[wire as usize].max(levels[i]);
+            }
+        }
+        for &wire in &self.outputs {
+            last_use[wire as usize] = usize::MAX;
+        }
+        let mut release = vec![Vec::new(); depth + 1];
+        for (wire, &level) in last_use.iter().enumerate() {
+            if level != usize::MAX {
+                release[level].push(wire);
+            }
+        }
+
+        let mut values: Vec<Option<Ciphertext>> = vec![None; self.gates.len()];
+        for level in 0..=depth {
+            let computed: Vec<(usize, Ciphertext)> = bootstrapped[level].par_iter()
+                .map(|&i| (i, self.execute_gate(i, &values, inputs, sk)))
+                .collect();
+            for (i, value) in computed {
+                values[i] = Some(value);
+            }
+            // In topological order, since NOT gates of a level can depend on each other
+            for &i in &free[level] {
+                values[i] = Some(self.execute_gate(i, &values, inputs, sk));
+            }
+            for &wire in &release[level] {
+                values[wire] = None;
+            }
+        }
+
+        self.outputs.iter().map(|&wire| values[wire as usize].clone().unwrap()).collect()
+    }
+
+    // Same as execute, after specializing the netlist to the inputs that are trivial ciphertexts, if any, and optimizing
+    // it again with every pass
+    pub fn execute_specialized(&self, inputs: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+        let known: Vec<Option<bool>> = inputs.iter()
+            .map(|bit| match bit {
+                Ciphertext::Trivial(value) => Some(*value),
+                Ciphertext::Encrypted(_) => None,
+            })
+            .collect();
+
+        if known.iter().any(Option::is_some) {
+            self.with_known_inputs(&known).optimize(Optimizations::ALL).execute(inputs, sk)
+        } else {
+            self.execute(inputs, sk)
+        }
+    }
+
+    fn execute_gate(&self, i: usize, values: &[Option<Ciphertext>], inputs: &[Ciphertext], sk: &ServerKey) -> Ciphertext {
+        let value = |wire: Wire| values[wire as usize].as_ref().unwrap();
+
+        match self.gates[i] {
+            Gate::Input(i) => inputs[i as usize].clone(),
+            Gate::Constant(value) => sk.trivial_encrypt(value),
+            Gate::Not(a) => sk.not(value(a)),
+            Gate::And(a, b) => and_gate(sk, value(a), value(b)),
+            Gate::Or(a, b) => or_gate(sk, value(a), value(b)),
+            Gate::Xor(a, b) => xor_gate(sk, value(a), value(b)),
+            Gate::Mux(c, t, e) => mux_gate(sk, value(c), value(t), value(e)),
+        }
+    }
+}
+
+impl Gate {
+    fn map_operands(self, f: impl Fn(Wire) -> Wire) -> Gate {
+        match self {
+            Gate::Input(_) | Gate::Constant(_) => self,
+            Gate::Not(a) => Gate::Not(f(a)),
+            Gate::And(a, b) => Gate::And(f(a), f(b)),
+            Gate::Or(a, b) => Gate::Or(f(a), f(b)),
+            Gate::Xor(a, b) => Gate::Xor(f(a), f(b)),
+            Gate::Mux(c, t, e) => Gate::Mux(f(c), f(t), f(e)),
+        }
+    }
+}
+
+// Passes run by Netlist::optimize
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Optimizations {
+    // Folds gates with constant operands, and gates with twice the same operand
+    pub constants: bool,
+    // Common-subexpression elimination: merges the gates with the same operation on the same operands
+    pub cse: bool,
+    // Removes the gates that no output depends on
+    pub dead_gates: bool,
+}
+
+impl Optimizations {
+    pub const ALL: Optimizations = Optimizations { constants: true, cse: true, dead_gates: true };
+    pub const NONE: Optimizations = Optimizations { constants: false, cse: false, dead_gates: false };
+}
+
+impl Default for Optimizations {
+    fn default() -> Self {
+        Optimizations::ALL
+    }
+}
+
+impl Netlist {
+    // Replaces the inputs known in advance with constants, e.g. the padding appended by the server as trivial
+    // ciphertexts, so that constant propagation can remove the gates that only depend on them. The netlist still takes
+    // all the inputs when executed
+    pub fn with_known_inputs(&self, known: &[Option<bool>]) -> Netlist {
+        assert_eq!(known.len(), self.inputs, "wrong number of inputs");
+
+        let gates = self.gates.iter()
+            .map(|&gate| match gate {
+                Gate::Input(i) => known[i as usize].map_or(gate, Gate::Constant),
+                gate => gate,
+            })
+            .collect();
+
+        Netlist { inputs: self.inputs, gates, outputs: self.outputs.clone() }
+    }
+
+    // Equivalent netlist after the given passes. Constant propagation and CSE run together in one pass over the gates,
+    // so that folding a gate can expose a common subexpression and vice versa
+    pub fn optimize(&self, optimizations: Optimizations) -> Netlist {
+        let mut optimizer = Optimizer { gates: Vec::new(), constants: [None; 2], seen: HashMap::new(), optimizations };
+
+        let mut wires: Vec<Wire> = Vec::with_capacity(self.gates.len());
+        for &gate in &self.gates {
+            wires.push(optimizer.add(gate.map_operands(|wire| wires[wire as usize])));
+        }
+
+        let netlist = Netlist {
+            inputs: self.inputs,
+            gates: optimizer.gates,
+            outputs: self.outputs.iter().map(|&wire| wires[wire as usize]).collect(),
+        };
+        if optimizations.dead_gates { netlist.without_dead_gates() } else { netlist }
+    }
+
+    fn without_dead_gates(self) -> Netlist {
+        let mut live = vec![false; self.gates.len()];
+        for &wire in &self.outputs {
+            live[wire as usize] = true;
+        }
+        for i in (0..self.gates.len()).rev() {
+            if live[i] {
+                for wire in self.gates[i].operands() {
+                    live[wire as usize] = true;
+                }
+            }
+        }
+
+        let mut wires: Vec<Wire> = vec![0; self.gates.len()];
+        let mut gates = Vec::with_capacity(live.iter().filter(|&&live| live).count());
+        for (i, &gate) in self.gates.iter().enumerate() {
+            if live[i] {
+                wires[i] = gates.len() as Wire;
+                gates.push(gate.map_operands(|wire| wires[wire as usize]));
+            }
+        }
+
+        let outputs = self.outputs.iter().map(|&wire| wires[wire as usize]).collect();
+        Netlist { inputs: self.inputs, gates, outputs }
+    }
+}
+
+struct Optimizer {
+    gates: Vec<Gate>,
+    constants: [Option<Wire>; 2],
+    // Gates already added, with commutative operands sorted, for CSE
+    seen: HashMap<Gate, Wire>,
+    optimizations: Optimizations,
+}
+
+enum Folded {
+    Wire(Wire),
+    Gate(Gate),
+}
+
+impl Optimizer {
+    // Adds a gate whose operands are wires of the optimized netlist, and returns the wire with its value
+    fn add(&mut self, gate: Gate) -> Wire {
+        let gate = if self.optimizations.constants {
+            match self.fold(gate) {
+                Folded::Wire(wire) => return wire,
+                Folded::Gate(gate) => gate,
+            }
+        } else {
+            gate
+        };
+
+        let gate = if self.optimizations.cse {
+            let gate = match gate {
+                Gate::And(a, b) => Gate::And(a.min(b), a.max(b)),
+                Gate::Or(a, b) => Gate::Or(a.min(b), a.max(b)),
+                Gate::Xor(a, b) => Gate::Xor(a.min(b), a.max(b)),
+                gate => gate,
+            };
+            if let Some(&wire) = self.seen.get(&gate) {
+                return wire;
+            }
+            gate
+        } else {
+            gate
+        };
+
+        self.gates.push(gate);
+        let wire = (self.gates.len() - 1) as Wire;
+        if self.optimizations.cse {
+            self.seen.insert(gate, wire);
+        }
+
+        wire
+    }
+
+    fn constant(&mut self, value: bool) -> Wire {
+        match self.constants[value as usize] {
+            Some(wire) => wire,
+            None => {
+                self.gates.push(Gate::Constant(value));
+                let wire = (self.gates.len() - 1) as Wire;
+                self.constants[value as usize] = Some(wire);
+                wire
+            }
+        }
+    }
+
+    fn value(&self, wire: Wire) -> Option<bool> {
+        match self.gates[wire as usize] {
+            Gate::Constant(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn fold(&mut self, gate: Gate) -> Folded {
+        let wire = match gate {
+            Gate::Constant(value) => self.constant(value),
+            Gate::Not(a) => match (self.value(a), self.gates[a as usize]) {
+                (Some(value), _) => self.constant(!value),
+                (None, Gate::Not(x)) => x,
+                _ => return Folded::Gate(gate),
+            },
+            Gate::And(a, b) | Gate::Or(a, b) => {
+                // The value that decides the result on its own: false for AND, true for OR
+                let absorbing = matches!(gate, Gate::Or(..));
+                match (self.value(a), self.value(b)) {
+                    (Some(value), _) | (_, Some(value)) if value == absorbing => self.constant(absorbing),
+                    (Some(_), _) => b,
+                    (_, Some(_)) => a,
+                    _ if a == b => a,
+                    _ => return Folded::Gate(gate),
+                }
+            }
+            Gate::Xor(a, b) => match (self.value(a), self.value(b)) {
+                (Some(false), _) => b,
+                (_, Some(false)) => a,
+                (Some(true), _) => self.add(Gate::Not(b)),
+                (_, Some(true)) => self.add(Gate::Not(a)),
+                _ if a == b => self.constant(false),
+                _ => return Folded::Gate(gate),
+            },
+            Gate::Mux(c, t, e) => match (self.value(c), self.value(t), self.value(e)) {
+                (Some(condition), _, _) => if condition { t } else { e },
+                _ if t == e => t,
+                (_, Some(true), Some(false)) => c,
+                (_, Some(false), Some(true)) => self.add(Gate::Not(c)),
+                _ if t == c => self.add(Gate::Or(c, e)),
+                _ if e == c => self.add(Gate::And(c, t)),
+                _ => return Folded::Gate(gate),
+            },
+            Gate::Input(_) => return Folded::Gate(gate),
+        };
+
+        Folded::Wire(wire)
+    }
+}
+
+// Appends gates to a netlist. The inputs are the first wires
+pub struct Builder {
+    netlist: Netlist,
+    constants: [Option<Wire>; 2],
+}
+
+impl Builder {
+    pub fn new(inputs: usize) -> Self {
+        let gates = (0..inputs as u32).map(Gate::Input).collect();
+        Builder { netlist: Netlist { inputs, gates, outputs: Vec::new() }, constants: [None; 2] }
+    }
+
+    pub fn input(&self, i: usize) -> Wire {
+        assert!(i < self.netlist.inputs, "input out of range");
+        i as Wire
+    }
+
+    pub fn push(&mut self, gate: Gate) -> Wire {
+        debug_assert!(gate.operands().all(|wire| (wire as usize) < self.netlist.gates.len()), "operand out of range");
+        self.netlist.gates.push(gate);
+        (self.netlist.gates.len() - 1) as Wire
+    }
+
+    pub fn constant(&mut self, value: bool) -> Wire {
+        match self.constants[value as usize] {
+            Some(wire) => wire,
+            None => {
+                let wire = self.push(Gate::Constant(value));
+                self.constants[value as usize] = Some(wire);
+                wire
+            }
+        }
+    }
+
+    pub fn not(&mut self, a: Wire) -> Wire {
+        self.push(Gate::Not(a))
+    }
+
+    pub fn and(&mut self, a: Wire, b: Wire) -> Wire {
+        self.push(Gate::And(a, b))
+    }
+
+    pub fn or(&mut self, a: Wire, b: Wire) -> Wire {
+        self.push(Gate::Or(a, b))
+    }
+
+    pub fn xor(&mut self, a: Wire, b: Wire) -> Wire {
+        self.push(Gate::Xor(a, b))
+    }
+
+    pub fn mux(&mut self, condition: Wire, then: Wire, otherwise: Wire) -> Wire {
+        self.push(Gate::Mux(condition, then, otherwise))
+    }
+
+    pub fn finish(mut self, outputs: Vec<Wire>) -> Netlist {
+        self.netlist.outputs = outputs;
+        self.netlist
+    }
+}
+
+// Parallel prefix network of the 32-bit adders in the netlist, as selected by the ladner_fischer feature for the
+// adders of the boolean_ops module
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Adder {
+    BrentKung,
+    LadnerFischer,
+}
+
+impl Default for Adder {
+    fn default() -> Self {
+        if cfg!(feature = "ladner_fischer") { Adder::LadnerFischer } else { Adder::BrentKung }
+    }
+}
+
+// Most significant bit first, as in the rest of the crate
+type Word = [Wire; 32];
+
+// Netlist of the sha256 of a padded input of the given number of blocks, with the digest bits as outputs
+pub fn sha256_netlist(blocks: usize, adder: Adder) -> Netlist {
+    let mut net = Builder::new(512 * blocks);
+    let mut hash: [Word; 8] = H0.map(|value| constant_word(&mut net, value));
+
+    for block in 0..blocks {
+        let input: [Word; 16] = std::array::from_fn(|i| std::array::from_fn(|bit| net.input(512 * block + 32 * i + bit)));
+        hash = compress(&mut net, hash, input, adder);
+    }
+
+    net.finish(hash.concat())
+}
+
+// sha256_fhe, executing the optimized netlist of the input's block count. It is specialized to the input bits that are
+// trivial ciphertexts (such as the padding appended by the server), if any
+pub fn sha256_fhe_netlist(padded_input: Vec<Ciphertext>, sk: &ServerKey) -> Vec<Ciphertext> {
+    assert_eq!(padded_input.len() % 512, 0, "padded input length is not a multiple of 512");
+
+    sha256_netlist(padded_input.len() / 512, Adder::default())
+        .optimize(Optimizations::ALL)
+        .execute_specialized(&padded_input, sk)
+}
+
+fn compress(net: &mut Builder, hash: [Word; 8], input: [Word; 16], adder: Adder) -> [Word; 8] {
+    let mut w = [[0; 32]; ROUNDS];
+    w[..16].copy_from_slice(&input);
+    for i in 16..ROUNDS {
+        let s0 = sigma(net, w[i - 15], [7, 18], 3);
+        let s1 = sigma(net, w[i - 2], [17, 19], 10);
+        let (sum, carry) = csa(net, s0, w[i - 7], w[i - 16]);
+        let (sum, carry) = csa(net, s1, sum, carry);
+        w[i] = add(net, sum, carry, adder);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = hash;
+    for i in 0..ROUNDS {
+        let s1 = big_sigma(net, e, [6, 11, 25]);
+        let ch: Word = std::array::from_fn(|bit| net.mux(e[bit], f[bit], g[bit]));
+        let k = constant_word(net, K[i]);
+        let (sum, carry) = csa(net, h, w[i], k);
+        let (sum, carry) = csa(net, sum, carry, ch);
+        let (sum, carry) = csa(net, sum, carry, s1);
+        let temp1 = add(net, sum, carry, adder);
+
+        let s0 = big_sigma(net, a, [2, 13, 22]);
+        let majority = maj(net, a, b, c);
+        let temp2 = add(net, s0, majority, adder);
+
+        h = g;
+        g = f;
+        f = e;
+        e = add(net, d, temp1, adder);
+        d = c;
+        c = b;
+        b = a;
+        a = add(net, temp1, temp2, adder);
+    }
+
+    let working = [a, b, c, d, e, f, g, h];
+    std::array::from_fn(|i| add(net, hash[i], working[i], adder))
+}
+
+fn constant_word(net: &mut Builder, value: u32) -> Word {
+    std::array::from_fn(|bit| net.constant((value >> (31 - bit)) & 1 == 1))
+}
+
+fn rotate_right(x: Word, n: usize) -> Word {
+    std::array::from_fn(|bit| x[(bit + 32 - n) % 32])
+}
+
+fn shift_right(net: &mut Builder, x: Word, n: usize) -> Word {
+    let zero = net.constant(false);
+    std::array::from_fn(|bit| if bit < n { zero } else { x[bit - n] })
+}
+
+fn xor_words(net: &mut Builder, x: Word, y: Word) -> Word {
+    std::array::from_fn(|bit| net.xor(x[bit], y[bit]))
+}
+
+// Small sigma of the message schedule: two rotations and a shift
+fn sigma(net: &mut Builder, x: Word, rotations: [usize; 2], shift: usize) -> Word {
+    let shifted = shift_right(net, x, shift);
+    let rotated = xor_words(net, rotate_right(x, rotations[0]), rotate_right(x, rotations[1]));
+    xor_words(net, rotated, shifted)
+}
+
+fn big_sigma(net: &mut Builder, x: Word, rotations: [usize; 3]) -> Word {
+    let rotated = xor_words(net, rotate_right(x, rotations[0]), rotate_right(x, rotations[1]));
+    xor_words(net, rotated, rotate_right(x, rotations[2]))
+}
+
+fn maj(net: &mut Builder, x: Word, y: Word, z: Word) -> Word {
+    std::array::from_fn(|bit| {
+        let either = net.xor(y[bit], z[bit]);
+        let left = net.and(x[bit], either);
+        let both = net.and(y[bit], z[bit]);
+        net.xor(left, both)
+    })
+}
+
+// Carry-save adder: x + y + z = sum + carry
+fn csa(net: &mut Builder, x: Word, y: Word, z: Word) -> (Word, Word) {
+    let sum: Word = std::array::from_fn(|bit| {
+        let yz = net.xor(y[bit], z[bit]);
+        net.xor(x[bit], yz)
+    });
+    let majority = maj(net, x, y, z);
+    let zero = net.constant(false);
+    let carry = std::array::from_fn(|bit| if bit == 31 { zero } else { majority[bit + 1] });
+
+    (sum, carry)
+}
+
+// Parallel prefix adder modulo 2^32
+fn add(net: &mut Builder, x: Word, y: Word, adder: Adder) -> Word {
+    // Least significant bit first from here on
+    let propagate: Vec<Wire> = (0..32).map(|i| net.xor(x[31 - i], y[31 - i])).collect();
+    let mut generate: Vec<Wire> = (0..32).map(|i| net.and(x[31 - i], y[31 - i])).collect();
+    let mut transmit = propagate.clone();
+
+    // Combines the (generate, transmit) pair of the span ending at i with the one of the span ending at j just below it.
+    // The span ending at the top bit is never combined, since the carry out of the adder is dropped
+    let mut combine = |i: usize, j: usize, with_transmit: bool| {
+        if i == 31 {
+            return;
+        }
+        let carried = net.and(transmit[i], generate[j]);
+        generate[i] = net.or(generate[i], carried);
+        if with_transmit {
+            transmit[i] = net.and(transmit[i], transmit[j]);
+        }
+    };
+
+    match adder {
+        Adder::BrentKung => {
+            let mut d = 1;
+            while d < 32 {
+                for i in (2 * d - 1..32).step_by(2 * d) {
+                    combine(i, i - d, true);
+                }
+                d *= 2;
+            }
+            d = 8;
+            while d >= 1 {
+                for i in (3 * d - 1..32).step_by(2 * d) {
+                    combine(i, i - d, false);
+                }
+                d /= 2;
+            }
+        }
+        Adder::LadnerFischer => {
+            let mut d = 1;
+            while d < 32 {
+                for i in (0..32).filter(|i| i & d != 0) {
+                    let j = (i & !(2 * d - 1)) + d - 1;
+                    combine(i, j, 2 * d < 32);
+                }
+                d *= 2;
+            }
+        }
+    }
+
+    // The carry into bit i is the generate of the span below it
+    let mut sum = [0; 32];
+    sum[31] = propagate[0];
+    for i in 1..32 {
+        sum[31 - i] = net.xor(propagate[i], generate[i - 1]);
+    }
+
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+    use crate::algorithm::Algorithm;
+    use crate::boolean_ops::bootstrapped_gates;
+    use crate::padding::pad_sha256_data;
+    use crate::test_keys::test_keys;
+
+    fn bits_to_bytes(bits: &[bool]) -> Vec<u8> {
+        bits.chunks(8).map(|byte| byte.iter().fold(0, |value, &bit| (value << 1) | bit as u8)).collect()
+    }
+
+    #[test]
+    fn test_sha256_netlist() {
+        for adder in [Adder::BrentKung, Adder::LadnerFischer] {
+            for message in [&b""[..], b"abc", &[0x5a; 64], &[0xa5; 130]] {
+                let padded_input = pad_sha256_data(message);
+                let netlist = sha256_netlist(padded_input.len() / 512, adder);
+
+                assert_eq!(bits_to_bytes(&netlist.evaluate(&padded_input)), Sha256::digest(message).to_vec());
+            }
+        }
+
+        // Gates with a constant operand (the round constants, the IV of the first block) are still in the netlist
+        let netlist = sha256_netlist(1, Adder::default());
+        assert_eq!(netlist.inputs, 512);
+        assert_eq!(netlist.outputs.len(), 256);
+        assert!(netlist.bootstrapped_gates() as u64 > Algorithm::Sha256.bootstrapped_gates_per_block());
+        assert!(netlist.depth() < netlist.bootstrapped_gates() / 50);
+        assert!(netlist.validate().is_ok());
+
+        // A gate using itself, an input that doesn't exist and an output that isn't a wire
+        let malformed = |gate, output| Netlist { inputs: 1, gates: vec![Gate::Input(0), gate], outputs: vec![output] };
+        assert!(malformed(Gate::Not(0), 1).validate().is_ok());
+        assert!(malformed(Gate::Not(1), 1).validate().is_err());
+        assert!(malformed(Gate::Input(1), 1).validate().is_err());
+        assert!(malformed(Gate::Not(0), 2).validate().is_err());
+    }
+
+    #[test]
+    fn test_execute() {
+        let (ck, sk) = test_keys();
+
+        // (x & y) ^ !z, and a mux of it, with one constant wire and one dead gate
+        let mut b = Builder::new(3);
+        let (x, y, z) = (b.input(0), b.input(1), b.input(2));
+        let and = b.and(x, y);
+        let not = b.not(z);
+        let xor = b.xor(and, not);
+        let one = b.constant(true);
+        b.or(xor, one);
+        let mux = b.mux(z, xor, x);
+        let netlist = b.finish(vec![xor, mux, one]);
+        assert_eq!(netlist.depth(), 3);
+
+        for input in 0..8 {
+            let bits = [input & 4 != 0, input & 2 != 0, input & 1 != 0];
+            let encrypted: Vec<Ciphertext> = bits.iter().map(|&bit| ck.encrypt(bit)).collect();
+
+            let output: Vec<bool> = netlist.execute(&encrypted, &sk).iter().map(|bit| ck.decrypt(bit)).collect();
+            assert_eq!(output, netlist.evaluate(&bits));
+        }
+
+        // The OR with a constant is counted in the netlist, but takes no bootstrapping when executed
+        let encrypted: Vec<Ciphertext> = [true, false, true].iter().map(|&bit| ck.encrypt(bit)).collect();
+        let before = bootstrapped_gates();
+        netlist.execute(&encrypted, &sk);
+        assert_eq!(netlist.bootstrapped_gates(), 4);
+        assert!(bootstrapped_gates() - before >= 3);
+    }
+
+    #[test]
+    fn test_optimize() {
+        let message = [0x3c; 100];
+        let padded_input = pad_sha256_data(&message);
+        let netlist = sha256_netlist(2, Adder::default());
+        let passes = [
+            Optimizations { constants: true, ..Optimizations::NONE },
+            Optimizations { cse: true, ..Optimizations::NONE },
+            Optimizations { dead_gates: true, ..Optimizations::NONE },
+            Optimizations::ALL,
+        ];
+
+        assert!(netlist.optimize(Optimizations::NONE) == netlist);
+        for optimizations in passes {
+            let optimized = netlist.optimize(optimizations);
+            assert!(optimized.bootstrapped_gates() < netlist.bootstrapped_gates(), "{:?}", optimizations);
+            assert_eq!(bits_to_bytes(&optimized.evaluate(&padded_input)), Sha256::digest(message).to_vec());
+        }
+
+        // The folded IV and round constants and the XORs shared by the sum and the carry of the carry-save adders make
+        // the optimized netlist smaller than the hand-written circuit
+        let optimized = sha256_netlist(1, Adder::default()).optimize(Optimizations::ALL);
+        assert!((optimized.bootstrapped_gates() as u64) < Algorithm::Sha256.bootstrapped_gates_per_block());
+        assert!(optimized.optimize(Optimizations::ALL) == optimized);
+    }
+
+    #[test]
+    fn test_optimize_gates() {
+        let mut b = Builder::new(2);
+        let (x, y) = (b.input(0), b.input(1));
+        let (zero, one) = (b.constant(false), b.constant(true));
+        let and = b.and(x, y);
+        let same = b.and(y, x);
+        let flipped = b.xor(same, one);
+        let not = b.not(flipped);
+        let kept = b.or(x, zero);
+        let mux = b.mux(kept, one, zero);
+        let xor = b.xor(and, not);
+        b.xor(x, y);
+        let netlist = b.finish(vec![flipped, mux, xor]);
+
+        // !(x & y), x and 0 once folded and merged, without the dead XOR
+        let optimized = netlist.optimize(Optimizations::ALL);
+        assert_eq!(optimized.gates, [Gate::Input(0), Gate::Input(1), Gate::Constant(false), Gate::And(0, 1), Gate::Not(3)]);
+        assert_eq!(optimized.outputs, [4, 0, 2]);
+        for input in 0..4 {
+            let bits = [input & 2 != 0, input & 1 != 0];
+            assert_eq!(optimized.evaluate(&bits), netlist.evaluate(&bits));
+        }
+    }
+
+    #[test]
+    fn test_known_inputs() {
+        let padded_input = pad_sha256_data(b"abc");
+        let netlist = sha256_netlist(1, Adder::default()).optimize(Optimizations::ALL);
+
+        // A public input folds the whole hash into constants
+        let known: Vec<Option<bool>> = padded_input.iter().map(|&bit| Some(bit)).collect();
+        let constant = netlist.with_known_inputs(&known).optimize(Optimizations::ALL);
+        assert_eq!(constant.bootstrapped_gates(), 0);
+        assert_eq!(bits_to_bytes(&constant.evaluate(&[false; 512])), Sha256::digest(b"abc").to_vec());
+
+        // Knowing the padding of a 3-byte message saves the gates that only depend on it
+        let known: Vec<Option<bool>> = padded_input.iter().enumerate().map(|(i, &bit)| (i >= 24).then_some(bit)).collect();
+        let padded = netlist.with_known_inputs(&known).optimize(Optimizations::ALL);
+        assert!(padded.bootstrapped_gates() < netlist.bootstrapped_gates());
+        assert_eq!(bits_to_bytes(&padded.evaluate(&padded_input)), Sha256::digest(b"abc").to_vec());
+    }
+
+    #[test]
+    fn test_sha256_fhe_netlist() {
+        let (ck, sk) = test_keys();
+        let padded_input: Vec<Ciphertext> = pad_sha256_data(b"abc").iter().map(|&bit| sk.trivial_encrypt(bit)).collect();
+
+        let digest: Vec<bool> = sha256_fhe_netlist(padded_input, &sk).iter().map(|bit| ck.decrypt(bit)).collect();
+        assert_eq!(bits_to_bytes(&digest), Sha256::digest(b"abc").to_vec());
+    }
+}
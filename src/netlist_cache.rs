@@ -0,0 +1,217 @@
+// This module caches optimized netlists on disk, so that a server building netlists for its jobs only pays for building
+// and optimizing each configuration once, rather than for every job. A configuration is the algorithm, the block count,
+// the adder and the optimization passes, which make up the file name of its netlist along with a checksum of the code
+// that generates the circuits, so that a netlist cached before the circuit changed is never used.
+//
+// As with the key cache, netlists are written to a temporary file and renamed, so that concurrent processes never read
+// a partial one. The directory is created private to the user, but a cached netlist is still checked before it's used:
+// it must be well formed (executing a malformed one would panic), have the inputs and outputs of the configuration and
+// hash a test message correctly. A cached netlist that can't be read or fails these checks is built again and replaces
+// it.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+#[cfg(unix)]
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use crate::algorithm::Algorithm;
+use crate::netlist::{Adder, Netlist, Optimizations};
+use crate::parameters::ParameterSet;
+use crate::serialization::{deserialize_from, serialize_into, PayloadKind, SerializationError};
+use crate::sha256::sha256_plain_reduced;
+
+// Code that generates the circuits, whose checksum is part of the file names
+const CIRCUIT_SOURCES: [&str; 2] = [include_str!("netlist.rs"), include_str!("sha256.rs")];
+
+// Netlists don't depend on the parameter set, so they are all stored with the default one in their header, which still
+// ties them to the crate version that built them
+const HEADER_PARAMETER_SET: ParameterSet = ParameterSet::Default;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NetlistConfig {
+    pub algorithm: Algorithm,
+    pub blocks: usize,
+    pub adder: Adder,
+    pub optimizations: Optimizations,
+}
+
+impl NetlistConfig {
+    // Configuration with the adder of this build and every optimization pass
+    pub fn new(algorithm: Algorithm, blocks: usize) -> Self {
+        NetlistConfig { algorithm, blocks, adder: Adder::default(), optimizations: Optimizations::ALL }
+    }
+
+    // Optimized netlist of the configuration, or None if the algorithm has no netlist
+    pub fn build(&self) -> Option<Netlist> {
+        Some(self.algorithm.netlist(self.blocks, self.adder)?.optimize(self.optimizations))
+    }
+
+    fn file_name(&self) -> String {
+        let adder = match self.adder {
+            Adder::BrentKung => "brent-kung",
+            Adder::LadnerFischer => "ladner-fischer",
+        };
+        let Optimizations { constants, cse, dead_gates } = self.optimizations;
+        let passes: Vec<&str> = [(constants, "constants"), (cse, "cse"), (dead_gates, "dead-gates")]
+            .into_iter()
+            .filter_map(|(enabled, pass)| enabled.then_some(pass))
+            .collect();
+        let passes = if passes.is_empty() { "unoptimized".to_string() } else { passes.join("-") };
+
+        let mut circuit = crc32fast::Hasher::new();
+        for source in CIRCUIT_SOURCES {
+            circuit.update(source.as_bytes());
+        }
+
+        format!(
+            "{}_{}_blocks_{}_{}_{:08x}_{}",
+            self.algorithm,
+            self.blocks,
+            adder,
+            passes,
+            circuit.finalize(),
+            PayloadKind::Netlist.file_name(),
+        )
+    }
+
+    // Checks that a netlist read from the cache is one of this configuration
+    fn check(&self, netlist: &Netlist) -> Result<(), String> {
+        netlist.validate()?;
+
+        let (inputs, outputs) = (self.algorithm.block_bits() * self.blocks, self.algorithm.digest_bits());
+        if netlist.inputs != inputs || netlist.outputs.len() != outputs {
+            let found = (netlist.inputs, netlist.outputs.len());
+            return Err(format!("{:?} inputs and outputs instead of {:?}", found, (inputs, outputs)));
+        }
+
+        // A message that fills the last block up to the padding
+        let message_len = (self.algorithm.block_bits() / 8 * self.blocks).saturating_sub(9);
+        let message: Vec<u8> = (0..message_len).map(|i| i as u8).collect();
+        let padded_input = self.algorithm.pad(&message);
+        let expected = match self.algorithm {
+            Algorithm::Sha256 => sha256_plain_reduced(&padded_input, 64),
+            Algorithm::Sm3 | Algorithm::Streebog256 | Algorithm::Streebog512 => {
+                return Err(format!("{} has no netlist", self.algorithm));
+            }
+        };
+        if padded_input.len() != inputs || netlist.evaluate(&padded_input) != expected {
+            return Err("wrong digest of the test message".to_string());
+        }
+        Ok(())
+    }
+}
+
+pub struct NetlistCache {
+    dir: PathBuf,
+}
+
+impl NetlistCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        NetlistCache { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // Netlist of the configuration, read from the cache, or built and cached if it isn't there yet
+    pub fn netlist(&self, config: NetlistConfig) -> Result<Netlist, SerializationError> {
+        let path = self.path(config);
+        let cached = File::open(&path).map_err(SerializationError::from).and_then(|file| {
+            deserialize_from(BufReader::new(file), PayloadKind::Netlist, HEADER_PARAMETER_SET)
+        });
+        if let Ok(netlist) = cached {
+            if config.check(&netlist).is_ok() {
+                return Ok(netlist);
+            }
+        }
+
+        let netlist = config.build().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Unsupported, format!("{} has no netlist", config.algorithm))
+        })?;
+        let mut dir = fs::DirBuilder::new();
+        dir.recursive(true);
+        #[cfg(unix)]
+        dir.mode(0o700);
+        dir.create(&self.dir)?;
+        let temporary = path.with_extension(format!("{}.tmp", process::id()));
+        let mut file = BufWriter::new(File::create(&temporary)?);
+        serialize_into(&mut file, PayloadKind::Netlist, &netlist, HEADER_PARAMETER_SET)?;
+        file.flush()?;
+        drop(file);
+        fs::rename(&temporary, &path)?;
+
+        Ok(netlist)
+    }
+
+    // Removes the cached netlist of the configuration, if any
+    pub fn clear(&self, config: NetlistConfig) -> Result<(), SerializationError> {
+        match fs::remove_file(self.path(config)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn path(&self, config: NetlistConfig) -> PathBuf {
+        self.dir.join(config.file_name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_netlist_cache() {
+        let dir = std::env::temp_dir().join(format!("sha256-fhe-netlist-cache-test-{}", process::id()));
+        let cache = NetlistCache::new(&dir);
+        let config = NetlistConfig { adder: Adder::BrentKung, ..NetlistConfig::new(Algorithm::Sha256, 1) };
+        let unoptimized = NetlistConfig { optimizations: Optimizations::NONE, ..config };
+        let file = dir.join(config.file_name());
+        assert!(config.file_name().starts_with("sha256_1_blocks_brent-kung_constants-cse-dead-gates_"));
+
+        // The cached netlist is the one built first
+        let netlist = cache.netlist(config).unwrap();
+        assert!(netlist == config.build().unwrap() && file.exists());
+        let built = fs::metadata(&file).unwrap().modified().unwrap();
+        assert!(cache.netlist(config).unwrap() == netlist);
+        assert_eq!(fs::metadata(&file).unwrap().modified().unwrap(), built);
+
+        // Each configuration has its own netlist
+        assert!(cache.netlist(unoptimized).unwrap() == Algorithm::Sha256.netlist(1, Adder::BrentKung).unwrap());
+        assert!(dir.join(unoptimized.file_name()).exists());
+        assert!(unoptimized.file_name().starts_with("sha256_1_blocks_brent-kung_unoptimized_"));
+
+        // A corrupted netlist is replaced
+        fs::write(&file, b"not a netlist").unwrap();
+        assert!(cache.netlist(config).unwrap() == netlist);
+        let netlist_file = BufReader::new(File::open(&file).unwrap());
+        let cached: Result<Netlist, _> = deserialize_from(netlist_file, PayloadKind::Netlist, HEADER_PARAMETER_SET);
+        assert!(cached.is_ok());
+
+        // So is a netlist that reads fine but is malformed, or isn't the one of the configuration
+        let mut malformed = netlist.clone();
+        malformed.gates[600] = crate::netlist::Gate::Not(700);
+        let wrong = NetlistConfig { blocks: 2, ..config }.build().unwrap();
+        let mut tampered = netlist.clone();
+        let last = tampered.outputs.len() - 1;
+        tampered.outputs.swap(0, last);
+        for replaced in [malformed, wrong, tampered] {
+            assert!(config.check(&replaced).is_err());
+            let mut writer = BufWriter::new(File::create(&file).unwrap());
+            serialize_into(&mut writer, PayloadKind::Netlist, &replaced, HEADER_PARAMETER_SET).unwrap();
+            drop(writer);
+            assert!(cache.netlist(config).unwrap() == netlist);
+        }
+
+        // Algorithms without a netlist are refused
+        assert!(cache.netlist(NetlistConfig::new(Algorithm::Sm3, 1)).is_err());
+
+        cache.clear(config).unwrap();
+        cache.clear(config).unwrap();
+        assert!(!file.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
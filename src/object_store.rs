@@ -0,0 +1,411 @@
+// This module stores payloads in a bucket of S3-compatible object storage (AWS S3, MinIO, Ceph...), available with the
+// "s3" feature, so that a server can keep its encrypted inputs, checkpoints and outputs off its local disk: the
+// ciphertexts of a multi-block input take hundreds of megabytes, more than an ephemeral server disk should hold.
+//
+// Requests are plain HTTP/1.1 with path-style addressing, signed with AWS Signature Version 4 (on the clear, unlike the
+// sigv4 module, since the server owns these credentials). The payloads are encrypted anyway, and the secret access key
+// never leaves the server, but the endpoint should be reachable without crossing untrusted networks (e.g. a MinIO
+// instance or a VPC endpoint), as HTTPS isn't supported. An https:// URL is rejected rather than sent in the clear.
+//
+// Every request has a connection timeout and a timeout on each read and write, so that an endpoint that stalls fails
+// the request instead of hanging the server.
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::env;
+use sha2::{Digest, Sha256};
+use crate::sigv4::credential_scope;
+
+const SERVICE: &str = "s3";
+const DEFAULT_REGION: &str = "us-east-1";
+const MAX_ERROR_LEN: u64 = 64 * 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// Longest wait for the endpoint to accept or send more bytes, not the time a whole transfer may take
+const IO_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Credentials and location of the objects, named <prefix><name> in the bucket
+pub struct S3Store {
+    host: String,
+    bucket: String,
+    prefix: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+impl S3Store {
+    // Store at http://<host>[:<port>]/<bucket>[/<prefix>], with the given credentials
+    pub fn new(url: &str, region: &str, access_key: &str, secret_key: &str) -> io::Result<S3Store> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("invalid object storage URL {}", url));
+        if url.starts_with("https://") {
+            let message = format!("{}: HTTPS object storage isn't supported, use an http:// endpoint", url);
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+
+        let location = url.strip_prefix("http://").ok_or_else(invalid)?;
+        let (host, path) = location.split_once('/').ok_or_else(invalid)?;
+        let (bucket, prefix) = path.split_once('/').unwrap_or((path, ""));
+        if host.is_empty() || bucket.is_empty() {
+            return Err(invalid());
+        }
+        let host = if host.contains(':') { host.to_string() } else { format!("{}:80", host) };
+        let prefix = match prefix.trim_end_matches('/') {
+            "" => String::new(),
+            prefix => format!("{}/", prefix),
+        };
+
+        Ok(S3Store {
+            host,
+            bucket: bucket.to_string(),
+            prefix,
+            region: region.to_string(),
+            access_key: access_key.to_string(),
+            secret_key: secret_key.to_string(),
+        })
+    }
+
+    // Store with the credentials of the usual environment variables: AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY and
+    // AWS_REGION (us-east-1 if unset, which MinIO accepts)
+    pub fn from_env(url: &str) -> io::Result<S3Store> {
+        let var = |name: &str| {
+            env::var(name).map_err(|_| io::Error::new(io::ErrorKind::NotFound, format!("{} isn't set", name)))
+        };
+        let region = env::var("AWS_REGION").unwrap_or_else(|_| DEFAULT_REGION.to_string());
+
+        // The URL is checked first, so that a wrong URL isn't reported as missing credentials
+        let store = S3Store::new(url, &region, "", "")?;
+        Ok(S3Store { access_key: var("AWS_ACCESS_KEY_ID")?, secret_key: var("AWS_SECRET_ACCESS_KEY")?, ..store })
+    }
+
+    // Contents of an object, or None if it doesn't exist. Objects larger than max_len bytes are rejected from their
+    // declared length, before they are read
+    pub fn get(&self, name: &str, max_len: u64) -> io::Result<Option<Vec<u8>>> {
+        let response = self.request("GET", name, &[], max_len)?;
+        match response.status {
+            200 => Ok(Some(response.body)),
+            404 => Ok(None),
+            _ => Err(response.error("GET", name)),
+        }
+    }
+
+    // Creates or replaces an object. Objects are replaced atomically, so readers never see a partial write
+    pub fn put(&self, name: &str, contents: &[u8]) -> io::Result<()> {
+        let response = self.request("PUT", name, contents, 0)?;
+        match response.status {
+            200 => Ok(()),
+            _ => Err(response.error("PUT", name)),
+        }
+    }
+
+    // Deletes an object, if it exists
+    pub fn delete(&self, name: &str) -> io::Result<()> {
+        let response = self.request("DELETE", name, &[], 0)?;
+        match response.status {
+            200 | 204 | 404 => Ok(()),
+            _ => Err(response.error("DELETE", name)),
+        }
+    }
+
+    fn request(&self, method: &str, name: &str, body: &[u8], max_len: u64) -> io::Result<Response> {
+        let path = format!("/{}/{}", uri_encode(&self.bucket), uri_encode(&format!("{}{}", self.prefix, name)));
+        let timestamp = timestamp(SystemTime::now());
+        let payload_hash = hex(&Sha256::digest(body));
+
+        // Only the headers that identify the request are signed
+        let headers = [("host", self.host.as_str()), ("x-amz-content-sha256", &payload_hash), ("x-amz-date", &timestamp)];
+        let canonical = canonical_request(method, &path, &headers, &payload_hash);
+        let signature = signature(self.secret_key.as_bytes(), &timestamp, &self.region, SERVICE, &canonical);
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key,
+            credential_scope(&timestamp[..8], &self.region, SERVICE),
+            signed_headers(&headers),
+            signature,
+        );
+
+        let mut stream = self.connect()?;
+        let mut request = format!("{} {} HTTP/1.1\r\n", method, path);
+        for (name, value) in headers.iter().chain(&[("authorization", authorization.as_str())]) {
+            request += &format!("{}: {}\r\n", name, value);
+        }
+        request += &format!("content-length: {}\r\nconnection: close\r\n\r\n", body.len());
+        stream.write_all(request.as_bytes())?;
+        stream.write_all(body)?;
+        stream.flush()?;
+
+        Response::read(BufReader::new(stream), max_len)
+    }
+
+    // Connection to the first address of the host that accepts it in time
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut last_error = io::Error::new(io::ErrorKind::NotFound, format!("{} has no address", self.host));
+        for address in self.host.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+                    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+                    return Ok(stream);
+                }
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+}
+
+impl fmt::Display for S3Store {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "http://{}/{}/{}", self.host, self.bucket, self.prefix)
+    }
+}
+
+struct Response {
+    status: u16,
+    body: Vec<u8>,
+}
+
+impl Response {
+    // Reads a response whose body, if the request succeeded, can't be larger than max_len bytes
+    fn read(mut reader: impl BufRead, max_len: u64) -> io::Result<Response> {
+        let invalid = |error: &str| io::Error::new(io::ErrorKind::InvalidData, format!("invalid response: {}", error));
+
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let status = line.split(' ').nth(1).and_then(|status| status.parse().ok()).ok_or_else(|| invalid("status line"))?;
+
+        // Error responses are short XML documents
+        let max_len = if status == 200 { max_len } else { MAX_ERROR_LEN };
+        let too_large = || io::Error::new(io::ErrorKind::InvalidData, format!("response larger than {} bytes", max_len));
+
+        let (mut content_length, mut chunked) = (None, false);
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(invalid("truncated headers"));
+            }
+            let Some((name, value)) = line.trim_end().split_once(':') else { break };
+            match name.to_ascii_lowercase().as_str() {
+                "content-length" => content_length = Some(value.trim().parse::<u64>().map_err(|_| invalid("length"))?),
+                "transfer-encoding" => chunked = value.trim().eq_ignore_ascii_case("chunked"),
+                _ => {}
+            }
+        }
+
+        let mut body = vec![];
+        if chunked {
+            loop {
+                line.clear();
+                reader.read_line(&mut line)?;
+                let size = u64::from_str_radix(line.trim_end().split(';').next().unwrap_or(""), 16)
+                    .map_err(|_| invalid("chunk size"))?;
+                if size == 0 {
+                    break;
+                }
+                if body.len() as u64 + size > max_len {
+                    return Err(too_large());
+                }
+                (&mut reader).take(size).read_to_end(&mut body)?;
+                line.clear();
+                reader.read_line(&mut line)?;
+            }
+        } else {
+            if content_length.is_some_and(|len| len > max_len) {
+                return Err(too_large());
+            }
+            reader.take(content_length.unwrap_or(max_len + 1)).read_to_end(&mut body)?;
+            if body.len() as u64 > max_len {
+                return Err(too_large());
+            }
+        }
+
+        Ok(Response { status, body })
+    }
+
+    fn error(&self, method: &str, name: &str) -> io::Error {
+        let message = String::from_utf8_lossy(&self.body);
+        io::Error::other(format!("{} {} failed with status {}: {}", method, name, self.status, message.trim()))
+    }
+}
+
+// Canonical request of SigV4 for a request without a query string. The headers must be sorted by (lowercase) name
+fn canonical_request(method: &str, path: &str, headers: &[(&str, &str)], payload_hash: &str) -> String {
+    let canonical_headers: String = headers.iter().map(|(name, value)| format!("{}:{}\n", name, value.trim())).collect();
+
+    format!("{}\n{}\n\n{}\n{}\n{}", method, path, canonical_headers, signed_headers(headers), payload_hash)
+}
+
+fn signed_headers(headers: &[(&str, &str)]) -> String {
+    headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";")
+}
+
+// Hex signature of a canonical request sent at a timestamp (YYYYMMDDTHHMMSSZ)
+fn signature(secret_key: &[u8], timestamp: &str, region: &str, service: &str, canonical_request: &str) -> String {
+    let scope = credential_scope(&timestamp[..8], region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        timestamp,
+        scope,
+        hex(&Sha256::digest(canonical_request.as_bytes())),
+    );
+
+    let key = [&timestamp[..8], region, service, "aws4_request"]
+        .iter()
+        .fold([b"AWS4", secret_key].concat(), |key, part| hmac_sha256(&key, part.as_bytes()).to_vec());
+
+    hex(&hmac_sha256(&key, string_to_sign.as_bytes()))
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0; 64];
+    if key.len() > 64 {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let inner = Sha256::new().chain_update(block.map(|byte| byte ^ 0x36)).chain_update(message).finalize();
+    Sha256::new().chain_update(block.map(|byte| byte ^ 0x5c)).chain_update(inner).finalize().into()
+}
+
+// Percent-encodes everything but the unreserved characters and the path separators, as SigV4 expects
+fn uri_encode(path: &str) -> String {
+    path.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// UTC timestamp in the basic ISO 8601 format of SigV4 (YYYYMMDDTHHMMSSZ)
+fn timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    let (days, time) = (seconds / 86400, seconds % 86400);
+
+    // Civil date from the day count, shifting the year to start in March so that leap days come last
+    let days = days + 719468;
+    let (era, day_of_era) = (days / 146097, days % 146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = era * 400 + year_of_era + (month <= 2) as u64;
+
+    format!("{:04}{:02}{:02}T{:02}{:02}{:02}Z", year, month, day, time / 3600, time / 60 % 60, time % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    // get-vanilla case of the AWS SigV4 test suite, as in the sigv4 module
+    #[test]
+    fn test_signature() {
+        let timestamp = timestamp(UNIX_EPOCH + Duration::from_secs(1440938160));
+        assert_eq!(timestamp, "20150830T123600Z");
+
+        let headers = [("host", "example.amazonaws.com"), ("x-amz-date", timestamp.as_str())];
+        let payload_hash = hex(&Sha256::digest(b""));
+        let canonical = canonical_request("GET", "/", &headers, &payload_hash);
+        assert_eq!(signed_headers(&headers), "host;x-amz-date");
+
+        let secret = b"wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+        assert_eq!(
+            signature(secret, &timestamp, "us-east-1", "service", &canonical),
+            "5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31",
+        );
+    }
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(UNIX_EPOCH), "19700101T000000Z");
+        // A leap day in a century year, and the last second of the century
+        assert_eq!(timestamp(UNIX_EPOCH + Duration::from_secs(951782400)), "20000229T000000Z");
+        assert_eq!(timestamp(UNIX_EPOCH + Duration::from_secs(4102444799)), "20991231T235959Z");
+    }
+
+    #[test]
+    fn test_url() {
+        let store = S3Store::new("http://localhost:9000/bucket/jobs/42/", "us-east-1", "key", "secret").unwrap();
+        assert_eq!(store.to_string(), "http://localhost:9000/bucket/jobs/42/");
+        assert_eq!(S3Store::new("http://minio/bucket", "us-east-1", "key", "secret").unwrap().to_string(), "http://minio:80/bucket/");
+
+        for url in ["https://host/bucket", "http://host", "http://host/", "http:///bucket"] {
+            assert!(S3Store::new(url, "us-east-1", "key", "secret").is_err(), "{}", url);
+        }
+        let https = S3Store::new("https://host/bucket", "us-east-1", "key", "secret");
+        assert!(https.err().unwrap().to_string().contains("HTTPS object storage isn't supported"));
+        assert_eq!(uri_encode("bucket/a b+c.bin"), "bucket/a%20b%2Bc.bin");
+    }
+
+    // Minimal object storage, keeping the objects of the requests it accepts in memory
+    fn serve(listener: TcpListener, requests: usize) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            let mut objects: HashMap<String, Vec<u8>> = HashMap::new();
+            for connection in listener.incoming().take(requests) {
+                let mut reader = BufReader::new(connection.unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let (method, path) = request_line.split_once(' ').unwrap();
+                let path = path.split(' ').next().unwrap().to_string();
+
+                let mut headers = HashMap::new();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    let (name, value) = line.trim_end().split_once(": ").unwrap();
+                    headers.insert(name.to_string(), value.to_string());
+                    line.clear();
+                }
+                let mut body = vec![0; headers["content-length"].parse().unwrap()];
+                reader.read_exact(&mut body).unwrap();
+                assert!(headers["authorization"].starts_with("AWS4-HMAC-SHA256 Credential=key/"));
+                assert_eq!(headers["x-amz-content-sha256"], hex(&Sha256::digest(&body)));
+
+                let (status, body) = match method {
+                    "PUT" => {
+                        objects.insert(path, body);
+                        (200, vec![])
+                    }
+                    "GET" => objects.get(&path).map_or((404, b"<Error>NoSuchKey</Error>".to_vec()), |body| (200, body.clone())),
+                    "DELETE" => {
+                        objects.remove(&path);
+                        (204, vec![])
+                    }
+                    _ => (405, vec![]),
+                };
+                // The client may hang up without reading a body that is too large
+                let response = format!("HTTP/1.1 {} Status\r\nContent-Length: {}\r\n\r\n", status, body.len());
+                let _ = reader.into_inner().write_all(&[response.as_bytes(), &body].concat());
+            }
+        })
+    }
+
+    #[test]
+    fn test_store() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/bucket/job", listener.local_addr().unwrap());
+        let server = serve(listener, 7);
+        let store = S3Store::new(&url, "us-east-1", "key", "secret").unwrap();
+
+        assert_eq!(store.get("input.bin", 1024).unwrap(), None);
+        store.put("input.bin", b"ciphertexts").unwrap();
+        assert_eq!(store.get("input.bin", 1024).unwrap().unwrap(), b"ciphertexts");
+        assert!(store.get("input.bin", 4).is_err());
+
+        store.put("input.bin", b"other ciphertexts").unwrap();
+        store.delete("input.bin").unwrap();
+        assert_eq!(store.get("input.bin", 1024).unwrap(), None);
+        server.join().unwrap();
+    }
+}
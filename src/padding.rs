@@ -1,14 +1,25 @@
 // This module contains the padding function, which is computed by the client over the plain text.
-// The function returns the padded data as a vector of bools, for later encryption. Note that padding
-// could also be performed by the server, by appending trivially encrypted bools. However, in our
-// implementation, the exact length of the pre-image (hashed message) is not revealed.
+// The function returns the padded data as a vector of bools, for later encryption. This way the exact
+// length of the pre-image (hashed message) is not revealed, only its number of blocks. When the length
+// can be public, the client may instead send just the message bits (message_bits) and let the server
+// append the padding as trivially encrypted bools (sha256::pad_encrypted), which shrinks the upload.
+//
+// Streebog has its own padding (pad_streebog_data), which puts the message length in a block of its own.
+//
+// SHA-256 is defined for messages of any length in bits, so the padding is also available for messages that aren't a
+// whole number of bytes (pad_sha256_bits), such as protocol fields packed at the bit level.
 
 // If input starts with "0x" and following characters are valid hexadecimal values, it's interpreted
 // as hex, otherwise input is interpreted as text
 pub fn pad_sha256_input(input: &str) -> Vec<bool> {
-    let bytes = if input.starts_with("0x") && is_valid_hex(&input[2..]) {
+    pad_sha256_data(&input_bytes(input))
+}
+
+// Bytes of an input, with the same hex or text interpretation as pad_sha256_input
+pub fn input_bytes(input: &str) -> Vec<u8> {
+    if input.starts_with("0x") && is_valid_hex(&input[2..]) {
         let no_prefix = &input[2..];
-        let hex_input = if no_prefix.len() % 2 == 0 { // hex value can be converted to bytes
+        let hex_input = if no_prefix.len().is_multiple_of(2) { // hex value can be converted to bytes
             no_prefix.to_string()
         } else {
             format!("0{}", no_prefix) // pad hex value to ensure a correct conversion to bytes
@@ -20,36 +31,123 @@ pub fn pad_sha256_input(input: &str) -> Vec<bool> {
             .collect::<Vec<u8>>()
     } else {
         input.as_bytes().to_vec()
-    };
+    }
+}
+
+// Length in bits of the padded input for a message of message_len bytes (always a multiple of 512)
+pub fn padded_len(message_len: usize) -> usize {
+    padded_len_bits(message_len * 8)
+}
 
-    pad_sha256_data(&bytes)
+// Length in bits of the padded input for a message of message_len bits
+pub fn padded_len_bits(message_len: usize) -> usize {
+    MdPadding::SHA256.padded_len(message_len)
 }
 
 fn is_valid_hex(hex: &str) -> bool {
-    hex.chars().all(|c| c.is_digit(16))
+    hex.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// Unpadded message bits, most significant bit of each byte first
+pub fn message_bits(data: &[u8]) -> Vec<bool> {
+    data.iter().flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1)).collect()
+}
+
+pub fn pad_sha256_data(data: &[u8]) -> Vec<bool> {
+    pad_sha256_bits(&message_bits(data))
+}
+
+// Padded input of a message of any length in bits
+pub fn pad_sha256_bits(message: &[bool]) -> Vec<bool> {
+    MdPadding::SHA256.pad(message)
 }
 
-fn pad_sha256_data(data: &[u8]) -> Vec<bool> {
-    let mut bits: Vec<bool> = data.iter().flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1)).collect();
+// Padded Streebog input: the full blocks of the message, the rest of it followed by a 1 byte and zeros up to a whole
+// block, and a block with the message length in bits as a little-endian number (see streebog::streebog_fhe)
+pub fn pad_streebog_data(data: &[u8]) -> Vec<bool> {
+    let full_blocks_len = data.len() / 64 * 64;
+    let mut padded = data.to_vec();
+    padded.push(0x01);
+    padded.resize(full_blocks_len + 64, 0);
+    padded.extend(((data.len() * 8) as u64).to_le_bytes());
+    padded.resize(full_blocks_len + 128, 0);
 
-    // Append a single '1' bit
-    bits.push(true);
+    message_bits(&padded)
+}
 
-    // Calculate the number of padding zeros required
-    let padding_zeros = (512 - ((bits.len() + 64) % 512)) % 512;
-    bits.extend(std::iter::repeat(false).take(padding_zeros));
+// Length in bits of the padded Streebog input for a message of message_len bytes
+pub fn streebog_padded_len(message_len: usize) -> usize {
+    (message_len / 64 + 2) * 512
+}
 
-    // Append a 64-bit big-endian representation of the original message length
-    let data_len_bits = (data.len() as u64) * 8;
-    bits.extend((0..64).rev().map(|i| (data_len_bits >> i) & 1 == 1));
+// Byte order of the length field (MD5 and RIPEMD use little-endian, the SHA family big-endian). Bits within each byte
+// are always most significant first
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endianness {
+    Big,
+    Little,
+}
 
-    bits
+// Merkle–Damgård strengthening of a hash: a 1 bit, zeros up to the length field, and the message length in bits, in a
+// field of length_bits bits (a whole number of bytes, at most 128) at the end of the last block. The server side engine
+// (merkle_damgard) pads with the same parameters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MdPadding {
+    pub block_bits: usize,
+    pub length_bits: usize,
+    pub length_endianness: Endianness,
+}
+
+impl MdPadding {
+    pub const SHA1: MdPadding = MdPadding { block_bits: 512, length_bits: 64, length_endianness: Endianness::Big };
+    pub const SHA256: MdPadding = MdPadding { block_bits: 512, length_bits: 64, length_endianness: Endianness::Big };
+    pub const SHA512: MdPadding = MdPadding { block_bits: 1024, length_bits: 128, length_endianness: Endianness::Big };
+    pub const MD5: MdPadding = MdPadding { block_bits: 512, length_bits: 64, length_endianness: Endianness::Little };
+
+    // Padding bits that follow a message of message_len bits
+    pub fn padding(&self, message_len: u64) -> Vec<bool> {
+        assert!(self.length_bits <= 128 && self.length_bits.is_multiple_of(8), "unsupported length field");
+        assert!(self.block_bits > self.length_bits, "length field doesn't fit in a block");
+
+        let zeros = self.padded_len(message_len as usize) - message_len as usize - 1 - self.length_bits;
+        let mut bits = Vec::with_capacity(1 + zeros + self.length_bits);
+        bits.push(true);
+        bits.extend(std::iter::repeat_n(false, zeros));
+
+        // The length is reduced modulo 2^length_bits, which a u64 never reaches with the usual 64 and 128-bit fields
+        let field = (message_len as u128).to_be_bytes();
+        let mut field = field[16 - self.length_bits / 8..].to_vec();
+        if self.length_endianness == Endianness::Little {
+            field.reverse();
+        }
+        bits.extend(message_bits(&field));
+
+        bits
+    }
+
+    // Padded input of a message of any length in bits
+    pub fn pad(&self, message: &[bool]) -> Vec<bool> {
+        let mut bits = message.to_vec();
+        bits.extend(self.padding(message.len() as u64));
+        bits
+    }
+
+    // Length in bits of the padded input for a message of message_len bits (always a multiple of the block size)
+    pub fn padded_len(&self, message_len: usize) -> usize {
+        (message_len + 1 + self.length_bits).div_ceil(self.block_bits) * self.block_bits
+    }
+}
+
+// Padded inputs of several messages, in order, for batch hashing. Each message is padded on its own, so the padded
+// inputs may have different numbers of blocks
+pub fn pad_sha256_many(messages: &[&[u8]]) -> Vec<Vec<bool>> {
+    messages.iter().map(|message| pad_sha256_data(message)).collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::sha256::bools_to_hex;
+    use crate::client::bools_to_hex;
 
     #[test]
     fn test_pad_sha256_input() {
@@ -64,4 +162,67 @@ mod tests {
 
         assert_eq!(hex_result, expected_output);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_padded_len() {
+        for message_len in [0, 1, 55, 56, 63, 64, 119, 120, 1000] {
+            assert_eq!(padded_len(message_len), pad_sha256_data(&vec![0u8; message_len]).len());
+        }
+    }
+
+    #[test]
+    fn test_pad_sha256_bits() {
+        for len in [0, 1, 5, 447, 448, 511, 512, 700] {
+            let message: Vec<bool> = (0..len).map(|i| i % 3 == 1).collect();
+            let padded = pad_sha256_bits(&message);
+
+            assert_eq!(padded.len(), padded_len_bits(len));
+            assert_eq!(padded[..len], message);
+            assert!(padded[len]);
+            assert!(padded[len + 1..padded.len() - 64].iter().all(|bit| !bit));
+
+            let length_field = padded[padded.len() - 64..].iter().fold(0u64, |acc, bit| (acc << 1) | *bit as u64);
+            assert_eq!(length_field, len as u64);
+        }
+
+        // Whole bytes are padded like pad_sha256_data
+        assert_eq!(pad_sha256_bits(&message_bits(b"abc")), pad_sha256_data(b"abc"));
+    }
+
+    #[test]
+    fn test_md_padding() {
+        // 1024-bit blocks with a 128-bit length field (SHA-512), and a little-endian 64-bit field (MD5)
+        let padding = MdPadding::SHA512.padding(24);
+        assert_eq!(padding.len(), 1000);
+        assert_eq!(padding[1000 - 128..], message_bits(&24u128.to_be_bytes()));
+
+        let padding = MdPadding::MD5.padding(8 * 300);
+        assert_eq!(padding.len(), 160);
+        assert_eq!(padding[..8], message_bits(&[0x80]));
+        assert_eq!(padding[padding.len() - 64..], message_bits(&2400u64.to_le_bytes()));
+
+        // The padding fills the last block exactly, or spills into a new one
+        for padding in [MdPadding::SHA1, MdPadding::SHA512, MdPadding::MD5] {
+            for len in [0, 1, 447, 448, 895, 896, 1023, 1024, 3000] {
+                let padded = padding.pad(&vec![true; len]);
+                assert_eq!(padded.len(), padding.padded_len(len));
+                assert_eq!(padded.len() % padding.block_bits, 0);
+                assert!(padded.len() - len > padding.length_bits);
+                assert!(padded.len() - len <= padding.block_bits + padding.length_bits);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pad_sha256_many() {
+        let messages: [&[u8]; 3] = [b"", b"abc", &[0x61; 100]];
+        let padded = pad_sha256_many(&messages);
+
+        assert_eq!(padded.len(), 3);
+        for (padded, message) in padded.iter().zip(messages) {
+            assert_eq!(*padded, pad_sha256_data(message));
+        }
+        assert_eq!(padded[2].len(), 1024);
+        assert!(pad_sha256_many(&[]).is_empty());
+    }
+}
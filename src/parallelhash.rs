@@ -0,0 +1,97 @@
+// This module implements ParallelHash128 and ParallelHash256 (NIST SP 800-185), which split an encrypted message into
+// blocks of a public length, hash every block independently with cSHAKE (as SHAKE, without strings) and then hash the
+// concatenation of their digests. The blocks are hashed in parallel on the thread pool, so a long message is no longer
+// a chain of permutations that each have to wait for the previous one, as it is with the other Keccak hashes: besides
+// the final hash, its latency is that of a single block when there are enough cores.
+
+use rayon::prelude::*;
+use tfhe::boolean::prelude::*;
+use crate::cshake::{cshake_sponge, left_encode, right_encode};
+
+// ParallelHash128 digest of the given length in bytes, over blocks of block_len bytes (the last one may be shorter),
+// with a public customization string (often empty)
+pub fn parallelhash128(
+    message: &[Ciphertext],
+    block_len: usize,
+    output_len: usize,
+    customization: &[u8],
+    sk: &ServerKey,
+) -> Vec<Ciphertext> {
+    parallelhash(message, 256, block_len, output_len, customization, sk)
+}
+
+pub fn parallelhash256(
+    message: &[Ciphertext],
+    block_len: usize,
+    output_len: usize,
+    customization: &[u8],
+    sk: &ServerKey,
+) -> Vec<Ciphertext> {
+    parallelhash(message, 512, block_len, output_len, customization, sk)
+}
+
+fn parallelhash(
+    message: &[Ciphertext],
+    capacity: usize,
+    block_len: usize,
+    output_len: usize,
+    customization: &[u8],
+    sk: &ServerKey,
+) -> Vec<Ciphertext> {
+    assert!(block_len > 0, "block length must be positive");
+    assert_eq!(message.len() % 8, 0, "message is not a whole number of bytes");
+
+    // Every block digest is as long as the capacity is in bytes, e.g. 32 bytes for ParallelHash128
+    let digests: Vec<Vec<Ciphertext>> = message
+        .par_chunks(8 * block_len)
+        .map(|block| {
+            let mut sponge = cshake_sponge(capacity, b"", b"", sk);
+            sponge.absorb(block, sk);
+            sponge.squeeze(capacity / 8, sk)
+        })
+        .collect();
+
+    let mut sponge = cshake_sponge(capacity, b"ParallelHash", customization, sk);
+    sponge.absorb_plain(&left_encode(block_len as u64), sk);
+    for digest in &digests {
+        sponge.absorb(digest, sk);
+    }
+    sponge.absorb_plain(&right_encode(digests.len() as u64), sk);
+    sponge.absorb_plain(&right_encode(8 * output_len as u64), sk);
+    sponge.squeeze(output_len, sk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boolean_ops::trivial_bytes;
+    use crate::test_helpers::{decrypt_bytes, from_hex};
+    use crate::test_keys::test_keys;
+
+    // NIST ParallelHash samples #1, #2 and #5, and a shorter last block (checked against a Python model), computed with
+    // the clear backend
+    #[test]
+    fn test_parallelhash() {
+        let (ck, sk) = test_keys();
+        let bytes: Vec<u8> = [0x00..0x08, 0x10..0x18, 0x20..0x28].into_iter().flatten().collect();
+        let message = trivial_bytes(&bytes, &sk);
+
+        assert_eq!(
+            decrypt_bytes(&parallelhash128(&message, 8, 32, b"", &sk), &ck),
+            from_hex("ba8dc1d1d979331d3f813603c67f72609ab5e44b94a0b8f9af46514454a2b4f5"),
+        );
+        assert_eq!(
+            decrypt_bytes(&parallelhash128(&message, 8, 32, b"Parallel Data", &sk), &ck),
+            from_hex("fc484dcb3f84dceedc353438151bee58157d6efed0445a81f165e495795b7206"),
+        );
+        assert_eq!(
+            decrypt_bytes(&parallelhash256(&message, 8, 64, b"Parallel Data", &sk), &ck),
+            from_hex("cdf15289b54f6212b4bc270528b49526006dd9b54e2b6add1ef6900dda3963bb33a72491f236969ca8afaea29c682d47a393c065b38e29fae651a2091c833110"),
+        );
+
+        assert_eq!(
+            decrypt_bytes(&parallelhash128(&message[..8 * 20], 8, 16, b"", &sk), &ck),
+            from_hex("d8ab96de48ad1a406b0bbda51d829f6d"),
+        );
+    }
+}
@@ -0,0 +1,92 @@
+// This module names the tfhe boolean parameter sets supported by this crate. Keys are generated from a named set,
+// so that serialized keys and ciphertexts can record which parameters they belong to. Key generation is client side
+// only, so it requires the "client" feature.
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use tfhe::boolean::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ParameterSet {
+    // tfhe's DEFAULT_PARAMETERS, the ones used by gen_keys()
+    Default,
+    // tfhe's TFHE_LIB_PARAMETERS, the original TFHE library parameters
+    TfheLib,
+}
+
+impl ParameterSet {
+    pub const ALL: &'static [ParameterSet] = &[ParameterSet::Default, ParameterSet::TfheLib];
+
+    pub fn parameters(&self) -> BooleanParameters {
+        match self {
+            ParameterSet::Default => DEFAULT_PARAMETERS,
+            ParameterSet::TfheLib => TFHE_LIB_PARAMETERS,
+        }
+    }
+
+    // Log2 of the probability that a bootstrapped gate decrypts to the wrong value, as published by tfhe for the set.
+    // A computation of n gates fails with a probability of about n times this
+    pub fn gate_failure_log2(&self) -> f64 {
+        match self {
+            ParameterSet::Default => -40.0,
+            ParameterSet::TfheLib => -165.0,
+        }
+    }
+
+    #[cfg(feature = "client")]
+    pub fn gen_keys(&self) -> (ClientKey, ServerKey) {
+        let ck = ClientKey::new(&self.parameters());
+        let sk = ServerKey::new(&ck);
+
+        (ck, sk)
+    }
+
+    // Generates the client key, then the server key on another thread while prepare runs with the client key (e.g. to
+    // pad and encrypt the input). The server key takes most of the key generation time, and tfhe doesn't expose the
+    // generation of its parts separately, so this overlaps it with the client's own work instead
+    #[cfg(feature = "client")]
+    pub fn gen_keys_with<T>(&self, prepare: impl FnOnce(&ClientKey) -> T) -> (ClientKey, ServerKey, T) {
+        let ck = ClientKey::new(&self.parameters());
+        let (sk, prepared) = std::thread::scope(|scope| {
+            let sk = scope.spawn(|| ServerKey::new(&ck));
+            let prepared = prepare(&ck);
+            (sk.join().expect("server key generation panicked"), prepared)
+        });
+
+        (ck, sk, prepared)
+    }
+
+    // Generates the server key in its compressed (seeded) form, which is much smaller to transfer to the server. It is
+    // decompressed on arrival with ServerKey::from
+    #[cfg(feature = "client")]
+    pub fn gen_keys_compressed(&self) -> (ClientKey, CompressedServerKey) {
+        let ck = ClientKey::new(&self.parameters());
+        let sk = CompressedServerKey::new(&ck);
+
+        (ck, sk)
+    }
+}
+
+impl fmt::Display for ParameterSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParameterSet::Default => write!(f, "default"),
+            ParameterSet::TfheLib => write!(f, "tfhe-lib"),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "client"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gen_keys_with() {
+        for parameter_set in ParameterSet::ALL {
+            let (ck, sk, encrypted) = parameter_set.gen_keys_with(|ck| [ck.encrypt(true), ck.encrypt(false)]);
+
+            assert!(ck.decrypt(&sk.or(&encrypted[0], &encrypted[1])));
+            assert!(!ck.decrypt(&sk.and(&encrypted[0], &encrypted[1])));
+        }
+    }
+}
@@ -0,0 +1,100 @@
+// This module verifies a chain of encrypted Bitcoin-style block headers, as a light client would: every header must hash
+// (with double sha256) to a value no greater than its target, and must commit to the hash of the previous header. The
+// server learns nothing but the number of headers, and the client decrypts a single validity bit.
+//
+// Headers are the 80-byte serializations, whose bytes 4 to 36 hold the previous header hash. The targets are 256-bit
+// encrypted numbers, most significant bit first.
+
+use rayon::prelude::*;
+use tfhe::boolean::prelude::*;
+use crate::boolean_ops::{all, eq_bits, le, reverse_bytes};
+use crate::sha256::{pad_encrypted, sha256_fhe};
+
+pub const HEADER_BITS: usize = 80 * 8;
+
+// sha256(sha256(header)), in the internal byte order (the one used by the prev-hash field)
+pub fn double_sha256(header: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    let first = sha256_fhe(pad_encrypted(header.to_vec(), sk), sk);
+    sha256_fhe(pad_encrypted(first, sk), sk)
+}
+
+// Returns an encrypted bit that is true if every header meets its target and links to the previous one. The prev-hash
+// of the first header is not checked, as it points outside of the chain
+pub fn verify_header_chain(headers: &[Vec<Ciphertext>], targets: &[[Ciphertext; 256]], sk: &ServerKey) -> Ciphertext {
+    assert_eq!(headers.len(), targets.len(), "every header needs a target");
+    assert!(headers.iter().all(|header| header.len() == HEADER_BITS), "headers are 80 bytes long");
+
+    let hashes: Vec<Vec<Ciphertext>> = headers.par_iter().map(|header| double_sha256(header, sk)).collect();
+
+    // Hashes are compared to the targets as little-endian numbers
+    let meets_target = hashes.par_iter()
+        .zip(targets.par_iter())
+        .map(|(hash, target)| {
+            let value: [Ciphertext; 256] = reverse_bytes(hash).try_into().unwrap();
+            le(&value, target, sk)
+        });
+
+    let links = hashes.par_iter()
+        .zip(headers[1..].par_iter())
+        .map(|(prev_hash, header)| eq_bits(prev_hash, &header[32..288], sk));
+
+    let checks: Vec<Ciphertext> = meets_target.chain(links).collect();
+    all(&checks, sk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_keys::test_keys;
+
+    // Bitcoin's genesis block and block 1
+    const GENESIS: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+    const BLOCK_1: &str = "010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000982051fd1e4ba744bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e61bc6649ffff001d01e36299";
+
+    // Trivially encrypts the bits of a hex string, so that the chain is verified with the clear backend
+    fn trivial_hex(hex: &str, sk: &ServerKey) -> Vec<Ciphertext> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1))
+            .map(|bit| sk.trivial_encrypt(bit))
+            .collect()
+    }
+
+    // Target of difficulty 1 (nBits = 0x1d00ffff)
+    fn difficulty_1(sk: &ServerKey) -> [Ciphertext; 256] {
+        let hex = format!("00000000ffff{}", "0".repeat(52));
+        trivial_hex(&hex, sk).try_into().unwrap()
+    }
+
+    fn decrypt(bit: &Ciphertext) -> bool {
+        match bit {
+            Ciphertext::Trivial(value) => *value,
+            Ciphertext::Encrypted(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_verify_header_chain() {
+        let (_, sk) = test_keys();
+
+        let headers = vec![trivial_hex(GENESIS, &sk), trivial_hex(BLOCK_1, &sk)];
+        let targets = vec![difficulty_1(&sk), difficulty_1(&sk)];
+
+        assert!(decrypt(&verify_header_chain(&headers, &targets, &sk)));
+
+        // The headers are valid on their own, but not linked in this order
+        let swapped = vec![headers[1].clone(), headers[0].clone()];
+        assert!(!decrypt(&verify_header_chain(&swapped, &targets, &sk)));
+
+        // Changing the nonce of block 1 breaks its proof of work
+        let mut tampered = headers.clone();
+        tampered[1][639] = sk.not(&tampered[1][639]);
+        assert!(!decrypt(&verify_header_chain(&tampered, &targets, &sk)));
+
+        let easy: [Ciphertext; 256] = trivial_hex(&format!("0001{}", "0".repeat(60)), &sk).try_into().unwrap();
+        let hard: [Ciphertext; 256] = trivial_hex(&"0".repeat(64), &sk).try_into().unwrap();
+        assert!(decrypt(&verify_header_chain(&headers[..1], &[easy], &sk)));
+        assert!(!decrypt(&verify_header_chain(&headers[..1], &[hard], &sk)));
+    }
+}
@@ -0,0 +1,297 @@
+// This module streams the progress of a hash computation to web clients as Server-Sent Events, so a web UI can show a
+// live progress bar for a computation lasting tens of minutes. A ProgressObserver turns every round into an event sent
+// over a channel, and ProgressServer broadcasts the events to every connected client. SSE is plain HTTP, so it is
+// served with the standard library alone (browsers consume it with EventSource). With the "tls" feature, the stream
+// can also be served over HTTPS.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::{io, thread};
+#[cfg(feature = "tls")]
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+use tfhe::boolean::prelude::*;
+use crate::sha256::RoundObserver;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProgressEvent {
+    pub block: usize,
+    pub round: usize,
+    pub total_blocks: usize,
+}
+
+impl ProgressEvent {
+    // Completed rounds over all the rounds of the computation
+    pub fn fraction(&self) -> f64 {
+        (64 * self.block + self.round + 1) as f64 / (64 * self.total_blocks) as f64
+    }
+
+    fn to_json(self) -> String {
+        format!(
+            "{{\"block\":{},\"round\":{},\"total_blocks\":{},\"fraction\":{:.4}}}",
+            self.block, self.round, self.total_blocks, self.fraction(),
+        )
+    }
+}
+
+// Round observer sending a progress event after every round. Events are dropped once the receiver is gone, so the
+// computation never fails because nobody listens
+pub struct ProgressObserver {
+    sender: Sender<ProgressEvent>,
+    total_blocks: usize,
+}
+
+impl ProgressObserver {
+    pub fn new(sender: Sender<ProgressEvent>, total_blocks: usize) -> Self {
+        ProgressObserver { sender, total_blocks }
+    }
+}
+
+impl RoundObserver for ProgressObserver {
+    fn after_round(&mut self, block: usize, round: usize, _state: [&[Ciphertext; 32]; 8]) {
+        let _ = self.sender.send(ProgressEvent { block, round, total_blocks: self.total_blocks });
+    }
+}
+
+// How long an event can wait for a client to read the previous ones before the client is dropped. The events are sent
+// to the clients in turn, so a stalled client delays the others by this much once
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Connection of a client to the event stream
+enum Client {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<StreamOwned<ServerConnection, TcpStream>>),
+}
+
+impl Client {
+    fn send(&mut self, message: &str) -> io::Result<()> {
+        match self {
+            Client::Plain(stream) => write_before_deadline(stream, message.as_bytes()),
+            #[cfg(feature = "tls")]
+            Client::Tls(stream) => write_before_deadline(stream, message.as_bytes()),
+        }
+    }
+
+    // Ends the stream cleanly, so that the client doesn't mistake the end for a truncation
+    fn close(self) {
+        match self {
+            Client::Plain(_) => {}
+            #[cfg(feature = "tls")]
+            Client::Tls(mut stream) => {
+                stream.conn.send_close_notify();
+                let _ = stream.flush();
+            }
+        }
+    }
+}
+
+// Broadcasts progress events to the clients connected to an address
+pub struct ProgressServer {
+    address: SocketAddr,
+    clients: Arc<Mutex<Vec<Client>>>,
+}
+
+impl ProgressServer {
+    // Accepts clients in the background, answering any request with the event stream
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<Self> {
+        Self::listen(address, |stream| Ok(Client::Plain(open_stream(stream)?)))
+    }
+
+    // Same as bind, serving the event stream over TLS
+    #[cfg(feature = "tls")]
+    pub fn bind_tls(address: impl ToSocketAddrs, config: Arc<ServerConfig>) -> io::Result<Self> {
+        Self::listen(address, move |stream| {
+            let connection = ServerConnection::new(Arc::clone(&config)).map_err(io::Error::other)?;
+            Ok(Client::Tls(Box::new(open_stream(StreamOwned::new(connection, stream))?)))
+        })
+    }
+
+    fn listen(
+        address: impl ToSocketAddrs,
+        accept: impl Fn(TcpStream) -> io::Result<Client> + Send + 'static,
+    ) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let clients = Arc::new(Mutex::new(vec![]));
+
+        let accepted = Arc::clone(&clients);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // A client that never sends its request (or never completes the handshake) can't block the others,
+                // and neither can one that stops reading the events: its writes time out and it is dropped
+                let timeouts = stream.set_read_timeout(Some(Duration::from_secs(10)))
+                    .and_then(|_| stream.set_write_timeout(Some(WRITE_TIMEOUT)));
+                if timeouts.is_err() {
+                    continue;
+                }
+                if let Ok(client) = accept(stream) {
+                    accepted.lock().unwrap().push(client);
+                }
+            }
+        });
+
+        Ok(ProgressServer { address, clients })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    // Forwards the events until the sender is dropped, then tells the clients that the computation is done and closes
+    // their connections
+    pub fn broadcast(&self, events: Receiver<ProgressEvent>) {
+        for event in events {
+            self.send(&format!("event: progress\ndata: {}\n\n", event.to_json()));
+        }
+        self.send("event: done\ndata: {}\n\n");
+
+        for client in self.clients.lock().unwrap().drain(..) {
+            client.close();
+        }
+    }
+
+    // Clients that disconnected or stopped reading are dropped
+    fn send(&self, message: &str) {
+        self.clients.lock().unwrap().retain_mut(|client| client.send(message).is_ok());
+    }
+}
+
+// Writes the whole message within WRITE_TIMEOUT. Every write of the socket times out on its own, and returns early once
+// it wrote some bytes, so a client reading slowly would otherwise hold each event for several timeouts
+fn write_before_deadline(stream: &mut impl Write, mut message: &[u8]) -> io::Result<()> {
+    let deadline = Instant::now() + WRITE_TIMEOUT;
+    while !message.is_empty() {
+        if Instant::now() >= deadline {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        match stream.write(message) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(written) => message = &message[written..],
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+    stream.flush()
+}
+
+// Reads the request headers and answers with the headers of an event stream. The request has no body, so nothing is
+// left in the reader's buffer
+fn open_stream<S: Read + Write>(stream: S) -> io::Result<S> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 && line != "\r\n" && line != "\n" {
+        line.clear();
+    }
+
+    let mut stream = reader.into_inner();
+    stream.write_all(
+        b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+        Access-Control-Allow-Origin: *\r\nConnection: keep-alive\r\n\r\n",
+    )?;
+    stream.flush()?;
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use crate::padding::pad_sha256_data;
+    use crate::sha256::sha256_fhe_observed;
+    use crate::test_keys::test_keys;
+
+    #[test]
+    fn test_progress_observer() {
+        let (_, sk) = test_keys();
+        let (sender, receiver) = channel();
+
+        let input: Vec<Ciphertext> = pad_sha256_data(&[0; 64]).iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+        sha256_fhe_observed(input, &mut ProgressObserver::new(sender, 2), &sk);
+
+        let events: Vec<ProgressEvent> = receiver.iter().collect();
+        assert_eq!(events.len(), 128);
+        assert_eq!(events[64], ProgressEvent { block: 1, round: 0, total_blocks: 2 });
+        assert_eq!(events[127].fraction(), 1.0);
+    }
+
+    #[test]
+    fn test_progress_server() {
+        let server = ProgressServer::bind("127.0.0.1:0").unwrap();
+
+        let mut client = TcpStream::connect(server.local_addr()).unwrap();
+        client.write_all(b"GET /progress HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        broadcast_one_event(&server);
+        client.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n"));
+        assert!(response.contains("event: progress\ndata: {\"block\":0,\"round\":63,\"total_blocks\":2,\"fraction\":0.5000}\n\n"));
+        assert!(response.ends_with("event: done\ndata: {}\n\n"));
+    }
+
+    #[test]
+    fn test_progress_server_slow_client() {
+        let server = ProgressServer::bind("127.0.0.1:0").unwrap();
+
+        // The client never reads, so its socket buffers fill up and the writes time out
+        let mut client = TcpStream::connect(server.local_addr()).unwrap();
+        client.write_all(b"GET /progress HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        while server.clients.lock().unwrap().is_empty() {
+            thread::yield_now();
+        }
+
+        let (sender, receiver) = channel();
+        for round in 0..200_000 {
+            sender.send(ProgressEvent { block: 0, round, total_blocks: 10_000 }).unwrap();
+        }
+        drop(sender);
+        server.broadcast(receiver);
+        assert!(server.clients.lock().unwrap().is_empty());
+    }
+
+    fn broadcast_one_event(server: &ProgressServer) {
+        // Wait for the server to accept the client
+        while server.clients.lock().unwrap().is_empty() {
+            thread::yield_now();
+        }
+
+        let (sender, receiver) = channel();
+        sender.send(ProgressEvent { block: 0, round: 63, total_blocks: 2 }).unwrap();
+        drop(sender);
+        server.broadcast(receiver);
+    }
+
+    #[test]
+    #[cfg(feature = "tls")]
+    fn test_progress_server_tls() {
+        use rustls::pki_types::pem::PemObject;
+        use rustls::pki_types::{CertificateDer, ServerName};
+        use rustls::{ClientConfig, ClientConnection, RootCertStore};
+        use crate::transport::server_config_from_pem;
+        use crate::transport::tests::{CERT_PEM, KEY_PEM};
+
+        let config = server_config_from_pem(CERT_PEM.as_bytes(), KEY_PEM.as_bytes()).unwrap();
+        let server = ProgressServer::bind_tls("127.0.0.1:0", config).unwrap();
+
+        let mut roots = RootCertStore::empty();
+        roots.add(CertificateDer::from_pem_slice(CERT_PEM.as_bytes()).unwrap()).unwrap();
+        let client_config = ClientConfig::builder().with_root_certificates(roots).with_no_client_auth();
+        let connection = ClientConnection::new(Arc::new(client_config), ServerName::try_from("localhost").unwrap()).unwrap();
+
+        // The handshake happens with the first write, before the server accepts the client
+        let mut client = StreamOwned::new(connection, TcpStream::connect(server.local_addr()).unwrap());
+        client.write_all(b"GET /progress HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        client.flush().unwrap();
+
+        let mut response = String::new();
+        broadcast_one_event(&server);
+        client.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n"));
+        assert!(response.ends_with("event: done\ndata: {}\n\n"));
+    }
+}
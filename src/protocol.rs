@@ -0,0 +1,165 @@
+// This module holds what the service and its client both speak: the job ids and statuses, the error codes of refused
+// requests and the reading of HTTP lines. It doesn't depend on the server side computation, so the client of the
+// service_client module builds with the "client" feature alone.
+
+use std::fmt;
+use std::io::{self, BufRead, Read};
+use crate::serialization::SerializationError;
+
+// Longest request, status or header line accepted
+const MAX_LINE_LEN: u64 = 8 * 1024;
+
+pub type JobId = u64;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JobStatus {
+    // Waiting for a job slot, with its first `block` blocks already compressed if it was preempted
+    Queued { block: usize, total_blocks: usize },
+    // Compressing the block, whose first `round` rounds are done
+    Running { block: usize, round: usize, total_blocks: usize },
+    // The digest is ready
+    Done,
+    // The computation panicked, so there will be no digest
+    Failed { reason: String },
+}
+
+// Cause of a refused request, sent as a stable code along with the HTTP status
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCode {
+    // Malformed request, e.g. an invalid query parameter or client id
+    BadRequest,
+    // Payload that isn't a valid EncryptedInput or CompressedServerKey, e.g. truncated or corrupted
+    InvalidPayload,
+    // Payload made by another version of the crate or tfhe, or for another parameter set, e.g. a key generated by an
+    // older client
+    IncompatibleVersion,
+    // Missing or unknown API key
+    Unauthorized,
+    // Request about another client's key
+    Forbidden,
+    // No such job, or a job of another client
+    JobNotFound,
+    // No server key registered for the client
+    KeyNotFound,
+    // No such endpoint
+    NotFound,
+    MethodNotAllowed,
+    // The digest isn't ready yet
+    JobNotDone,
+    // The computation of the job failed, so it has no digest
+    JobFailed,
+    // No such upload, or an upload of another client
+    UploadNotFound,
+    // Part of an upload that doesn't start where the upload is, e.g. sent again after its response was lost
+    OffsetMismatch,
+    // Upload submitted as a job before all of it was received
+    UploadIncomplete,
+    LengthRequired,
+    // Payload larger than the limits of the service, or with more than max_blocks blocks
+    PayloadTooLarge,
+    // The client holds max_jobs_per_client jobs (or the limit of its API key)
+    QuotaExceeded,
+    // The service holds max_jobs jobs
+    CapacityExceeded,
+    // Failure of the service, e.g. to store a key
+    Internal,
+}
+
+impl ErrorCode {
+    pub const ALL: &'static [ErrorCode] = &[
+        ErrorCode::BadRequest,
+        ErrorCode::InvalidPayload,
+        ErrorCode::IncompatibleVersion,
+        ErrorCode::Unauthorized,
+        ErrorCode::Forbidden,
+        ErrorCode::JobNotFound,
+        ErrorCode::KeyNotFound,
+        ErrorCode::NotFound,
+        ErrorCode::MethodNotAllowed,
+        ErrorCode::JobNotDone,
+        ErrorCode::JobFailed,
+        ErrorCode::UploadNotFound,
+        ErrorCode::OffsetMismatch,
+        ErrorCode::UploadIncomplete,
+        ErrorCode::LengthRequired,
+        ErrorCode::PayloadTooLarge,
+        ErrorCode::QuotaExceeded,
+        ErrorCode::CapacityExceeded,
+        ErrorCode::Internal,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorCode::BadRequest => "bad_request",
+            ErrorCode::InvalidPayload => "invalid_payload",
+            ErrorCode::IncompatibleVersion => "incompatible_version",
+            ErrorCode::Unauthorized => "unauthorized",
+            ErrorCode::Forbidden => "forbidden",
+            ErrorCode::JobNotFound => "job_not_found",
+            ErrorCode::KeyNotFound => "key_not_found",
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::MethodNotAllowed => "method_not_allowed",
+            ErrorCode::JobNotDone => "job_not_done",
+            ErrorCode::JobFailed => "job_failed",
+            ErrorCode::UploadNotFound => "upload_not_found",
+            ErrorCode::OffsetMismatch => "offset_mismatch",
+            ErrorCode::UploadIncomplete => "upload_incomplete",
+            ErrorCode::LengthRequired => "length_required",
+            ErrorCode::PayloadTooLarge => "payload_too_large",
+            ErrorCode::QuotaExceeded => "quota_exceeded",
+            ErrorCode::CapacityExceeded => "capacity_exceeded",
+            ErrorCode::Internal => "internal",
+        }
+    }
+
+    // Error code of a response, or None if it isn't one of these
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|error_code| error_code.code() == code)
+    }
+
+    // HTTP status of the responses with the code
+    pub fn status(self) -> u16 {
+        match self {
+            ErrorCode::BadRequest | ErrorCode::InvalidPayload | ErrorCode::IncompatibleVersion => 400,
+            ErrorCode::Unauthorized => 401,
+            ErrorCode::Forbidden => 403,
+            ErrorCode::JobNotFound | ErrorCode::UploadNotFound | ErrorCode::KeyNotFound | ErrorCode::NotFound => 404,
+            ErrorCode::MethodNotAllowed => 405,
+            ErrorCode::JobNotDone | ErrorCode::OffsetMismatch | ErrorCode::UploadIncomplete => 409,
+            ErrorCode::LengthRequired => 411,
+            ErrorCode::PayloadTooLarge => 413,
+            ErrorCode::QuotaExceeded => 429,
+            ErrorCode::CapacityExceeded => 503,
+            ErrorCode::JobFailed | ErrorCode::Internal => 500,
+        }
+    }
+
+    // Code of the response refusing a payload that couldn't be read
+    pub fn for_payload(e: &SerializationError) -> Self {
+        match e {
+            SerializationError::TooLarge { .. } | SerializationError::TooManyBlocks { .. } => ErrorCode::PayloadTooLarge,
+            SerializationError::CrateVersionMismatch { .. }
+            | SerializationError::TfheVersionMismatch { .. }
+            | SerializationError::ParameterSetMismatch { .. } => ErrorCode::IncompatibleVersion,
+            _ => ErrorCode::InvalidPayload,
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+// Line without its line ending, failing on lines longer than MAX_LINE_LEN or a connection closed before the end
+pub(crate) fn read_line(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut line = String::new();
+    reader.by_ref().take(MAX_LINE_LEN).read_line(&mut line)?;
+    if !line.ends_with('\n') {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "line too long or truncated"));
+    }
+
+    line.truncate(line.trim_end_matches(['\r', '\n']).len());
+    Ok(line)
+}
@@ -0,0 +1,118 @@
+// This module implements a reliability mode for parameter sets whose gate failure probability is marginal for a circuit
+// of tens of thousands of gates: the hash is computed on k independently encrypted copies of the input, and the digest is
+// the homomorphic bitwise majority of the k digests, so it is only wrong where more than half of the copies are.
+//
+// The copies must be encrypted separately by the client. Evaluating the same gates again on the same ciphertexts doesn't
+// help, since bootstrapping is deterministic: a gate that fails on some input ciphertexts fails again on them. Voting
+// after every round wouldn't either, as all the copies would continue from the same voted ciphertexts. The cost is k
+// hashes plus the vote, which is a few gates per digest bit.
+
+use rayon::prelude::*;
+use tfhe::boolean::prelude::*;
+use crate::boolean_ops::{and, maj, or};
+use crate::sha256::sha256_fhe;
+
+// Bitwise majority of an odd number of copies of the same bits
+pub fn majority<const N: usize>(copies: &[[Ciphertext; N]], sk: &ServerKey) -> [Ciphertext; N] {
+    assert!(copies.len() % 2 == 1, "majority needs an odd number of copies");
+
+    match copies {
+        [copy] => copy.clone(),
+        [x, y, z] => maj(x, y, z, sk),
+        _ => {
+            // Median of each bit, sorting the copies with an odd-even transposition network where a compare-exchange
+            // of bits is an AND and an OR
+            let mut sorted = copies.to_vec();
+            for pass in 0..sorted.len() {
+                for i in (pass % 2..sorted.len() - 1).step_by(2) {
+                    let (low, high) = rayon::join(
+                        || and(&sorted[i], &sorted[i + 1], sk),
+                        || or(&sorted[i], &sorted[i + 1], sk),
+                    );
+                    (sorted[i], sorted[i + 1]) = (low, high);
+                }
+            }
+            sorted.swap_remove(copies.len() / 2)
+        }
+    }
+}
+
+// Bootstrapped gates of majority for the given number of copies of N bits: 4 per bit for a maj, and 2 per bit for each
+// of the k (k - 1) / 2 compare-exchanges of the sorting network
+pub fn majority_gates(copies: usize, bits: usize) -> u64 {
+    match copies {
+        1 => 0,
+        3 => 4 * bits as u64,
+        _ => (copies * (copies - 1) * bits) as u64,
+    }
+}
+
+// sha256 of an odd number of independently encrypted copies of the same padded input, computed concurrently, and voted
+pub fn sha256_fhe_redundant(copies: Vec<Vec<Ciphertext>>, sk: &ServerKey) -> Vec<Ciphertext> {
+    assert!(copies.windows(2).all(|pair| pair[0].len() == pair[1].len()), "copies have different lengths");
+
+    let digests: Vec<[Ciphertext; 256]> = copies.into_par_iter()
+        .map(|copy| sha256_fhe(copy, sk).try_into().unwrap())
+        .collect();
+
+    majority(&digests, sk).to_vec()
+}
+
+// Upper bound (log2) on the probability that a digest voted from the given number of copies is wrong, from the failure
+// probability (log2) of one copy. Wrong bits need more than half of the copies to fail, which is bounded by the number
+// of ways to choose them times the probability that they all fail, ignoring the few gates of the vote
+pub fn redundant_failure_log2(failure_log2: f64, copies: usize) -> f64 {
+    let needed = copies / 2 + 1;
+    let ways: f64 = (0..needed).map(|i| ((copies - i) as f64 / (i + 1) as f64).log2()).sum();
+
+    (ways + needed as f64 * failure_log2).min(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+    use crate::client::bools_to_hex;
+    use crate::padding::pad_sha256_data;
+    use crate::test_helpers::decrypt_bits;
+    use crate::test_keys::test_keys;
+
+    #[test]
+    fn test_majority() {
+        let (ck, sk) = test_keys();
+
+        // Every combination of 5 bits, one per bit position
+        let copies: Vec<[Ciphertext; 32]> = (0..5)
+            .map(|copy| std::array::from_fn(|i| sk.trivial_encrypt((i >> copy) & 1 == 1)))
+            .collect();
+
+        for count in [1, 3, 5] {
+            let voted = decrypt_bits(&majority(&copies[..count], &sk), &ck);
+            let expected: Vec<bool> = (0..32u32).map(|i| (i & ((1 << count) - 1)).count_ones() as usize > count / 2).collect();
+            assert_eq!(voted, expected, "{} copies", count);
+        }
+    }
+
+    #[test]
+    fn test_sha256_redundant() {
+        let (ck, sk) = test_keys();
+        let padded_input: Vec<Ciphertext> = pad_sha256_data(b"abc").iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+
+        // A copy corrupted by a failure is outvoted by the two others
+        let mut corrupted = padded_input.clone();
+        corrupted[7] = sk.not(&corrupted[7]);
+
+        let output = sha256_fhe_redundant(vec![padded_input.clone(), corrupted, padded_input], &sk);
+        assert_eq!(bools_to_hex(decrypt_bits(&output, &ck)), format!("{:x}", Sha256::digest(b"abc")));
+    }
+
+    #[test]
+    fn test_redundant_failure() {
+        assert_eq!(redundant_failure_log2(-30.0, 1), -30.0);
+        // 3 ways for 2 of 3 copies to fail
+        assert!((redundant_failure_log2(-30.0, 3) - (3f64.log2() - 60.0)).abs() < 1e-9);
+        // 10 ways for 3 of 5 copies to fail
+        assert!((redundant_failure_log2(-30.0, 5) - (10f64.log2() - 90.0)).abs() < 1e-9);
+        assert_eq!(redundant_failure_log2(-0.5, 3), 0.0);
+    }
+}
@@ -0,0 +1,162 @@
+// This module builds a machine-readable report of a hash computation (algorithm, parameter set, blocks, threads, gate
+// counts and timings), written as JSON so that benchmarks can be collected and compared across machines and versions.
+// The computation is split in named phases (e.g. loading the key, hashing, writing the output), each one timed and
+// with its count of bootstrapped gates and the memory it needed: the resident memory at its end and its peak, where the
+// platform reports them (see the memory module). Gate counts and memory are process-wide, so they are only meaningful
+// for a single computation at a time. The report also records the failure estimate of the run made before it started
+// (see the failure module), if any.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use crate::algorithm::Algorithm;
+use crate::boolean_ops::bootstrapped_gates;
+use crate::failure::FailureEstimate;
+use crate::memory::{memory_usage, reset_peak, MemoryUsage};
+use crate::parameters::ParameterSet;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PhaseReport {
+    pub name: String,
+    pub duration: Duration,
+    pub bootstrapped_gates: u64,
+    pub memory: Option<MemoryUsage>,
+}
+
+pub struct RunReport {
+    pub algorithm: Algorithm,
+    pub parameter_set: ParameterSet,
+    pub blocks: usize,
+    pub threads: usize,
+    pub failure: Option<FailureEstimate>,
+    // Resident memory after each block, for computations that record it
+    pub block_resident_bytes: Vec<u64>,
+    phases: Vec<PhaseReport>,
+    started: Instant,
+}
+
+impl RunReport {
+    // Starts the report, and its total duration, with the thread count of the current rayon pool
+    pub fn new(algorithm: Algorithm, parameter_set: ParameterSet, blocks: usize) -> Self {
+        RunReport {
+            algorithm,
+            parameter_set,
+            blocks,
+            threads: rayon::current_num_threads(),
+            failure: None,
+            block_resident_bytes: vec![],
+            phases: vec![],
+            started: Instant::now(),
+        }
+    }
+
+    // Runs one phase of the computation, recording its duration, gate count and memory
+    pub fn phase<T>(&mut self, name: &str, f: impl FnOnce() -> T) -> T {
+        reset_peak();
+        let (start, gates) = (Instant::now(), bootstrapped_gates());
+        let result = f();
+
+        self.phases.push(PhaseReport {
+            name: name.to_string(),
+            duration: start.elapsed(),
+            bootstrapped_gates: bootstrapped_gates() - gates,
+            memory: memory_usage(),
+        });
+        result
+    }
+
+    pub fn phases(&self) -> &[PhaseReport] {
+        &self.phases
+    }
+
+    // Report with the given total duration. Durations are in seconds and memory in bytes (null where unavailable), and
+    // the failure probability is its log2 (null without an estimate, or for a run without bootstrapped gates)
+    pub fn to_json(&self, total: Duration) -> String {
+        let bytes = |value: Option<u64>| value.map_or("null".to_string(), |value| value.to_string());
+        let estimated_gates = bytes(self.failure.map(|failure| failure.bootstrapped_gates));
+        let failure_log2 = self.failure
+            .map(|failure| failure.failure_log2)
+            .filter(|failure_log2| failure_log2.is_finite())
+            .map_or("null".to_string(), |failure_log2| format!("{:.3}", failure_log2));
+        let phases: Vec<String> = self.phases
+            .iter()
+            .map(|phase| format!(
+                "{{\"name\":\"{}\",\"seconds\":{:.6},\"bootstrapped_gates\":{},\"resident_bytes\":{},\
+                \"peak_resident_bytes\":{}}}",
+                escape(&phase.name), phase.duration.as_secs_f64(), phase.bootstrapped_gates,
+                bytes(phase.memory.map(|memory| memory.resident_bytes)),
+                bytes(phase.memory.map(|memory| memory.peak_resident_bytes)),
+            ))
+            .collect();
+        let gates: u64 = self.phases.iter().map(|phase| phase.bootstrapped_gates).sum();
+        let blocks: Vec<String> = self.block_resident_bytes.iter().map(u64::to_string).collect();
+
+        format!(
+            "{{\"version\":\"{}\",\"algorithm\":\"{}\",\"parameter_set\":\"{}\",\"blocks\":{},\"threads\":{},\
+            \"estimated_bootstrapped_gates\":{},\"failure_log2\":{},\"bootstrapped_gates\":{},\"phases\":[{}],\
+            \"block_resident_bytes\":[{}],\"total_seconds\":{:.6}}}",
+            env!("CARGO_PKG_VERSION"), self.algorithm, self.parameter_set, self.blocks, self.threads,
+            estimated_gates, failure_log2, gates, phases.join(","), blocks.join(","), total.as_secs_f64(),
+        )
+    }
+
+    // Writes the report to a file, with the time elapsed since it was started as the total duration
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_json(self.started.elapsed()) + "\n")
+    }
+}
+
+// Escapes a string for a JSON string literal
+pub(crate) fn escape(value: &str) -> String {
+    value.chars().fold(String::new(), |mut escaped, c| {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+        escaped
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tfhe::boolean::prelude::*;
+    use crate::boolean_ops::xor;
+    use crate::test_keys::test_keys;
+
+    #[test]
+    fn test_run_report() {
+        let (ck, sk) = test_keys();
+        let a: [Ciphertext; 32] = std::array::from_fn(|i| ck.encrypt(i % 3 == 0));
+
+        let mut report = RunReport::new(Algorithm::Sha256, ParameterSet::Default, 1);
+        let output = report.phase("xor", || xor(&a, &a, &sk));
+        assert_eq!(output.len(), 32);
+        report.phase("idle", || ());
+
+        // Other tests may run gates concurrently, so only a lower bound holds
+        assert_eq!(report.phases().len(), 2);
+        assert!(report.phases()[0].bootstrapped_gates >= 32);
+
+        let json = report.to_json(Duration::from_millis(1500));
+        assert!(json.starts_with(&format!("{{\"version\":\"{}\",\"algorithm\":\"sha256\"", env!("CARGO_PKG_VERSION"))));
+        assert!(json.contains("\"parameter_set\":\"default\",\"blocks\":1,"));
+        assert!(json.contains(",\"estimated_bootstrapped_gates\":null,\"failure_log2\":null,"));
+        assert!(json.contains("{\"name\":\"xor\",\"seconds\":"));
+        assert!(json.contains(",\"block_resident_bytes\":[],"));
+        #[cfg(target_os = "linux")]
+        {
+            let memory = report.phases()[0].memory.unwrap();
+            assert!(json.contains(&format!("\"resident_bytes\":{},", memory.resident_bytes)));
+        }
+        assert!(json.ends_with("\"total_seconds\":1.500000}"));
+
+        report.failure = Some(FailureEstimate::new(1 << 20, ParameterSet::Default));
+        let json = report.to_json(Duration::from_millis(1500));
+        assert!(json.contains(",\"estimated_bootstrapped_gates\":1048576,\"failure_log2\":-20.000,"));
+        assert_eq!(escape("a\"b\\c\n"), "a\\\"b\\\\c\\u000a");
+    }
+}
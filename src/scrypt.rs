@@ -0,0 +1,251 @@
+// This module is an EXPERIMENTAL implementation of scrypt (RFC 7914) over an encrypted password, the second
+// memory-hard KDF next to Argon2id. It composes the homomorphic PBKDF2-HMAC-SHA256 with ROMix, whose BlockMix is built
+// from the Salsa20/8 core on the generic 32-bit word operations. Only tiny parameters are practical: every 128 * r
+// bytes of memory are 1024 * r ciphertexts, and there are N of them per lane.
+//
+// The second loop of ROMix reads V[j] where j comes from the (encrypted) current block, so the block is selected
+// obliviously with a tree of MUXes over all the N candidates.
+//
+// The computation is exposed as a ScryptState that performs one BlockMix per step and can be serialized between
+// steps, to checkpoint as often as every block.
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tfhe::boolean::prelude::*;
+use crate::boolean_ops::{add, from_le_bytes, rotate_left, select_index, swap_bytes, xor, xor_gate};
+use crate::pbkdf2::pbkdf2_hmac_sha256;
+
+type Word = [Ciphertext; 32];
+
+// Salsa20/8 applies the quarter round to the columns of the 4x4 state and then to its rows, each one given as
+// (a, b, c, d) in the order the quarter round updates b, c, d and a
+const COLUMNS: [[usize; 4]; 4] = [[0, 4, 8, 12], [5, 9, 13, 1], [10, 14, 2, 6], [15, 3, 7, 11]];
+const ROWS: [[usize; 4]; 4] = [[0, 1, 2, 3], [5, 6, 7, 4], [10, 11, 8, 9], [15, 12, 13, 14]];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScryptParams {
+    // The cost N is 2^log_n
+    pub log_n: u32,
+    // Block size factor, each block is 128 * r bytes
+    pub r: u32,
+    // Parallelization factor, the number of independent ROMix lanes
+    pub p: u32,
+    // Output length in bytes
+    pub dk_len: u32,
+}
+
+impl ScryptParams {
+    pub fn new(log_n: u32, r: u32, p: u32, dk_len: u32) -> Self {
+        assert!((1..=16).contains(&log_n), "N must be a power of two between 2 and 2^16");
+        assert!(r >= 1 && p >= 1, "r and p must be at least 1");
+        assert!(dk_len >= 1, "output must be at least one byte long");
+
+        ScryptParams { log_n, r, p, dk_len }
+    }
+
+    pub fn cost(&self) -> usize {
+        1 << self.log_n
+    }
+
+    fn block_bits(&self) -> usize {
+        1024 * self.r as usize
+    }
+}
+
+// Encrypted scrypt key derived from the password and the salt
+pub fn scrypt(password: &[Ciphertext], salt: &[Ciphertext], params: ScryptParams, sk: &ServerKey) -> Vec<Ciphertext> {
+    let mut state = ScryptState::new(password, salt, params, sk);
+    while !state.is_done() {
+        state.step(sk);
+    }
+    state.finalize(sk)
+}
+
+// Blocks and position of an scrypt computation. Blocks are kept as 32-bit words, most significant bit first
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ScryptState {
+    params: ScryptParams,
+    // The password is needed again by the final PBKDF2
+    password: Vec<Ciphertext>,
+    // The p blocks from the first PBKDF2, replaced by the output of ROMix as each lane is done
+    blocks: Vec<Vec<Ciphertext>>,
+    lane: usize,
+    // BlockMix calls done in the current lane, N to fill V and N more to mix it
+    position: usize,
+    x: Vec<Ciphertext>,
+    v: Vec<Vec<Ciphertext>>,
+}
+
+impl ScryptState {
+    // Expands the password and the salt into the p blocks
+    pub fn new(password: &[Ciphertext], salt: &[Ciphertext], params: ScryptParams, sk: &ServerKey) -> Self {
+        let bytes = pbkdf2_hmac_sha256(password, salt, 1, params.p as usize * params.block_bits() / 8, sk);
+        let blocks: Vec<Vec<Ciphertext>> = bytes
+            .chunks(params.block_bits())
+            .map(|block| block.chunks(32).flat_map(from_le_bytes::<32>).collect())
+            .collect();
+
+        ScryptState { params, password: password.to_vec(), x: blocks[0].clone(), blocks, lane: 0, position: 0, v: vec![] }
+    }
+
+    pub fn params(&self) -> ScryptParams {
+        self.params
+    }
+
+    // Lane and BlockMix count within the lane of the next step, for progress reports
+    pub fn progress(&self) -> (usize, usize) {
+        (self.lane, self.position)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.lane == self.params.p as usize
+    }
+
+    // Performs the next BlockMix of ROMix
+    pub fn step(&mut self, sk: &ServerKey) {
+        assert!(!self.is_done(), "every lane is already done");
+
+        let n = self.params.cost();
+        if self.position < n {
+            self.v.push(self.x.clone());
+            self.x = block_mix(&self.x, sk);
+        } else {
+            // Integerify: j is the low log_n bits of the first word of the last 64-byte sub-block
+            let first = self.x.len() - 512;
+            let j = &self.x[first + 32 - self.params.log_n as usize..first + 32];
+
+            let selected = select_index(j, self.v.clone(), sk);
+            self.x = block_mix(&xor_bits(&self.x, &selected, sk), sk);
+        }
+
+        self.position += 1;
+        if self.position == 2 * n {
+            self.blocks[self.lane] = std::mem::take(&mut self.x);
+            self.v.clear();
+            self.position = 0;
+            self.lane += 1;
+            if !self.is_done() {
+                self.x = self.blocks[self.lane].clone();
+            }
+        }
+    }
+
+    // Derived key, from the password and the mixed blocks
+    pub fn finalize(self, sk: &ServerKey) -> Vec<Ciphertext> {
+        assert!(self.is_done(), "the lanes are not done");
+
+        let salt: Vec<Ciphertext> = self.blocks
+            .concat()
+            .chunks(32)
+            .flat_map(|word| swap_bytes::<32>(&word.to_vec().try_into().unwrap()))
+            .collect();
+        pbkdf2_hmac_sha256(&self.password, &salt, 1, self.params.dk_len as usize, sk)
+    }
+}
+
+// BlockMix with Salsa20/8 over the 2r sub-blocks of 64 bytes. The even outputs go to the first half of the block and
+// the odd ones to the second half
+fn block_mix(block: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    let sub_blocks: Vec<&[Ciphertext]> = block.chunks(512).collect();
+
+    let mut x = sub_blocks[sub_blocks.len() - 1].to_vec();
+    let mut outputs = Vec::with_capacity(sub_blocks.len());
+    for sub_block in &sub_blocks {
+        x = salsa20_8(&xor_bits(&x, sub_block, sk), sk);
+        outputs.push(x.clone());
+    }
+
+    let (even, odd): (Vec<_>, Vec<_>) = outputs.into_iter().enumerate().partition(|(i, _)| i % 2 == 0);
+    even.into_iter().chain(odd).flat_map(|(_, output)| output).collect()
+}
+
+// Salsa20/8 core over 16 words: four double rounds, and the input added to the result
+fn salsa20_8(input: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    let input: Vec<Word> = input.chunks(32).map(|word| word.to_vec().try_into().unwrap()).collect();
+    let mut x = input.clone();
+
+    for _ in 0..4 {
+        // The four quarter rounds of each step touch different words, so they run in parallel
+        for indices in [COLUMNS, ROWS] {
+            let mixed: Vec<[Word; 4]> = indices
+                .par_iter()
+                .map(|[a, b, c, d]| quarter_round([&x[*a], &x[*b], &x[*c], &x[*d]], sk))
+                .collect();
+
+            for (words, indices) in mixed.into_iter().zip(indices) {
+                for (word, index) in words.into_iter().zip(indices) {
+                    x[index] = word;
+                }
+            }
+        }
+    }
+
+    x.par_iter().zip(input.par_iter()).flat_map_iter(|(x, input)| add(x, input, sk)).collect()
+}
+
+fn quarter_round([a, b, c, d]: [&Word; 4], sk: &ServerKey) -> [Word; 4] {
+    let b = xor(b, &rotate_left(&add(a, d, sk), 7, sk), sk);
+    let c = xor(c, &rotate_left(&add(&b, a, sk), 9, sk), sk);
+    let d = xor(d, &rotate_left(&add(&c, &b, sk), 13, sk), sk);
+    let a = xor(a, &rotate_left(&add(&d, &c, sk), 18, sk), sk);
+
+    [a, b, c, d]
+}
+
+fn xor_bits(a: &[Ciphertext], b: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    a.par_iter().zip(b.par_iter()).map(|(a, b)| xor_gate(sk, a, b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boolean_ops::trivial_bytes;
+    use crate::test_helpers::{decrypt_bytes, from_hex};
+    use crate::test_keys::test_keys;
+
+    // RFC 7914 section 8, computed with the clear backend
+    #[test]
+    fn test_salsa20_8() {
+        let (ck, sk) = test_keys();
+
+        let input = from_hex("7e879a214f3ec9867ca940e641718f26baee555b8c61c1b50df846116dcd3b1dee24f319df9b3d8514121e4b5ac5aa3276021d2909c74829edebc68db8b8c25e");
+        let words: Vec<Ciphertext> = trivial_bytes(&input, &sk).chunks(32).flat_map(from_le_bytes::<32>).collect();
+
+        let output: Vec<Ciphertext> = salsa20_8(&words, &sk)
+            .chunks(32)
+            .flat_map(|word| swap_bytes::<32>(&word.to_vec().try_into().unwrap()))
+            .collect();
+        assert_eq!(
+            decrypt_bytes(&output, &ck),
+            from_hex("a41f859c6608cc993b81cacb020cef05044b2181a2fd337dfd7b1c6396682f29b4393168e3c9e6bcfe6bc5b7a06d96bae424cc102c91745c24ad673dc7618f81"),
+        );
+    }
+
+    // RFC 7914 section 12, first vector (N = 16, r = 1, p = 1)
+    #[test]
+    fn test_scrypt() {
+        let (ck, sk) = test_keys();
+
+        let key = scrypt(&[], &[], ScryptParams::new(4, 1, 1, 64), &sk);
+        assert_eq!(
+            decrypt_bytes(&key, &ck),
+            from_hex("77d6576238657b203b19ca42c18a0497f16b4844e3074ae8dfdffa3fede21442fcd0069ded0948f8326a753a0fc81f17e8d3e0fb2e0d3628cf35e20c38d18906"),
+        );
+    }
+
+    // Two lanes and a checkpoint after every step (N = 4, r = 1, p = 2, checked against Python's hashlib)
+    #[test]
+    fn test_scrypt_checkpoint() {
+        let (ck, sk) = test_keys();
+        let password = trivial_bytes(b"password", &sk);
+        let salt = trivial_bytes(b"NaCl", &sk);
+
+        let mut state = ScryptState::new(&password, &salt, ScryptParams::new(2, 1, 2, 16), &sk);
+        while !state.is_done() {
+            state.step(&sk);
+            state = bincode::deserialize(&bincode::serialize(&state).unwrap()).unwrap();
+        }
+        assert_eq!(state.progress(), (2, 0));
+        assert_eq!(decrypt_bytes(&state.finalize(&sk), &ck), from_hex("2181b9ed2feba209a5c7895f3bc22668"));
+    }
+}
@@ -0,0 +1,601 @@
+// This module serializes keys, ciphertexts and digests with a small header stamping the crate version, the tfhe version
+// and the parameter set. Loading an artifact produced by a different version or for a different parameter set fails with
+// an error, instead of silently producing a garbage hash. The header is checked before the payload is decoded.
+//
+// Every serialized payload is wrapped in a frame: magic bytes, a payload type tag, the body length and a CRC32 of the
+// body. Truncated or corrupted transfers are then detected when reading, before any computation starts.
+//
+// The size functions at the end give the exact serialized size of the encrypted input and digest, so bandwidth and
+// storage can be planned before encrypting anything. The server also uses them as upper bounds when it reads payloads
+// provided by a client (see deserialize_from_limited), so a malicious payload is rejected before any big allocation.
+
+use std::fmt;
+use std::io::{Read, Write};
+use bincode::Options;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tfhe::boolean::prelude::{Ciphertext, CompressedServerKey, ServerKey};
+use crate::padding::padded_len;
+use crate::parameters::ParameterSet;
+use crate::trivium::{IV_BYTES, KEY_BITS};
+
+pub const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// Keys and ciphertexts are only compatible within the same tfhe minor version (keep in sync with Cargo.toml)
+pub const TFHE_VERSION: &str = "0.2";
+
+pub const MAGIC: [u8; 4] = *b"SHFE";
+
+// Magic, type tag, body length and CRC32
+const FRAME_OVERHEAD: u64 = 4 + 1 + 8 + 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PayloadKind {
+    ClientKey = 1,
+    ServerKey = 2,
+    EncryptedInput = 3,
+    EncryptedDigest = 4,
+    CompressedServerKey = 5,
+    EncryptedMatch = 6,
+    EncryptedMessage = 7,
+    Checkpoint = 8,
+    EncryptedBlock = 9,
+    TranscipheredInput = 10,
+    CompactDigest = 11,
+    KeyPair = 12,
+    Netlist = 13,
+}
+
+impl PayloadKind {
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            1 => Some(PayloadKind::ClientKey),
+            2 => Some(PayloadKind::ServerKey),
+            3 => Some(PayloadKind::EncryptedInput),
+            4 => Some(PayloadKind::EncryptedDigest),
+            5 => Some(PayloadKind::CompressedServerKey),
+            6 => Some(PayloadKind::EncryptedMatch),
+            7 => Some(PayloadKind::EncryptedMessage),
+            8 => Some(PayloadKind::Checkpoint),
+            9 => Some(PayloadKind::EncryptedBlock),
+            10 => Some(PayloadKind::TranscipheredInput),
+            11 => Some(PayloadKind::CompactDigest),
+            12 => Some(PayloadKind::KeyPair),
+            13 => Some(PayloadKind::Netlist),
+            _ => None,
+        }
+    }
+
+    // Conventional file name of each payload when client and server exchange them through a directory
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            PayloadKind::ClientKey => "client_key.bin",
+            PayloadKind::ServerKey => "server_key.bin",
+            PayloadKind::EncryptedInput => "input.bin",
+            PayloadKind::EncryptedDigest => "digest.bin",
+            PayloadKind::CompressedServerKey => "compressed_server_key.bin",
+            PayloadKind::EncryptedMatch => "match.bin",
+            PayloadKind::EncryptedMessage => "message.bin",
+            PayloadKind::Checkpoint => "checkpoint.bin",
+            PayloadKind::EncryptedBlock => "blocks.bin",
+            PayloadKind::TranscipheredInput => "transciphered.bin",
+            PayloadKind::CompactDigest => "compact_digest.bin",
+            PayloadKind::KeyPair => "key_pair.bin",
+            PayloadKind::Netlist => "netlist.bin",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Header {
+    pub crate_version: String,
+    pub tfhe_version: String,
+    pub parameter_set: ParameterSet,
+}
+
+impl Header {
+    pub fn new(parameter_set: ParameterSet) -> Self {
+        Header {
+            crate_version: CRATE_VERSION.to_string(),
+            tfhe_version: TFHE_VERSION.to_string(),
+            parameter_set,
+        }
+    }
+
+    // Checks that an artifact with this header can be used by this build with the expected parameter set
+    pub fn check(&self, parameter_set: ParameterSet) -> Result<(), SerializationError> {
+        if self.crate_version != CRATE_VERSION {
+            return Err(SerializationError::CrateVersionMismatch { found: self.crate_version.clone() });
+        }
+        if self.tfhe_version != TFHE_VERSION {
+            return Err(SerializationError::TfheVersionMismatch { found: self.tfhe_version.clone() });
+        }
+        if self.parameter_set != parameter_set {
+            return Err(SerializationError::ParameterSetMismatch { expected: parameter_set, found: self.parameter_set });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum SerializationError {
+    Io(std::io::Error),
+    Bincode(bincode::Error),
+    BadMagic,
+    UnknownPayloadKind(u8),
+    UnexpectedPayloadKind { expected: PayloadKind, found: PayloadKind },
+    Truncated,
+    TooLarge { limit: u64, found: u64 },
+    ChecksumMismatch,
+    CrateVersionMismatch { found: String },
+    TfheVersionMismatch { found: String },
+    ParameterSetMismatch { expected: ParameterSet, found: ParameterSet },
+    InvalidBlockLength { expected: usize, found: usize },
+    TooManyBlocks { limit: usize },
+}
+
+impl fmt::Display for SerializationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SerializationError::Io(e) => write!(f, "I/O error: {}", e),
+            SerializationError::Bincode(e) => write!(f, "serialization error: {}", e),
+            SerializationError::BadMagic => write!(f, "not a sha256-fhe payload (bad magic bytes)"),
+            SerializationError::UnknownPayloadKind(tag) => write!(f, "unknown payload type tag {}", tag),
+            SerializationError::UnexpectedPayloadKind { expected, found } => write!(
+                f, "expected a {:?} payload, found a {:?} payload", expected, found
+            ),
+            SerializationError::Truncated => write!(f, "payload is truncated"),
+            SerializationError::TooLarge { limit, found } => write!(
+                f, "payload of {} bytes exceeds the limit of {} bytes", found, limit
+            ),
+            SerializationError::ChecksumMismatch => write!(f, "payload is corrupted (checksum mismatch)"),
+            SerializationError::CrateVersionMismatch { found } => write!(
+                f, "artifact was produced by sha256-fhe {}, but this is sha256-fhe {}", found, CRATE_VERSION
+            ),
+            SerializationError::TfheVersionMismatch { found } => write!(
+                f, "artifact was produced with tfhe {}, but this build uses tfhe {}", found, TFHE_VERSION
+            ),
+            SerializationError::ParameterSetMismatch { expected, found } => write!(
+                f, "artifact uses the {} parameter set, expected {}", found, expected
+            ),
+            SerializationError::InvalidBlockLength { expected, found } => write!(
+                f, "encrypted block has {} bits, expected {}", found, expected
+            ),
+            SerializationError::TooManyBlocks { limit } => write!(f, "input has more than {} blocks", limit),
+        }
+    }
+}
+
+impl std::error::Error for SerializationError {}
+
+impl From<std::io::Error> for SerializationError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => SerializationError::Truncated,
+            _ => SerializationError::Io(e),
+        }
+    }
+}
+
+impl From<bincode::Error> for SerializationError {
+    fn from(e: bincode::Error) -> Self {
+        SerializationError::Bincode(e)
+    }
+}
+
+// Frame layout: magic (4 bytes) | type tag (1 byte) | body length (u64 LE) | CRC32 of the body (u32 LE) | body
+pub fn write_frame<W: Write>(mut writer: W, kind: PayloadKind, body: &[u8]) -> Result<(), SerializationError> {
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&[kind as u8])?;
+    writer.write_all(&(body.len() as u64).to_le_bytes())?;
+    writer.write_all(&crc32fast::hash(body).to_le_bytes())?;
+    writer.write_all(body)?;
+    Ok(())
+}
+
+// Reads a whole frame of the expected kind and returns its body once the length and checksum are verified
+pub fn read_frame<R: Read>(reader: R, kind: PayloadKind) -> Result<Vec<u8>, SerializationError> {
+    read_frame_limited(reader, kind, u64::MAX)
+}
+
+// Same as read_frame, rejecting a frame whose body is longer than max_body_len before reading the body
+pub fn read_frame_limited<R: Read>(mut reader: R, kind: PayloadKind, max_body_len: u64) -> Result<Vec<u8>, SerializationError> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(SerializationError::BadMagic);
+    }
+
+    let mut tag = [0u8; 1];
+    reader.read_exact(&mut tag)?;
+    let found = PayloadKind::from_tag(tag[0]).ok_or(SerializationError::UnknownPayloadKind(tag[0]))?;
+    if found != kind {
+        return Err(SerializationError::UnexpectedPayloadKind { expected: kind, found });
+    }
+
+    let mut len = [0u8; 8];
+    reader.read_exact(&mut len)?;
+    if u64::from_le_bytes(len) > max_body_len {
+        return Err(SerializationError::TooLarge { limit: max_body_len, found: u64::from_le_bytes(len) });
+    }
+    let mut crc = [0u8; 4];
+    reader.read_exact(&mut crc)?;
+
+    let mut body = vec![];
+    reader.take(u64::from_le_bytes(len)).read_to_end(&mut body)?;
+    if body.len() as u64 != u64::from_le_bytes(len) {
+        return Err(SerializationError::Truncated);
+    }
+    if crc32fast::hash(&body) != u32::from_le_bytes(crc) {
+        return Err(SerializationError::ChecksumMismatch);
+    }
+
+    Ok(body)
+}
+
+// Writes a frame whose body is the header followed by the value (a key, ciphertexts or a digest)
+pub fn serialize_into<W: Write, T: Serialize + ?Sized>(
+    writer: W,
+    kind: PayloadKind,
+    value: &T,
+    parameter_set: ParameterSet,
+) -> Result<(), SerializationError> {
+    let mut body = bincode::serialize(&Header::new(parameter_set))?;
+    bincode::serialize_into(&mut body, value)?;
+
+    write_frame(writer, kind, &body)
+}
+
+// Reads and verifies the frame, checks the header, then decodes the value
+pub fn deserialize_from<R: Read, T: DeserializeOwned>(
+    reader: R,
+    kind: PayloadKind,
+    parameter_set: ParameterSet,
+) -> Result<T, SerializationError> {
+    deserialize_from_limited(reader, kind, parameter_set, u64::MAX)
+}
+
+// Same as deserialize_from for untrusted payloads, which can't be larger than max_size bytes (frame included). The body
+// is rejected from its declared length, before it is read, and no length prefix inside it can claim more bytes than
+// the body has, so decoding never allocates more than the payload size
+pub fn deserialize_from_limited<R: Read, T: DeserializeOwned>(
+    reader: R,
+    kind: PayloadKind,
+    parameter_set: ParameterSet,
+    max_size: u64,
+) -> Result<T, SerializationError> {
+    let body = read_frame_limited(reader, kind, max_size.saturating_sub(FRAME_OVERHEAD))?;
+    let mut body = body.as_slice();
+
+    // Same encoding as bincode::serialize, with the body length as the decoding limit
+    let options = bincode::options().with_fixint_encoding().allow_trailing_bytes().with_limit(body.len() as u64);
+
+    let header: Header = options.deserialize_from(&mut body)?;
+    header.check(parameter_set)?;
+
+    Ok(options.deserialize_from(body)?)
+}
+
+// Writes a padded input as a stream of EncryptedBlock frames, one per block, followed by an empty frame marking the end.
+// Blocks are taken from the iterator as they are written, so a client can encrypt the next block while the previous
+// ones are on their way, and the server can hash the first blocks before the last ones arrive
+pub fn serialize_blocks_into<W: Write>(
+    mut writer: W,
+    blocks: impl IntoIterator<Item = Vec<Ciphertext>>,
+    parameter_set: ParameterSet,
+) -> Result<(), SerializationError> {
+    for block in blocks {
+        assert!(!block.is_empty(), "empty blocks mark the end of the stream");
+        serialize_into(&mut writer, PayloadKind::EncryptedBlock, &block, parameter_set)?;
+        writer.flush()?;
+    }
+
+    serialize_into(&mut writer, PayloadKind::EncryptedBlock, &Vec::<Ciphertext>::new(), parameter_set)?;
+    writer.flush()?;
+    Ok(())
+}
+
+// Reads the next block of a stream written by serialize_blocks_into, or None at the end of the stream. Blocks come from
+// a client, so each frame is bounded by the size of a block of block_bits bits
+pub fn deserialize_block_from<R: Read>(
+    reader: R,
+    block_bits: usize,
+    parameter_set: ParameterSet,
+) -> Result<Option<Vec<Ciphertext>>, SerializationError> {
+    let limit = encrypted_block_size(block_bits, parameter_set);
+    let block: Vec<Ciphertext> = deserialize_from_limited(reader, PayloadKind::EncryptedBlock, parameter_set, limit)?;
+
+    match block.len() {
+        0 => Ok(None),
+        len if len == block_bits => Ok(Some(block)),
+        found => Err(SerializationError::InvalidBlockLength { expected: block_bits, found }),
+    }
+}
+
+// Reads a compressed server key and decompresses it, ready to be used for computation. The key is usually provided by
+// a client, so it is bounded by compressed_server_key_size_limit
+pub fn deserialize_compressed_server_key<R: Read>(
+    reader: R,
+    parameter_set: ParameterSet,
+) -> Result<ServerKey, SerializationError> {
+    let limit = compressed_server_key_size_limit(parameter_set);
+    let compressed: CompressedServerKey =
+        deserialize_from_limited(reader, PayloadKind::CompressedServerKey, parameter_set, limit)?;
+
+    Ok(ServerKey::from(compressed))
+}
+
+// Serialized size in bytes of the encrypted padded input for a message of message_len bytes
+pub fn encrypted_input_size(message_len: usize, parameter_set: ParameterSet) -> u64 {
+    framed_ciphertexts_size(padded_len(message_len) as u64, parameter_set)
+}
+
+// Serialized size in bytes of the encrypted unpadded message of message_len bytes, when the server does the padding
+pub fn encrypted_message_size(message_len: usize, parameter_set: ParameterSet) -> u64 {
+    framed_ciphertexts_size(message_len as u64 * 8, parameter_set)
+}
+
+// Serialized size in bytes of the transciphered input (see trivium::TranscipheredInput) of a message of message_len
+// bytes: the encrypted key, the IV and the Trivium ciphertext
+pub fn transciphered_input_size(message_len: usize, parameter_set: ParameterSet) -> u64 {
+    framed_ciphertexts_size(KEY_BITS as u64, parameter_set) + IV_BYTES as u64 + 8 + message_len as u64
+}
+
+// Serialized size in bytes of one frame of a block stream (see serialize_blocks_into)
+pub fn encrypted_block_size(block_bits: usize, parameter_set: ParameterSet) -> u64 {
+    framed_ciphertexts_size(block_bits as u64, parameter_set)
+}
+
+// Serialized size in bytes of the encrypted 256-bit digest
+pub fn encrypted_digest_size(parameter_set: ParameterSet) -> u64 {
+    framed_ciphertexts_size(256, parameter_set)
+}
+
+// Serialized size in bytes of the 256-bit digest compacted to coefficient_bits bits per coefficient (see compact)
+pub fn compact_digest_size(parameter_set: ParameterSet, coefficient_bits: u32) -> u64 {
+    let header_size = bincode::serialized_size(&Header::new(parameter_set)).expect("header is serializable");
+    let lwe_size = parameter_set.parameters().lwe_dimension.0 as u64 + 1;
+
+    FRAME_OVERHEAD + header_size + 3 * 4 + 8 + (256 * lwe_size * coefficient_bits as u64).div_ceil(8)
+}
+
+// Upper bound on the serialized size of a compressed server key. The seeded bootstrapping key stores one polynomial
+// per GLWE row of its GGSW ciphertexts and the seeded key switching key one body per LWE ciphertext, all of them u32.
+// The bound doubles that, leaving room for the encoding overhead
+pub fn compressed_server_key_size_limit(parameter_set: ParameterSet) -> u64 {
+    let parameters = parameter_set.parameters();
+    let lwe_dimension = parameters.lwe_dimension.0 as u64;
+    let glwe_size = parameters.glwe_dimension.0 as u64 + 1;
+    let polynomial_size = parameters.polynomial_size.0 as u64;
+
+    let bootstrapping_key = lwe_dimension * parameters.pbs_level.0 as u64 * glwe_size * polynomial_size;
+    let key_switching_key = (glwe_size - 1) * polynomial_size * parameters.ks_level.0 as u64;
+
+    2 * 4 * (bootstrapping_key + key_switching_key) + 4096
+}
+
+// Each encrypted bool is an LWE ciphertext of lwe_dimension + 1 u32 values, preceded by the enum variant tag (u32)
+// and the vector length (u64)
+fn ciphertext_size(parameter_set: ParameterSet) -> u64 {
+    4 + 8 + (parameter_set.parameters().lwe_dimension.0 as u64 + 1) * 4
+}
+
+fn framed_ciphertexts_size(count: u64, parameter_set: ParameterSet) -> u64 {
+    let header_size = bincode::serialized_size(&Header::new(parameter_set)).expect("header is serializable");
+
+    FRAME_OVERHEAD + header_size + 8 + count * ciphertext_size(parameter_set)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "client")]
+    use tfhe::boolean::prelude::*;
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "client")]
+    fn test_ciphertexts_roundtrip() {
+        let (ck, _) = ParameterSet::Default.gen_keys();
+        let bools = [true, false, false, true, true];
+        let ciphertexts: Vec<Ciphertext> = bools.iter().map(|b| ck.encrypt(*b)).collect();
+
+        let mut bytes = vec![];
+        serialize_into(&mut bytes, PayloadKind::EncryptedInput, &ciphertexts, ParameterSet::Default).unwrap();
+        let result: Vec<Ciphertext> =
+            deserialize_from(bytes.as_slice(), PayloadKind::EncryptedInput, ParameterSet::Default).unwrap();
+        let decrypted: Vec<bool> = result.iter().map(|c| ck.decrypt(c)).collect();
+
+        assert_eq!(decrypted, bools);
+    }
+
+    #[test]
+    #[cfg(feature = "client")]
+    fn test_compressed_server_key() {
+        let (ck, compressed) = ParameterSet::Default.gen_keys_compressed();
+
+        let mut bytes = vec![];
+        serialize_into(&mut bytes, PayloadKind::CompressedServerKey, &compressed, ParameterSet::Default).unwrap();
+        let sk = deserialize_compressed_server_key(bytes.as_slice(), ParameterSet::Default).unwrap();
+
+        let output = sk.xor(&ck.encrypt(true), &ck.encrypt(false));
+        assert!(ck.decrypt(&output));
+    }
+
+    #[test]
+    #[cfg(feature = "client")]
+    fn test_size_estimates() {
+        for parameter_set in [ParameterSet::Default, ParameterSet::TfheLib] {
+            let (ck, _) = parameter_set.gen_keys();
+            let padded_input = crate::padding::pad_sha256_input("abc");
+            let ciphertexts: Vec<Ciphertext> = padded_input.iter().map(|b| ck.encrypt(*b)).collect();
+
+            let mut bytes = vec![];
+            serialize_into(&mut bytes, PayloadKind::EncryptedInput, &ciphertexts, parameter_set).unwrap();
+            assert_eq!(bytes.len() as u64, encrypted_input_size(3, parameter_set));
+
+            let mut bytes = vec![];
+            serialize_into(&mut bytes, PayloadKind::EncryptedDigest, &ciphertexts[..256].to_vec(), parameter_set).unwrap();
+            assert_eq!(bytes.len() as u64, encrypted_digest_size(parameter_set));
+
+            let compact = crate::compact::CompactCiphertexts::compact(&ciphertexts[..256], parameter_set, 12);
+            let mut bytes = vec![];
+            serialize_into(&mut bytes, PayloadKind::CompactDigest, &compact, parameter_set).unwrap();
+            assert_eq!(bytes.len() as u64, compact_digest_size(parameter_set, 12));
+
+            let mut bytes = vec![];
+            serialize_into(&mut bytes, PayloadKind::EncryptedMessage, &ciphertexts[..24].to_vec(), parameter_set).unwrap();
+            assert_eq!(bytes.len() as u64, encrypted_message_size(3, parameter_set));
+
+            let input = crate::trivium::TranscipheredInput::new(b"abc", b"secret key", [0; IV_BYTES], &ck);
+            let mut bytes = vec![];
+            serialize_into(&mut bytes, PayloadKind::TranscipheredInput, &input, parameter_set).unwrap();
+            assert_eq!(bytes.len() as u64, transciphered_input_size(3, parameter_set));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "client")]
+    fn test_block_stream() {
+        let (ck, _) = ParameterSet::Default.gen_keys();
+        let padded_input = crate::padding::pad_sha256_data(&[0x61; 100]);
+        let blocks: Vec<Vec<Ciphertext>> = padded_input.chunks(512).map(|block| block.iter().map(|b| ck.encrypt(*b)).collect()).collect();
+
+        let mut bytes = vec![];
+        serialize_blocks_into(&mut bytes, blocks.clone(), ParameterSet::Default).unwrap();
+        let block_size = encrypted_block_size(512, ParameterSet::Default);
+        assert_eq!(bytes.len() as u64, 2 * block_size + encrypted_block_size(0, ParameterSet::Default));
+
+        let mut reader = bytes.as_slice();
+        let mut received = vec![];
+        while let Some(block) = deserialize_block_from(&mut reader, 512, ParameterSet::Default).unwrap() {
+            received.extend(block.iter().map(|c| ck.decrypt(c)));
+        }
+        assert_eq!(received, padded_input);
+        assert!(reader.is_empty());
+
+        // Blocks of the wrong size are rejected, shorter ones once decoded and longer ones from the frame length
+        let result = deserialize_block_from(bytes.as_slice(), 256, ParameterSet::Default);
+        assert!(matches!(result, Err(SerializationError::TooLarge { .. })));
+        let mut bytes = vec![];
+        serialize_blocks_into(&mut bytes, [blocks[0][..100].to_vec()], ParameterSet::Default).unwrap();
+        let result = deserialize_block_from(bytes.as_slice(), 512, ParameterSet::Default);
+        assert!(matches!(result, Err(SerializationError::InvalidBlockLength { expected: 512, found: 100 })));
+    }
+
+    #[test]
+    fn test_mismatches_are_rejected() {
+        let kind = PayloadKind::EncryptedDigest;
+
+        let mut bytes = vec![];
+        serialize_into(&mut bytes, kind, &vec![true, false], ParameterSet::Default).unwrap();
+        let result = deserialize_from::<_, Vec<bool>>(bytes.as_slice(), kind, ParameterSet::TfheLib);
+        assert!(matches!(result, Err(SerializationError::ParameterSetMismatch { .. })));
+
+        let result = deserialize_from::<_, Vec<bool>>(bytes.as_slice(), PayloadKind::ServerKey, ParameterSet::Default);
+        assert!(matches!(result, Err(SerializationError::UnexpectedPayloadKind { .. })));
+
+        let header = Header { crate_version: "0.0.0".to_string(), ..Header::new(ParameterSet::Default) };
+        let mut body = bincode::serialize(&header).unwrap();
+        body.extend(bincode::serialize(&vec![true, false]).unwrap());
+        let mut bytes = vec![];
+        write_frame(&mut bytes, kind, &body).unwrap();
+        let result = deserialize_from::<_, Vec<bool>>(bytes.as_slice(), kind, ParameterSet::Default);
+        assert!(matches!(result, Err(SerializationError::CrateVersionMismatch { .. })));
+    }
+
+    #[test]
+    fn test_corrupted_frames_are_rejected() {
+        let kind = PayloadKind::EncryptedInput;
+
+        let mut bytes = vec![];
+        serialize_into(&mut bytes, kind, &vec![true; 64], ParameterSet::Default).unwrap();
+
+        let truncated = &bytes[..bytes.len() - 1];
+        let result = deserialize_from::<_, Vec<bool>>(truncated, kind, ParameterSet::Default);
+        assert!(matches!(result, Err(SerializationError::Truncated)));
+
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        let result = deserialize_from::<_, Vec<bool>>(corrupted.as_slice(), kind, ParameterSet::Default);
+        assert!(matches!(result, Err(SerializationError::ChecksumMismatch)));
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        let result = deserialize_from::<_, Vec<bool>>(bad_magic.as_slice(), kind, ParameterSet::Default);
+        assert!(matches!(result, Err(SerializationError::BadMagic)));
+    }
+
+    #[test]
+    fn test_size_limits() {
+        let kind = PayloadKind::EncryptedInput;
+
+        let mut bytes = vec![];
+        serialize_into(&mut bytes, kind, &vec![true; 64], ParameterSet::Default).unwrap();
+        let size = bytes.len() as u64;
+
+        let result = deserialize_from_limited::<_, Vec<bool>>(bytes.as_slice(), kind, ParameterSet::Default, size);
+        assert_eq!(result.unwrap(), vec![true; 64]);
+        let result = deserialize_from_limited::<_, Vec<bool>>(bytes.as_slice(), kind, ParameterSet::Default, size - 1);
+        assert!(matches!(result, Err(SerializationError::TooLarge { .. })));
+
+        // A frame announcing a huge body is rejected from its header alone
+        let mut huge = bytes[..5].to_vec();
+        huge.extend(u64::MAX.to_le_bytes());
+        let result = deserialize_from_limited::<_, Vec<bool>>(huge.as_slice(), kind, ParameterSet::Default, size);
+        assert!(matches!(result, Err(SerializationError::TooLarge { found: u64::MAX, .. })));
+
+        // A valid frame whose vector length prefix claims far more elements than the body holds
+        let mut body = bincode::serialize(&Header::new(ParameterSet::Default)).unwrap();
+        body.extend(u64::MAX.to_le_bytes());
+        let mut bytes = vec![];
+        write_frame(&mut bytes, kind, &body).unwrap();
+        let result = deserialize_from::<_, Vec<u64>>(bytes.as_slice(), kind, ParameterSet::Default);
+        assert!(matches!(result, Err(SerializationError::Bincode(_))));
+    }
+    // The client/server split of the client and server examples, with the payloads exchanged through files in a
+    // directory and only the compressed server key, the encrypted input and the encrypted digest crossing it
+    #[test]
+    #[cfg(all(feature = "client", feature = "server"))]
+    fn test_file_exchange() {
+        use std::fs::{self, File};
+        use std::path::Path;
+        use crate::client::{bools_to_hex, decrypt_bools, encrypt_bools};
+        use crate::padding::pad_sha256_data;
+        use crate::sha256::sha256_fhe;
+
+        fn write<T: Serialize>(dir: &Path, kind: PayloadKind, value: &T) {
+            serialize_into(File::create(dir.join(kind.file_name())).unwrap(), kind, value, ParameterSet::Default).unwrap();
+        }
+        fn read<T: DeserializeOwned>(dir: &Path, kind: PayloadKind) -> T {
+            deserialize_from(File::open(dir.join(kind.file_name())).unwrap(), kind, ParameterSet::Default).unwrap()
+        }
+
+        let dir = std::env::temp_dir().join(format!("sha256-fhe-file-exchange-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        // Client: keys, then the encrypted input
+        let (ck, compressed) = ParameterSet::Default.gen_keys_compressed();
+        write(&dir, PayloadKind::ClientKey, &ck);
+        write(&dir, PayloadKind::CompressedServerKey, &compressed);
+
+        let ck: ClientKey = read(&dir, PayloadKind::ClientKey);
+        write(&dir, PayloadKind::EncryptedInput, &encrypt_bools(&pad_sha256_data(b"file exchange"), &ck));
+
+        // Server: the key and the input, bounded as untrusted payloads, then the encrypted digest
+        let file = File::open(dir.join(PayloadKind::CompressedServerKey.file_name())).unwrap();
+        let sk = deserialize_compressed_server_key(file, ParameterSet::Default).unwrap();
+        let file = File::open(dir.join(PayloadKind::EncryptedInput.file_name())).unwrap();
+        let limit = encrypted_input_size(13, ParameterSet::Default);
+        let encrypted_input: Vec<Ciphertext> =
+            deserialize_from_limited(file, PayloadKind::EncryptedInput, ParameterSet::Default, limit).unwrap();
+        write(&dir, PayloadKind::EncryptedDigest, &sha256_fhe(encrypted_input, &sk));
+
+        // Client: the digest
+        let digest: Vec<Ciphertext> = read(&dir, PayloadKind::EncryptedDigest);
+        assert_eq!(
+            bools_to_hex(decrypt_bools(&digest, &ck)),
+            "c85cdcd2a12f3400e05a8f4e79a5854ac76affb058c1f518cbd7fd3586b6773a",
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,1047 @@
+// This module serves a JobQueue over HTTP, so a client submits its encrypted input, disconnects, and comes back for the
+// digest once the job is done, instead of holding a connection open for the whole computation (about an hour for a
+// long input). It's plain HTTP/1.1 with one request per connection, served with the standard library like the progress
+// stream, so it can be used with curl and the payload files of the client example:
+//
+//   POST /jobs[?priority=<n>]  the encrypted padded input (an EncryptedInput payload), answered with {"id":<id>}
+//   POST /uploads?length=<n>   starts an upload of an input of n bytes, answered with {"id":<id>,"offset":0,...}
+//   PATCH /uploads/<id>?offset=<n>  the next part of the upload, from the offset the service has received
+//   GET /uploads/<id>          how much of the upload the service has received, e.g. {"id":2,"offset":1048576,...}
+//   DELETE /uploads/<id>       abandons an upload
+//   GET /jobs/<id>             the status of the job, e.g. {"id":3,"state":"running","block":0,"round":12,...}
+//   GET /jobs/<id>/digest      the encrypted digest (an EncryptedDigest payload), once the job is done
+//   DELETE /jobs/<id>          forgets a job that is done (along with its digest) or failed
+//   PUT /keys/<client>         registers the server key of a client (a CompressedServerKey payload) in a key registry
+//   DELETE /keys/<client>      removes the server key of a client
+//   GET /openapi.json          the OpenAPI description of these endpoints, for generating clients in other languages
+//
+// A job is "queued" (with the blocks already compressed if it was preempted), "running" (with the rounds done of the
+// block being compressed), "done" or "failed" (with the reason, e.g. the message of a panic of the computation, whose
+// digest request is then refused with job_failed). Its digest is kept until it's deleted, so a client whose download
+// failed can download it again. Payloads are framed as in the serialization module, and bounded before they are read.
+// A download that was cut off can be resumed with a `Range: bytes=<offset>-` header, answered with the rest of the
+// digest (206).
+//
+// An input can also be uploaded in parts, so that an upload that was cut off (hundreds of megabytes for a long input)
+// resumes where it stopped instead of starting over: the client asks for the offset the service has received, keeps
+// sending from there, and submits the complete upload with POST /jobs?upload=<id> (without a body). The service keeps
+// what it received of a part that was cut off. Uploads are held in memory until they're submitted or abandoned, and
+// count against their own limits, the same max_jobs and max_jobs_per_client as the jobs.
+//
+// Jobs are admitted within the ServiceLimits before they are queued: a job can't have more blocks than max_blocks
+// (413), the service holds at most max_jobs jobs (503) and each client at most max_jobs_per_client (429), so a single
+// client can't fill the queue with hours of work. A job counts until it's deleted, since its digest is kept until then.
+// Clients are told apart by their IP address, unless the service has API keys.
+//
+// With API keys (with_api_keys), every request needs the key of a client in an `Authorization: Bearer <key>` header,
+// or it's refused with a 401. Clients are then told apart by their key, each one only sees its own jobs, and a key can
+// come with its own limit on the jobs held. The keys are read from a file of `<client> <key> [<max jobs>]` lines:
+//
+// # Nightly integrity checks, which submit up to 16 jobs at once
+// integrity 6f1c9a0e2b7d4c35a8e1f0b9d2c6e4a7 16
+//
+// Keys are sent in the clear over plain HTTP, so the service should only be exposed beyond a trusted network behind a
+// TLS terminating proxy.
+//
+// A service either computes every job under a single server key (new), or serves several clients with their own keys
+// from a KeyRegistry (with_registry). Clients then upload their key once with PUT /keys/<client>, and submit their jobs
+// with POST /jobs?key=<client> to have them computed under it. With API keys, a client can only register its own key
+// (the client name of its API key), and its jobs are always computed under it.
+//
+// Refused requests are answered with an error status and a body such as
+// {"error":{"code":"job_not_found","message":"no such job"}}. The code is one of the ErrorCode strings, which stay the
+// same across versions (unlike the messages), so clients can tell the errors apart without parsing the messages.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tfhe::boolean::prelude::*;
+use crate::jobs::{JobId, JobQueue, JobStatus, Scheduler};
+use crate::key_registry::KeyRegistry;
+use crate::parameters::ParameterSet;
+pub use crate::protocol::ErrorCode;
+use crate::protocol::read_line;
+use crate::report::escape;
+use crate::serialization::{deserialize_from_limited, encrypted_block_size, serialize_into, PayloadKind, SerializationError};
+
+// A client may take this long to send the next part of its request, or to read the next part of the response
+const IO_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Number of headers accepted in a request
+const MAX_HEADERS: usize = 64;
+
+// Hand-written, so test_openapi checks that it describes exactly the ROUTES and the ErrorCodes
+const OPENAPI: &str = include_str!("openapi.json");
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Endpoint {
+    SubmitJob,
+    JobStatus,
+    Digest,
+    DeleteJob,
+    StartUpload,
+    UploadStatus,
+    AppendToUpload,
+    DeleteUpload,
+    RegisterKey,
+    RemoveKey,
+    OpenApi,
+}
+
+// Method and path of every endpoint, as in openapi.json. A request is answered by the endpoint of the route that
+// matches it, with the `{}` segments of the path as parameters
+const ROUTES: &[(&str, &str, Endpoint)] = &[
+    ("POST", "/jobs", Endpoint::SubmitJob),
+    ("GET", "/jobs/{id}", Endpoint::JobStatus),
+    ("GET", "/jobs/{id}/digest", Endpoint::Digest),
+    ("DELETE", "/jobs/{id}", Endpoint::DeleteJob),
+    ("POST", "/uploads", Endpoint::StartUpload),
+    ("GET", "/uploads/{id}", Endpoint::UploadStatus),
+    ("PATCH", "/uploads/{id}", Endpoint::AppendToUpload),
+    ("DELETE", "/uploads/{id}", Endpoint::DeleteUpload),
+    ("PUT", "/keys/{client}", Endpoint::RegisterKey),
+    ("DELETE", "/keys/{client}", Endpoint::RemoveKey),
+    ("GET", "/openapi.json", Endpoint::OpenApi),
+];
+
+// Longest part of a refused request's body that is still read before the response
+const MAX_UNREAD_BODY: u64 = 8 << 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ServiceLimits {
+    // Blocks of the padded input of a job
+    pub max_blocks: usize,
+    // Jobs held by the service, from their submission until they're deleted
+    pub max_jobs: usize,
+    // Jobs held for a single client
+    pub max_jobs_per_client: usize,
+}
+
+impl Default for ServiceLimits {
+    // Inputs of up to 64 KiB, and a few hours of work on a large machine
+    fn default() -> Self {
+        ServiceLimits { max_blocks: 1025, max_jobs: 64, max_jobs_per_client: 4 }
+    }
+}
+
+// Server keys the jobs are computed under
+enum ServiceKeys {
+    Single(Arc<ServerKey>),
+    Registry(KeyRegistry),
+}
+
+pub type UploadId = u64;
+
+// Input being uploaded in parts, which becomes a job once it's complete
+struct Upload {
+    client: String,
+    length: usize,
+    received: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Uploads {
+    held: HashMap<UploadId, Upload>,
+    next_id: UploadId,
+}
+
+pub struct JobService {
+    queue: JobQueue,
+    keys: ServiceKeys,
+    parameter_set: ParameterSet,
+    limits: ServiceLimits,
+    api_keys: Option<ApiKeys>,
+    // Client of every job held
+    clients: Mutex<HashMap<JobId, String>>,
+    uploads: Mutex<Uploads>,
+}
+
+impl JobService {
+    // Service running the jobs under the server key, preempting them after every block so a short job submitted later
+    // with a higher priority doesn't wait for a long one
+    pub fn new(sk: ServerKey, parameter_set: ParameterSet, limits: ServiceLimits) -> Self {
+        Self::with_keys(ServiceKeys::Single(Arc::new(sk)), parameter_set, limits)
+    }
+
+    // Service running the jobs of each client under the key it registered
+    pub fn with_registry(registry: KeyRegistry, parameter_set: ParameterSet, limits: ServiceLimits) -> Self {
+        Self::with_keys(ServiceKeys::Registry(registry), parameter_set, limits)
+    }
+
+    fn with_keys(keys: ServiceKeys, parameter_set: ParameterSet, limits: ServiceLimits) -> Self {
+        JobService {
+            queue: JobQueue::new(true),
+            keys,
+            parameter_set,
+            limits,
+            api_keys: None,
+            clients: Mutex::new(HashMap::new()),
+            uploads: Mutex::new(Uploads::default()),
+        }
+    }
+
+    // Only answers the requests authenticated with one of the keys
+    pub fn with_api_keys(self, api_keys: ApiKeys) -> Self {
+        JobService { api_keys: Some(api_keys), ..self }
+    }
+
+    pub fn queue(&self) -> &JobQueue {
+        &self.queue
+    }
+
+    // Answers the requests of the clients connecting to the listener, one thread per connection, while the scheduler
+    // runs the submitted jobs. Never returns
+    pub fn serve(&self, listener: TcpListener, scheduler: &Scheduler) {
+        thread::scope(|scope| {
+            scope.spawn(|| scheduler.serve_keyed(&self.queue));
+            for stream in listener.incoming().flatten() {
+                scope.spawn(move || self.handle(stream));
+            }
+        });
+    }
+
+    // Answers the request of one connection, which is then closed
+    pub fn handle(&self, stream: TcpStream) {
+        let timeouts = stream.set_read_timeout(Some(IO_TIMEOUT)).and_then(|_| stream.set_write_timeout(Some(IO_TIMEOUT)));
+        if timeouts.is_err() {
+            return;
+        }
+
+        let Ok(peer) = stream.peer_addr() else {
+            return;
+        };
+
+        let mut reader = BufReader::new(stream);
+        let response = match read_request(&mut reader) {
+            Ok(request) => {
+                let mut body = reader.by_ref().take(request.content_length().unwrap_or(0));
+                // The API description is public, so anyone can generate a client
+                let response = match self.client(&request, peer) {
+                    _ if matches!(route(&request), Ok((Endpoint::OpenApi, _))) => Response::json(200, OPENAPI.to_string()),
+                    Ok(client) => self.respond(&request, &client, &mut body),
+                    Err(response) => response,
+                };
+                // Closing the connection before reading the whole request resets it, and the client may lose the
+                // response, so the rest of a body that wasn't needed is read unless it's too long
+                if body.limit() <= MAX_UNREAD_BODY {
+                    let _ = io::copy(&mut body, &mut io::sink());
+                }
+                response
+            }
+            Err(e) => Response::error(ErrorCode::BadRequest, &format!("invalid request: {}", e)),
+        };
+        let _ = response.write_to(reader.get_mut());
+    }
+
+    // Client sending the request, or the response refusing it when it isn't authenticated
+    fn client(&self, request: &Request, peer: SocketAddr) -> Result<Client, Response> {
+        let Some(api_keys) = &self.api_keys else {
+            return Ok(Client { name: peer.ip().to_string(), max_jobs: self.limits.max_jobs_per_client });
+        };
+
+        let key = request.header("authorization").and_then(|value| value.strip_prefix("Bearer "));
+        match key.and_then(|key| api_keys.authenticate(key)) {
+            Some(api_key) => Ok(Client {
+                name: api_key.client.clone(),
+                max_jobs: api_key.max_jobs.unwrap_or(self.limits.max_jobs_per_client),
+            }),
+            None => Err(
+                Response::error(ErrorCode::Unauthorized, "missing or unknown API key")
+                    .with_header("WWW-Authenticate", "Bearer".to_string()),
+            ),
+        }
+    }
+
+    fn respond(&self, request: &Request, client: &Client, body: &mut impl Read) -> Response {
+        let (endpoint, id) = match route(request) {
+            Ok(route) => route,
+            Err(response) => return response,
+        };
+        match endpoint {
+            Endpoint::SubmitJob => self.submit(request, client, body),
+            Endpoint::JobStatus => self.with_job(id, client, |id| {
+                let status = self.queue.status(id)?;
+                Some(Response::json(200, status_json(id, status)))
+            }),
+            Endpoint::Digest => self.with_job(id, client, |id| match self.queue.status(id)? {
+                JobStatus::Done => Some(self.digest(id, request.header("range"))),
+                JobStatus::Failed { reason } => Some(Response::error(ErrorCode::JobFailed, &format!("the job failed: {}", reason))),
+                _ => Some(Response::error(ErrorCode::JobNotDone, "the job isn't done yet")),
+            }),
+            Endpoint::DeleteJob => self.with_job(id, client, |id| match self.queue.status(id)? {
+                JobStatus::Done | JobStatus::Failed { .. } => self.queue.forget(id).then(|| {
+                    self.clients.lock().unwrap().remove(&id);
+                    Response::empty(204)
+                }),
+                _ => Some(Response::error(ErrorCode::JobNotDone, "the job isn't done yet")),
+            }),
+            Endpoint::StartUpload => self.start_upload(request, client),
+            Endpoint::UploadStatus => {
+                self.with_upload(id, client, |id, upload| Ok(Response::json(200, upload_json(id, upload)))).unwrap_or_else(|e| e)
+            }
+            Endpoint::AppendToUpload => self.append_to_upload(id, request, client, body),
+            Endpoint::DeleteUpload => self.take_upload(id, client).map_or_else(|e| e, |_| Response::empty(204)),
+            Endpoint::RegisterKey => self.with_client_key(id, client, |registry| match registry.register(id, body) {
+                Ok(()) => Response::empty(204),
+                Err(e @ SerializationError::Io(_)) => Response::error(ErrorCode::Internal, &format!("couldn't store the key: {}", e)),
+                Err(e) => Response::error(ErrorCode::for_payload(&e), &format!("invalid server key: {}", e)),
+            }),
+            Endpoint::RemoveKey => self.with_client_key(id, client, |registry| match registry.remove(id) {
+                Ok(true) => Response::empty(204),
+                Ok(false) => Response::error(ErrorCode::KeyNotFound, "no such key"),
+                Err(e) => Response::error(ErrorCode::Internal, &format!("couldn't remove the key: {}", e)),
+            }),
+            Endpoint::OpenApi => Response::json(200, OPENAPI.to_string()),
+        }
+    }
+
+    fn submit(&self, request: &Request, client: &Client, body: &mut impl Read) -> Response {
+        let Ok(priority) = request.query("priority").map_or(Ok(0), str::parse::<u32>) else {
+            return Response::error(ErrorCode::BadRequest, "invalid priority");
+        };
+        if request.query("upload").is_none() && request.content_length().is_none() {
+            return Response::error(ErrorCode::LengthRequired, "the request needs a Content-Length");
+        }
+        let key = match self.job_key(request, client) {
+            Ok(key) => key,
+            Err(response) => return response,
+        };
+
+        // The input is the body, or an upload completed beforehand
+        let upload = match request.query("upload").map(|id| self.take_upload(id, client)) {
+            Some(Ok((id, upload))) if upload.received.len() < upload.length => {
+                let message = format!("the upload has {} of its {} bytes", upload.received.len(), upload.length);
+                self.uploads.lock().unwrap().held.insert(id, upload);
+                return Response::error(ErrorCode::UploadIncomplete, &message);
+            }
+            Some(Ok(upload)) => Some(upload),
+            Some(Err(response)) => return response,
+            None => None,
+        };
+        let (kind, limit) = (PayloadKind::EncryptedInput, self.input_limit());
+        let result = match &upload {
+            Some((_, upload)) => deserialize_from_limited(upload.received.as_slice(), kind, self.parameter_set, limit),
+            None => deserialize_from_limited(body, kind, self.parameter_set, limit),
+        };
+        let input: Vec<Ciphertext> = match result {
+            Ok(input) => input,
+            Err(e) => return Response::error(ErrorCode::for_payload(&e), &format!("invalid encrypted input: {}", e)),
+        };
+        if input.is_empty() || !input.len().is_multiple_of(512) {
+            return Response::error(ErrorCode::InvalidPayload, "the padded input isn't a whole number of blocks");
+        }
+        // Trivial ciphertexts are smaller than encrypted ones, so the size limit alone doesn't bound the block count
+        if input.len() / 512 > self.limits.max_blocks {
+            let message = format!("the input has more than {} blocks", self.limits.max_blocks);
+            return Response::error(ErrorCode::PayloadTooLarge, &message);
+        }
+
+        let mut clients = self.clients.lock().unwrap();
+        let refused = if clients.len() >= self.limits.max_jobs {
+            Some(Response::error(ErrorCode::CapacityExceeded, "the service is at capacity, try again later"))
+        } else if clients.values().filter(|owner| **owner == client.name).count() >= client.max_jobs {
+            Some(Response::error(ErrorCode::QuotaExceeded, "too many jobs held for this client, delete the ones that are done"))
+        } else {
+            None
+        };
+        if let Some(response) = refused {
+            // The upload is kept, for the client to submit it again once the service or its quota has room
+            if let Some((id, upload)) = upload {
+                self.uploads.lock().unwrap().held.insert(id, upload);
+            }
+            return response;
+        }
+        let id = self.queue.submit_with_key(priority, input, key);
+        clients.insert(id, client.name.clone());
+        Response::json(202, format!("{{\"id\":{}}}", id)).with_header("Location", format!("/jobs/{}", id))
+    }
+
+    // The frame of the ciphertexts of max_blocks blocks
+    fn input_limit(&self) -> u64 {
+        encrypted_block_size(512 * self.limits.max_blocks, self.parameter_set)
+    }
+
+    fn start_upload(&self, request: &Request, client: &Client) -> Response {
+        let Some(Ok(length)) = request.query("length").map(str::parse::<usize>) else {
+            return Response::error(ErrorCode::BadRequest, "the upload needs its length in bytes (?length=<bytes>)");
+        };
+        if length as u64 > self.input_limit() {
+            return Response::error(ErrorCode::PayloadTooLarge, &format!("inputs have at most {} bytes", self.input_limit()));
+        }
+
+        let mut uploads = self.uploads.lock().unwrap();
+        if uploads.held.len() >= self.limits.max_jobs {
+            return Response::error(ErrorCode::CapacityExceeded, "the service is at capacity, try again later");
+        }
+        if uploads.held.values().filter(|upload| upload.client == client.name).count() >= client.max_jobs {
+            return Response::error(ErrorCode::QuotaExceeded, "too many uploads held for this client, submit or delete them");
+        }
+        let id = uploads.next_id;
+        uploads.next_id += 1;
+        let upload = Upload { client: client.name.clone(), length, received: vec![] };
+        let response = Response::json(201, upload_json(id, &upload)).with_header("Location", format!("/uploads/{}", id));
+        uploads.held.insert(id, upload);
+        response
+    }
+
+    // Appends the part in the body to the upload, if it starts where the upload is. The part is read without holding
+    // the uploads, and what was received of a part that was cut off is kept
+    fn append_to_upload(&self, id: &str, request: &Request, client: &Client, body: &mut impl Read) -> Response {
+        let Ok(offset) = request.query("offset").map_or(Ok(0), str::parse::<usize>) else {
+            return Response::error(ErrorCode::BadRequest, "invalid offset");
+        };
+        let Some(length) = request.content_length() else {
+            return Response::error(ErrorCode::LengthRequired, "the request needs a Content-Length");
+        };
+        if let Err(response) = self.with_upload(id, client, |_, upload| check_part(upload, offset, length as usize)) {
+            return response;
+        }
+
+        let mut part = vec![];
+        let read = body.read_to_end(&mut part);
+        // Another part may have been appended in the meantime
+        self.with_upload(id, client, |id, upload| {
+            check_part(upload, offset, part.len())?;
+            upload.received.extend_from_slice(&part);
+            match read {
+                Ok(_) if part.len() as u64 == length => Ok(Response::json(200, upload_json(id, upload))),
+                _ => Err(Response::error(ErrorCode::BadRequest, "the part was cut off")),
+            }
+        })
+        .unwrap_or_else(|response| response)
+    }
+
+    // Result of f on the upload, or a 404 if there's no such upload (with API keys, of the client)
+    fn with_upload<T>(
+        &self,
+        id: &str,
+        client: &Client,
+        f: impl FnOnce(UploadId, &mut Upload) -> Result<T, Response>,
+    ) -> Result<T, Response> {
+        let mut uploads = self.uploads.lock().unwrap();
+        let upload = id.parse().ok().and_then(|id| Some((id, uploads.held.get_mut(&id)?)));
+        match upload {
+            Some((id, upload)) if self.sees(client, upload) => f(id, upload),
+            _ => Err(Response::error(ErrorCode::UploadNotFound, "no such upload")),
+        }
+    }
+
+    // Removes the upload from the service, returning it
+    fn take_upload(&self, id: &str, client: &Client) -> Result<(UploadId, Upload), Response> {
+        let mut uploads = self.uploads.lock().unwrap();
+        let id = id.parse().ok().filter(|id| uploads.held.get(id).is_some_and(|upload| self.sees(client, upload)));
+        id.and_then(|id| Some((id, uploads.held.remove(&id)?)))
+            .ok_or_else(|| Response::error(ErrorCode::UploadNotFound, "no such upload"))
+    }
+
+    // With API keys, clients only see their own uploads
+    fn sees(&self, client: &Client, upload: &Upload) -> bool {
+        self.api_keys.is_none() || upload.client == client.name
+    }
+
+    // The serialized digest, or its bytes from the offset of a `bytes=<offset>-` range. Other ranges are ignored, as
+    // HTTP allows, and answered with the whole digest
+    fn digest(&self, id: JobId, range: Option<&str>) -> Response {
+        let Some(digest) = self.queue.digest(id) else {
+            return Response::error(ErrorCode::JobNotFound, "no such job");
+        };
+
+        let mut body = vec![];
+        if let Err(e) = serialize_into(&mut body, PayloadKind::EncryptedDigest, &digest, self.parameter_set) {
+            return Response::error(ErrorCode::Internal, &format!("couldn't serialize the digest: {}", e));
+        }
+        let response = Response { content_type: "application/octet-stream", ..Response::empty(200) }
+            .with_header("Accept-Ranges", "bytes".to_string());
+
+        let offset = range.and_then(|range| range.strip_prefix("bytes=")?.strip_suffix('-')?.parse::<usize>().ok());
+        match offset {
+            Some(offset) if offset < body.len() => {
+                let content_range = format!("bytes {}-{}/{}", offset, body.len() - 1, body.len());
+                Response { status: 206, body: body.split_off(offset), ..response }.with_header("Content-Range", content_range)
+            }
+            _ => Response { body, ..response },
+        }
+    }
+
+    // Key to compute a job under: the single key, or the key of the client named by the key parameter (with API keys,
+    // the client's own key)
+    fn job_key(&self, request: &Request, client: &Client) -> Result<Arc<ServerKey>, Response> {
+        let registry = match &self.keys {
+            ServiceKeys::Single(sk) => return Ok(Arc::clone(sk)),
+            ServiceKeys::Registry(registry) => registry,
+        };
+
+        let id = match (request.query("key"), &self.api_keys) {
+            (Some(id), Some(_)) if id != client.name => {
+                return Err(Response::error(ErrorCode::Forbidden, "jobs can only use the client's own key"));
+            }
+            (_, Some(_)) => client.name.as_str(),
+            (Some(id), None) => id,
+            (None, None) => {
+                return Err(Response::error(ErrorCode::BadRequest, "the job needs the client whose key to use (?key=<client>)"));
+            }
+        };
+        match registry.get(id) {
+            Ok(Some(key)) => Ok(key),
+            Ok(None) => Err(Response::error(ErrorCode::KeyNotFound, &format!("no server key registered for {}", id))),
+            Err(e) => Err(Response::error(ErrorCode::Internal, &format!("couldn't load the key of {}: {}", id, e))),
+        }
+    }
+
+    // Response to a request about the key of a client, which needs a key registry. With API keys, clients can only
+    // change their own key
+    fn with_client_key(&self, id: &str, client: &Client, f: impl FnOnce(&KeyRegistry) -> Response) -> Response {
+        let ServiceKeys::Registry(registry) = &self.keys else {
+            return Response::error(ErrorCode::NotFound, "the service has a single server key");
+        };
+        if !KeyRegistry::is_valid_id(id) {
+            return Response::error(ErrorCode::BadRequest, "invalid client id");
+        }
+        if self.api_keys.is_some() && id != client.name {
+            return Response::error(ErrorCode::Forbidden, "clients can only change their own key");
+        }
+        f(registry)
+    }
+
+    // Response to a request about a job, or a 404 if the id isn't a job in the queue (f returns None then). With API
+    // keys, the jobs of the other clients don't exist either
+    fn with_job(&self, id: &str, client: &Client, f: impl FnOnce(JobId) -> Option<Response>) -> Response {
+        let visible = |id: &JobId| {
+            self.api_keys.is_none() || self.clients.lock().unwrap().get(id).is_some_and(|owner| *owner == client.name)
+        };
+        id.parse().ok().filter(visible).and_then(f).unwrap_or_else(|| Response::error(ErrorCode::JobNotFound, "no such job"))
+    }
+}
+
+// Client of a request, with the number of jobs it may hold
+struct Client {
+    name: String,
+    max_jobs: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ApiKey {
+    client: String,
+    key: String,
+    // Overrides ServiceLimits::max_jobs_per_client
+    max_jobs: Option<usize>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ApiKeys(Vec<ApiKey>);
+
+// Shortest key accepted, so that keys can't be guessed
+const MIN_KEY_LEN: usize = 16;
+
+impl ApiKeys {
+    // One `<client> <key> [<max jobs>]` line per key, with blank lines and `#` comments ignored
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut keys: Vec<ApiKey> = vec![];
+
+        for (number, line) in text.lines().enumerate() {
+            let fields: Vec<&str> = line.split('#').next().unwrap().split_whitespace().collect();
+            let (client, key, max_jobs) = match fields[..] {
+                [] => continue,
+                [client, key] => (client, key, None),
+                [client, key, max_jobs] => match max_jobs.parse() {
+                    Ok(max_jobs) => (client, key, Some(max_jobs)),
+                    Err(_) => return Err(format!("line {}: invalid job limit {}", number + 1, max_jobs)),
+                },
+                _ => return Err(format!("line {}: expected `<client> <key> [<max jobs>]`", number + 1)),
+            };
+
+            if !KeyRegistry::is_valid_id(client) {
+                return Err(format!("line {}: invalid client name {}", number + 1, client));
+            }
+            if key.len() < MIN_KEY_LEN {
+                return Err(format!("line {}: keys must have at least {} characters", number + 1, MIN_KEY_LEN));
+            }
+            if keys.iter().any(|api_key| api_key.key == key) {
+                return Err(format!("line {}: duplicate key", number + 1));
+            }
+            keys.push(ApiKey { client: client.to_string(), key: key.to_string(), max_jobs });
+        }
+
+        Ok(ApiKeys(keys))
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // Every key is compared in full, so the time taken doesn't tell how much of a guess was right
+    fn authenticate(&self, key: &str) -> Option<&ApiKey> {
+        self.0.iter().fold(None, |found, api_key| {
+            if constant_time_eq(api_key.key.as_bytes(), key.as_bytes()) { Some(api_key) } else { found }
+        })
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |difference, (x, y)| difference | (x ^ y)) == 0
+}
+
+fn status_json(id: JobId, status: JobStatus) -> String {
+    match status {
+        JobStatus::Queued { block, total_blocks } => format!(
+            "{{\"id\":{},\"state\":\"queued\",\"block\":{},\"total_blocks\":{}}}", id, block, total_blocks,
+        ),
+        JobStatus::Running { block, round, total_blocks } => format!(
+            "{{\"id\":{},\"state\":\"running\",\"block\":{},\"round\":{},\"total_blocks\":{}}}", id, block, round, total_blocks,
+        ),
+        JobStatus::Done => format!("{{\"id\":{},\"state\":\"done\"}}", id),
+        JobStatus::Failed { reason } => format!("{{\"id\":{},\"state\":\"failed\",\"reason\":\"{}\"}}", id, escape(&reason)),
+    }
+}
+
+// Endpoint of the request, with the parameter of its path (the job, upload or client id, or an empty string), or the
+// response refusing a path that isn't a route or a method that the path doesn't have
+fn route(request: &Request) -> Result<(Endpoint, &str), Response> {
+    let segments: Vec<&str> = request.path.split('/').skip(1).collect();
+    let mut path_found = false;
+
+    for (method, path, endpoint) in ROUTES {
+        let template: Vec<&str> = path.split('/').skip(1).collect();
+        let matches = template.len() == segments.len()
+            && template.iter().zip(&segments).all(|(part, segment)| part.starts_with('{') || part == segment);
+        if !matches {
+            continue;
+        }
+        if *method != request.method {
+            path_found = true;
+            continue;
+        }
+        let parameter = template.iter().zip(&segments).find(|(part, _)| part.starts_with('{'));
+        return Ok((*endpoint, parameter.map_or("", |(_, segment)| *segment)));
+    }
+
+    if path_found {
+        Err(Response::error(ErrorCode::MethodNotAllowed, "method not allowed"))
+    } else {
+        Err(Response::error(ErrorCode::NotFound, "no such endpoint"))
+    }
+}
+
+fn upload_json(id: UploadId, upload: &Upload) -> String {
+    format!("{{\"id\":{},\"offset\":{},\"length\":{}}}", id, upload.received.len(), upload.length)
+}
+
+// Refuses a part of `length` bytes from the offset unless it continues the upload, within its length
+fn check_part(upload: &Upload, offset: usize, length: usize) -> Result<(), Response> {
+    if offset != upload.received.len() {
+        let message = format!("the upload continues from offset {}", upload.received.len());
+        return Err(Response::error(ErrorCode::OffsetMismatch, &message));
+    }
+    if offset + length > upload.length {
+        return Err(Response::error(ErrorCode::BadRequest, "the part goes past the length of the upload"));
+    }
+    Ok(())
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: String,
+    // Header names are lowercase
+    headers: Vec<(String, String)>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(header, _)| header == name).map(|(_, value)| value.as_str())
+    }
+
+    fn query(&self, name: &str) -> Option<&str> {
+        self.query.split('&').find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+    }
+
+    fn content_length(&self) -> Option<u64> {
+        self.header("content-length")?.parse().ok()
+    }
+}
+
+// Reads the request line and the headers, leaving the body in the reader
+fn read_request(reader: &mut impl BufRead) -> io::Result<Request> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    let request_line = read_line(reader)?;
+    let mut parts = request_line.split(' ');
+    let (Some(method), Some(target), Some(_version), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid("malformed request line"));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let mut headers = vec![];
+    loop {
+        let line = read_line(reader)?;
+        if line.is_empty() {
+            break;
+        }
+        if headers.len() == MAX_HEADERS {
+            return Err(invalid("too many headers"));
+        }
+        let (name, value) = line.split_once(':').ok_or_else(|| invalid("malformed header"))?;
+        headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+    }
+
+    Ok(Request { method: method.to_string(), path: path.to_string(), query: query.to_string(), headers })
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn empty(status: u16) -> Self {
+        Response { status, content_type: "text/plain", headers: vec![], body: vec![] }
+    }
+
+    fn json(status: u16, body: String) -> Self {
+        Response { content_type: "application/json", body: body.into_bytes(), ..Response::empty(status) }
+    }
+
+    fn error(code: ErrorCode, message: &str) -> Self {
+        let body = format!("{{\"error\":{{\"code\":\"{}\",\"message\":\"{}\"}}}}", code, escape(message));
+        Response::json(code.status(), body)
+    }
+
+    fn with_header(mut self, name: &'static str, value: String) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+
+    fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.status, reason(self.status), self.content_type, self.body.len(),
+        );
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+
+        writer.write_all(head.as_bytes())?;
+        writer.write_all(&self.body)?;
+        writer.flush()
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        206 => "Partial Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        429 => "Too Many Requests",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Shutdown;
+    use std::sync::Arc;
+    use sha2::{Digest, Sha256};
+    use crate::padding::pad_sha256_data;
+    use crate::serialization::deserialize_from;
+    use crate::test_keys::test_keys;
+
+    // Sends a request to the service and returns the status and the body of the response
+    fn request(address: &str, head: &str, body: &[u8]) -> (u16, Vec<u8>) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(format!("{}\r\nContent-Length: {}\r\n\r\n", head, body.len()).as_bytes()).unwrap();
+        stream.write_all(body).unwrap();
+
+        let mut response = vec![];
+        stream.read_to_end(&mut response).unwrap();
+        let end = response.windows(4).position(|window| window == b"\r\n\r\n").unwrap();
+        let status = String::from_utf8_lossy(&response[9..12]).parse().unwrap();
+        (status, response[end + 4..].to_vec())
+    }
+
+    fn start(service: JobService) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let service = Arc::new(service);
+        thread::spawn(move || service.serve(listener, &Scheduler::new(1, 2).unwrap()));
+        address
+    }
+
+    #[test]
+    fn test_job_service() {
+        let (ck, sk) = test_keys();
+        let address = start(JobService::new(sk.clone(), ParameterSet::Default, ServiceLimits::default()));
+
+        let input: Vec<Ciphertext> = pad_sha256_data(b"abc").iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+        let mut payload = vec![];
+        serialize_into(&mut payload, PayloadKind::EncryptedInput, &input, ParameterSet::Default).unwrap();
+
+        let (status, body) = request(&address, "POST /jobs?priority=2 HTTP/1.1", &payload);
+        assert_eq!((status, body.as_slice()), (202, b"{\"id\":0}".as_slice()));
+
+        // Polls until the job is done, then downloads the digest twice and deletes the job
+        loop {
+            let (status, body) = request(&address, "GET /jobs/0 HTTP/1.1", b"");
+            assert_eq!(status, 200);
+            if body == b"{\"id\":0,\"state\":\"done\"}" {
+                break;
+            }
+            assert!(body.starts_with(b"{\"id\":0,\"state\":\"queued\"") || body.starts_with(b"{\"id\":0,\"state\":\"running\""));
+            thread::sleep(Duration::from_millis(10));
+        }
+        for _ in 0..2 {
+            let (status, body) = request(&address, "GET /jobs/0/digest HTTP/1.1", b"");
+            assert_eq!(status, 200);
+            let digest: Vec<Ciphertext> = deserialize_from(body.as_slice(), PayloadKind::EncryptedDigest, ParameterSet::Default).unwrap();
+            let bytes: Vec<u8> = digest.chunks(8).map(|byte| byte.iter().fold(0, |acc, bit| (acc << 1) | ck.decrypt(bit) as u8)).collect();
+            assert_eq!(bytes, Sha256::digest(b"abc").to_vec());
+        }
+        // Resumes a download after its first 100 bytes, and ignores ranges past the end
+        let (_, full) = request(&address, "GET /jobs/0/digest HTTP/1.1", b"");
+        assert_eq!(request(&address, "GET /jobs/0/digest HTTP/1.1\r\nRange: bytes=100-", b""), (206, full[100..].to_vec()));
+        let past_end = format!("GET /jobs/0/digest HTTP/1.1\r\nRange: bytes={}-", full.len());
+        assert_eq!(request(&address, &past_end, b""), (200, full));
+        assert_eq!(request(&address, "DELETE /jobs/0 HTTP/1.1", b"").0, 204);
+        assert_eq!(request(&address, "GET /jobs/0 HTTP/1.1", b"").0, 404);
+    }
+
+    #[test]
+    fn test_uploads() {
+        let (ck, sk) = test_keys();
+        let address = start(JobService::new(sk.clone(), ParameterSet::Default, ServiceLimits::default()));
+
+        let input: Vec<Ciphertext> = pad_sha256_data(b"abc").iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+        let mut payload = vec![];
+        serialize_into(&mut payload, PayloadKind::EncryptedInput, &input, ParameterSet::Default).unwrap();
+        let upload = |offset: usize| format!("{{\"id\":0,\"offset\":{},\"length\":{}}}", offset, payload.len()).into_bytes();
+
+        assert_eq!(request(&address, &format!("POST /uploads?length={} HTTP/1.1", payload.len()), b""), (201, upload(0)));
+
+        // What was received of a part cut off after 100 of its 1000 bytes is kept
+        let mut stream = TcpStream::connect(&address).unwrap();
+        stream.write_all(b"PATCH /uploads/0?offset=0 HTTP/1.1\r\nContent-Length: 1000\r\n\r\n").unwrap();
+        stream.write_all(&payload[..100]).unwrap();
+        stream.shutdown(Shutdown::Write).unwrap();
+        let mut response = vec![];
+        stream.read_to_end(&mut response).unwrap();
+        assert!(response.starts_with(b"HTTP/1.1 400 "));
+        assert_eq!(request(&address, "GET /uploads/0 HTTP/1.1", b""), (200, upload(100)));
+
+        // Parts that don't continue the upload are refused, and it's only submitted once it's complete
+        assert_eq!(request(&address, "PATCH /uploads/0?offset=0 HTTP/1.1", &payload[..100]).0, 409);
+        assert_eq!(request(&address, "POST /jobs?upload=0 HTTP/1.1", b"").0, 409);
+        assert_eq!(request(&address, "PATCH /uploads/0?offset=100 HTTP/1.1", &payload[100..]), (200, upload(payload.len())));
+        assert_eq!(request(&address, "POST /jobs?upload=0 HTTP/1.1", b""), (202, b"{\"id\":0}".to_vec()));
+        assert_eq!(request(&address, "GET /uploads/0 HTTP/1.1", b"").0, 404);
+
+        loop {
+            let (status, body) = request(&address, "GET /jobs/0/digest HTTP/1.1", b"");
+            if status == 200 {
+                let digest: Vec<Ciphertext> = deserialize_from(body.as_slice(), PayloadKind::EncryptedDigest, ParameterSet::Default).unwrap();
+                let bytes: Vec<u8> = digest.chunks(8).map(|byte| byte.iter().fold(0, |acc, bit| (acc << 1) | ck.decrypt(bit) as u8)).collect();
+                assert_eq!(bytes, Sha256::digest(b"abc").to_vec());
+                break;
+            }
+            assert_eq!(status, 409);
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_invalid_requests() {
+        let (_, sk) = test_keys();
+        let limits = ServiceLimits { max_blocks: 2, ..ServiceLimits::default() };
+        let address = start(JobService::new(sk.clone(), ParameterSet::Default, limits));
+
+        let not_found = request(&address, "GET /jobs/7 HTTP/1.1", b"");
+        assert_eq!(not_found, (404, b"{\"error\":{\"code\":\"job_not_found\",\"message\":\"no such job\"}}".to_vec()));
+        assert_eq!(request(&address, "GET /jobs/x/digest HTTP/1.1", b"").0, 404);
+        assert_eq!(request(&address, "PUT /jobs HTTP/1.1", b"").0, 405);
+        assert_eq!(request(&address, "GET /keys HTTP/1.1", b"").0, 404);
+        assert_eq!(request(&address, "POST /jobs?priority=high HTTP/1.1", b"").0, 400);
+        assert_eq!(request(&address, "POST /jobs HTTP/1.1", b"garbage").0, 400);
+
+        // Inputs of more than 2 blocks, and inputs that aren't whole blocks
+        let mut payload = vec![];
+        let input = vec![sk.trivial_encrypt(false); 1536];
+        serialize_into(&mut payload, PayloadKind::EncryptedInput, &input, ParameterSet::Default).unwrap();
+        assert_eq!(request(&address, "POST /jobs HTTP/1.1", &payload).0, 413);
+
+        let mut payload = vec![];
+        serialize_into(&mut payload, PayloadKind::EncryptedInput, &input[..500].to_vec(), ParameterSet::Default).unwrap();
+        assert_eq!(request(&address, "POST /jobs HTTP/1.1", &payload).0, 400);
+
+        // An input for another parameter set
+        let mut payload = vec![];
+        serialize_into(&mut payload, PayloadKind::EncryptedInput, &input[..512].to_vec(), ParameterSet::TfheLib).unwrap();
+        let (status, body) = request(&address, "POST /jobs HTTP/1.1", &payload);
+        assert_eq!(status, 400);
+        assert!(body.starts_with(b"{\"error\":{\"code\":\"incompatible_version\""));
+    }
+
+    #[test]
+    fn test_admission() {
+        let (_, sk) = test_keys();
+        let limits = ServiceLimits { max_blocks: 2, max_jobs: 3, max_jobs_per_client: 2 };
+        let service = JobService::new(sk.clone(), ParameterSet::Default, limits);
+
+        let mut payload = vec![];
+        serialize_into(&mut payload, PayloadKind::EncryptedInput, &vec![sk.trivial_encrypt(false); 512], ParameterSet::Default).unwrap();
+        let request = Request {
+            method: "POST".to_string(),
+            path: "/jobs".to_string(),
+            query: String::new(),
+            headers: vec![("content-length".to_string(), payload.len().to_string())],
+        };
+        let submit = |name: &str| {
+            let client = Client { name: name.to_string(), max_jobs: limits.max_jobs_per_client };
+            service.respond(&request, &client, &mut payload.as_slice()).status
+        };
+
+        // Nothing runs the jobs, so they are all still held
+        assert_eq!([submit("a"), submit("a"), submit("a")], [202, 202, 429]);
+        assert_eq!([submit("b"), submit("c")], [202, 503]);
+        assert_eq!(service.queue().status(2), Some(JobStatus::Queued { block: 0, total_blocks: 1 }));
+    }
+
+    #[test]
+    fn test_api_keys() {
+        let (_, sk) = test_keys();
+        let text = "# clients\nalice 0123456789abcdef\nbob fedcba9876543210 1 # batch jobs\n";
+        let api_keys = ApiKeys::parse(text).unwrap();
+        assert_eq!(api_keys.authenticate("fedcba9876543210").map(|api_key| api_key.max_jobs), Some(Some(1)));
+        assert!(api_keys.authenticate("fedcba987654321").is_none());
+
+        assert_eq!(ApiKeys::parse("alice short").unwrap_err(), "line 1: keys must have at least 16 characters");
+        assert_eq!(ApiKeys::parse("\nalice 0123456789abcdef x").unwrap_err(), "line 2: invalid job limit x");
+        assert_eq!(ApiKeys::parse("alice").unwrap_err(), "line 1: expected `<client> <key> [<max jobs>]`");
+        assert_eq!(ApiKeys::parse("a 0123456789abcdef\nb 0123456789abcdef").unwrap_err(), "line 2: duplicate key");
+
+        let address = start(JobService::new(sk.clone(), ParameterSet::Default, ServiceLimits::default()).with_api_keys(api_keys));
+        let mut payload = vec![];
+        serialize_into(&mut payload, PayloadKind::EncryptedInput, &vec![sk.trivial_encrypt(false); 512], ParameterSet::Default).unwrap();
+
+        assert_eq!(request(&address, "POST /jobs HTTP/1.1", &payload).0, 401);
+        assert_eq!(request(&address, "POST /jobs HTTP/1.1\r\nAuthorization: Bearer 0123456789abcdeg", &payload).0, 401);
+
+        // Bob may only hold one job, and neither client sees the jobs of the other
+        let bob = "Authorization: Bearer fedcba9876543210";
+        assert_eq!(request(&address, &format!("POST /jobs HTTP/1.1\r\n{}", bob), &payload), (202, b"{\"id\":0}".to_vec()));
+        assert_eq!(request(&address, &format!("POST /jobs HTTP/1.1\r\n{}", bob), &payload).0, 429);
+        assert_eq!(request(&address, &format!("GET /jobs/0 HTTP/1.1\r\n{}", bob), b"").0, 200);
+        assert_eq!(request(&address, "GET /jobs/0 HTTP/1.1\r\nAuthorization: Bearer 0123456789abcdef", b"").0, 404);
+    }
+
+    #[test]
+    fn test_status_json() {
+        assert_eq!(status_json(3, JobStatus::Done), "{\"id\":3,\"state\":\"done\"}");
+        let failed = status_json(3, JobStatus::Failed { reason: "index \"7\" out of range".to_string() });
+        assert_eq!(failed, "{\"id\":3,\"state\":\"failed\",\"reason\":\"index \\\"7\\\" out of range\"}");
+    }
+
+    #[test]
+    fn test_openapi() {
+        let (_, sk) = test_keys();
+        let api_keys = ApiKeys::parse("alice 0123456789abcdef").unwrap();
+        let address = start(JobService::new(sk, ParameterSet::Default, ServiceLimits::default()).with_api_keys(api_keys));
+
+        // Served without an API key
+        let (status, body) = request(&address, "GET /openapi.json HTTP/1.1", b"");
+        assert_eq!((status, body.as_slice()), (200, OPENAPI.as_bytes()));
+
+        // Every route is described, and nothing else
+        let mut described = vec![];
+        let mut path = "";
+        for line in OPENAPI[OPENAPI.find("\"paths\"").unwrap()..].lines() {
+            let Some(key) = line.trim_start().strip_prefix('"').and_then(|line| line.strip_suffix("\": {")) else {
+                continue;
+            };
+            match line.len() - line.trim_start().len() {
+                4 => path = key,
+                6 => described.push((key.to_ascii_uppercase(), path.to_string())),
+                _ => {}
+            }
+        }
+        let mut routes: Vec<(String, String)> = ROUTES.iter().map(|(method, path, _)| (method.to_string(), path.to_string())).collect();
+        described.sort();
+        routes.sort();
+        assert_eq!(described, routes);
+
+        // Every error code is described, and nothing else
+        let codes = &OPENAPI[OPENAPI.find("\"code\": {").unwrap()..];
+        let codes = &codes[codes.find('[').unwrap() + 1..codes.find(']').unwrap()];
+        let mut described: Vec<&str> = codes.split(',').map(|code| code.trim().trim_matches('"')).collect();
+        let mut known: Vec<&str> = ErrorCode::ALL.iter().map(|code| code.code()).collect();
+        described.sort();
+        known.sort();
+        assert_eq!(described, known);
+        for code in ErrorCode::ALL {
+            // The 405 of the methods an endpoint doesn't have isn't described, as OpenAPI only describes the others
+            let described = OPENAPI.contains(&format!("\"{}\": {{ \"$ref\"", code.status()));
+            assert!(described || *code == ErrorCode::MethodNotAllowed, "{} isn't described", code.status());
+        }
+    }
+
+    #[test]
+    fn test_key_registry() {
+        let (ck, sk) = test_keys();
+        let dir = std::env::temp_dir().join(format!("sha256-fhe-service-keys-{}", std::process::id()));
+        let registry = KeyRegistry::open(&dir, ParameterSet::Default).unwrap();
+        let api_keys = ApiKeys::parse("alice 0123456789abcdef\nbob fedcba9876543210").unwrap();
+        let address = start(JobService::with_registry(registry, ParameterSet::Default, ServiceLimits::default()).with_api_keys(api_keys));
+        let (alice, bob) = ("Authorization: Bearer 0123456789abcdef", "Authorization: Bearer fedcba9876543210");
+
+        let input: Vec<Ciphertext> = pad_sha256_data(b"abc").iter().map(|bit| ck.encrypt(*bit)).collect();
+        let mut payload = vec![];
+        serialize_into(&mut payload, PayloadKind::EncryptedInput, &input, ParameterSet::Default).unwrap();
+        let mut key = vec![];
+        serialize_into(&mut key, PayloadKind::CompressedServerKey, &CompressedServerKey::new(&ck), ParameterSet::Default).unwrap();
+
+        // Jobs need a registered key, and clients can only register and use their own
+        assert_eq!(request(&address, &format!("POST /jobs HTTP/1.1\r\n{}", alice), &payload).0, 404);
+        assert_eq!(request(&address, &format!("PUT /keys/alice HTTP/1.1\r\n{}", bob), &key).0, 403);
+        assert_eq!(request(&address, &format!("PUT /keys/alice HTTP/1.1\r\n{}", alice), &key[..100]).0, 400);
+        assert_eq!(request(&address, &format!("PUT /keys/alice HTTP/1.1\r\n{}", alice), &key).0, 204);
+        assert_eq!(request(&address, &format!("POST /jobs?key=alice HTTP/1.1\r\n{}", bob), &payload).0, 403);
+        assert_eq!(request(&address, &format!("POST /jobs HTTP/1.1\r\n{}", alice), &payload).0, 202);
+
+        loop {
+            let (status, body) = request(&address, &format!("GET /jobs/0/digest HTTP/1.1\r\n{}", alice), b"");
+            if status == 200 {
+                let digest: Vec<Ciphertext> = deserialize_from(body.as_slice(), PayloadKind::EncryptedDigest, ParameterSet::Default).unwrap();
+                let bytes: Vec<u8> = digest.chunks(8).map(|byte| byte.iter().fold(0, |acc, bit| (acc << 1) | ck.decrypt(bit) as u8)).collect();
+                assert_eq!(bytes, Sha256::digest(b"abc").to_vec());
+                break;
+            }
+            assert_eq!(status, 409);
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(request(&address, &format!("DELETE /keys/alice HTTP/1.1\r\n{}", alice), b"").0, 204);
+        assert_eq!(request(&address, &format!("DELETE /keys/alice HTTP/1.1\r\n{}", alice), b"").0, 404);
+        std::fs::remove_dir_all(dir).unwrap();
+
+        // A service with a single key has no keys to manage
+        let address = start(JobService::new(sk, ParameterSet::Default, ServiceLimits::default()));
+        assert_eq!(request(&address, "PUT /keys/alice HTTP/1.1", &key).0, 404);
+    }
+}
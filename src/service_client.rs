@@ -0,0 +1,523 @@
+// This module is a client for the HTTP endpoints of the service module, so a Rust application can submit its jobs and
+// download their digests without writing the requests and the payload framing itself. Like the service, it's built on
+// the standard library, with one connection per request, and it only needs the "client" feature:
+//
+// let client = ServiceClient::new("hashing.internal:8080", ParameterSet::Default).with_api_key(key);
+// let id = client.submit(&encrypt_bools(&pad_sha256_data(message), &ck), 0)?;
+// let digest = client.wait(id, Duration::from_secs(10))?;
+// client.delete(id)?;
+//
+// Refused requests fail with a ClientError::Service carrying the ErrorCode of the response, so callers can e.g. retry
+// later on CapacityExceeded rather than matching on messages.
+//
+// Transfers of large inputs over real networks fail routinely, so requests have connect and I/O timeouts, and those
+// that fail with a transient error are retried with an exponential backoff (see RetryPolicy). Requests are only
+// retried when that's safe: the status and digest requests and the deletions can be repeated, but a submission whose
+// connection broke after it was sent may have been queued, so it's only retried when the service couldn't be reached
+// or was at capacity. Inputs are uploaded in parts (see the service module) before they're submitted, so an upload that
+// was cut off resumes from the offset the service received, and a digest download from the bytes already received.
+//
+// The client is blocking on purpose: its requests spend their time transferring payloads of megabytes or waiting for a
+// job of minutes, and an async front end would tie the crate to one runtime for little gain. Async applications call
+// it from a blocking task (e.g. tokio's spawn_blocking), one per job.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use std::{error, fmt, thread};
+use tfhe::boolean::prelude::*;
+use crate::parameters::ParameterSet;
+use crate::protocol::{read_line, ErrorCode, JobId, JobStatus};
+use crate::serialization::{deserialize_from_limited, encrypted_digest_size, serialize_into, PayloadKind, SerializationError};
+
+#[derive(Debug)]
+pub enum ClientError {
+    // The service couldn't be reached, so the request wasn't sent
+    Connect(io::Error),
+    Io(io::Error),
+    Payload(SerializationError),
+    // Request refused by the service. The code is None if this version of the crate doesn't know it
+    Service { status: u16, code: Option<ErrorCode>, message: String },
+    // Response that isn't one of the service
+    InvalidResponse(String),
+}
+
+impl ClientError {
+    // Code of the error of a request refused by the service
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            ClientError::Service { code, .. } => *code,
+            _ => None,
+        }
+    }
+
+    // Whether the request may succeed if sent again. Requests that can't be repeated safely (a submission, which may
+    // have been queued) are only retried if they weren't received
+    fn is_transient(&self, repeatable: bool) -> bool {
+        match self {
+            ClientError::Connect(_) => true,
+            ClientError::Io(_) | ClientError::InvalidResponse(_) => repeatable,
+            ClientError::Service { code, .. } => *code == Some(ErrorCode::CapacityExceeded),
+            ClientError::Payload(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientError::Connect(e) => write!(f, "couldn't connect to the service: {}", e),
+            ClientError::Io(e) => write!(f, "I/O error: {}", e),
+            ClientError::Payload(e) => write!(f, "invalid payload: {}", e),
+            ClientError::Service { status, code: Some(code), message } => write!(f, "{} ({}): {}", code, status, message),
+            ClientError::Service { status, code: None, message } => write!(f, "error {}: {}", status, message),
+            ClientError::InvalidResponse(message) => write!(f, "invalid response: {}", message),
+        }
+    }
+}
+
+impl error::Error for ClientError {}
+
+impl From<io::Error> for ClientError {
+    fn from(e: io::Error) -> Self {
+        ClientError::Io(e)
+    }
+}
+
+impl From<SerializationError> for ClientError {
+    fn from(e: SerializationError) -> Self {
+        ClientError::Payload(e)
+    }
+}
+
+// How many times a request is sent before its error is returned, and how long to wait between the attempts: the
+// backoff starts at initial_backoff and doubles after every attempt, up to max_backoff
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    // Every request is sent once
+    pub const NONE: RetryPolicy = RetryPolicy { max_attempts: 1, initial_backoff: Duration::ZERO, max_backoff: Duration::ZERO };
+}
+
+impl Default for RetryPolicy {
+    // About a minute of retries
+    fn default() -> Self {
+        RetryPolicy { max_attempts: 6, initial_backoff: Duration::from_secs(1), max_backoff: Duration::from_secs(30) }
+    }
+}
+
+pub struct ServiceClient {
+    // host:port of the service
+    address: String,
+    parameter_set: ParameterSet,
+    api_key: Option<String>,
+    connect_timeout: Duration,
+    // Longest wait for the service to take the next part of a request, or to send the next part of a response
+    io_timeout: Duration,
+    retries: RetryPolicy,
+}
+
+impl ServiceClient {
+    pub fn new(address: impl Into<String>, parameter_set: ParameterSet) -> Self {
+        ServiceClient {
+            address: address.into(),
+            parameter_set,
+            api_key: None,
+            connect_timeout: Duration::from_secs(10),
+            io_timeout: Duration::from_secs(60),
+            retries: RetryPolicy::default(),
+        }
+    }
+
+    // Authenticates the requests with the key, for a service with API keys
+    pub fn with_api_key(self, api_key: impl Into<String>) -> Self {
+        ServiceClient { api_key: Some(api_key.into()), ..self }
+    }
+
+    pub fn with_timeouts(self, connect_timeout: Duration, io_timeout: Duration) -> Self {
+        ServiceClient { connect_timeout, io_timeout, ..self }
+    }
+
+    pub fn with_retries(self, retries: RetryPolicy) -> Self {
+        ServiceClient { retries, ..self }
+    }
+
+    // Registers the server key of the client, for a service with a key registry
+    pub fn register_key(&self, client: &str, key: &CompressedServerKey) -> Result<(), ClientError> {
+        let mut body = vec![];
+        serialize_into(&mut body, PayloadKind::CompressedServerKey, key, self.parameter_set)?;
+        self.request("PUT", &format!("/keys/{}", client), &body)?;
+        Ok(())
+    }
+
+    pub fn remove_key(&self, client: &str) -> Result<(), ClientError> {
+        self.delete_request(&format!("/keys/{}", client), ErrorCode::KeyNotFound)
+    }
+
+    // Submits the encrypted padded input, computed under the key of the service (or the client's own key, with API
+    // keys and a key registry)
+    pub fn submit(&self, padded_input: &[Ciphertext], priority: u32) -> Result<JobId, ClientError> {
+        self.submit_to(&format!("/jobs?priority={}", priority), padded_input)
+    }
+
+    // Same as submit, computed under the key that the client registered in the key registry of the service
+    pub fn submit_with_key(&self, padded_input: &[Ciphertext], priority: u32, client: &str) -> Result<JobId, ClientError> {
+        self.submit_to(&format!("/jobs?priority={}&key={}", priority, client), padded_input)
+    }
+
+    fn submit_to(&self, target: &str, padded_input: &[Ciphertext]) -> Result<JobId, ClientError> {
+        let mut payload = vec![];
+        serialize_into(&mut payload, PayloadKind::EncryptedInput, padded_input, self.parameter_set)?;
+        let upload = self.upload(&payload)?;
+
+        let target = format!("{}&upload={}", target, upload);
+        let response = self.retrying(false, || self.send("POST", &target, &[], &mut vec![]))?;
+        json_number(&response, "id")
+    }
+
+    // Uploads the payload and returns the id of the upload. An attempt that fails sends the rest of the payload from
+    // the offset the service received
+    fn upload(&self, payload: &[u8]) -> Result<u64, ClientError> {
+        let start = format!("/uploads?length={}", payload.len());
+        let response = self.retrying(false, || self.send("POST", &start, &[], &mut vec![]))?;
+        let id = json_number(&response, "id")?;
+
+        let target = format!("/uploads/{}", id);
+        let mut attempts = 0;
+        self.retrying(true, || {
+            attempts += 1;
+            let offset = match attempts {
+                1 => 0,
+                _ => json_number(&self.send("GET", &target, &[], &mut vec![])?, "offset")? as usize,
+            };
+            match payload.get(offset..) {
+                Some([]) => Ok(vec![]),
+                Some(rest) => self.send("PATCH", &format!("{}?offset={}", target, offset), rest, &mut vec![]),
+                None => Err(ClientError::InvalidResponse("offset past the end of the upload".to_string())),
+            }
+        })?;
+        Ok(id)
+    }
+
+    pub fn status(&self, id: JobId) -> Result<JobStatus, ClientError> {
+        let response = self.request("GET", &format!("/jobs/{}", id), &[])?;
+        parse_status(&response).ok_or_else(|| ClientError::InvalidResponse("invalid job status".to_string()))
+    }
+
+    // Digest of a job that is done, which the service keeps until the job is deleted. A download that is cut off is
+    // resumed from the bytes received so far
+    pub fn digest(&self, id: JobId) -> Result<Vec<Ciphertext>, ClientError> {
+        let target = format!("/jobs/{}/digest", id);
+        let mut received = vec![];
+        let response = self.retrying(true, || self.send("GET", &target, &[], &mut received))?;
+
+        let limit = encrypted_digest_size(self.parameter_set);
+        Ok(deserialize_from_limited(response.as_slice(), PayloadKind::EncryptedDigest, self.parameter_set, limit)?)
+    }
+
+    pub fn delete(&self, id: JobId) -> Result<(), ClientError> {
+        self.delete_request(&format!("/jobs/{}", id), ErrorCode::JobNotFound)
+    }
+
+    // A deletion that is retried may have been done by an attempt whose response was lost, in which case the retry
+    // fails with the `gone` code
+    fn delete_request(&self, target: &str, gone: ErrorCode) -> Result<(), ClientError> {
+        let mut attempts = 0;
+        self.retrying(true, || {
+            attempts += 1;
+            match self.send("DELETE", target, &[], &mut vec![]) {
+                Err(e) if attempts > 1 && e.code() == Some(gone) => Ok(vec![]),
+                result => result,
+            }
+        })?;
+        Ok(())
+    }
+
+    // Polls the status of the job every poll_interval until it's done, and downloads its digest. A job that failed
+    // fails with the job_failed error of its digest request
+    pub fn wait(&self, id: JobId, poll_interval: Duration) -> Result<Vec<Ciphertext>, ClientError> {
+        while let JobStatus::Queued { .. } | JobStatus::Running { .. } = self.status(id)? {
+            thread::sleep(poll_interval);
+        }
+        self.digest(id)
+    }
+
+    // Body of the response to a request that can be repeated, or the error of the service
+    fn request(&self, method: &str, target: &str, body: &[u8]) -> Result<Vec<u8>, ClientError> {
+        self.retrying(true, || self.send(method, target, body, &mut vec![]))
+    }
+
+    // Result of the first attempt that doesn't fail with a transient error, or of the last one
+    fn retrying(
+        &self,
+        repeatable: bool,
+        mut attempt: impl FnMut() -> Result<Vec<u8>, ClientError>,
+    ) -> Result<Vec<u8>, ClientError> {
+        let mut backoff = self.retries.initial_backoff;
+        for _ in 1..self.retries.max_attempts {
+            match attempt() {
+                Err(e) if e.is_transient(repeatable) => {
+                    thread::sleep(backoff);
+                    backoff = (backoff * 2).min(self.retries.max_backoff);
+                }
+                result => return result,
+            }
+        }
+        attempt()
+    }
+
+    // Sends the request once and returns the body of the response. The body is received into `received`, so that a
+    // response cut off in a previous attempt is resumed from its end (with a Range request) rather than sent again
+    fn send(&self, method: &str, target: &str, body: &[u8], received: &mut Vec<u8>) -> Result<Vec<u8>, ClientError> {
+        let mut stream = self.connect()?;
+        stream.set_read_timeout(Some(self.io_timeout))?;
+        stream.set_write_timeout(Some(self.io_timeout))?;
+
+        let mut head = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            method, target, self.address, body.len(),
+        );
+        if let Some(api_key) = &self.api_key {
+            head.push_str(&format!("Authorization: Bearer {}\r\n", api_key));
+        }
+        if !received.is_empty() {
+            head.push_str(&format!("Range: bytes={}-\r\n", received.len()));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(body)?;
+        stream.flush()?;
+
+        let status = read_response(&mut BufReader::new(stream), received)?;
+        let body = std::mem::take(received);
+        if (200..300).contains(&status) {
+            return Ok(body);
+        }
+        let code = json_field(&body, "code").and_then(|code| ErrorCode::from_code(&code));
+        let message = json_field(&body, "message").unwrap_or_else(|| String::from_utf8_lossy(&body).into_owned());
+        Err(ClientError::Service { status, code, message })
+    }
+
+    fn connect(&self) -> Result<TcpStream, ClientError> {
+        let mut last_error = io::Error::new(io::ErrorKind::NotFound, format!("{} has no address", self.address));
+        for address in self.address.to_socket_addrs().map_err(ClientError::Connect)? {
+            match TcpStream::connect_timeout(&address, self.connect_timeout) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(ClientError::Connect(last_error))
+    }
+}
+
+// Reads a response whose body is Content-Length bytes long and returns its status. The body is appended to `body` if
+// the response is the rest of it (206), and replaces it otherwise, including when the connection breaks in the middle
+fn read_response(reader: &mut impl BufRead, body: &mut Vec<u8>) -> Result<u16, ClientError> {
+    let invalid = |message: &str| ClientError::InvalidResponse(message.to_string());
+
+    let status_line = read_line(reader)?;
+    let status = status_line.split(' ').nth(1).and_then(|status| status.parse().ok());
+    let status = status.ok_or_else(|| invalid("malformed status line"))?;
+
+    let mut content_length = None;
+    loop {
+        let line = read_line(reader)?;
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':').ok_or_else(|| invalid("malformed header"))?;
+        if name.trim().eq_ignore_ascii_case("content-length") {
+            content_length = Some(value.trim().parse().map_err(|_| invalid("invalid Content-Length"))?);
+        }
+    }
+
+    let content_length: u64 = content_length.ok_or_else(|| invalid("no Content-Length"))?;
+    if status != 206 {
+        body.clear();
+    }
+    let received = reader.take(content_length).read_to_end(body)?;
+    if received as u64 != content_length {
+        return Err(invalid("truncated body"));
+    }
+    Ok(status)
+}
+
+// Value of a field of the JSON written by the service, unquoted if it's a string, e.g. 3 for "id" in {"id":3}
+fn json_field(json: &[u8], name: &str) -> Option<String> {
+    let json = std::str::from_utf8(json).ok()?;
+    let rest = &json[json.find(&format!("\"{}\":", name))? + name.len() + 3..];
+
+    let Some(string) = rest.strip_prefix('"') else {
+        return Some(rest[..rest.find([',', '}'])?].to_string());
+    };
+    let mut value = String::new();
+    let mut chars = string.chars();
+    loop {
+        match chars.next()? {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'u' => {
+                    let code: String = chars.by_ref().take(4).collect();
+                    value.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+                }
+                c => value.push(c),
+            },
+            c => value.push(c),
+        }
+    }
+}
+
+fn json_number(json: &[u8], name: &str) -> Result<u64, ClientError> {
+    json_field(json, name)
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| ClientError::InvalidResponse(format!("no {} in the response", name)))
+}
+
+fn parse_status(json: &[u8]) -> Option<JobStatus> {
+    let number = |name| json_field(json, name)?.parse().ok();
+    match json_field(json, "state")?.as_str() {
+        "queued" => Some(JobStatus::Queued { block: number("block")?, total_blocks: number("total_blocks")? }),
+        "running" => Some(JobStatus::Running {
+            block: number("block")?,
+            round: number("round")?,
+            total_blocks: number("total_blocks")?,
+        }),
+        "done" => Some(JobStatus::Done),
+        "failed" => Some(JobStatus::Failed { reason: json_field(json, "reason")? }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use crate::test_keys::test_keys;
+    // The end-to-end test needs a service to talk to
+    #[cfg(feature = "server")]
+    use std::sync::Arc;
+    #[cfg(feature = "server")]
+    use sha2::{Digest, Sha256};
+    #[cfg(feature = "server")]
+    use crate::{jobs::Scheduler, padding::pad_sha256_data, service::{ApiKeys, JobService, ServiceLimits}};
+
+    #[cfg(feature = "server")]
+    fn start(service: JobService) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let service = Arc::new(service);
+        thread::spawn(move || service.serve(listener, &Scheduler::new(1, 2).unwrap()));
+        address
+    }
+
+    #[test]
+    #[cfg(feature = "server")]
+    fn test_service_client() {
+        let (ck, sk) = test_keys();
+        let api_keys = ApiKeys::parse("alice 0123456789abcdef").unwrap();
+        let address = start(JobService::new(sk.clone(), ParameterSet::Default, ServiceLimits::default()).with_api_keys(api_keys));
+        let client = ServiceClient::new(address.as_str(), ParameterSet::Default).with_api_key("0123456789abcdef");
+
+        let input: Vec<Ciphertext> = pad_sha256_data(b"abc").iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+        let id = client.submit(&input, 1).unwrap();
+        let digest = client.wait(id, Duration::from_millis(10)).unwrap();
+        let bytes: Vec<u8> = digest.chunks(8).map(|byte| byte.iter().fold(0, |acc, bit| (acc << 1) | ck.decrypt(bit) as u8)).collect();
+        assert_eq!(bytes, Sha256::digest(b"abc").to_vec());
+        assert_eq!(client.status(id).unwrap(), JobStatus::Done);
+        client.delete(id).unwrap();
+
+        // Errors come with their code
+        assert_eq!(client.status(id).unwrap_err().code(), Some(ErrorCode::JobNotFound));
+        assert_eq!(client.submit(&input[..100], 0).unwrap_err().code(), Some(ErrorCode::InvalidPayload));
+        assert_eq!(client.remove_key("alice").unwrap_err().code(), Some(ErrorCode::NotFound));
+        let anonymous = ServiceClient::new(address, ParameterSet::Default);
+        assert_eq!(anonymous.status(id).unwrap_err().code(), Some(ErrorCode::Unauthorized));
+    }
+
+    // Answers each connection with the next response (after reading the request head), and returns the requests
+    fn fake_service(responses: Vec<Vec<u8>>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            responses.into_iter().map(|response| {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut head = String::new();
+                while !head.ends_with("\r\n\r\n") {
+                    reader.read_line(&mut head).unwrap();
+                }
+                stream.write_all(&response).unwrap();
+                head
+            }).collect()
+        });
+        (address, handle)
+    }
+
+    #[test]
+    fn test_retries() {
+        let (ck, sk) = test_keys();
+        let digest: Vec<Ciphertext> = (0..256).map(|i| sk.trivial_encrypt(i % 3 == 0)).collect();
+        let mut payload = vec![];
+        serialize_into(&mut payload, PayloadKind::EncryptedDigest, &digest, ParameterSet::Default).unwrap();
+        let retries = RetryPolicy { max_attempts: 3, initial_backoff: Duration::from_millis(1), max_backoff: Duration::from_millis(2) };
+
+        // A busy service, then a download cut off after 1000 bytes, which is resumed
+        let busy = b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 51\r\n\r\n{\"error\":{\"code\":\"capacity_exceeded\",\"message\":\"\"}}";
+        let mut cut_off = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", payload.len()).into_bytes();
+        cut_off.extend_from_slice(&payload[..1000]);
+        let mut rest = format!("HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\n\r\n", payload.len() - 1000).into_bytes();
+        rest.extend_from_slice(&payload[1000..]);
+
+        let (address, service) = fake_service(vec![busy.to_vec(), cut_off, rest]);
+        let client = ServiceClient::new(address, ParameterSet::Default).with_retries(retries);
+        let downloaded = client.digest(0).unwrap();
+        assert!(downloaded.iter().zip(&digest).all(|(a, b)| ck.decrypt(a) == ck.decrypt(b)));
+        let requests = service.join().unwrap();
+        assert!(!requests[1].contains("Range") && requests[2].contains("Range: bytes=1000-\r\n"));
+
+        // An upload cut off after 10 bytes resumes from there. The submission's connection then breaks, and the job may
+        // have been queued, so it isn't sent again
+        let mut input = vec![];
+        serialize_into(&mut input, PayloadKind::EncryptedInput, &digest[..0], ParameterSet::Default).unwrap();
+        let upload = |status: &str, offset: usize| {
+            let json = format!("{{\"id\":4,\"offset\":{},\"length\":{}}}", offset, input.len());
+            format!("HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n{}", status, json.len(), json).into_bytes()
+        };
+        let (address, service) = fake_service(vec![
+            upload("201 Created", 0),
+            b"HTTP/1.1 200 OK\r\nContent-Length: 40\r\n\r\n{\"id\"".to_vec(),
+            upload("200 OK", 10),
+            upload("200 OK", input.len()),
+            b"HTTP/1.1 202 Accepted\r\nContent-Length: 8\r\n\r\n{\"id\"".to_vec(),
+        ]);
+        let client = ServiceClient::new(address, ParameterSet::Default).with_retries(retries);
+        assert!(matches!(client.submit(&digest[..0], 0), Err(ClientError::InvalidResponse(_))));
+        let requests = service.join().unwrap();
+        assert!(requests[0].starts_with(&format!("POST /uploads?length={} ", input.len())));
+        assert!(requests[1].starts_with("PATCH /uploads/4?offset=0 ") && requests[2].starts_with("GET /uploads/4 "));
+        assert!(requests[3].starts_with("PATCH /uploads/4?offset=10 ") && requests[4].starts_with("POST /jobs?priority=0&upload=4 "));
+
+        // Nothing listens on the port once the fake service is done
+        let client = ServiceClient::new(client.address, ParameterSet::Default).with_retries(RetryPolicy::NONE);
+        assert!(matches!(client.status(0), Err(ClientError::Connect(_))));
+    }
+
+    #[test]
+    fn test_json_field() {
+        let json = br#"{"error":{"code":"bad_request","message":"invalid \"x\" \u000a"}}"#;
+        assert_eq!(json_field(json, "code").as_deref(), Some("bad_request"));
+        assert_eq!(json_field(json, "message").as_deref(), Some("invalid \"x\" \n"));
+        assert_eq!(json_field(b"{\"id\":12}", "id").as_deref(), Some("12"));
+        assert_eq!(json_field(b"{\"id\":12}", "round"), None);
+
+        let status = parse_status(b"{\"id\":3,\"state\":\"running\",\"block\":0,\"round\":12,\"total_blocks\":2}");
+        assert_eq!(status, Some(JobStatus::Running { block: 0, round: 12, total_blocks: 2 }));
+        let status = parse_status(b"{\"id\":3,\"state\":\"failed\",\"reason\":\"out of \\\"memory\\\"\"}");
+        assert_eq!(status, Some(JobStatus::Failed { reason: "out of \"memory\"".to_string() }));
+    }
+}
@@ -1,135 +1,504 @@
 // This module implements the main sha256 homomorphic function using parallel processing when possible and some helper functions
 
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use tfhe::boolean::prelude::*;
-use crate::boolean_ops::{add, sigma0, sigma1, ch, maj, sigma_upper_case_0, sigma_upper_case_1, trivial_bools, csa};
+use crate::boolean_ops::{add, add_constant, rotate_right, shift_right, trivial_bools, csa, eq_bits, trivial_bytes, xor, GateBatch};
+use crate::merkle_damgard::{self, compress_blocks, hash_padded, CompressionFn, Endianness, MerkleDamgard};
+
+// Rounds of the sha256 compression function
+pub const ROUNDS: usize = 64;
 
 pub fn sha256_fhe(padded_input: Vec<Ciphertext>, sk: &ServerKey) -> Vec<Ciphertext> {
+    sha256_from_midstate(Sha256State::new(sk), &padded_input, sk)
+}
+
+// sha256 reduced to its first rounds (1 to 64) in every compression, for cryptanalysis experiments such as confirming
+// reduced-round collisions under encryption. The feed-forward and the padding are unchanged
+pub fn sha256_reduced(padded_input: Vec<Ciphertext>, rounds: usize, sk: &ServerKey) -> Vec<Ciphertext> {
     assert_eq!(padded_input.len() % 512, 0, "padded input length is not a multiple of 512");
 
-    // Initialize hash values
-    let mut hash: [[Ciphertext; 32]; 8] = [
-        trivial_bools(&hex_to_bools(0x6a09e667), &sk),
-        trivial_bools(&hex_to_bools(0xbb67ae85), &sk),
-        trivial_bools(&hex_to_bools(0x3c6ef372), &sk),
-        trivial_bools(&hex_to_bools(0xa54ff53a), &sk),
-        trivial_bools(&hex_to_bools(0x510e527f), &sk),
-        trivial_bools(&hex_to_bools(0x9b05688c), &sk),
-        trivial_bools(&hex_to_bools(0x1f83d9ab), &sk),
-        trivial_bools(&hex_to_bools(0x5be0cd19), &sk),
-    ];
-
-    let chunks = padded_input.chunks(512);
-
-    for chunk in chunks {
-
-        // Compute the 64 words
-        let mut w = initialize_w(&sk);
-
-        for i in 0..16 {
-            w[i].clone_from_slice(&chunk[i * 32..(i + 1) * 32]);
-        }
-
-        for i in (16..64).step_by(2) {
-            let u = i+1;
-
-            let (word_i, word_u) = rayon::join(
-                || {
-                    let (s0, s1) = rayon::join(
-                        || sigma0(&w[i - 15], sk),
-                        || sigma1(&w[i - 2], sk));
-
-                    let (sum, carry) = csa(&s0, &w[i - 7], &w[i - 16], sk);
-                    let (sum, carry) = csa(&s1, &sum, &carry, sk);
-                    add(&sum, &carry, sk)
-                },
-                || {
-                    let (s0, s1) = rayon::join(
-                        || sigma0(&w[u - 15], sk),
-                        || sigma1(&w[u - 2], sk));
-
-                    let (sum, carry) = csa(&s0, &w[u - 7], &w[u - 16], sk);
-                    let (sum, carry) = csa(&s1, &sum, &carry, sk);
-                    add(&sum, &carry, sk)
-                }
-            );
-
-            w[i] = word_i;
-            w[u] = word_u;
-        }
-
-        let mut a = hash[0].clone();
-        let mut b = hash[1].clone();
-        let mut c = hash[2].clone();
-        let mut d = hash[3].clone();
-        let mut e = hash[4].clone();
-        let mut f = hash[5].clone();
-        let mut g = hash[6].clone();
-        let mut h = hash[7].clone();
-
-        // Compression loop
-        for i in 0..64 {
-            let (temp1, temp2) = rayon::join(
-                || {
-                    let ((sum, carry), s1) = rayon::join(
-                        || {
-                            let ((sum, carry), ch) = rayon::join(
-                                || csa(&h, &w[i], &trivial_bools(&hex_to_bools(K[i]), sk), sk),
-                                || ch(&e, &f, &g, sk),
-                            );
-                            csa(&sum, &carry, &ch, sk)
-                        },
-                        || sigma_upper_case_1(&e, sk)
-                    );
-
-                    let (sum, carry) = csa(&sum, &carry, &s1, sk);
-                    add(&sum, &carry, sk)
-                },
-                || {
-                    add(&sigma_upper_case_0(&a, sk), &maj(&a, &b, &c, sk), sk)
-                },
-            );
-
-            let (temp_e, temp_a) = rayon::join(
-                || add(&d, &temp1, sk),
-                || add(&temp1, &temp2, sk),
-            );
+    let mut state = Sha256State::new(sk);
+    for chunk in padded_input.chunks(512) {
+        state.compress_reduced(chunk, rounds, sk);
+    }
+
+    state.into()
+}
+
+// Hook for inspecting the encrypted intermediate states of the compression function, e.g. to capture them for research
+// or debugging. after_round is called with the block index, the round index (0 to 63) and the working variables a..h
+// at the end of that round. Closures with the same arguments implement it
+pub trait RoundObserver {
+    fn after_round(&mut self, block: usize, round: usize, state: [&[Ciphertext; 32]; 8]);
+}
+
+impl<F: FnMut(usize, usize, [&[Ciphertext; 32]; 8])> RoundObserver for F {
+    fn after_round(&mut self, block: usize, round: usize, state: [&[Ciphertext; 32]; 8]) {
+        self(block, round, state)
+    }
+}
+
+// Hook for cryptanalysis experiments on the encrypted circuit (e.g. differential paths): unlike an observer, it can
+// replace values. schedule is called with the 64 message schedule words of each block before its rounds, and
+// after_round with the working variables a..h at the end of each round, which the next round then uses. Both do nothing
+// by default, so a hook only implements the ones it needs. The schedule is always encrypted when a hook is used, even
+// for public blocks (trivial ciphertexts, so computing it takes no bootstrapping)
+pub trait RoundHook {
+    fn schedule(&mut self, _block: usize, _w: &mut [[Ciphertext; 32]; 64]) {}
+
+    fn after_round(&mut self, _block: usize, _round: usize, _state: &mut [[Ciphertext; 32]; 8]) {}
+}
+
+// sha256_fhe, with an observer called after every round of every block
+pub fn sha256_fhe_observed(padded_input: Vec<Ciphertext>, observer: &mut dyn RoundObserver, sk: &ServerKey) -> Vec<Ciphertext> {
+    assert_eq!(padded_input.len() % 512, 0, "padded input length is not a multiple of 512");
+
+    let mut state = Sha256State::new(sk);
+    for chunk in padded_input.chunks(512) {
+        state.compress_observed(chunk, Some(&mut *observer), sk);
+    }
+
+    state.into()
+}
+
+// sha256_fhe, with a hook that can read and replace the schedule and the working variables of every block
+pub fn sha256_fhe_hooked(padded_input: Vec<Ciphertext>, hook: &mut dyn RoundHook, sk: &ServerKey) -> Vec<Ciphertext> {
+    assert_eq!(padded_input.len() % 512, 0, "padded input length is not a multiple of 512");
+
+    let mut state = Sha256State::new(sk);
+    for chunk in padded_input.chunks(512) {
+        state.compress_hooked(chunk, &mut *hook, sk);
+    }
+
+    state.into()
+}
+
+// Chaining value of a sha256 computation: the eight encrypted hash words, along with the number of input bits that were
+// compressed into them (always a whole number of blocks). It can be serialized to resume the computation later
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Sha256State {
+    words: [[Ciphertext; 32]; 8],
+    length: u64,
+}
+
+impl Sha256State {
+    // Initial state, with the sha256 initial hash values
+    pub fn new(sk: &ServerKey) -> Self {
+        Sha256State {
+            words: H0.map(|word| trivial_bools(&hex_to_bools(word), sk)),
+            length: 0,
+        }
+    }
+
+    // State with the given hash words, after compressing length bits of input
+    pub fn from_words(words: [[Ciphertext; 32]; 8], length: u64) -> Self {
+        assert_eq!(length % 512, 0, "length is not a multiple of 512");
+        Sha256State { words, length }
+    }
+
+    pub fn words(&self) -> &[[Ciphertext; 32]; 8] {
+        &self.words
+    }
+
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    // Compresses one 512-bit chunk into the state
+    pub fn compress(&mut self, chunk: &[Ciphertext], sk: &ServerKey) {
+        self.compress_observed(chunk, None, sk);
+    }
+
+    // Same as compress, reporting the working variables after each round to the observer
+    pub fn compress_observed(&mut self, chunk: &[Ciphertext], observer: Option<&mut dyn RoundObserver>, sk: &ServerKey) {
+        assert_eq!(chunk.len(), 512, "chunk is not 512 bits long");
+
+        compress(&mut self.words, chunk, (self.length / 512) as usize, ROUNDS, observer.map(Instrument::Observer), sk);
+        self.length += 512;
+    }
+
+    // Same as compress, letting the hook read and replace values of the computation
+    pub fn compress_hooked(&mut self, chunk: &[Ciphertext], hook: &mut dyn RoundHook, sk: &ServerKey) {
+        assert_eq!(chunk.len(), 512, "chunk is not 512 bits long");
+
+        compress(&mut self.words, chunk, (self.length / 512) as usize, ROUNDS, Some(Instrument::Hook(hook)), sk);
+        self.length += 512;
+    }
+
+    // Same as compress, running only the first rounds of the compression function
+    pub fn compress_reduced(&mut self, chunk: &[Ciphertext], rounds: usize, sk: &ServerKey) {
+        assert_eq!(chunk.len(), 512, "chunk is not 512 bits long");
+        assert!((1..=ROUNDS).contains(&rounds), "rounds must be between 1 and {}", ROUNDS);
+
+        compress(&mut self.words, chunk, (self.length / 512) as usize, rounds, None, sk);
+        self.length += 512;
+    }
+}
+
+// The 256-bit concatenation of the hash words, which is the digest once the padded input has been compressed
+impl From<Sha256State> for Vec<Ciphertext> {
+    fn from(state: Sha256State) -> Self {
+        state.words.into_iter().flatten().collect()
+    }
+}
+
+impl CompressionFn for Sha256State {
+    const BLOCK_BITS: usize = 512;
+    const LENGTH_BITS: usize = 64;
+    const LENGTH_ENDIANNESS: Endianness = Endianness::Big;
+
+    fn initial(sk: &ServerKey) -> Self {
+        Sha256State::new(sk)
+    }
+
+    fn compress(&mut self, block: &[Ciphertext], sk: &ServerKey) {
+        Sha256State::compress(self, block, sk);
+    }
+
+    fn digest(self) -> Vec<Ciphertext> {
+        self.into()
+    }
+
+    fn length(&self) -> u64 {
+        self.length
+    }
+}
+
+// Chaining value after compressing some leading blocks of a message (which must not include the padding). Messages that
+// share these blocks can reuse it instead of compressing them again
+pub fn sha256_midstate(prefix_blocks: &[Ciphertext], sk: &ServerKey) -> Sha256State {
+    let mut state = Sha256State::new(sk);
+    compress_blocks(&mut state, prefix_blocks, sk);
+    state
+}
+
+// Finishes a hash from a midstate, given the remaining blocks of the padded input (the padding accounts for the length
+// of the whole message, prefix included)
+pub fn sha256_from_midstate(midstate: Sha256State, padded_remainder: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    hash_padded(midstate, padded_remainder, sk)
+}
+
+// Hashes several messages that start with the same blocks, compressing those blocks only once. Each remainder is the
+// rest of its padded message, after the shared prefix
+pub fn sha256_shared_prefix(prefix_blocks: &[Ciphertext], padded_remainders: Vec<Vec<Ciphertext>>, sk: &ServerKey) -> Vec<Vec<Ciphertext>> {
+    let midstate = sha256_midstate(prefix_blocks, sk);
+
+    padded_remainders.into_par_iter()
+        .map(|remainder| sha256_from_midstate(midstate.clone(), &remainder, sk))
+        .collect()
+}
+
+// Incremental sha256 hasher for inputs that arrive in pieces, such as an encrypted prefix followed later by a plaintext
+// suffix. Plaintext pieces are trivially encrypted, so the blocks made only of them take the constant schedule path,
+// and tfhe evaluates their gates with trivial operands without bootstrapping
+pub type Sha256Hasher = MerkleDamgard<Sha256State>;
+
+// Observer or hook of a compression
+enum Instrument<'a> {
+    Observer(&'a mut dyn RoundObserver),
+    Hook(&'a mut dyn RoundHook),
+}
+
+// Compresses one 512-bit chunk into the chaining value with the given number of rounds, reporting the working variables
+// to the observer or hook (if any) after each round. block is the index of the chunk within the input, for the observer
+// or hook
+fn compress(
+    hash: &mut [[Ciphertext; 32]; 8],
+    chunk: &[Ciphertext],
+    block: usize,
+    rounds: usize,
+    mut instrument: Option<Instrument>,
+    sk: &ServerKey,
+) {
+    // A chunk made only of trivial ciphertexts (e.g. a padding-only block) has a public message schedule,
+    // so we compute it in the clear and fold each word with its round constant, saving a CSA per round
+    let schedule = match (trivial_chunk_words(chunk), &mut instrument) {
+        (_, Some(Instrument::Hook(hook))) => {
+            let mut w = Box::new(compute_w(chunk, sk));
+            hook.schedule(block, &mut w);
+            Schedule::Encrypted(w)
+        },
+        (Some(words), _) => {
+            let w = compute_w_plain(&words);
+            let mut kw = [0u32; 64];
+            for i in 0..64 {
+                kw[i] = K[i].wrapping_add(w[i]);
+            }
+            Schedule::Constant(Box::new(kw))
+        },
+        (None, _) => Schedule::Encrypted(Box::new(compute_w(chunk, sk))),
+    };
+
+    let mut a = hash[0].clone();
+    let mut b = hash[1].clone();
+    let mut c = hash[2].clone();
+    let mut d = hash[3].clone();
+    let mut e = hash[4].clone();
+    let mut f = hash[5].clone();
+    let mut g = hash[6].clone();
+    let mut h = hash[7].clone();
+
+    // Compression loop
+    for i in 0..rounds {
+        // The round constant doesn't depend on the round functions, so it's added in the meantime with the constant
+        // adder: to w[i] with an encrypted schedule, or to h with a constant one (where it's folded with w[i])
+        let ([s1, ch, s0, maj], with_k) = rayon::join(
+            || round_functions(&a, &b, &c, &e, &f, &g, sk),
+            || match &schedule {
+                Schedule::Encrypted(w) => add_constant(&w[i], K[i] as u64, sk),
+                Schedule::Constant(kw) => add_constant(&h, kw[i] as u64, sk),
+            },
+        );
+
+        let (temp1, temp2) = rayon::join(
+            || {
+                let (sum, carry) = match &schedule {
+                    Schedule::Encrypted(_) => {
+                        let (sum, carry) = csa(&h, &with_k, &ch, sk);
+                        csa(&sum, &carry, &s1, sk)
+                    }
+                    Schedule::Constant(_) => csa(&with_k, &ch, &s1, sk),
+                };
+                add(&sum, &carry, sk)
+            },
+            || add(&s0, &maj, sk),
+        );
+
+        let (temp_e, temp_a) = rayon::join(
+            || add(&d, &temp1, sk),
+            || add(&temp1, &temp2, sk),
+        );
+
+        h = g;
+        g = f;
+        f = e;
+        e = temp_e;
+        d = c;
+        c = b;
+        b = a;
+        a = temp_a;
+
+        match &mut instrument {
+            Some(Instrument::Observer(observer)) => observer.after_round(block, i, [&a, &b, &c, &d, &e, &f, &g, &h]),
+            Some(Instrument::Hook(hook)) => {
+                let mut state = [a, b, c, d, e, f, g, h];
+                hook.after_round(block, i, &mut state);
+                [a, b, c, d, e, f, g, h] = state;
+            },
+            None => {},
+        }
+    }
+
+    hash[0] = add(&hash[0], &a, sk);
+    hash[1] = add(&hash[1], &b, sk);
+    hash[2] = add(&hash[2], &c, sk);
+    hash[3] = add(&hash[3], &d, sk);
+    hash[4] = add(&hash[4], &e, sk);
+    hash[5] = add(&hash[5], &f, sk);
+    hash[6] = add(&hash[6], &g, sk);
+    hash[7] = add(&hash[7], &h, sk);
+}
+
+// Sigma1(e), Ch(e, f, g), Sigma0(a) and Maj(a, b, c) of a round. Their gates are batched by depth rather than evaluated
+// function by function: the first XOR of both sigmas, Ch and the two gates of Maj on b and c, then the second XOR of
+// both sigmas and the AND of Maj, and the last XOR of Maj on its own
+fn round_functions(
+    a: &[Ciphertext; 32],
+    b: &[Ciphertext; 32],
+    c: &[Ciphertext; 32],
+    e: &[Ciphertext; 32],
+    f: &[Ciphertext; 32],
+    g: &[Ciphertext; 32],
+    sk: &ServerKey,
+) -> [[Ciphertext; 32]; 4] {
+    let [e6, e11, e25] = [6, 11, 25].map(|n| rotate_right(e, n, sk));
+    let [a2, a13, a22] = [2, 13, 22].map(|n| rotate_right(a, n, sk));
+
+    let mut batch = GateBatch::new();
+    batch.xor(&e6, &e11);
+    batch.mux(e, f, g);
+    batch.xor(&a2, &a13);
+    batch.xor(b, c);
+    batch.and(b, c);
+    let [s1, ch, s0, b_xor_c, b_and_c] = batch.run(sk).try_into().unwrap_or_else(|_| unreachable!("GateBatch returned the wrong number of outputs"));
+
+    let mut batch = GateBatch::new();
+    batch.xor(&s1, &e25);
+    batch.xor(&s0, &a22);
+    batch.and(a, &b_xor_c);
+    let [s1, s0, a_and] = batch.run(sk).try_into().unwrap_or_else(|_| unreachable!("GateBatch returned the wrong number of outputs"));
+
+    [s1, ch, s0, xor(&a_and, &b_and_c, sk)]
+}
+
+// Compares the digest of the input with a public expected digest and returns a single encrypted bit, so the response
+// is one ciphertext instead of 256 and the client only decrypts that bit
+pub fn sha256_matches(padded_input: Vec<Ciphertext>, expected: &[u8; 32], sk: &ServerKey) -> Ciphertext {
+    let digest = sha256_fhe(padded_input, sk);
+    eq_bits(&digest, &trivial_bytes(expected, sk), sk)
+}
+
+// Hashes many candidate messages and returns, for each one, an encrypted bit telling whether it matches the expected
+// digest. The candidates are hashed concurrently on the rayon pool (nested inside each hash's own parallelism), and the
+// trivially encrypted expected digest is shared between all of them
+pub fn sha256_matches_batch(candidates: Vec<Vec<Ciphertext>>, expected: &[u8; 32], sk: &ServerKey) -> Vec<Ciphertext> {
+    let expected = trivial_bytes(expected, sk);
+
+    candidates.into_par_iter()
+        .map(|padded_input| eq_bits(&sha256_fhe(padded_input, sk), &expected, sk))
+        .collect()
+}
+
+// Appends the sha256 padding to an encrypted message whose length (in bits, not necessarily a whole number of bytes) is
+// public. The padding then only depends on the length, so it is trivially encrypted
+pub fn pad_encrypted(message: Vec<Ciphertext>, sk: &ServerKey) -> Vec<Ciphertext> {
+    merkle_damgard::pad_encrypted::<Sha256State>(message, sk)
+}
+
+// Message schedule of a chunk, either encrypted or (when the chunk is public) already added to the round constants
+enum Schedule {
+    Encrypted(Box<[[Ciphertext; 32]; 64]>),
+    Constant(Box<[u32; 64]>),
+}
+
+// Runs the same circuit over trivially encrypted bits, which tfhe evaluates in the clear without bootstrapping. This
+// clear backend is fast enough for extensive correctness testing of the circuit logic (chaining, multi-block inputs...)
+pub fn sha256_clear(padded_input: &[bool], sk: &ServerKey) -> Vec<bool> {
+    sha256_clear_reduced(padded_input, ROUNDS, sk)
+}
+
+// sha256_reduced on the clear backend
+pub fn sha256_clear_reduced(padded_input: &[bool], rounds: usize, sk: &ServerKey) -> Vec<bool> {
+    let input = padded_input.iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+
+    sha256_reduced(input, rounds, sk)
+        .iter()
+        .map(|bit| match bit {
+            Ciphertext::Trivial(value) => *value,
+            Ciphertext::Encrypted(_) => unreachable!("trivial inputs only produce trivial outputs"),
+        })
+        .collect()
+}
+
+// Compute the 64 words of the message schedule of an encrypted chunk
+fn compute_w(chunk: &[Ciphertext], sk: &ServerKey) -> [[Ciphertext; 32]; 64] {
+    let mut w = initialize_w(sk);
+
+    for i in 0..16 {
+        w[i].clone_from_slice(&chunk[i * 32..(i + 1) * 32]);
+    }
+
+    for i in (16..64).step_by(2) {
+        let u = i+1;
+
+        // The four small sigmas of both words, in two batches
+        let sigma_inputs = [&w[i - 15], &w[i - 2], &w[u - 15], &w[u - 2]];
+        let rotations: Vec<[[Ciphertext; 32]; 3]> = sigma_inputs.iter()
+            .zip([(7, 18, 3), (17, 19, 10), (7, 18, 3), (17, 19, 10)])
+            .map(|(x, (r1, r2, s))| [rotate_right(x, r1, sk), rotate_right(x, r2, sk), shift_right(x, s, sk)])
+            .collect();
+
+        let mut batch = GateBatch::new();
+        for [x, y, _] in &rotations {
+            batch.xor(x, y);
+        }
+        let partial = batch.run(sk);
+
+        let mut batch = GateBatch::new();
+        for (xy, [_, _, z]) in partial.iter().zip(&rotations) {
+            batch.xor(xy, z);
+        }
+        let [s0_i, s1_i, s0_u, s1_u] = batch.run(sk).try_into().unwrap_or_else(|_| unreachable!("GateBatch returned the wrong number of outputs"));
+
+        let (word_i, word_u) = rayon::join(
+            || {
+                let (sum, carry) = csa(&s0_i, &w[i - 7], &w[i - 16], sk);
+                let (sum, carry) = csa(&s1_i, &sum, &carry, sk);
+                add(&sum, &carry, sk)
+            },
+            || {
+                let (sum, carry) = csa(&s0_u, &w[u - 7], &w[u - 16], sk);
+                let (sum, carry) = csa(&s1_u, &sum, &carry, sk);
+                add(&sum, &carry, sk)
+            }
+        );
+
+        w[i] = word_i;
+        w[u] = word_u;
+    }
+
+    w
+}
+
+// Returns the 16 message words of a chunk when all of its bits are trivial ciphertexts (publicly known)
+fn trivial_chunk_words(chunk: &[Ciphertext]) -> Option<[u32; 16]> {
+    let mut words = [0u32; 16];
+
+    for (i, bit) in chunk.iter().enumerate() {
+        match bit {
+            Ciphertext::Trivial(value) => words[i / 32] = (words[i / 32] << 1) | *value as u32,
+            Ciphertext::Encrypted(_) => return None,
+        }
+    }
+    Some(words)
+}
+
+// Plaintext sha256 reduced to its first rounds, without any ciphertext. It is much faster than the clear backend, e.g.
+// for searching reduced-round collisions, which the clear backend or the FHE circuit then confirm
+pub fn sha256_plain_reduced(padded_input: &[bool], rounds: usize) -> Vec<bool> {
+    assert_eq!(padded_input.len() % 512, 0, "padded input length is not a multiple of 512");
+    assert!((1..=ROUNDS).contains(&rounds), "rounds must be between 1 and {}", ROUNDS);
+
+    let mut hash = H0;
+    for chunk in padded_input.chunks(512) {
+        let words: [u32; 16] = std::array::from_fn(|i| chunk[32 * i..32 * (i + 1)].iter().fold(0, |acc, bit| (acc << 1) | *bit as u32));
+        let w = compute_w_plain(&words);
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = hash;
+
+        for i in 0..rounds {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let temp1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(maj);
 
             h = g;
             g = f;
             f = e;
-            e = temp_e;
+            e = d.wrapping_add(temp1);
             d = c;
             c = b;
             b = a;
-            a = temp_a;
+            a = temp1.wrapping_add(temp2);
         }
 
-        hash[0] = add(&hash[0], &a, sk);
-        hash[1] = add(&hash[1], &b, sk);
-        hash[2] = add(&hash[2], &c, sk);
-        hash[3] = add(&hash[3], &d, sk);
-        hash[4] = add(&hash[4], &e, sk);
-        hash[5] = add(&hash[5], &f, sk);
-        hash[6] = add(&hash[6], &g, sk);
-        hash[7] = add(&hash[7], &h, sk);
+        for (word, value) in hash.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(value);
+        }
     }
 
-    // Concatenate the final hash values to produce a 256-bit hash
-    let mut output = vec![];
+    hash.iter().flat_map(|word| hex_to_bools(*word)).collect()
+}
 
-    for i in 0..8 {
-        for j in 0..32 {
-            output.push(hash[i][j].clone());
-        }
-    }
+// Plaintext message schedule, used for chunks whose content is public
+pub(crate) fn compute_w_plain(words: &[u32; 16]) -> [u32; 64] {
+    let mut w = [0u32; 64];
+    w[..16].copy_from_slice(words);
 
-    output
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+    }
+    w
 }
 
 // Initialize the 64 words with trivial encryptions
 fn initialize_w(sk: &ServerKey) -> [[Ciphertext; 32]; 64] {
     let t = || -> [Ciphertext; 32] { // captures server key for brevity
-        trivial_bools(&[false; 32], &sk)
+        trivial_bools(&[false; 32], sk)
     };
 
     [
@@ -139,50 +508,23 @@ fn initialize_w(sk: &ServerKey) -> [[Ciphertext; 32]; 64] {
     ]
 }
 
-// To represent decrypted digest bools as hexadecimal String
-pub fn bools_to_hex(bools: Vec<bool>) -> String {
-    let mut hex_string = String::new();
-    let mut byte = 0u8;
-    let mut counter = 0;
-
-    for bit in bools {
-        byte <<= 1;
-        if bit {
-            byte |= 1;
-        }
-
-        counter += 1;
-
-        if counter == 8 {
-            hex_string.push_str(&format!("{:02x}", byte));
-            byte = 0;
-            counter = 0;
-        }
-    }
-
-    // Handle any remaining bits in case the bools vector length is not a multiple of 8
-    if counter > 0 {
-        byte <<= 8 - counter;
-        hex_string.push_str(&format!("{:02x}", byte));
-    }
-
-    hex_string
-}
-
 // To represent constant values as bool arrays
 fn hex_to_bools(hex_value: u32) -> [bool; 32] {
     let mut bool_array = [false; 32];
     let mut mask = 0x8000_0000;
 
-    for i in 0..32 {
-        bool_array[i] = (hex_value & mask) != 0;
+    for bit in bool_array.iter_mut() {
+        *bit = (hex_value & mask) != 0;
         mask >>= 1;
     }
 
     bool_array
 }
 
-const K: [u32; 64] = [
+// Initial hash values
+pub(crate) const H0: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+
+pub(crate) const K: [u32; 64] = [
     0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
     0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
     0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
@@ -196,6 +538,10 @@ const K: [u32; 64] = [
 #[cfg(test)]
 mod tests {
     use super::*;
+    use sha2::{Digest, Sha256};
+    use crate::client::bools_to_hex;
+    use crate::padding::{message_bits, pad_sha256_bits, pad_sha256_data, pad_sha256_input};
+    use crate::test_keys::test_keys;
 
     fn to_bool_array(arr: [i32; 32]) -> [bool; 32] {
         let mut bool_arr = [false; 32];
@@ -208,11 +554,291 @@ mod tests {
     }
 
     #[test]
-    fn test_bools_to_hex() {
-        let bools = to_bool_array([1,0,0,1,0,0,0,0,1,0,1,1,1,1,1,0,1,1,1,1,1,1,1,1,1,1,1,1,1,0,1,0,]);
-        let hex_bools = bools_to_hex(bools.to_vec());
+    fn test_sha256_public_input() {
+        let (ck, sk) = test_keys();
+
+        // Every chunk is trivially encrypted, so the constant schedule path is taken for both of them
+        let padded_input = pad_sha256_input("abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq");
+        let input = padded_input.iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+
+        let output = sha256_fhe(input, &sk);
+        let result = bools_to_hex(output.iter().map(|bit| ck.decrypt(bit)).collect());
+
+        assert_eq!(result, "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+    }
+
+    fn clear_digest(message: &[u8], sk: &ServerKey) -> Vec<u8> {
+        let bools = sha256_clear(&pad_sha256_data(message), sk);
+        bools.chunks(8).map(|byte| byte.iter().fold(0u8, |acc, bit| (acc << 1) | *bit as u8)).collect()
+    }
+
+    // SHAVS Monte Carlo procedure, diffed against the sha2 crate. Each message is the concatenation of the last three
+    // digests, so it spans two blocks and every digest feeds into the next input
+    fn monte_carlo(seed: [u8; 32], checkpoints: usize, iterations: usize, sk: &ServerKey) {
+        let mut seed = seed.to_vec();
+
+        for _ in 0..checkpoints {
+            let mut md = [seed.clone(), seed.clone(), seed.clone()];
+            for _ in 0..iterations {
+                let message = [md[0].as_slice(), md[1].as_slice(), md[2].as_slice()].concat();
+                let digest = clear_digest(&message, sk);
+
+                assert_eq!(digest, Sha256::digest(&message).to_vec());
+                md = [md[1].clone(), md[2].clone(), digest];
+            }
+            seed = md[2].clone();
+        }
+    }
+
+    #[test]
+    fn test_monte_carlo() {
+        let (_, sk) = test_keys();
+        monte_carlo(Sha256::digest(b"sha256_fhe monte carlo").into(), 1, 50, &sk);
+    }
+
+    #[test]
+    #[ignore] // the full SHAVS procedure (100 checkpoints of 1000 iterations) takes a long time
+    fn test_monte_carlo_full() {
+        let (_, sk) = test_keys();
+        monte_carlo(Sha256::digest(b"sha256_fhe monte carlo").into(), 100, 1000, &sk);
+    }
+
+    #[test]
+    fn test_message_lengths() {
+        let (_, sk) = test_keys();
+
+        // Every length around the one and two block padding boundaries, plus a few long multi-block messages
+        let lengths = (0..=130).chain([200, 511, 512, 513, 1000]);
+        for len in lengths {
+            let message: Vec<u8> = (0..len).map(|i| (i * 31 + 7) as u8).collect();
+            assert_eq!(clear_digest(&message, &sk), Sha256::digest(&message).to_vec(), "message length {}", len);
+        }
+    }
+
+    #[test]
+    fn test_sha256_matches() {
+        let (ck, sk) = test_keys();
+
+        let message = b"sha256_fhe";
+        let input: Vec<Ciphertext> = pad_sha256_data(message).iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+        let expected: [u8; 32] = Sha256::digest(message).into();
+
+        let mut wrong = expected;
+        wrong[0] ^= 0x80;
+
+        assert!(ck.decrypt(&sha256_matches(input.clone(), &expected, &sk)));
+        assert!(!ck.decrypt(&sha256_matches(input, &wrong, &sk)));
+    }
+
+    #[test]
+    fn test_sha256_matches_batch() {
+        let (ck, sk) = test_keys();
+
+        let messages: [&[u8]; 3] = [b"first", b"sha256_fhe", &[0x61; 100]];
+        let candidates = messages.iter()
+            .map(|message| pad_sha256_data(message).iter().map(|bit| sk.trivial_encrypt(*bit)).collect())
+            .collect();
+        let expected: [u8; 32] = Sha256::digest(b"sha256_fhe").into();
+
+        let matches: Vec<bool> = sha256_matches_batch(candidates, &expected, &sk).iter().map(|bit| ck.decrypt(bit)).collect();
+        assert_eq!(matches, vec![false, true, false]);
+    }
+
+    #[test]
+    fn test_sha256_shared_prefix() {
+        let (ck, sk) = test_keys();
+
+        // Both messages share their first block, which is compressed once
+        let messages = [[[0x42; 64].as_slice(), b"first tail"].concat(), [[0x42; 64].as_slice(), &[0x17; 80]].concat()];
+        let padded: Vec<Vec<Ciphertext>> = messages.iter()
+            .map(|message| pad_sha256_data(message).iter().map(|bit| sk.trivial_encrypt(*bit)).collect())
+            .collect();
+
+        let remainders = padded.iter().map(|input| input[512..].to_vec()).collect();
+        let digests = sha256_shared_prefix(&padded[0][..512], remainders, &sk);
+
+        for (digest, message) in digests.iter().zip(messages.iter()) {
+            assert_eq!(bools_to_hex(digest.iter().map(|bit| ck.decrypt(bit)).collect()), format!("{:x}", Sha256::digest(message)));
+        }
+    }
+
+    #[test]
+    fn test_sha256_hasher() {
+        let (ck, sk) = test_keys();
+
+        let prefix = [0x5a; 70];
+        let suffix = [0xc3; 100];
+
+        // The encrypted prefix arrives in two pieces that straddle a block boundary
+        let mut hasher = Sha256Hasher::new(&sk);
+        let encrypted_prefix: Vec<Ciphertext> = pad_sha256_data(&prefix)[..560].iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+        hasher.update(&encrypted_prefix[..200], &sk);
+        hasher.update(&encrypted_prefix[200..], &sk);
+        hasher.update_plain(&suffix, &sk);
+
+        // The hasher can be serialized midway and resumed
+        let bytes = bincode::serialize(&hasher).unwrap();
+        let hasher: Sha256Hasher = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(hasher.length(), 170 * 8);
+
+        let digest = hasher.finalize(&sk);
+        let expected = Sha256::digest([prefix.as_slice(), suffix.as_slice()].concat());
+        assert_eq!(bools_to_hex(digest.iter().map(|bit| ck.decrypt(bit)).collect()), format!("{:x}", expected));
+
+        let empty = Sha256Hasher::new(&sk).finalize(&sk);
+        assert_eq!(bools_to_hex(empty.iter().map(|bit| ck.decrypt(bit)).collect()), format!("{:x}", Sha256::digest(b"")));
+    }
+
+    #[test]
+    fn test_pad_encrypted() {
+        let (ck, sk) = test_keys();
+
+        for len in [0, 1, 55, 56, 64, 100] {
+            let message: Vec<u8> = (0..len as u8).collect();
+            let encrypted_message = message_bits(&message).iter().map(|bit| ck.encrypt(*bit)).collect();
+
+            let padded: Vec<bool> = pad_encrypted(encrypted_message, &sk).iter().map(|bit| ck.decrypt(bit)).collect();
+            assert_eq!(padded, pad_sha256_data(&message));
+        }
+    }
+
+    #[test]
+    fn test_bit_length_messages() {
+        let (ck, sk) = test_keys();
+        let to_hex = |digest: Vec<Ciphertext>| bools_to_hex(digest.iter().map(|bit| ck.decrypt(bit)).collect());
+
+        // The 5-bit message 01101 of the NIST bit-oriented test vectors, and 700 bits of 0, 1, 2... (two blocks)
+        let short = [false, true, true, false, true];
+        let long = message_bits(&(0..100).collect::<Vec<u8>>())[..700].to_vec();
+        let expected = [
+            "d6d3e02a31a84a8caa9718ed6c2057be09db45e7823eb5079ce7a573a3760f95",
+            "cea290251a362c5c2f268e157b656fe77ebeee5365a4c8010cdc1eb812326317",
+        ];
+
+        for (message, expected) in [short.as_slice(), &long].into_iter().zip(expected) {
+            assert_eq!(bools_to_hex(sha256_clear(&pad_sha256_bits(message), &sk)), expected);
+
+            let encrypted: Vec<Ciphertext> = message.iter().map(|bit| ck.encrypt(*bit)).collect();
+            assert_eq!(to_hex(sha256_fhe(pad_encrypted(encrypted.clone(), &sk), &sk)), expected);
+
+            // An encrypted prefix that ends mid-byte, followed by plaintext bits
+            let mut hasher = Sha256Hasher::new(&sk);
+            hasher.update(&encrypted[..3], &sk);
+            hasher.update_plain_bits(&message[3..], &sk);
+            assert_eq!(to_hex(hasher.finalize(&sk)), expected);
+        }
+    }
+
+    #[test]
+    fn test_round_observer() {
+        let (ck, sk) = test_keys();
 
-        assert_eq!(hex_bools, "90befffa");
+        let input: Vec<Ciphertext> = pad_sha256_input("abc").iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+
+        let mut rounds = vec![];
+        let mut first_a = 0;
+        let mut observer = |block: usize, round: usize, state: [&[Ciphertext; 32]; 8]| {
+            rounds.push((block, round));
+            if round == 0 {
+                first_a = state[0].iter().fold(0u32, |acc, bit| (acc << 1) | ck.decrypt(bit) as u32);
+            }
+        };
+        let output = sha256_fhe_observed(input, &mut observer, &sk);
+
+        // Value of a after the first round of sha256("abc"), from the FIPS 180-2 example
+        assert_eq!(first_a, 0x5d6aebcd);
+        assert_eq!(rounds, (0..64).map(|round| (0, round)).collect::<Vec<_>>());
+        assert_eq!(
+            bools_to_hex(output.iter().map(|bit| ck.decrypt(bit)).collect()),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        );
+    }
+
+    // Replaces the schedule of every block with a given one, records w[16] and flips the lowest bit of a after the
+    // last round
+    struct TestHook<'a> {
+        replacement: Option<[[Ciphertext; 32]; 64]>,
+        w16: Vec<[Ciphertext; 32]>,
+        flip_last_a: bool,
+        sk: &'a ServerKey,
+    }
+
+    impl RoundHook for TestHook<'_> {
+        fn schedule(&mut self, _block: usize, w: &mut [[Ciphertext; 32]; 64]) {
+            if let Some(replacement) = &self.replacement {
+                *w = replacement.clone();
+            }
+            self.w16.push(w[16].clone());
+        }
+
+        fn after_round(&mut self, _block: usize, round: usize, state: &mut [[Ciphertext; 32]; 8]) {
+            if self.flip_last_a && round == 63 {
+                state[0][31] = self.sk.not(&state[0][31]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_hook() {
+        let (ck, sk) = test_keys();
+        let word = |bits: &[Ciphertext]| bits.iter().fold(0u32, |acc, bit| (acc << 1) | ck.decrypt(bit) as u32);
+
+        let abc: Vec<Ciphertext> = pad_sha256_input("abc").iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+        let expected = sha256_fhe(abc.clone(), &sk);
+
+        // Extracting a schedule word
+        let mut hook = TestHook { replacement: None, w16: vec![], flip_last_a: false, sk: &sk };
+        let output = sha256_fhe_hooked(abc.clone(), &mut hook, &sk);
+        assert_eq!(word(&output), word(&expected));
+        assert_eq!(word(&hook.w16[0]), compute_w_plain(&trivial_chunk_words(&abc).unwrap())[16]);
+
+        // Injecting a difference into a after the last round only changes the first digest word, through the
+        // feed-forward
+        let mut hook = TestHook { replacement: None, w16: vec![], flip_last_a: true, sk: &sk };
+        let output = sha256_fhe_hooked(abc.clone(), &mut hook, &sk);
+        let last_a = word(&expected[..32]).wrapping_sub(H0[0]);
+        assert_eq!(word(&output[..32]), H0[0].wrapping_add(last_a ^ 1));
+        assert_eq!(word(&output[32..]), word(&expected[32..]));
+
+        // Replacing the whole schedule with the one of another message gives the digest of that message
+        let other: Vec<Ciphertext> = pad_sha256_input("other").iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+        let mut hook = TestHook { replacement: Some(compute_w(&other, &sk)), w16: vec![], flip_last_a: false, sk: &sk };
+        let output = sha256_fhe_hooked(abc, &mut hook, &sk);
+        assert_eq!(bools_to_hex(output.iter().map(|bit| ck.decrypt(bit)).collect()), format!("{:x}", Sha256::digest(b"other")));
+    }
+
+    #[test]
+    fn test_reduced_rounds() {
+        let (_, sk) = test_keys();
+        let message = pad_sha256_data(&[0x5a; 100]);
+
+        assert_eq!(bools_to_hex(sha256_plain_reduced(&message, ROUNDS)), format!("{:x}", Sha256::digest([0x5a; 100])));
+        for rounds in [1, 13, 20, 63] {
+            assert_eq!(sha256_clear_reduced(&message, rounds, &sk), sha256_plain_reduced(&message, rounds), "{} rounds", rounds);
+        }
+
+        // Message word 13 is only used from round 13 on, so two one-block messages that differ only there collide
+        // with 13 rounds, but not with 14
+        let mut other = [0x5a; 55];
+        other[53] ^= 1;
+        let (message, other) = (pad_sha256_data(&[0x5a; 55]), pad_sha256_data(&other));
+        assert_eq!(sha256_clear_reduced(&message, 13, &sk), sha256_clear_reduced(&other, 13, &sk));
+        assert_ne!(sha256_clear_reduced(&message, 14, &sk), sha256_clear_reduced(&other, 14, &sk));
+    }
+
+    #[test]
+    fn test_encrypted_schedule() {
+        let (_, sk) = test_keys();
+
+        // The encrypted schedule isn't used for trivial chunks, so check it directly against the plaintext one
+        let words: [u32; 16] = std::array::from_fn(|i| (i as u32).wrapping_mul(0x9e3779b9));
+        let chunk: Vec<Ciphertext> = words.iter().flat_map(|w| trivial_bools(&hex_to_bools(*w), &sk)).collect();
+
+        let w = compute_w(&chunk, &sk);
+        let expected = compute_w_plain(&words);
+        for i in 0..64 {
+            assert_eq!(trivial_chunk_words(&w[i]).unwrap()[0], expected[i]);
+        }
     }
 
     #[test]
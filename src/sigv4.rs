@@ -0,0 +1,93 @@
+// This module implements AWS Signature Version 4 request signing on top of the homomorphic HMAC, with an encrypted secret
+// access key. The signing key is derived by chaining HMACs over the date, the region and the service, and then signs
+// the string to sign of the request, so a server can sign requests for a client without ever seeing its credentials.
+// Everything else (the request, its timestamp and the credential scope) is public and trivially encrypted.
+//
+// The client decrypts the 256-bit signature and hex encodes it into the Authorization header.
+
+use tfhe::boolean::prelude::*;
+use crate::boolean_ops::trivial_bytes;
+use crate::hmac::hmac_sha256;
+use crate::padding::pad_sha256_data;
+use crate::sha256::sha256_clear;
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+const TERMINATOR: &str = "aws4_request";
+
+// Derives the signing key from the secret access key, for a date (YYYYMMDD), region and service
+pub fn signing_key(secret: &[Ciphertext], date: &str, region: &str, service: &str, sk: &ServerKey) -> Vec<Ciphertext> {
+    let mut key = trivial_bytes(b"AWS4", sk);
+    key.extend_from_slice(secret);
+
+    [date, region, service, TERMINATOR]
+        .iter()
+        .fold(key, |key, part| hmac_sha256(&key, &trivial_bytes(part.as_bytes(), sk), sk))
+}
+
+// Credential scope of the requests signed on a date (YYYYMMDD)
+pub fn credential_scope(date: &str, region: &str, service: &str) -> String {
+    format!("{}/{}/{}/{}", date, region, service, TERMINATOR)
+}
+
+// String to sign of a canonical request sent at a timestamp (YYYYMMDDTHHMMSSZ). Both are public, so the hash of the
+// canonical request is computed on the clear backend
+pub fn string_to_sign(timestamp: &str, region: &str, service: &str, canonical_request: &str, sk: &ServerKey) -> String {
+    let hash = sha256_clear(&pad_sha256_data(canonical_request.as_bytes()), sk);
+    let hash: String = hash.chunks(4).map(|nibble| format!("{:x}", nibble.iter().fold(0, |acc, bit| (acc << 1) | *bit as u8))).collect();
+
+    format!("{}\n{}\n{}\n{}", ALGORITHM, timestamp, credential_scope(&timestamp[..8], region, service), hash)
+}
+
+// Encrypted signature of a canonical request sent at a timestamp (YYYYMMDDTHHMMSSZ)
+pub fn sigv4_signature(
+    secret: &[Ciphertext],
+    timestamp: &str,
+    region: &str,
+    service: &str,
+    canonical_request: &str,
+    sk: &ServerKey,
+) -> Vec<Ciphertext> {
+    assert!(timestamp.len() == 16 && timestamp.ends_with('Z'), "timestamp is not in the YYYYMMDDTHHMMSSZ format");
+
+    let key = signing_key(secret, &timestamp[..8], region, service, sk);
+    let string_to_sign = string_to_sign(timestamp, region, service, canonical_request, sk);
+    hmac_sha256(&key, &trivial_bytes(string_to_sign.as_bytes(), sk), sk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_keys::test_keys;
+
+    const SECRET: &[u8] = b"wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+
+    fn decrypt_hex(bits: &[Ciphertext], ck: &ClientKey) -> String {
+        bits.chunks(8).map(|byte| format!("{:02x}", byte.iter().fold(0u8, |acc, bit| (acc << 1) | ck.decrypt(bit) as u8))).collect()
+    }
+
+    // Signing key example of the AWS documentation
+    #[test]
+    fn test_signing_key() {
+        let (ck, sk) = test_keys();
+
+        let key = signing_key(&trivial_bytes(SECRET, &sk), "20120215", "us-east-1", "iam", &sk);
+        assert_eq!(decrypt_hex(&key, &ck), "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d");
+    }
+
+    // get-vanilla case of the AWS SigV4 test suite
+    #[test]
+    fn test_sigv4_signature() {
+        let (ck, sk) = test_keys();
+        let canonical_request = "GET\n/\n\nhost:example.amazonaws.com\nx-amz-date:20150830T123600Z\n\nhost;x-amz-date\n\
+            e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+        assert_eq!(
+            string_to_sign("20150830T123600Z", "us-east-1", "service", canonical_request, &sk),
+            "AWS4-HMAC-SHA256\n20150830T123600Z\n20150830/us-east-1/service/aws4_request\n\
+            bb579772317eb040ac9ed261061d46c1f17a8133879d6129b6e1c25292927e63",
+        );
+
+        let signature = sigv4_signature(&trivial_bytes(SECRET, &sk), "20150830T123600Z", "us-east-1", "service", canonical_request, &sk);
+        assert_eq!(decrypt_hex(&signature, &ck), "5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31");
+    }
+}
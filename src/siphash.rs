@@ -0,0 +1,106 @@
+// This module implements SipHash-2-4 with an encrypted 128-bit key, a lightweight keyed hash for short-input MACs. Its
+// rounds are only 64-bit additions, rotations and XORs, so they map directly onto the generic word operations, and a
+// short message takes a handful of rounds instead of the four sha256 compressions of HMAC-SHA256.
+//
+// SipHash reads the key and the message as little-endian 64-bit words, and its output is the little-endian encoding of
+// the final word. Every byte is still stored most significant bit first, like everywhere else in the crate.
+
+use tfhe::boolean::prelude::*;
+use crate::boolean_ops::{add, from_le_bytes, rotate_left, swap_bytes, trivial_bytes, xor, xor_constant};
+
+type Word = [Ciphertext; 64];
+
+const INIT: [u64; 4] = [0x736f6d6570736575, 0x646f72616e646f6d, 0x6c7967656e657261, 0x7465646279746573];
+
+// 8-byte MAC of the message under the 16-byte key. The message length is public
+pub fn siphash24(key: &[Ciphertext], message: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    assert_eq!(key.len(), 128, "key must be 16 bytes long");
+    assert_eq!(message.len() % 8, 0, "message is not a whole number of bytes");
+
+    let k0: Word = from_le_bytes(&key[..64]);
+    let k1: Word = from_le_bytes(&key[64..]);
+    let mut v = [
+        xor_constant(&k0, INIT[0], sk),
+        xor_constant(&k1, INIT[1], sk),
+        xor_constant(&k0, INIT[2], sk),
+        xor_constant(&k1, INIT[3], sk),
+    ];
+
+    // The last word holds the remaining bytes, zero padded, and the message length modulo 256 in its last byte
+    let message_len = message.len() / 8;
+    let mut padded = message.to_vec();
+    padded.resize(8 * (message_len / 8 * 8 + 7), sk.trivial_encrypt(false));
+    padded.extend(trivial_bytes(&[message_len as u8], sk));
+
+    for chunk in padded.chunks(64) {
+        let m: Word = from_le_bytes(chunk);
+        v[3] = xor(&v[3], &m, sk);
+        sip_round(&mut v, sk);
+        sip_round(&mut v, sk);
+        v[0] = xor(&v[0], &m, sk);
+    }
+
+    v[2] = xor_constant(&v[2], 0xff, sk);
+    for _ in 0..4 {
+        sip_round(&mut v, sk);
+    }
+
+    let result = xor(&xor(&v[0], &v[1], sk), &xor(&v[2], &v[3], sk), sk);
+    swap_bytes(&result).to_vec()
+}
+
+// The (v0, v1) and (v2, v3) halves are independent in each half round, so they are computed in parallel
+fn sip_round(v: &mut [Word; 4], sk: &ServerKey) {
+    let ((v0, v1), (v2, v3)) = rayon::join(
+        || {
+            let v0 = add(&v[0], &v[1], sk);
+            (rotate_left(&v0, 32, sk), xor(&rotate_left(&v[1], 13, sk), &v0, sk))
+        },
+        || {
+            let v2 = add(&v[2], &v[3], sk);
+            (v2.clone(), xor(&rotate_left(&v[3], 16, sk), &v2, sk))
+        },
+    );
+
+    let ((v0, v3), (v2, v1)) = rayon::join(
+        || {
+            let v0 = add(&v0, &v3, sk);
+            let v3 = xor(&rotate_left(&v3, 21, sk), &v0, sk);
+            (v0, v3)
+        },
+        || {
+            let v2 = add(&v2, &v1, sk);
+            let v1 = xor(&rotate_left(&v1, 17, sk), &v2, sk);
+            (rotate_left(&v2, 32, sk), v1)
+        },
+    );
+
+    *v = [v0, v1, v2, v3];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::decrypt_bytes;
+    use crate::test_keys::test_keys;
+
+    // Test vectors of the SipHash reference implementation (key 00..0f, message 00..len-1), computed with the clear
+    // backend
+    #[test]
+    fn test_siphash24() {
+        let (ck, sk) = test_keys();
+        let key_bytes: Vec<u8> = (0..16).collect();
+        let key = trivial_bytes(&key_bytes, &sk);
+
+        let vectors: [(usize, [u8; 8]); 3] = [
+            (0, [0x31, 0x0e, 0x0e, 0xdd, 0x47, 0xdb, 0x6f, 0x72]),
+            (8, [0x62, 0x24, 0x93, 0x9a, 0x79, 0xf5, 0xf5, 0x93]),
+            (15, [0xe5, 0x45, 0xbe, 0x49, 0x61, 0xca, 0x29, 0xa1]),
+        ];
+        for (len, expected) in vectors {
+            let message: Vec<u8> = (0..len as u8).collect();
+            let mac = siphash24(&key, &trivial_bytes(&message, &sk), &sk);
+            assert_eq!(decrypt_bytes(&mac, &ck), expected);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+// This module implements the SM3 hash (GB/T 32905-2016) on the Merkle–Damgård engine. SM3 has the structure of sha256,
+// with 32-bit big-endian words, a message expansion and 64 rounds over eight working variables, and the same padding.
+// It differs in its expansion (68 words, plus the XOR of words 4 apart for the left half of each round), its boolean
+// functions, which are plain XORs in the first 16 rounds, and its feed-forward, which XORs the working variables into
+// the chaining value instead of adding them.
+
+use serde::{Deserialize, Serialize};
+use tfhe::boolean::prelude::*;
+use crate::boolean_ops::{add, ch, csa, maj, rotate_left, trivial_word, xor};
+use crate::merkle_damgard::{self, hash_padded, CompressionFn, Endianness, MerkleDamgard};
+
+pub type Word = [Ciphertext; 32];
+
+pub const ROUNDS: usize = 64;
+
+const IV: [u32; 8] = [0x7380166f, 0x4914b2b9, 0x172442d7, 0xda8a0600, 0xa96f30bc, 0x163138aa, 0xe38dee4d, 0xb0fb0e4e];
+
+// Round constants of the first 16 rounds and of the others, rotated left by the round index
+const T: [u32; 2] = [0x79cc4519, 0x7a879d8a];
+
+// Digest of an encrypted input padded as for sha256 (pad_sha256_data)
+pub fn sm3_fhe(padded_input: Vec<Ciphertext>, sk: &ServerKey) -> Vec<Ciphertext> {
+    assert_eq!(padded_input.len() % 512, 0, "padded input length is not a multiple of 512");
+
+    hash_padded(Sm3State::new(sk), &padded_input, sk)
+}
+
+// Server side padding of an encrypted message whose length is public
+pub fn pad_encrypted(message: Vec<Ciphertext>, sk: &ServerKey) -> Vec<Ciphertext> {
+    merkle_damgard::pad_encrypted::<Sm3State>(message, sk)
+}
+
+// Incremental SM3 hasher, for inputs that arrive in pieces
+pub type Sm3Hasher = MerkleDamgard<Sm3State>;
+
+// Chaining value of an SM3 computation, along with the number of input bits compressed into it. It can be serialized
+// to resume the computation later
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Sm3State {
+    words: [Word; 8],
+    length: u64,
+}
+
+impl Sm3State {
+    pub fn new(sk: &ServerKey) -> Self {
+        Sm3State { words: IV.map(|word| trivial_word(word as u64, sk)), length: 0 }
+    }
+
+    pub fn words(&self) -> &[Word; 8] {
+        &self.words
+    }
+}
+
+impl CompressionFn for Sm3State {
+    const BLOCK_BITS: usize = 512;
+    const LENGTH_BITS: usize = 64;
+    const LENGTH_ENDIANNESS: Endianness = Endianness::Big;
+
+    fn initial(sk: &ServerKey) -> Self {
+        Sm3State::new(sk)
+    }
+
+    fn compress(&mut self, block: &[Ciphertext], sk: &ServerKey) {
+        compress(&mut self.words, block, sk);
+        self.length += 512;
+    }
+
+    fn digest(self) -> Vec<Ciphertext> {
+        self.words.into_iter().flatten().collect()
+    }
+
+    fn length(&self) -> u64 {
+        self.length
+    }
+}
+
+fn compress(v: &mut [Word; 8], block: &[Ciphertext], sk: &ServerKey) {
+    assert_eq!(block.len(), 512, "block is not 512 bits long");
+
+    let mut w: Vec<Word> = block.chunks(32).map(|word| word.to_vec().try_into().unwrap()).collect();
+    for j in 16..68 {
+        let x = xor(&xor(&w[j - 16], &w[j - 9], sk), &rotate_left(&w[j - 3], 15, sk), sk);
+        let y = xor(&rotate_left(&w[j - 13], 7, sk), &w[j - 6], sk);
+        w.push(xor(&p1(&x, sk), &y, sk));
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = v.clone();
+    for j in 0..ROUNDS {
+        let a12 = rotate_left(&a, 12, sk);
+        let t = trivial_word(T[(j >= 16) as usize].rotate_left(j as u32 % 32) as u64, sk);
+        let (sum, carry) = csa(&a12, &e, &t, sk);
+        let ss1 = rotate_left(&add(&sum, &carry, sk), 7, sk);
+        let ss2 = xor(&ss1, &a12, sk);
+
+        let (tt1, tt2) = rayon::join(
+            || {
+                let ff = if j < 16 { xor(&xor(&a, &b, sk), &c, sk) } else { maj(&a, &b, &c, sk) };
+                let (sum, carry) = csa(&ff, &d, &ss2, sk);
+                let (sum, carry) = csa(&sum, &carry, &xor(&w[j], &w[j + 4], sk), sk);
+                add(&sum, &carry, sk)
+            },
+            || {
+                let gg = if j < 16 { xor(&xor(&e, &f, sk), &g, sk) } else { ch(&e, &f, &g, sk) };
+                let (sum, carry) = csa(&gg, &h, &ss1, sk);
+                let (sum, carry) = csa(&sum, &carry, &w[j], sk);
+                add(&sum, &carry, sk)
+            },
+        );
+
+        d = c;
+        c = rotate_left(&b, 9, sk);
+        b = a;
+        a = tt1;
+        h = g;
+        g = rotate_left(&f, 19, sk);
+        f = e;
+        e = p0(&tt2, sk);
+    }
+
+    for (word, working) in v.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *word = xor(word, &working, sk);
+    }
+}
+
+// Permutation of the round function
+fn p0(x: &Word, sk: &ServerKey) -> Word {
+    xor(&xor(x, &rotate_left(x, 9, sk), sk), &rotate_left(x, 17, sk), sk)
+}
+
+// Permutation of the message expansion
+fn p1(x: &Word, sk: &ServerKey) -> Word {
+    xor(&xor(x, &rotate_left(x, 15, sk), sk), &rotate_left(x, 23, sk), sk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boolean_ops::trivial_bytes;
+    use crate::padding::pad_sha256_data;
+    use crate::test_helpers::{decrypt_bytes, from_hex};
+    use crate::test_keys::test_keys;
+
+    // The two examples of GB/T 32905-2016 (one and two blocks), computed with the clear backend
+    #[test]
+    fn test_sm3() {
+        let (ck, sk) = test_keys();
+        let trivial = |data: &[u8]| pad_sha256_data(data).iter().map(|bit| sk.trivial_encrypt(*bit)).collect();
+
+        assert_eq!(
+            decrypt_bytes(&sm3_fhe(trivial(b"abc"), &sk), &ck),
+            from_hex("66c7f0f462eeedd9d1f2d46bdc10e4e24167c4875cf2f7a2297da02b8f4ba8e0"),
+        );
+        assert_eq!(
+            decrypt_bytes(&sm3_fhe(trivial(&b"abcd".repeat(16)), &sk), &ck),
+            from_hex("debe9ff92275b8a138604889c18e5a4d6fdb70e5387e5765293dcba39c0c5732"),
+        );
+    }
+
+    #[test]
+    fn test_sm3_hasher() {
+        let (ck, sk) = test_keys();
+
+        let mut hasher = Sm3Hasher::new(&sk);
+        hasher.update_plain(b"abcdabcdabcdabcdabcdabcdabcdabcdabcdabcd", &sk);
+        hasher.update_plain(b"abcdabcdabcdabcdabcdabcd", &sk);
+        assert_eq!(
+            decrypt_bytes(&hasher.finalize(&sk), &ck),
+            from_hex("debe9ff92275b8a138604889c18e5a4d6fdb70e5387e5765293dcba39c0c5732"),
+        );
+
+        assert_eq!(
+            decrypt_bytes(&sm3_fhe(pad_encrypted(trivial_bytes(b"abc", &sk), &sk), &sk), &ck),
+            from_hex("66c7f0f462eeedd9d1f2d46bdc10e4e24167c4875cf2f7a2297da02b8f4ba8e0"),
+        );
+    }
+}
@@ -0,0 +1,194 @@
+// This module is the generic sponge construction, shared by every hash built on a permutation (the Keccak family here,
+// and any other permutation-based hash later on). A hash only implements Permutation and picks its rate and domain
+// suffix, and gets absorbing, pad10*1 padding and squeezing (of any output length, for XOFs) from this module.
+//
+// Sponges work on bytes: the state is WIDTH / 8 bytes, each one stored most significant bit first like everywhere else
+// in the crate, and the permutation reads them in whatever order it defines. The padding only depends on the message
+// length, which is public, so it is trivially encrypted.
+
+use std::marker::PhantomData;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tfhe::boolean::prelude::*;
+use crate::boolean_ops::{trivial_bytes, xor_gate};
+
+// Fixed permutation of a WIDTH-bit state
+pub trait Permutation {
+    const WIDTH: usize;
+
+    fn permute(state: &mut [Ciphertext], sk: &ServerKey);
+}
+
+// Sponge over the permutation P, with a rate of `rate` bits and the remaining WIDTH - rate bits as capacity. The domain
+// suffix is given in the delimited form of FIPS 202 (the suffix bits, least significant first, followed by a 1 bit):
+// 0x06 for SHA-3, 0x1f for SHAKE, 0x04 for cSHAKE and 0x01 for plain Keccak. It can be serialized between calls
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Sponge<P> {
+    state: Vec<Ciphertext>,
+    rate: usize,
+    suffix: u8,
+    // Absorbed bits that don't fill a whole block yet
+    buffer: Vec<Ciphertext>,
+    // Output bits of the current block not squeezed yet, once the padding has been absorbed
+    output: Option<Vec<Ciphertext>>,
+    permutation: PhantomData<P>,
+}
+
+impl<P: Permutation> Sponge<P> {
+    pub fn new(rate: usize, suffix: u8, sk: &ServerKey) -> Self {
+        assert!(rate > 0 && rate < P::WIDTH, "rate must leave room for a capacity");
+        assert!(rate.is_multiple_of(8), "rate is not a whole number of bytes");
+        assert!(suffix != 0 && suffix < 0x80, "invalid domain suffix");
+
+        Sponge {
+            state: vec![sk.trivial_encrypt(false); P::WIDTH],
+            rate,
+            suffix,
+            buffer: Vec::with_capacity(rate),
+            output: None,
+            permutation: PhantomData,
+        }
+    }
+
+    pub fn rate(&self) -> usize {
+        self.rate
+    }
+
+    pub fn capacity(&self) -> usize {
+        P::WIDTH - self.rate
+    }
+
+    // Absorbs encrypted bytes, permuting after every full block
+    pub fn absorb(&mut self, bits: &[Ciphertext], sk: &ServerKey) {
+        assert!(self.output.is_none(), "can't absorb after squeezing");
+        assert_eq!(bits.len() % 8, 0, "input is not a whole number of bytes");
+
+        self.buffer.extend_from_slice(bits);
+
+        let full = self.buffer.len() - self.buffer.len() % self.rate;
+        if full > 0 {
+            let blocks: Vec<Ciphertext> = self.buffer.drain(..full).collect();
+            for block in blocks.chunks(self.rate) {
+                self.absorb_block(block, sk);
+            }
+        }
+    }
+
+    // Absorbs plaintext bytes as trivial ciphertexts
+    pub fn absorb_plain(&mut self, bytes: &[u8], sk: &ServerKey) {
+        self.absorb(&trivial_bytes(bytes, sk), sk);
+    }
+
+    // Squeezes the next output bytes. The first call pads and absorbs the last block, and later calls continue the
+    // output stream
+    pub fn squeeze(&mut self, output_len: usize, sk: &ServerKey) -> Vec<Ciphertext> {
+        if self.output.is_none() {
+            self.absorb_padding(sk);
+        }
+
+        let mut squeezed = Vec::with_capacity(output_len * 8);
+        while squeezed.len() < output_len * 8 {
+            let available = self.output.as_mut().unwrap();
+            if available.is_empty() {
+                P::permute(&mut self.state, sk);
+                *available = self.state[..self.rate].to_vec();
+            }
+
+            let take = available.len().min(output_len * 8 - squeezed.len());
+            squeezed.extend(available.drain(..take));
+        }
+
+        squeezed
+    }
+
+    // pad10*1 with the domain suffix: the suffix byte after the message and a final 0x80, in the same byte if it's the
+    // last one of the block
+    fn absorb_padding(&mut self, sk: &ServerKey) {
+        let remaining = (self.rate - self.buffer.len()) / 8;
+        let mut padding = vec![0u8; remaining];
+        padding[0] = self.suffix;
+        padding[remaining - 1] ^= 0x80;
+
+        let mut block = std::mem::take(&mut self.buffer);
+        block.extend(trivial_bytes(&padding, sk));
+        self.absorb_block(&block, sk);
+
+        self.output = Some(self.state[..self.rate].to_vec());
+    }
+
+    fn absorb_block(&mut self, block: &[Ciphertext], sk: &ServerKey) {
+        let mixed: Vec<Ciphertext> = self.state[..self.rate]
+            .par_iter()
+            .zip(block.par_iter())
+            .map(|(state, bit)| xor_gate(sk, state, bit))
+            .collect();
+
+        self.state[..self.rate].clone_from_slice(&mixed);
+        P::permute(&mut self.state, sk);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::decrypt_bytes;
+    use crate::test_keys::test_keys;
+
+    // Rotates the state by one byte, so the state is easy to predict in the clear
+    struct RotateByte;
+
+    impl Permutation for RotateByte {
+        const WIDTH: usize = 256;
+
+        fn permute(state: &mut [Ciphertext], _: &ServerKey) {
+            state.rotate_left(8);
+        }
+    }
+
+    fn rotate_byte_sponge(message: &[u8], rate: usize, suffix: u8, output_len: usize) -> Vec<u8> {
+        let mut padded = message.to_vec();
+        let remaining = rate - message.len() % rate;
+        padded.push(suffix);
+        padded.resize(message.len() + remaining, 0);
+        *padded.last_mut().unwrap() ^= 0x80;
+
+        let mut state = [0u8; 32];
+        for block in padded.chunks(rate) {
+            for (byte, input) in state.iter_mut().zip(block) {
+                *byte ^= input;
+            }
+            state.rotate_left(1);
+        }
+
+        let mut output = state[..rate].to_vec();
+        while output.len() < output_len {
+            state.rotate_left(1);
+            output.extend_from_slice(&state[..rate]);
+        }
+        output.truncate(output_len);
+        output
+    }
+
+    #[test]
+    fn test_sponge() {
+        let (ck, sk) = test_keys();
+
+        // Absorbing in pieces, padding at every position of the block (the last one sharing the suffix byte), and
+        // squeezing more than one block in several calls
+        for len in 0..=40 {
+            let message: Vec<u8> = (0..len).map(|i| (i * 31 + 7) as u8).collect();
+
+            let mut sponge = Sponge::<RotateByte>::new(8 * 12, 0x06, &sk);
+            for piece in message.chunks(5) {
+                sponge.absorb_plain(piece, &sk);
+            }
+            let mut output = decrypt_bytes(&sponge.squeeze(10, &sk), &ck);
+            output.extend(decrypt_bytes(&sponge.squeeze(20, &sk), &ck));
+
+            assert_eq!(output, rotate_byte_sponge(&message, 12, 0x06, 30), "message length {}", len);
+        }
+
+        assert_eq!(Sponge::<RotateByte>::new(8 * 12, 0x1f, &sk).capacity(), 160);
+    }
+}
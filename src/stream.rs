@@ -0,0 +1,119 @@
+// This module hashes an encrypted input while it is still arriving over the network. The client sends the padded input
+// as a block stream (serialization::serialize_blocks_into), and a reader thread decodes every block as soon as its frame
+// is complete and hands it over to the compression loop. Compressing a block then overlaps with the transfer of the
+// following ones, so the digest of a long input is ready shortly after its last block arrives, instead of a whole
+// transfer plus a whole computation later.
+//
+// The stream is read from anything implementing Read, such as a TcpStream or a rustls StreamOwned with the "tls" feature.
+
+use std::io::Read;
+use std::sync::mpsc::sync_channel;
+use std::thread;
+use tfhe::boolean::prelude::*;
+use crate::merkle_damgard::CompressionFn;
+use crate::parameters::ParameterSet;
+use crate::serialization::{deserialize_block_from, SerializationError};
+
+// Decoded blocks waiting for the compression loop. The reader stops reading from the connection beyond this, so a
+// client sending faster than the server computes can't make it buffer the whole input
+const BLOCKS_AHEAD: usize = 2;
+
+// Hashes the padded input read from a block stream, compressing each block as soon as it is received, and returns the
+// digest with the number of blocks. Streams of more than max_blocks blocks are rejected once the extra block arrives,
+// and an empty stream is rejected as truncated
+pub fn hash_stream<C: CompressionFn, R: Read + Send>(
+    reader: R,
+    max_blocks: usize,
+    parameter_set: ParameterSet,
+    sk: &ServerKey,
+) -> Result<(Vec<Ciphertext>, usize), SerializationError> {
+    let (sender, receiver) = sync_channel(BLOCKS_AHEAD);
+
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            let mut reader = reader;
+            let mut received = 0;
+            loop {
+                let block = match deserialize_block_from(&mut reader, C::BLOCK_BITS, parameter_set) {
+                    Ok(None) => break,
+                    Ok(Some(_)) if received == max_blocks => Err(SerializationError::TooManyBlocks { limit: max_blocks }),
+                    Ok(Some(block)) => Ok(block),
+                    Err(e) => Err(e),
+                };
+                received += 1;
+
+                // Stop at the first error, or once the compression loop is gone
+                let failed = block.is_err();
+                if sender.send(block).is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        let mut state = C::initial(sk);
+        let mut compressed = 0;
+        for block in receiver {
+            state.compress(&block?, sk);
+            compressed += 1;
+        }
+
+        if compressed == 0 {
+            return Err(SerializationError::Truncated);
+        }
+        Ok((state.digest(), compressed))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{TcpListener, TcpStream};
+    use sha2::{Digest, Sha256};
+    use crate::client::bools_to_hex;
+    use crate::padding::pad_sha256_data;
+    use crate::serialization::serialize_blocks_into;
+    use crate::sha256::Sha256State;
+    use crate::test_keys::test_keys;
+
+    fn blocks(message: &[u8], sk: &ServerKey) -> Vec<Vec<Ciphertext>> {
+        pad_sha256_data(message).chunks(512).map(|block| block.iter().map(|bit| sk.trivial_encrypt(*bit)).collect()).collect()
+    }
+
+    #[test]
+    fn test_hash_stream() {
+        let (ck, sk) = test_keys();
+        let message = [0x61; 150];
+
+        // Over a real connection, the client writing while the server hashes
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let input = blocks(&message, &sk);
+        let client = thread::spawn(move || serialize_blocks_into(TcpStream::connect(address).unwrap(), input, ParameterSet::Default));
+
+        let (stream, _) = listener.accept().unwrap();
+        let (digest, blocks) = hash_stream::<Sha256State, _>(stream, 3, ParameterSet::Default, &sk).unwrap();
+        client.join().unwrap().unwrap();
+        assert_eq!(blocks, 3);
+        assert_eq!(bools_to_hex(digest.iter().map(|bit| ck.decrypt(bit)).collect()), format!("{:x}", Sha256::digest(message)));
+    }
+
+    #[test]
+    fn test_invalid_streams() {
+        let (_, sk) = test_keys();
+
+        let mut bytes = vec![];
+        serialize_blocks_into(&mut bytes, blocks(&[0x61; 150], &sk), ParameterSet::Default).unwrap();
+
+        let result = hash_stream::<Sha256State, _>(bytes.as_slice(), 2, ParameterSet::Default, &sk);
+        assert!(matches!(result, Err(SerializationError::TooManyBlocks { limit: 2 })));
+
+        // A stream without its end marker, and one without any block
+        let result = hash_stream::<Sha256State, _>(&bytes[..bytes.len() - 1], 3, ParameterSet::Default, &sk);
+        assert!(matches!(result, Err(SerializationError::Truncated)));
+
+        let mut bytes = vec![];
+        serialize_blocks_into(&mut bytes, [], ParameterSet::Default).unwrap();
+        let result = hash_stream::<Sha256State, _>(bytes.as_slice(), 3, ParameterSet::Default, &sk);
+        assert!(matches!(result, Err(SerializationError::Truncated)));
+    }
+}
@@ -0,0 +1,314 @@
+// This module implements the Streebog hash (GOST R 34.11-2012, RFC 6986) with 256 and 512-bit digests. The input is
+// padded by the client (padding::pad_streebog_data), which also puts the message length in a last block of its own, so
+// that only the number of blocks is public, as with the Merkle–Damgård hashes. The block counter N then only depends on
+// the block index and is public too, while the checksum of the blocks is encrypted like them. A message whose length
+// can be public is padded by the server instead (pad_encrypted).
+//
+// Streebog reads a block of the message as a little-endian 512-bit number (byte i of the block is byte i of the number)
+// and its linear layer works on little-endian 64-bit words, which are read with the generic word operations as in
+// BLAKE2b. Its S-box has no compact circuit, so each byte goes through a lookup in the public table (a tree of MUXes),
+// about a thousand bootstrapped gates per byte and 65k per layer of S-boxes. A block with an encrypted chaining value
+// takes 25 such layers, about 1.8M bootstrapped gates or twelve times a sha256 block, and the last block and the
+// finalization add three more compressions to those of the full blocks.
+
+use rayon::prelude::*;
+use tfhe::boolean::prelude::*;
+use crate::boolean_ops::{add, from_le_bytes, parity, reverse_bytes, select_index, swap_bytes, trivial_bytes, xor};
+
+pub const BLOCK_BITS: usize = 512;
+
+// 512-bit value, as a little-endian byte string with every byte stored most significant bit first
+type Block = [Ciphertext; BLOCK_BITS];
+
+const PI: [u8; 256] = [
+    252, 238, 221, 17, 207, 110, 49, 22, 251, 196, 250, 218, 35, 197, 4, 77,
+    233, 119, 240, 219, 147, 46, 153, 186, 23, 54, 241, 187, 20, 205, 95, 193,
+    249, 24, 101, 90, 226, 92, 239, 33, 129, 28, 60, 66, 139, 1, 142, 79,
+    5, 132, 2, 174, 227, 106, 143, 160, 6, 11, 237, 152, 127, 212, 211, 31,
+    235, 52, 44, 81, 234, 200, 72, 171, 242, 42, 104, 162, 253, 58, 206, 204,
+    181, 112, 14, 86, 8, 12, 118, 18, 191, 114, 19, 71, 156, 183, 93, 135,
+    21, 161, 150, 41, 16, 123, 154, 199, 243, 145, 120, 111, 157, 158, 178, 177,
+    50, 117, 25, 61, 255, 53, 138, 126, 109, 84, 198, 128, 195, 189, 13, 87,
+    223, 245, 36, 169, 62, 168, 67, 201, 215, 121, 214, 246, 124, 34, 185, 3,
+    224, 15, 236, 222, 122, 148, 176, 188, 220, 232, 40, 80, 78, 51, 10, 74,
+    167, 151, 96, 115, 30, 0, 98, 68, 26, 184, 56, 130, 100, 159, 38, 65,
+    173, 69, 70, 146, 39, 94, 85, 47, 140, 163, 165, 125, 105, 213, 149, 59,
+    7, 88, 179, 64, 134, 172, 29, 247, 48, 55, 107, 228, 136, 217, 231, 137,
+    225, 27, 131, 73, 76, 63, 248, 254, 141, 83, 170, 144, 202, 216, 133, 97,
+    32, 113, 103, 164, 45, 43, 9, 91, 203, 155, 37, 208, 190, 229, 108, 82,
+    89, 166, 116, 210, 230, 244, 180, 192, 209, 102, 175, 194, 57, 75, 99, 182,
+];
+const A: [u64; 64] = [
+    0x8e20faa72ba0b470, 0x47107ddd9b505a38, 0xad08b0e0c3282d1c, 0xd8045870ef14980e,
+    0x6c022c38f90a4c07, 0x3601161cf205268d, 0x1b8e0b0e798c13c8, 0x83478b07b2468764,
+    0xa011d380818e8f40, 0x5086e740ce47c920, 0x2843fd2067adea10, 0x14aff010bdd87508,
+    0x0ad97808d06cb404, 0x05e23c0468365a02, 0x8c711e02341b2d01, 0x46b60f011a83988e,
+    0x90dab52a387ae76f, 0x486dd4151c3dfdb9, 0x24b86a840e90f0d2, 0x125c354207487869,
+    0x092e94218d243cba, 0x8a174a9ec8121e5d, 0x4585254f64090fa0, 0xaccc9ca9328a8950,
+    0x9d4df05d5f661451, 0xc0a878a0a1330aa6, 0x60543c50de970553, 0x302a1e286fc58ca7,
+    0x18150f14b9ec46dd, 0x0c84890ad27623e0, 0x0642ca05693b9f70, 0x0321658cba93c138,
+    0x86275df09ce8aaa8, 0x439da0784e745554, 0xafc0503c273aa42a, 0xd960281e9d1d5215,
+    0xe230140fc0802984, 0x71180a8960409a42, 0xb60c05ca30204d21, 0x5b068c651810a89e,
+    0x456c34887a3805b9, 0xac361a443d1c8cd2, 0x561b0d22900e4669, 0x2b838811480723ba,
+    0x9bcf4486248d9f5d, 0xc3e9224312c8c1a0, 0xeffa11af0964ee50, 0xf97d86d98a327728,
+    0xe4fa2054a80b329c, 0x727d102a548b194e, 0x39b008152acb8227, 0x9258048415eb419d,
+    0x492c024284fbaec0, 0xaa16012142f35760, 0x550b8e9e21f7a530, 0xa48b474f9ef5dc18,
+    0x70a6a56e2440598e, 0x3853dc371220a247, 0x1ca76e95091051ad, 0x0edd37c48a08a6d8,
+    0x07e095624504536c, 0x8d70c431ac02a736, 0xc83862965601dd1b, 0x641c314b2b8ee083,
+];
+const C: [[u8; 64]; 12] = [
+    [
+        0x07, 0x45, 0xa6, 0xf2, 0x59, 0x65, 0x80, 0xdd, 0x23, 0x4d, 0x74, 0xcc, 0x36, 0x74, 0x76, 0x05,
+        0x15, 0xd3, 0x60, 0xa4, 0x08, 0x2a, 0x42, 0xa2, 0x01, 0x69, 0x67, 0x92, 0x91, 0xe0, 0x7c, 0x4b,
+        0xfc, 0xc4, 0x85, 0x75, 0x8d, 0xb8, 0x4e, 0x71, 0x16, 0xd0, 0x45, 0x2e, 0x43, 0x76, 0x6a, 0x2f,
+        0x1f, 0x7c, 0x65, 0xc0, 0x81, 0x2f, 0xcb, 0xeb, 0xe9, 0xda, 0xca, 0x1e, 0xda, 0x5b, 0x08, 0xb1,
+    ],
+    [
+        0xb7, 0x9b, 0xb1, 0x21, 0x70, 0x04, 0x79, 0xe6, 0x56, 0xcd, 0xcb, 0xd7, 0x1b, 0xa2, 0xdd, 0x55,
+        0xca, 0xa7, 0x0a, 0xdb, 0xc2, 0x61, 0xb5, 0x5c, 0x58, 0x99, 0xd6, 0x12, 0x6b, 0x17, 0xb5, 0x9a,
+        0x31, 0x01, 0xb5, 0x16, 0x0f, 0x5e, 0xd5, 0x61, 0x98, 0x2b, 0x23, 0x0a, 0x72, 0xea, 0xfe, 0xf3,
+        0xd7, 0xb5, 0x70, 0x0f, 0x46, 0x9d, 0xe3, 0x4f, 0x1a, 0x2f, 0x9d, 0xa9, 0x8a, 0xb5, 0xa3, 0x6f,
+    ],
+    [
+        0xb2, 0x0a, 0xba, 0x0a, 0xf5, 0x96, 0x1e, 0x99, 0x31, 0xdb, 0x7a, 0x86, 0x43, 0xf4, 0xb6, 0xc2,
+        0x09, 0xdb, 0x62, 0x60, 0x37, 0x3a, 0xc9, 0xc1, 0xb1, 0x9e, 0x35, 0x90, 0xe4, 0x0f, 0xe2, 0xd3,
+        0x7b, 0x7b, 0x29, 0xb1, 0x14, 0x75, 0xea, 0xf2, 0x8b, 0x1f, 0x9c, 0x52, 0x5f, 0x5e, 0xf1, 0x06,
+        0x35, 0x84, 0x3d, 0x6a, 0x28, 0xfc, 0x39, 0x0a, 0xc7, 0x2f, 0xce, 0x2b, 0xac, 0xdc, 0x74, 0xf5,
+    ],
+    [
+        0x2e, 0xd1, 0xe3, 0x84, 0xbc, 0xbe, 0x0c, 0x22, 0xf1, 0x37, 0xe8, 0x93, 0xa1, 0xea, 0x53, 0x34,
+        0xbe, 0x03, 0x52, 0x93, 0x33, 0x13, 0xb7, 0xd8, 0x75, 0xd6, 0x03, 0xed, 0x82, 0x2c, 0xd7, 0xa9,
+        0x3f, 0x35, 0x5e, 0x68, 0xad, 0x1c, 0x72, 0x9d, 0x7d, 0x3c, 0x5c, 0x33, 0x7e, 0x85, 0x8e, 0x48,
+        0xdd, 0xe4, 0x71, 0x5d, 0xa0, 0xe1, 0x48, 0xf9, 0xd2, 0x66, 0x15, 0xe8, 0xb3, 0xdf, 0x1f, 0xef,
+    ],
+    [
+        0x57, 0xfe, 0x6c, 0x7c, 0xfd, 0x58, 0x17, 0x60, 0xf5, 0x63, 0xea, 0xa9, 0x7e, 0xa2, 0x56, 0x7a,
+        0x16, 0x1a, 0x27, 0x23, 0xb7, 0x00, 0xff, 0xdf, 0xa3, 0xf5, 0x3a, 0x25, 0x47, 0x17, 0xcd, 0xbf,
+        0xbd, 0xff, 0x0f, 0x80, 0xd7, 0x35, 0x9e, 0x35, 0x4a, 0x10, 0x86, 0x16, 0x1f, 0x1c, 0x15, 0x7f,
+        0x63, 0x23, 0xa9, 0x6c, 0x0c, 0x41, 0x3f, 0x9a, 0x99, 0x47, 0x47, 0xad, 0xac, 0x6b, 0xea, 0x4b,
+    ],
+    [
+        0x6e, 0x7d, 0x64, 0x46, 0x7a, 0x40, 0x68, 0xfa, 0x35, 0x4f, 0x90, 0x36, 0x72, 0xc5, 0x71, 0xbf,
+        0xb6, 0xc6, 0xbe, 0xc2, 0x66, 0x1f, 0xf2, 0x0a, 0xb4, 0xb7, 0x9a, 0x1c, 0xb7, 0xa6, 0xfa, 0xcf,
+        0xc6, 0x8e, 0xf0, 0x9a, 0xb4, 0x9a, 0x7f, 0x18, 0x6c, 0xa4, 0x42, 0x51, 0xf9, 0xc4, 0x66, 0x2d,
+        0xc0, 0x39, 0x30, 0x7a, 0x3b, 0xc3, 0xa4, 0x6f, 0xd9, 0xd3, 0x3a, 0x1d, 0xae, 0xae, 0x4f, 0xae,
+    ],
+    [
+        0x93, 0xd4, 0x14, 0x3a, 0x4d, 0x56, 0x86, 0x88, 0xf3, 0x4a, 0x3c, 0xa2, 0x4c, 0x45, 0x17, 0x35,
+        0x04, 0x05, 0x4a, 0x28, 0x83, 0x69, 0x47, 0x06, 0x37, 0x2c, 0x82, 0x2d, 0xc5, 0xab, 0x92, 0x09,
+        0xc9, 0x93, 0x7a, 0x19, 0x33, 0x3e, 0x47, 0xd3, 0xc9, 0x87, 0xbf, 0xe6, 0xc7, 0xc6, 0x9e, 0x39,
+        0x54, 0x09, 0x24, 0xbf, 0xfe, 0x86, 0xac, 0x51, 0xec, 0xc5, 0xaa, 0xee, 0x16, 0x0e, 0xc7, 0xf4,
+    ],
+    [
+        0x1e, 0xe7, 0x02, 0xbf, 0xd4, 0x0d, 0x7f, 0xa4, 0xd9, 0xa8, 0x51, 0x59, 0x35, 0xc2, 0xac, 0x36,
+        0x2f, 0xc4, 0xa5, 0xd1, 0x2b, 0x8d, 0xd1, 0x69, 0x90, 0x06, 0x9b, 0x92, 0xcb, 0x2b, 0x89, 0xf4,
+        0x9a, 0xc4, 0xdb, 0x4d, 0x3b, 0x44, 0xb4, 0x89, 0x1e, 0xde, 0x36, 0x9c, 0x71, 0xf8, 0xb7, 0x4e,
+        0x41, 0x41, 0x6e, 0x0c, 0x02, 0xaa, 0xe7, 0x03, 0xa7, 0xc9, 0x93, 0x4d, 0x42, 0x5b, 0x1f, 0x9b,
+    ],
+    [
+        0xdb, 0x5a, 0x23, 0x83, 0x51, 0x44, 0x61, 0x72, 0x60, 0x2a, 0x1f, 0xcb, 0x92, 0xdc, 0x38, 0x0e,
+        0x54, 0x9c, 0x07, 0xa6, 0x9a, 0x8a, 0x2b, 0x7b, 0xb1, 0xce, 0xb2, 0xdb, 0x0b, 0x44, 0x0a, 0x80,
+        0x84, 0x09, 0x0d, 0xe0, 0xb7, 0x55, 0xd9, 0x3c, 0x24, 0x42, 0x89, 0x25, 0x1b, 0x3a, 0x7d, 0x3a,
+        0xde, 0x5f, 0x16, 0xec, 0xd8, 0x9a, 0x4c, 0x94, 0x9b, 0x22, 0x31, 0x16, 0x54, 0x5a, 0x8f, 0x37,
+    ],
+    [
+        0xed, 0x9c, 0x45, 0x98, 0xfb, 0xc7, 0xb4, 0x74, 0xc3, 0xb6, 0x3b, 0x15, 0xd1, 0xfa, 0x98, 0x36,
+        0xf4, 0x52, 0x76, 0x3b, 0x30, 0x6c, 0x1e, 0x7a, 0x4b, 0x33, 0x69, 0xaf, 0x02, 0x67, 0xe7, 0x9f,
+        0x03, 0x61, 0x33, 0x1b, 0x8a, 0xe1, 0xff, 0x1f, 0xdb, 0x78, 0x8a, 0xff, 0x1c, 0xe7, 0x41, 0x89,
+        0xf3, 0xf3, 0xe4, 0xb2, 0x48, 0xe5, 0x2a, 0x38, 0x52, 0x6f, 0x05, 0x80, 0xa6, 0xde, 0xbe, 0xab,
+    ],
+    [
+        0x1b, 0x2d, 0xf3, 0x81, 0xcd, 0xa4, 0xca, 0x6b, 0x5d, 0xd8, 0x6f, 0xc0, 0x4a, 0x59, 0xa2, 0xde,
+        0x98, 0x6e, 0x47, 0x7d, 0x1d, 0xcd, 0xba, 0xef, 0xca, 0xb9, 0x48, 0xea, 0xef, 0x71, 0x1d, 0x8a,
+        0x79, 0x66, 0x84, 0x14, 0x21, 0x80, 0x01, 0x20, 0x61, 0x07, 0xab, 0xeb, 0xbb, 0x6b, 0xfa, 0xd8,
+        0x94, 0xfe, 0x5a, 0x63, 0xcd, 0xc6, 0x02, 0x30, 0xfb, 0x89, 0xc8, 0xef, 0xd0, 0x9e, 0xcd, 0x7b,
+    ],
+    [
+        0x20, 0xd7, 0x1b, 0xf1, 0x4a, 0x92, 0xbc, 0x48, 0x99, 0x1b, 0xb2, 0xd9, 0xd5, 0x17, 0xf4, 0xfa,
+        0x52, 0x28, 0xe1, 0x88, 0xaa, 0xa4, 0x1d, 0xe7, 0x86, 0xcc, 0x91, 0x18, 0x9d, 0xef, 0x80, 0x5d,
+        0x9b, 0x9f, 0x21, 0x30, 0xd4, 0x12, 0x20, 0xf8, 0x77, 0x1d, 0xdf, 0xbc, 0x32, 0x3c, 0xa4, 0xcd,
+        0x7a, 0xb1, 0x49, 0x04, 0xb0, 0x80, 0x13, 0xd2, 0xba, 0x31, 0x16, 0xf1, 0x67, 0xe7, 0x8e, 0x37,
+    ],
+];
+
+pub fn streebog256(message: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    streebog_fhe(pad_encrypted(message.to_vec(), sk), 256, sk)
+}
+
+pub fn streebog512(message: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    streebog_fhe(pad_encrypted(message.to_vec(), sk), 512, sk)
+}
+
+// Digest of digest_bits bits (256 or 512) of an encrypted input padded with pad_streebog_data
+pub fn streebog_fhe(padded_input: Vec<Ciphertext>, digest_bits: usize, sk: &ServerKey) -> Vec<Ciphertext> {
+    assert!(
+        padded_input.len() >= 2 * BLOCK_BITS && padded_input.len().is_multiple_of(BLOCK_BITS),
+        "padded input is not a whole number of at least two blocks",
+    );
+
+    // The two digest sizes only differ by their initial value and the truncation
+    let mut h = constant(&[if digest_bits == 256 { 0x01 } else { 0x00 }; 64], sk);
+    let mut checksum = constant(&[0; 64], sk);
+
+    // The message blocks, the last of them padded, and then the length block
+    let (blocks, length) = padded_input.split_at(padded_input.len() - BLOCK_BITS);
+    for (i, block) in blocks.chunks(BLOCK_BITS).enumerate() {
+        let block: Block = block.to_vec().try_into().unwrap();
+        h = compress(&h, (i * BLOCK_BITS) as u64, &block, sk);
+        checksum = add_le(&checksum, &block, sk);
+    }
+
+    h = compress(&h, 0, &length.to_vec().try_into().unwrap(), sk);
+    h = compress(&h, 0, &checksum, sk);
+
+    // A 256-bit digest is the most significant half
+    h[BLOCK_BITS - digest_bits..].to_vec()
+}
+
+// Server side padding of an encrypted message whose length is public, as pad_streebog_data does
+pub fn pad_encrypted(mut message: Vec<Ciphertext>, sk: &ServerKey) -> Vec<Ciphertext> {
+    assert_eq!(message.len() % 8, 0, "message is not a whole number of bytes");
+
+    // The rest of the message (possibly nothing) is followed by a 1 byte and zeros to fill the last block
+    let length = message.len() as u64;
+    let full_blocks_len = message.len() / BLOCK_BITS * BLOCK_BITS;
+    message.extend(trivial_bytes(&[0x01], sk));
+    message.resize(full_blocks_len + BLOCK_BITS, sk.trivial_encrypt(false));
+    message.extend(number(length, sk));
+    message
+}
+
+// Compression function g_N(h, m) = E(LPS(h ^ N), m) ^ h ^ m, where N is the number of message bits hashed before m
+fn compress(h: &Block, n: u64, m: &Block, sk: &ServerKey) -> Block {
+    let key = lps(&xor_constant(h, &number_bytes(n), sk), sk);
+    let encrypted = encrypt(key, m.clone(), sk);
+    xor(&xor(&encrypted, h, sk), m, sk)
+}
+
+// Block cipher E of the compression function: 12 rounds, with round keys derived from the key by the same LPS
+// transform and the round constants
+fn encrypt(mut key: Block, mut state: Block, sk: &ServerKey) -> Block {
+    for c in C.iter() {
+        (state, key) = rayon::join(|| lps(&xor(&key, &state, sk), sk), || lps(&xor_constant(&key, c, sk), sk));
+    }
+    xor(&key, &state, sk)
+}
+
+// The S-box on every byte, the transposition of the bytes as an 8x8 matrix (P), and the linear layer on every 64-bit
+// word (L), which multiplies it by the matrix A over GF(2): output bit i is the parity of the input bits j such that bit
+// i of A[j] is set
+fn lps(x: &Block, sk: &ServerKey) -> Block {
+    let bytes: Vec<Vec<Ciphertext>> = x.par_chunks(8).map(|byte| sbox(byte, sk)).collect();
+    let permuted: Vec<Ciphertext> = (0..64).flat_map(|i| bytes[(i % 8) * 8 + i / 8].clone()).collect();
+
+    let words: Vec<[Ciphertext; 64]> = permuted.par_chunks(64)
+        .map(|bytes| {
+            let word: [Ciphertext; 64] = from_le_bytes(bytes);
+            let output: Vec<Ciphertext> = (0..64).into_par_iter()
+                .map(|i| {
+                    let bits: Vec<Ciphertext> = (0..64)
+                        .filter(|&j| (A[j] >> (63 - i)) & 1 == 1)
+                        .map(|j| word[j].clone())
+                        .collect();
+                    parity(&bits, sk)
+                })
+                .collect();
+            swap_bytes(&output.try_into().unwrap())
+        })
+        .collect();
+
+    words.into_iter().flatten().collect::<Vec<_>>().try_into().unwrap()
+}
+
+fn sbox(byte: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    select_index(byte, PI.iter().map(|value| trivial_bytes(&[*value], sk)).collect(), sk)
+}
+
+// Addition modulo 2^512 of two little-endian numbers
+fn add_le(a: &Block, b: &Block, sk: &ServerKey) -> Block {
+    let big_endian = |x: &[Ciphertext]| -> Block { reverse_bytes(x).try_into().unwrap() };
+    big_endian(&add(&big_endian(a), &big_endian(b), sk))
+}
+
+// XOR with a public value, which only takes NOT gates
+fn xor_constant(x: &Block, bytes: &[u8; 64], sk: &ServerKey) -> Block {
+    std::array::from_fn(|i| if (bytes[i / 8] >> (7 - i % 8)) & 1 == 1 { sk.not(&x[i]) } else { x[i].clone() })
+}
+
+fn number_bytes(n: u64) -> [u8; 64] {
+    let mut bytes = [0; 64];
+    bytes[..8].copy_from_slice(&n.to_le_bytes());
+    bytes
+}
+
+fn number(n: u64, sk: &ServerKey) -> Block {
+    constant(&number_bytes(n), sk)
+}
+
+fn constant(bytes: &[u8; 64], sk: &ServerKey) -> Block {
+    trivial_bytes(bytes, sk).try_into().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::padding::{pad_streebog_data, streebog_padded_len};
+    use crate::test_helpers::{decrypt_bytes, from_hex};
+    use crate::test_keys::test_keys;
+
+    // The examples of RFC 6986 (as byte strings, which reverses the numbers of the RFC) and the empty message, computed
+    // with the clear backend
+    #[test]
+    fn test_streebog() {
+        let (ck, sk) = test_keys();
+        let m1 = trivial_bytes(b"012345678901234567890123456789012345678901234567890123456789012", &sk);
+        let mut m2 = from_hex(concat!(
+            "fbe2e5f0eee3c820fbeafaebef20fffbf0e1e0f0f520e0ed20e8ece0ebe5f0f2f120fff0eeec20f120faf2fee5e2202ce8f6f3ede220e8",
+            "e6eee1e8f0f2d1202ce8f0f2e5e220e5d1",
+        ));
+        m2.reverse();
+        let m2 = trivial_bytes(&m2, &sk);
+
+        assert_eq!(
+            decrypt_bytes(&streebog512(&m1, &sk), &ck),
+            from_hex(concat!(
+                "1b54d01a4af5b9d5cc3d86d68d285462b19abc2475222f35c085122be4ba1ffa",
+                "00ad30f8767b3a82384c6574f024c311e2a481332b08ef7f41797891c1646f48",
+            )),
+        );
+        assert_eq!(
+            decrypt_bytes(&streebog256(&m1, &sk), &ck),
+            from_hex("9d151eefd8590b89daa6ba6cb74af9275dd051026bb149a452fd84e5e57b5500"),
+        );
+        assert_eq!(
+            decrypt_bytes(&streebog512(&m2, &sk), &ck),
+            from_hex(concat!(
+                "1e88e62226bfca6f9994f1f2d51569e0daf8475a3b0fe61a5300eee46d961376",
+                "035fe83549ada2b8620fcd7c496ce5b33f0cb9dddc2b6460143b03dabac9fb28",
+            )),
+        );
+        assert_eq!(
+            decrypt_bytes(&streebog256(&m2, &sk), &ck),
+            from_hex("9dd2fe4e90409e5da87f53976d7405b0c0cac628fc669a741d50063c557e8f50"),
+        );
+        assert_eq!(
+            decrypt_bytes(&streebog256(&[], &sk), &ck),
+            from_hex("3f539a213e97c802cc229d474c6aa32a825a360b2a933a949fd925208d9ce1bb"),
+        );
+    }
+
+    // The server padding of a message is the one of the client
+    #[test]
+    fn test_pad_encrypted() {
+        let (ck, sk) = test_keys();
+
+        for len in [0, 63, 64, 100] {
+            let message: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let padded = pad_encrypted(trivial_bytes(&message, &sk), &sk);
+            let padded: Vec<bool> = padded.iter().map(|bit| ck.decrypt(bit)).collect();
+
+            assert_eq!(padded, pad_streebog_data(&message));
+            assert_eq!(padded.len(), streebog_padded_len(len));
+        }
+    }
+}
@@ -0,0 +1,34 @@
+// This module gathers the helpers that the tests of the gate evaluation modules share, to decrypt their outputs and
+// write their test vectors, instead of each test module keeping its own copy.
+
+use sha2::{Digest, Sha256};
+use tfhe::boolean::prelude::*;
+
+pub fn decrypt_bits(bits: &[Ciphertext], ck: &ClientKey) -> Vec<bool> {
+    bits.iter().map(|bit| ck.decrypt(bit)).collect()
+}
+
+// Bytes of bits stored most significant bit first
+pub fn decrypt_bytes(bits: &[Ciphertext], ck: &ClientKey) -> Vec<u8> {
+    bits.chunks(8).map(|byte| byte.iter().fold(0u8, |acc, bit| (acc << 1) | ck.decrypt(bit) as u8)).collect()
+}
+
+pub fn from_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect()
+}
+
+// N-bit number, such as a field element, most significant bit first
+pub fn encrypt_uint<const N: usize>(value: u64, ck: &ClientKey) -> [Ciphertext; N] {
+    std::array::from_fn(|i| ck.encrypt((value >> (N - 1 - i)) & 1 == 1))
+}
+
+pub fn decrypt_uint<const N: usize>(element: &[Ciphertext; N], ck: &ClientKey) -> u64 {
+    element.iter().fold(0, |acc, bit| (acc << 1) | ck.decrypt(bit) as u64)
+}
+
+// Plaintext HMAC-SHA256 for a key shorter than a block
+pub fn hmac_sha256_plain(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let pad = |byte: u8| -> Vec<u8> { (0..64).map(|i| key.get(i).copied().unwrap_or(0) ^ byte).collect() };
+    let inner = Sha256::new().chain_update(pad(0x36)).chain_update(message).finalize();
+    Sha256::new().chain_update(pad(0x5c)).chain_update(inner).finalize().to_vec()
+}
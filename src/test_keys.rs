@@ -0,0 +1,30 @@
+// This module provides a fixed key pair for tests and examples, available with the "test-keys" feature (and always in
+// this crate's own tests). It is generated on first use and shared by every test of the process, instead of each test
+// paying for its own key generation. The keys are insecure by design: anything encrypted under them must be considered
+// public, so they must never be used outside of tests.
+
+use std::sync::OnceLock;
+use tfhe::boolean::prelude::*;
+
+static KEYS: OnceLock<(ClientKey, ServerKey)> = OnceLock::new();
+
+// Copy of the key pair shared by the whole process, with the default parameters of gen_keys. Copying the keys takes a
+// few milliseconds, against seconds to generate them
+pub fn test_keys() -> (ClientKey, ServerKey) {
+    KEYS.get_or_init(gen_keys).clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_are_shared() {
+        let (ck, _) = test_keys();
+        let (_, sk) = test_keys();
+
+        // Keys from two calls belong together
+        assert!(ck.decrypt(&sk.and(&ck.encrypt(true), &ck.encrypt(true))));
+        assert!(!ck.decrypt(&sk.xor(&ck.encrypt(true), &ck.encrypt(true))));
+    }
+}
@@ -0,0 +1,75 @@
+// This module controls how many threads the homomorphic evaluation uses. Every gate-level parallel step (rayon::join and
+// the parallel iterators of boolean_ops) runs on the pool of the calling thread, so running a computation inside
+// ThreadLimit::install bounds it to the limit's thread count instead of one thread per core of the global pool.
+//
+// The limit can be changed while a server is running, e.g. lowered when a co-tenant needs the cores. Computations
+// started afterwards (the next block, for a server that installs each block separately) get a pool of the new size,
+// while the ones already running finish on the previous pool.
+
+use std::sync::{Arc, Mutex};
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+use tfhe::boolean::prelude::*;
+use crate::parameters::ParameterSet;
+use crate::warm_up::{calibrate, Calibration};
+
+pub struct ThreadLimit {
+    pool: Mutex<Arc<ThreadPool>>,
+}
+
+impl ThreadLimit {
+    pub fn new(threads: usize) -> Result<Self, ThreadPoolBuildError> {
+        Ok(ThreadLimit { pool: Mutex::new(Arc::new(build_pool(threads)?)) })
+    }
+
+    pub fn threads(&self) -> usize {
+        self.pool.lock().unwrap().current_num_threads()
+    }
+
+    // Later computations use the new thread count
+    pub fn set_threads(&self, threads: usize) -> Result<(), ThreadPoolBuildError> {
+        if threads != self.threads() {
+            *self.pool.lock().unwrap() = Arc::new(build_pool(threads)?);
+        }
+        Ok(())
+    }
+
+    // Runs the computation with the current thread count
+    pub fn install<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        let pool = Arc::clone(&self.pool.lock().unwrap());
+        pool.install(op)
+    }
+
+    // Runs a calibration batch on the current pool, so that its threads are ready for the first computation (see the
+    // warm_up module)
+    pub fn warm_up(&self, parameter_set: ParameterSet, sk: &ServerKey) -> Calibration {
+        self.install(|| calibrate(parameter_set, sk))
+    }
+}
+
+fn build_pool(threads: usize) -> Result<ThreadPool, ThreadPoolBuildError> {
+    assert!(threads > 0, "at least one thread is needed");
+    ThreadPoolBuilder::new().num_threads(threads).build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boolean_ops::{add, trivial_word};
+    use crate::test_keys::test_keys;
+
+    #[test]
+    fn test_thread_limit() {
+        let (ck, sk) = test_keys();
+        let limit = ThreadLimit::new(3).unwrap();
+        assert_eq!(limit.install(rayon::current_num_threads), 3);
+
+        limit.set_threads(1).unwrap();
+        assert_eq!(limit.threads(), 1);
+        assert_eq!(limit.install(rayon::current_num_threads), 1);
+
+        // Gate-level parallelism runs on the limited pool, with the same results
+        let x: [Ciphertext; 32] = trivial_word(0x12345678, &sk);
+        let sum = limit.install(|| add(&x, &x, &sk));
+        assert_eq!(sum.iter().fold(0u32, |acc, bit| (acc << 1) | ck.decrypt(bit) as u32), 0x2468acf0);
+    }
+}
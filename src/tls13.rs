@@ -0,0 +1,121 @@
+// This module implements the TLS 1.3 key schedule (RFC 8446, section 7.1) for TLS_AES_128_GCM_SHA256 and the other
+// sha256 cipher suites, over encrypted secrets. The (EC)DHE shared secret, the PSK and every derived secret stay
+// encrypted, so a server can run the schedule on behalf of an endpoint without learning any traffic secret. Labels and
+// lengths are public, and transcript hashes may be encrypted or trivially encrypted.
+//
+//             0
+//             |
+//             v
+//   PSK ->  HKDF-Extract = Early Secret
+//             |
+//             v
+//       Derive-Secret(., "derived", "")
+//             |
+//             v
+//   (EC)DHE -> HKDF-Extract = Handshake Secret
+//             |
+//             v
+//       Derive-Secret(., "derived", "")
+//             |
+//             v
+//   0 -> HKDF-Extract = Master Secret
+
+use tfhe::boolean::prelude::*;
+use crate::boolean_ops::trivial_bytes;
+use crate::hkdf::{hkdf_expand, hkdf_extract};
+
+pub const HASH_LEN: usize = 32;
+
+// sha256 of the empty string, the transcript hash used by the "derived" secrets
+const EMPTY_HASH: [u8; HASH_LEN] = [
+    0xe3, 0xb0, 0xc4, 0x42, 0x98, 0xfc, 0x1c, 0x14, 0x9a, 0xfb, 0xf4, 0xc8, 0x99, 0x6f, 0xb9, 0x24,
+    0x27, 0xae, 0x41, 0xe4, 0x64, 0x9b, 0x93, 0x4c, 0xa4, 0x95, 0x99, 0x1b, 0x78, 0x52, 0xb8, 0x55,
+];
+
+// HKDF-Expand-Label(Secret, Label, Context, Length)
+pub fn hkdf_expand_label(secret: &[Ciphertext], label: &str, context: &[Ciphertext], length: usize, sk: &ServerKey) -> Vec<Ciphertext> {
+    let label = format!("tls13 {}", label);
+    assert!(label.len() <= 255 && context.len() <= 255 * 8, "label or context too long");
+
+    // struct { uint16 length; opaque label<7..255>; opaque context<0..255>; } HkdfLabel
+    let mut prefix = (length as u16).to_be_bytes().to_vec();
+    prefix.push(label.len() as u8);
+    prefix.extend_from_slice(label.as_bytes());
+    prefix.push((context.len() / 8) as u8);
+
+    let mut hkdf_label = trivial_bytes(&prefix, sk);
+    hkdf_label.extend_from_slice(context);
+
+    hkdf_expand(secret, &hkdf_label, length, sk)
+}
+
+// Derive-Secret(Secret, Label, Messages), taking the transcript hash of the messages
+pub fn derive_secret(secret: &[Ciphertext], label: &str, transcript_hash: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    hkdf_expand_label(secret, label, transcript_hash, HASH_LEN, sk)
+}
+
+// Early Secret, from the PSK or from a string of zeros when there is none
+pub fn early_secret(psk: Option<&[Ciphertext]>, sk: &ServerKey) -> Vec<Ciphertext> {
+    let zeros = trivial_bytes(&[0; HASH_LEN], sk);
+    hkdf_extract(&[], psk.unwrap_or(&zeros), sk)
+}
+
+// Handshake Secret, from the Early Secret and the (EC)DHE shared secret
+pub fn handshake_secret(early_secret: &[Ciphertext], shared_secret: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    hkdf_extract(&derived(early_secret, sk), shared_secret, sk)
+}
+
+// Master Secret, from the Handshake Secret
+pub fn master_secret(handshake_secret: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    hkdf_extract(&derived(handshake_secret, sk), &trivial_bytes(&[0; HASH_LEN], sk), sk)
+}
+
+fn derived(secret: &[Ciphertext], sk: &ServerKey) -> Vec<Ciphertext> {
+    derive_secret(secret, "derived", &trivial_bytes(&EMPTY_HASH, sk), sk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{decrypt_bytes, from_hex};
+    use crate::test_keys::test_keys;
+
+    // The simple 1-RTT handshake of RFC 8448 (section 3), computed with the clear backend
+    #[test]
+    fn test_key_schedule() {
+        let (ck, sk) = test_keys();
+        let trivial_hex = |hex: &str| trivial_bytes(&from_hex(hex), &sk);
+
+        let early = early_secret(None, &sk);
+        assert_eq!(decrypt_bytes(&early, &ck), from_hex("33ad0a1c607ec03b09e6cd9893680ce210adf300aa1f2660e1b22e10f170f92a"));
+
+        let shared_secret = trivial_hex("8bd4054fb55b9d63fdfbacf9f04b9f0d35e6d63f537563efd46272900f89492d");
+        let handshake = handshake_secret(&early, &shared_secret, &sk);
+        assert_eq!(decrypt_bytes(&handshake, &ck), from_hex("1dc826e93606aa6fdc0aadc12f741b01046aa6b99f691ed221a9f0ca043fbeac"));
+
+        // Transcript hash of ClientHello..ServerHello
+        let hello_hash = trivial_hex("860c06edc07858ee8e78f0e7428c58edd6b43f2ca3e6e95f02ed063cf0e1cad8");
+        assert_eq!(
+            decrypt_bytes(&derive_secret(&handshake, "c hs traffic", &hello_hash, &sk), &ck),
+            from_hex("b3eddb126e067f35a780b3abf45e2d8f3b1a950738f52e9600746a0e27a55a21"),
+        );
+        assert_eq!(
+            decrypt_bytes(&derive_secret(&handshake, "s hs traffic", &hello_hash, &sk), &ck),
+            from_hex("b67b7d690cc16c4e75e54213cb2d37b4e9c912bcded9105d42befd59d391ad38"),
+        );
+
+        let master = master_secret(&handshake, &sk);
+        assert_eq!(decrypt_bytes(&master, &ck), from_hex("18df06843d13a08bf2a449844c5f8a478001bc4d4c627984d5a41da8d0402919"));
+
+        // Transcript hash of ClientHello..server Finished
+        let finished_hash = trivial_hex("9608102a0f1ccc6db6250b7b7e417b1a000eaada3daae4777a7686c9ff83df13");
+        assert_eq!(
+            decrypt_bytes(&derive_secret(&master, "c ap traffic", &finished_hash, &sk), &ck),
+            from_hex("9e40646ce79a7f9dc05af8889bce6552875afa0b06df0087f792ebb7c17504a5"),
+        );
+        assert_eq!(
+            decrypt_bytes(&derive_secret(&master, "s ap traffic", &finished_hash, &sk), &ck),
+            from_hex("a11af9f05531f856ad47116b45a950328204b4f44bfb6b3a4b4f1f3fcb631643"),
+        );
+    }
+}
@@ -0,0 +1,212 @@
+// This module implements transciphering with the Trivium stream cipher (eSTREAM). Instead of encrypting every message bit
+// under TFHE, which makes a ciphertext of several kilobytes per bit, the client encrypts the message with Trivium and
+// only encrypts the 80-bit Trivium key under TFHE. The server runs Trivium homomorphically from the encrypted key to get
+// the encrypted keystream, and XORs it with the public Trivium ciphertext, which gives the message encrypted under TFHE
+// without ever decrypting it. The upload is then the size of the message plus 80 TFHE ciphertexts.
+//
+// Trivium only takes 3 AND and 11 XOR gates per keystream bit, and its 64 next bits never depend on each other, so the
+// server computes them 64 at a time, as 64-bit words. The initialization (1152 rounds) is the same for every message length.
+//
+// Keys, IVs and keystreams follow the byte and bit order of the eSTREAM reference implementation and test vectors. An
+// IV must never be reused with the same key, since two messages encrypted with the same keystream leak their XOR.
+
+use serde::{Deserialize, Serialize};
+use tfhe::boolean::prelude::*;
+#[cfg(feature = "server")]
+use crate::boolean_ops::{and, xor};
+
+pub const KEY_BYTES: usize = 10;
+pub const IV_BYTES: usize = 10;
+
+// Encrypted key bits, in the order they are loaded into the state
+pub const KEY_BITS: usize = KEY_BYTES * 8;
+
+const STATE_BITS: usize = 288;
+const WARMUP_ROUNDS: usize = 4 * STATE_BITS;
+
+// Input sent by a transciphering client: the message encrypted with Trivium, and the Trivium key encrypted under TFHE
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TranscipheredInput {
+    pub key: Vec<Ciphertext>,
+    pub iv: [u8; IV_BYTES],
+    pub ciphertext: Vec<u8>,
+}
+
+impl TranscipheredInput {
+    // Encrypts the message with Trivium, and the Trivium key with the client key
+    #[cfg(feature = "client")]
+    pub fn new(message: &[u8], key: &[u8; KEY_BYTES], iv: [u8; IV_BYTES], ck: &ClientKey) -> Self {
+        TranscipheredInput {
+            key: crate::client::encrypt_bools(&key_bits(key), ck),
+            iv,
+            ciphertext: apply_keystream(message, key, &iv),
+        }
+    }
+
+    // Message bits encrypted under TFHE, homomorphically decrypted from the Trivium ciphertext
+    #[cfg(feature = "server")]
+    pub fn transcipher(&self, sk: &ServerKey) -> Vec<Ciphertext> {
+        transcipher(&self.key, &self.iv, &self.ciphertext, sk)
+    }
+}
+
+// Key bits in the order they are loaded into the state
+pub fn key_bits(key: &[u8; KEY_BYTES]) -> Vec<bool> {
+    load_order(key)
+}
+
+// Encrypts or decrypts data by XORing it with the keystream
+pub fn apply_keystream(data: &[u8], key: &[u8; KEY_BYTES], iv: &[u8; IV_BYTES]) -> Vec<u8> {
+    let keystream = keystream(key, iv, data.len() * 8);
+
+    data.iter()
+        .zip(keystream.chunks(8))
+        .map(|(byte, bits)| byte ^ bits.iter().rev().fold(0, |acc, bit| (acc << 1) | *bit as u8))
+        .collect()
+}
+
+// First bits of the keystream, in the order Trivium outputs them (bytes are filled from their least significant bit)
+pub fn keystream(key: &[u8; KEY_BYTES], iv: &[u8; IV_BYTES], bits: usize) -> Vec<bool> {
+    let mut state = [false; STATE_BITS];
+    state[..KEY_BITS].copy_from_slice(&load_order(key));
+    state[93..93 + IV_BYTES * 8].copy_from_slice(&load_order(iv));
+    state[STATE_BITS - 3..].fill(true);
+
+    let mut keystream = Vec::with_capacity(bits);
+    for round in 0..WARMUP_ROUNDS + bits {
+        // Taps are the (1-based) positions of the Trivium specification
+        let s = |position: usize| state[position - 1];
+        let (t1, t2, t3) = (s(66) ^ s(93), s(162) ^ s(177), s(243) ^ s(288));
+        if round >= WARMUP_ROUNDS {
+            keystream.push(t1 ^ t2 ^ t3);
+        }
+
+        let t1 = t1 ^ (s(91) & s(92)) ^ s(171);
+        let t2 = t2 ^ (s(175) & s(176)) ^ s(264);
+        let t3 = t3 ^ (s(286) & s(287)) ^ s(69);
+
+        state.copy_within(0..STATE_BITS - 1, 1);
+        state[0] = t3;
+        state[93] = t1;
+        state[177] = t2;
+    }
+
+    keystream
+}
+
+// Homomorphic keystream from the encrypted key bits (see key_bits) and the public IV
+#[cfg(feature = "server")]
+pub fn keystream_fhe(key: &[Ciphertext], iv: &[u8; IV_BYTES], bits: usize, sk: &ServerKey) -> Vec<Ciphertext> {
+    assert_eq!(key.len(), KEY_BITS, "the key must have {} bits", KEY_BITS);
+
+    let mut state: Vec<Ciphertext> = key.to_vec();
+    state.extend((KEY_BITS..93).map(|_| sk.trivial_encrypt(false)));
+    state.extend(load_order(iv).iter().map(|bit| sk.trivial_encrypt(*bit)));
+    state.extend((93 + IV_BYTES * 8..STATE_BITS).map(|i| sk.trivial_encrypt(i >= STATE_BITS - 3)));
+
+    let mut keystream = Vec::with_capacity(bits.next_multiple_of(64));
+    for round in (0..WARMUP_ROUNDS + bits).step_by(64) {
+        let [z, t1, t2, t3] = rounds_64(&state, sk);
+
+        // The 64 new bits of each register enter it in reverse order, the last one first
+        state = [
+            t3.into_iter().rev().chain(state[..93 - 64].iter().cloned()).collect::<Vec<_>>(),
+            t1.into_iter().rev().chain(state[93..177 - 64].iter().cloned()).collect(),
+            t2.into_iter().rev().chain(state[177..STATE_BITS - 64].iter().cloned()).collect(),
+        ].concat();
+
+        if round >= WARMUP_ROUNDS {
+            keystream.extend(z);
+        }
+    }
+
+    keystream.truncate(bits);
+    keystream
+}
+
+// Upper bound on the bootstrapped gates of keystream_fhe for the given number of bits, which are computed 64 at a time.
+// Some gates of the initialization have a public operand and are free, so the actual count is a bit lower
+pub fn keystream_gates(bits: usize) -> u64 {
+    14 * (WARMUP_ROUNDS + bits.next_multiple_of(64)) as u64
+}
+
+// Message bits encrypted under TFHE, from the encrypted Trivium key, the IV and the Trivium ciphertext. XORing an
+// encrypted keystream bit with a public ciphertext bit is a copy or a NOT, so this costs no more than the keystream
+#[cfg(feature = "server")]
+pub fn transcipher(key: &[Ciphertext], iv: &[u8; IV_BYTES], ciphertext: &[u8], sk: &ServerKey) -> Vec<Ciphertext> {
+    let keystream = keystream_fhe(key, iv, ciphertext.len() * 8, sk);
+
+    // Message bits are most significant first, while the keystream fills each byte from its least significant bit
+    (0..ciphertext.len() * 8)
+        .map(|i| {
+            let z = &keystream[i - i % 8 + 7 - i % 8];
+            if (ciphertext[i / 8] >> (7 - i % 8)) & 1 == 1 { sk.not(z) } else { z.clone() }
+        })
+        .collect()
+}
+
+// Output bits and new register bits (t1, t2, t3) of the next 64 rounds, with the bits of round k at index k. The taps
+// are at least 66 bits into the registers, so none of these rounds reads a bit produced by another, and they are
+// evaluated together as 64-bit words
+#[cfg(feature = "server")]
+fn rounds_64(state: &[Ciphertext], sk: &ServerKey) -> [[Ciphertext; 64]; 4] {
+    // Bits at a (1-based) tap position of the specification, for each of the rounds
+    let s = |position: usize| -> [Ciphertext; 64] { std::array::from_fn(|k| state[position - 1 - k].clone()) };
+
+    let (t1, t2, t3) = (xor(&s(66), &s(93), sk), xor(&s(162), &s(177), sk), xor(&s(243), &s(288), sk));
+    let z = xor(&xor(&t1, &t2, sk), &t3, sk);
+
+    let t1 = xor(&t1, &xor(&and(&s(91), &s(92), sk), &s(171), sk), sk);
+    let t2 = xor(&t2, &xor(&and(&s(175), &s(176), sk), &s(264), sk), sk);
+    let t3 = xor(&t3, &xor(&and(&s(286), &s(287), sk), &s(69), sk), sk);
+    [z, t1, t2, t3]
+}
+
+// Bits of a key or IV in the order they are loaded into the state: the reference implementation loads the last bit
+// first, taking the bits of each byte from the least significant one
+fn load_order(bytes: &[u8]) -> Vec<bool> {
+    (0..bytes.len() * 8).rev().map(|i| (bytes[i / 8] >> (i % 8)) & 1 == 1).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_bytes(bits: &[bool]) -> Vec<u8> {
+        bits.chunks(8).map(|bits| bits.iter().rev().fold(0, |acc, bit| (acc << 1) | *bit as u8)).collect()
+    }
+
+    // eSTREAM test vector set 1, vector 0
+    #[test]
+    fn test_keystream() {
+        let mut key = [0; KEY_BYTES];
+        key[0] = 0x80;
+
+        assert_eq!(to_bytes(&keystream(&key, &[0; IV_BYTES], 64)), [0x38, 0xeb, 0x86, 0xff, 0x73, 0x0d, 0x7a, 0x9c]);
+
+        let message = b"attack at dawn";
+        let iv = *b"0123456789";
+        assert_ne!(apply_keystream(message, &key, &iv), message);
+        assert_eq!(apply_keystream(&apply_keystream(message, &key, &iv), &key, &iv), message);
+    }
+
+    #[test]
+    #[cfg(all(feature = "client", feature = "server"))]
+    fn test_transcipher() {
+        use crate::client::{decrypt_bools, encrypt_bools};
+        use crate::padding::message_bits;
+        use crate::test_keys::test_keys;
+
+        let (ck, sk) = test_keys();
+        let key = *b"secret key";
+        let iv = *b"unique iv!";
+
+        let encrypted_key = encrypt_bools(&key_bits(&key), &ck);
+        let keystream = keystream_fhe(&encrypted_key, &iv, 100, &sk);
+        assert_eq!(decrypt_bools(&keystream, &ck), super::keystream(&key, &iv, 100));
+
+        let message = b"transciphered message";
+        let input = TranscipheredInput::new(message, &key, iv, &ck);
+        assert_eq!(decrypt_bools(&input.transcipher(&sk), &ck), message_bits(message));
+    }
+}
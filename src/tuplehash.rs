@@ -0,0 +1,85 @@
+// This module implements TupleHash128 and TupleHash256 (NIST SP 800-185), which hash a tuple of encrypted strings
+// unambiguously: every string is absorbed after its length, so that e.g. ("a", "bc") and ("ab", "c") have unrelated
+// digests, which a hash of the concatenation can't tell apart. The strings are encrypted while their lengths, the
+// output length and the customization string are public, so only the strings themselves cost gates to absorb.
+
+use tfhe::boolean::prelude::*;
+use crate::cshake::{cshake_sponge, left_encode, right_encode};
+
+// TupleHash128 digest of the given length in bytes, with a public customization string (often empty)
+pub fn tuplehash128(
+    tuple: &[&[Ciphertext]],
+    output_len: usize,
+    customization: &[u8],
+    sk: &ServerKey,
+) -> Vec<Ciphertext> {
+    tuplehash(tuple, 256, output_len, customization, sk)
+}
+
+pub fn tuplehash256(
+    tuple: &[&[Ciphertext]],
+    output_len: usize,
+    customization: &[u8],
+    sk: &ServerKey,
+) -> Vec<Ciphertext> {
+    tuplehash(tuple, 512, output_len, customization, sk)
+}
+
+fn tuplehash(
+    tuple: &[&[Ciphertext]],
+    capacity: usize,
+    output_len: usize,
+    customization: &[u8],
+    sk: &ServerKey,
+) -> Vec<Ciphertext> {
+    let mut sponge = cshake_sponge(capacity, b"TupleHash", customization, sk);
+
+    // encode_string of every string, with the length in bits as a trivial prefix
+    for string in tuple {
+        assert_eq!(string.len() % 8, 0, "string is not a whole number of bytes");
+        sponge.absorb_plain(&left_encode(string.len() as u64), sk);
+        sponge.absorb(string, sk);
+    }
+
+    sponge.absorb_plain(&right_encode(8 * output_len as u64), sk);
+    sponge.squeeze(output_len, sk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boolean_ops::trivial_bytes;
+    use crate::test_helpers::{decrypt_bytes, from_hex};
+    use crate::test_keys::test_keys;
+
+    // NIST TupleHash samples #1, #2 and #6, computed with the clear backend
+    #[test]
+    fn test_tuplehash() {
+        let (ck, sk) = test_keys();
+        let strings: Vec<Vec<Ciphertext>> = [0x00..0x03, 0x10..0x16, 0x20..0x29]
+            .into_iter()
+            .map(|bytes| trivial_bytes(&bytes.collect::<Vec<u8>>(), &sk))
+            .collect();
+        let tuple: Vec<&[Ciphertext]> = strings.iter().map(Vec::as_slice).collect();
+
+        assert_eq!(
+            decrypt_bytes(&tuplehash128(&tuple[..2], 32, b"", &sk), &ck),
+            from_hex("c5d8786c1afb9b82111ab34b65b2c0048fa64e6d48e263264ce1707d3ffc8ed1"),
+        );
+        assert_eq!(
+            decrypt_bytes(&tuplehash128(&tuple[..2], 32, b"My Tuple App", &sk), &ck),
+            from_hex("75cdb20ff4db1154e841d758e24160c54bae86eb8c13e7f5f40eb35588e96dfb"),
+        );
+        assert_eq!(
+            decrypt_bytes(&tuplehash256(&tuple, 64, b"My Tuple App", &sk), &ck),
+            from_hex("45000be63f9b6bfd89f54717670f69a9bc763591a4f05c50d68891a744bcc6e7d6d5b5e82c018da999ed35b0bb49c9678e526abd8e85c13ed254021db9e790ce"),
+        );
+
+        // The same bytes split differently (checked against a Python model)
+        let (empty, ab) = (trivial_bytes(b"", &sk), trivial_bytes(b"ab", &sk));
+        let (a, b) = (trivial_bytes(b"a", &sk), trivial_bytes(b"b", &sk));
+        let split = |tuple: &[&[Ciphertext]]| decrypt_bytes(&tuplehash128(tuple, 16, b"", &sk), &ck);
+        assert_eq!(split(&[&empty, &ab]), from_hex("54328881b45aba19484a73dc37362574"));
+        assert_eq!(split(&[&a, &b]), from_hex("65761f0b90e495aed29b9ebcb0eedce0"));
+    }
+}
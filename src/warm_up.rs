@@ -0,0 +1,88 @@
+// This module pays the one-time costs of the server side before the first job, so that neither its latency nor the
+// first measurement of a benchmark include them. Building a Scheduler or a ThreadLimit starts the threads of its pools,
+// but each thread still allocates its bootstrapping buffers (and tfhe its FFT plans) on its first bootstrapped gate,
+// which a calibration batch of a few gates for each thread takes care of. The batch also measures the throughput of
+// the pool, e.g. for a server to log it, or to estimate the time of a job from its bootstrapped gates (as counted by
+// the failure module).
+//
+// Gates on trivial ciphertexts are computed in the clear, so the batch runs on the round constants of SHA-256 as
+// noiseless LWE ciphertexts (encrypted without a key, as the compact module expands them), which are bootstrapped like
+// any encrypted input. The constants themselves have nothing to cache: K is added as plaintext (add_constant) and the
+// initial hash values are trivially encrypted by Sha256State::new, both for free.
+//
+// A server that executes the netlists of the netlist_cache module can also build the netlist of its usual block count
+// ahead of time, with NetlistCache::netlist.
+
+use std::time::{Duration, Instant};
+use tfhe::boolean::prelude::*;
+use crate::boolean_ops::{trivial_word, GateBatch};
+use crate::compact::CompactCiphertexts;
+use crate::parameters::ParameterSet;
+use crate::sha256::K;
+
+// Bootstrapped gates of the calibration batch for each thread of the pool
+const GATES_PER_THREAD: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Calibration {
+    pub gates: usize,
+    pub elapsed: Duration,
+}
+
+impl Calibration {
+    pub fn gates_per_second(&self) -> f64 {
+        self.gates as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+// Runs the calibration batch on the pool of the calling thread (e.g. inside ThreadLimit::install), under the server key
+// of the parameter set
+pub fn calibrate(parameter_set: ParameterSet, sk: &ServerKey) -> Calibration {
+    let words = (rayon::current_num_threads() * GATES_PER_THREAD).div_ceil(32);
+    let constants = constant_words(words + 1, parameter_set, sk);
+
+    let mut batch = GateBatch::new();
+    for pair in constants.windows(2) {
+        batch.and(&pair[0], &pair[1]);
+    }
+    let start = Instant::now();
+    batch.run(sk);
+
+    Calibration { gates: words * 32, elapsed: start.elapsed() }
+}
+
+// The first count round constants (cycling through them), as noiseless LWE ciphertexts
+fn constant_words(count: usize, parameter_set: ParameterSet, sk: &ServerKey) -> Vec<[Ciphertext; 32]> {
+    let trivial: Vec<Ciphertext> = (0..count).flat_map(|i| trivial_word::<32>(K[i % K.len()] as u64, sk)).collect();
+    let noiseless = CompactCiphertexts::compact(&trivial, parameter_set, 32)
+        .expand()
+        .unwrap_or_else(|e| unreachable!("{}", e));
+
+    noiseless.chunks(32).map(|word| std::array::from_fn(|i| word[i].clone())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::decrypt_uint;
+    use crate::test_keys::test_keys;
+    use crate::threads::ThreadLimit;
+
+    #[test]
+    fn test_calibrate() {
+        let (ck, sk) = test_keys();
+        let calibration = ThreadLimit::new(3).unwrap().warm_up(ParameterSet::Default, &sk);
+        assert_eq!(calibration.gates, 32);
+        assert!(calibration.gates_per_second() > 0.0);
+
+        // The constants are encrypted, rather than trivial, and their gates are computed correctly
+        let constants = constant_words(66, ParameterSet::Default, &sk);
+        assert!(constants.iter().flatten().all(|bit| matches!(bit, Ciphertext::Encrypted(_))));
+        assert_eq!(decrypt_uint(&constants[1], &ck), K[1] as u64);
+        assert_eq!(decrypt_uint(&constants[65], &ck), K[1] as u64);
+
+        let mut batch = GateBatch::new();
+        batch.and(&constants[0], &constants[1]);
+        assert_eq!(decrypt_uint(&batch.run(&sk)[0], &ck), (K[0] & K[1]) as u64);
+    }
+}